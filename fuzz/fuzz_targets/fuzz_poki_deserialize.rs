@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use poki::Poki;
+
+use std::io::Cursor;
+
+// Poki files may come from anywhere, so deserializing one must never panic, no matter what it
+// contains. Both ways of deserializing are exercised, since they read the table of unresolved
+// symbols differently.
+//
+// cargo-fuzz builds with the address sanitizer by default, which also checks the reinterpretation
+// of words as bytes which the reader performs.
+fuzz_target!(|data: &[u8]| {
+    let _ = Poki::deserialize(&mut Cursor::new(data));
+    let _ = Poki::from_slice(data);
+});
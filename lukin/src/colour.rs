@@ -0,0 +1,73 @@
+use clap::ValueEnum;
+
+use std::env;
+
+// How the parts of lukin's output are styled: either not at all, so that the output is plain text
+// which is the same wherever it ends up, or with ANSI escape codes, for display in a terminal.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Palette {
+    colour: bool,
+}
+
+impl Palette {
+    pub fn new(colour: bool) -> Self {
+        Self { colour }
+    }
+
+    pub fn is_plain(self) -> bool {
+        !self.colour
+    }
+
+    // The line which begins each section of the output.
+    pub fn header(self, text: &str) -> String {
+        self.paint("1", text)
+    }
+
+    // A label which is exported from some segment.
+    pub fn label(self, text: &str) -> String {
+        self.paint("32", text)
+    }
+
+    // A symbol which is referred to, but not exported from any segment.
+    pub fn unresolved(self, text: &str) -> String {
+        self.paint("31", text)
+    }
+
+    // Where a relocation refers to, when that isn't an unresolved symbol.
+    pub fn target(self, text: &str) -> String {
+        self.paint("36", text)
+    }
+
+    fn paint(self, code: &str, text: &str) -> String {
+        if self.colour {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+// When lukin's output is coloured.
+#[derive(ValueEnum, Debug, Clone, Copy, Hash, PartialEq, Eq, Default)]
+pub enum When {
+    // Only when the output is written to a terminal, and NO_COLOR isn't set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl When {
+    // The palette for output which is written to a terminal if `terminal` is set.
+    //
+    // NOTE: As https://no-color.org asks, NO_COLOR is ignored when it is set but empty, and an
+    // explicit request for colour overrides it.
+    pub fn palette(self, terminal: bool) -> Palette {
+        let colour = match self {
+            When::Auto => terminal && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+            When::Always => true,
+            When::Never => false,
+        };
+        Palette::new(colour)
+    }
+}
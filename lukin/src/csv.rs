@@ -0,0 +1,103 @@
+use crate::display::permissions;
+use crate::symbolic::{label_plus_delta, Target};
+
+use clap::ValueEnum;
+use poki::Poki;
+
+use std::fmt::Write;
+
+// The tables of a poki file which may be written as CSV.
+#[derive(ValueEnum, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Table {
+    Exports,
+    Relocations,
+    Segments,
+}
+
+// Render `table` as CSV, with a header row naming its columns. Numbers are written in decimal, as
+// spreadsheets expect.
+pub fn render(poki: &Poki, table: Table) -> String {
+    let mut rendered = String::new();
+    match table {
+        Table::Exports => {
+            write_row(&mut rendered, &["segment", "offset", "label"]);
+            for (segment_index, export_table_entry) in poki.find_all_exports() {
+                write_row(
+                    &mut rendered,
+                    &[
+                        &segment_index.to_string(),
+                        &export_table_entry.offset.to_string(),
+                        &export_table_entry.label,
+                    ],
+                );
+            }
+        }
+        Table::Relocations => {
+            write_row(
+                &mut rendered,
+                &[
+                    "segment",
+                    "offset",
+                    "target_segment",
+                    "target_offset",
+                    "target_label",
+                ],
+            );
+            for (segment_index, segment) in poki.segments.iter().enumerate() {
+                for relocation_table_entry in &segment.relocation_table {
+                    // NOTE: A relocation which refers to somewhere no export precedes, or to an
+                    // unresolved symbol missing from the table, has no label to speak of.
+                    let target_label = match Target::resolve(poki, relocation_table_entry) {
+                        Target::Export { label, delta } => label_plus_delta(label, delta),
+                        Target::Unresolved(symbol) => symbol.to_string(),
+                        Target::Corrupt(_) | Target::Unnamed { .. } => String::new(),
+                    };
+                    write_row(
+                        &mut rendered,
+                        &[
+                            &segment_index.to_string(),
+                            &relocation_table_entry.offset.to_string(),
+                            &relocation_table_entry.segment_index.to_string(),
+                            &relocation_table_entry.segment_offset.to_string(),
+                            &target_label,
+                        ],
+                    );
+                }
+            }
+        }
+        Table::Segments => {
+            write_row(
+                &mut rendered,
+                &["index", "permissions", "words", "relocs", "exports"],
+            );
+            for (segment_index, segment) in poki.segments.iter().enumerate() {
+                write_row(
+                    &mut rendered,
+                    &[
+                        &segment_index.to_string(),
+                        &permissions(segment_index),
+                        &segment.contents.len().to_string(),
+                        &segment.relocation_table.len().to_string(),
+                        &segment.export_table.len().to_string(),
+                    ],
+                );
+            }
+        }
+    }
+    rendered
+}
+
+fn write_row(rendered: &mut String, fields: &[&str]) {
+    let fields: Vec<_> = fields.iter().map(|field| quote(field)).collect();
+    writeln!(rendered, "{}", fields.join(",")).unwrap();
+}
+
+// Quote `field` as RFC 4180 asks, if it contains anything which would otherwise be mistaken for the
+// end of the field or of the row.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,192 @@
+use poki::{Poki, RelocationTableEntry};
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub struct Options {
+    pub ignore_relocations: bool,
+    pub exports_only: bool,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum Change {
+    Header(String),
+    Removed(String),
+    Added(String),
+}
+
+// Compare two poki files, returning the changes which turn `a` into `b`, grouped by segment, with
+// the changes to the table of unresolved symbols last. Each change is described by a line in the
+// text format of poki files, so that the output reads the same as a diff of the two files rendered
+// as text would.
+//
+// NOTE: Words are compared by offset, since moving a word changes the meaning of the program, but
+// relocations, exports, and unresolved symbols are compared as sets, so that two poki files which
+// differ only in the order of their tables, as `Poki::normalize` would make them, are identical.
+// Relocations which refer to unresolved symbols are compared by the labels to which they refer,
+// since their indices into the table of unresolved symbols mean nothing on their own.
+pub fn diff(a: &Poki, b: &Poki, options: Options) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    for (segment_index, (a_segment, b_segment)) in a.segments.iter().zip(&b.segments).enumerate() {
+        let mut segment_changes = Vec::new();
+
+        if !options.exports_only {
+            diff_contents(
+                segment_index,
+                &a_segment.contents,
+                &b_segment.contents,
+                &mut segment_changes,
+            );
+
+            if !options.ignore_relocations {
+                let relocations = |poki: &Poki, relocation_table: &[RelocationTableEntry]| {
+                    relocation_table
+                        .iter()
+                        .map(|relocation_table_entry| {
+                            relocation_line(segment_index, relocation_table_entry, poki)
+                        })
+                        .collect::<BTreeSet<_>>()
+                };
+                diff_sets(
+                    relocations(a, &a_segment.relocation_table),
+                    relocations(b, &b_segment.relocation_table),
+                    |line| line.clone(),
+                    &mut segment_changes,
+                );
+            }
+        }
+
+        let exports = |poki: &Poki| {
+            poki.segments[segment_index]
+                .export_table
+                .iter()
+                .map(|export_table_entry| {
+                    (export_table_entry.label.clone(), export_table_entry.offset)
+                })
+                .collect::<BTreeSet<_>>()
+        };
+        diff_sets(
+            exports(a),
+            exports(b),
+            |(label, offset)| format!("seg{segment_index} export {offset:#06x} {label:?}"),
+            &mut segment_changes,
+        );
+
+        if !segment_changes.is_empty() {
+            changes.push(Change::Header(format!("seg{segment_index}")));
+            changes.extend(segment_changes);
+        }
+    }
+
+    if !options.exports_only {
+        let mut unresolved_changes = Vec::new();
+        diff_sets(
+            a.unresolved_table.iter().collect(),
+            b.unresolved_table.iter().collect(),
+            |symbol| format!("unresolved {symbol:?}"),
+            &mut unresolved_changes,
+        );
+        if !unresolved_changes.is_empty() {
+            changes.push(Change::Header("unresolved".to_string()));
+            changes.extend(unresolved_changes);
+        }
+    }
+
+    changes
+}
+
+// Compare the contents of a segment word by word. Each run of consecutive words which differ is
+// shown as the words removed, followed by the words added, as in a unified diff.
+fn diff_contents(segment_index: usize, a: &[u16], b: &[u16], changes: &mut Vec<Change>) {
+    let word_line =
+        |offset: usize, word: &u16| format!("seg{segment_index} {offset:#06x}: {word:#06x}");
+
+    let mut offset = 0;
+    while offset < a.len().max(b.len()) {
+        if a.get(offset) == b.get(offset) {
+            offset += 1;
+            continue;
+        }
+
+        let start = offset;
+        while offset < a.len().max(b.len()) && a.get(offset) != b.get(offset) {
+            offset += 1;
+        }
+
+        for (offset, word) in a.iter().enumerate().take(offset).skip(start) {
+            changes.push(Change::Removed(word_line(offset, word)));
+        }
+        for (offset, word) in b.iter().enumerate().take(offset).skip(start) {
+            changes.push(Change::Added(word_line(offset, word)));
+        }
+    }
+}
+
+// Compare two sets, producing a change for each element which is in only one of them, in order.
+fn diff_sets<T: Ord>(
+    a: BTreeSet<T>,
+    b: BTreeSet<T>,
+    line: impl Fn(&T) -> String,
+    changes: &mut Vec<Change>,
+) {
+    for element in a.union(&b) {
+        match (a.contains(element), b.contains(element)) {
+            (true, false) => changes.push(Change::Removed(line(element))),
+            (false, true) => changes.push(Change::Added(line(element))),
+            _ => {}
+        }
+    }
+}
+
+fn relocation_line(
+    segment_index: usize,
+    relocation_table_entry: &RelocationTableEntry,
+    poki: &Poki,
+) -> String {
+    let target = if relocation_table_entry.segment_index == 0xFFFF {
+        match poki
+            .unresolved_table
+            .get(usize::from(relocation_table_entry.segment_offset))
+        {
+            Some(symbol) => format!("unresolved {symbol:?}"),
+            None => format!("unresolved {:#06x}", relocation_table_entry.segment_offset),
+        }
+    } else {
+        format!(
+            "seg{} {:#06x}",
+            relocation_table_entry.segment_index, relocation_table_entry.segment_offset
+        )
+    };
+    format!(
+        "seg{segment_index} reloc {:#06x} -> {target}",
+        relocation_table_entry.offset
+    )
+}
+
+// Render `changes` as a unified diff between `a_name` and `b_name`, optionally coloured with ANSI
+// escape codes.
+pub fn render(changes: &[Change], a_name: &str, b_name: &str, colour: bool) -> String {
+    let paint = |code: &str, line: String| {
+        if colour {
+            format!("\x1b[{code}m{line}\x1b[0m")
+        } else {
+            line
+        }
+    };
+
+    let mut rendered = String::new();
+    writeln!(rendered, "{}", paint("1", format!("--- {a_name}"))).unwrap();
+    writeln!(rendered, "{}", paint("1", format!("+++ {b_name}"))).unwrap();
+    for change in changes {
+        let line = match change {
+            Change::Header(header) => paint("36", format!("@@ {header} @@")),
+            Change::Removed(line) => paint("31", format!("-{line}")),
+            Change::Added(line) => paint("32", format!("+{line}")),
+        };
+        writeln!(rendered, "{line}").unwrap();
+    }
+
+    rendered
+}
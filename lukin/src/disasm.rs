@@ -0,0 +1,194 @@
+use crate::colour::Palette;
+use crate::display::permissions;
+use crate::dump::Target;
+use crate::symbolic;
+
+use poki::Poki;
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// The mnemonic of each opcode, indexed by the opcode itself. Opcodes which the lawa isa leaves
+// undefined have no mnemonic.
+const MNEMONICS: [Option<&str>; 64] = {
+    let mut mnemonics = [None; 64];
+    mnemonics[0b000000] = Some("add");
+    mnemonics[0b000001] = Some("sub");
+    mnemonics[0b000010] = Some("and");
+    mnemonics[0b000011] = Some("or");
+    mnemonics[0b000100] = Some("xor");
+    mnemonics[0b000101] = Some("sll");
+    mnemonics[0b000110] = Some("srl");
+    mnemonics[0b000111] = Some("sra");
+    mnemonics[0b001000] = Some("addi");
+    mnemonics[0b001010] = Some("andi");
+    mnemonics[0b001011] = Some("ori");
+    mnemonics[0b001100] = Some("xori");
+    mnemonics[0b001101] = Some("slli");
+    mnemonics[0b001111] = Some("srai");
+    mnemonics[0b010000] = Some("ld");
+    mnemonics[0b010001] = Some("st");
+    mnemonics[0b010010] = Some("dei");
+    mnemonics[0b010011] = Some("deo");
+    mnemonics[0b010100] = Some("rcsr");
+    mnemonics[0b010101] = Some("wcsr");
+    mnemonics[0b010110] = Some("swpr");
+    mnemonics[0b010111] = Some("wfi");
+    mnemonics[0b011000] = Some("ldio");
+    mnemonics[0b011001] = Some("stio");
+    mnemonics[0b101000] = Some("jal");
+    mnemonics[0b101001] = Some("jsh");
+    mnemonics[0b101010] = Some("beq");
+    mnemonics[0b101011] = Some("bne");
+    mnemonics[0b101100] = Some("blt");
+    mnemonics[0b101101] = Some("bge");
+    mnemonics[0b101110] = Some("bltu");
+    mnemonics[0b101111] = Some("bgeu");
+    mnemonics
+};
+
+// The names of the control/status registers, indexed by their numbers.
+const CONTROL_STATUS_REGISTERS: [&str; 32] = [
+    "im0", "im1", "im2", "im3", "im4", "im5", "im6", "im7", "im8", "im9", "im10", "im11", "im12",
+    "im13", "im14", "im15", "iv", "ipc", "ic", "cycle", "instret", "counterh", "mpc0", "mpc1",
+    "mpa0", "mpa1", "mpa2", "mpa3", "mpa4", "mpa5", "mpa6", "mpa7",
+];
+
+const JSH: u16 = 0b101001;
+const RCSR: u16 = 0b010100;
+const WCSR: u16 = 0b010101;
+const WFI: u16 = 0b010111;
+
+// Whether an instruction with the given opcode is followed by an immediate word, which is the case
+// exactly when the fourth bit of its opcode is set, with the exception of jsh, whose immediate is
+// packed into the instruction word itself.
+fn takes_immediate(opcode: u16) -> bool {
+    (opcode & 0b001000) != 0 && opcode != JSH
+}
+
+// Decode the instruction beginning with `word`, followed by `immediate` if there is a word after
+// it, returning its assembly along with the number of words which it occupies, or `None` if it
+// can't be decoded.
+fn decode(word: u16, immediate: Option<u16>) -> Option<(String, usize)> {
+    let opcode = word & 0b0000000000111111;
+    let dst = (word & 0b0000011111000000) >> 6;
+    let src = (word & 0b1111100000000000) >> 11;
+    let mnemonic = MNEMONICS[usize::from(opcode)]?;
+
+    let decoded = match opcode {
+        WFI => (mnemonic.to_string(), 1),
+        JSH => (format!("{mnemonic} {:#05x}", word >> 6), 1),
+        RCSR => (
+            format!(
+                "{mnemonic} r{dst}, {}",
+                CONTROL_STATUS_REGISTERS[usize::from(src)]
+            ),
+            1,
+        ),
+        WCSR => (
+            format!(
+                "{mnemonic} {}, r{src}",
+                CONTROL_STATUS_REGISTERS[usize::from(dst)]
+            ),
+            1,
+        ),
+        _ if takes_immediate(opcode) => {
+            (format!("{mnemonic} r{dst}, r{src}, {:#06x}", immediate?), 2)
+        }
+        _ => (format!("{mnemonic} r{dst}, r{src}"), 1),
+    };
+    Some(decoded)
+}
+
+// Disassemble the segments named by `targets`, with the labels exported from each offset shown
+// above it, and each word which a relocation patches annotated with the target of the relocation.
+// Dumping all of the segments disassembles only those which are executable and non-empty.
+pub fn render(poki: &Poki, targets: &[Target], palette: Palette) -> String {
+    let segment_indices: Vec<usize> = if targets.contains(&Target::All) {
+        (0..poki.segments.len())
+            .filter(|segment_index| {
+                segment_index & 0b001 != 0 && !poki.segments[*segment_index].contents.is_empty()
+            })
+            .collect()
+    } else {
+        targets
+            .iter()
+            .filter_map(|target| match target {
+                Target::Segment(segment_index) => Some(*segment_index),
+                Target::All => None,
+            })
+            .collect()
+    };
+
+    segment_indices
+        .into_iter()
+        .map(|segment_index| render_segment(poki, segment_index, palette))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_segment(poki: &Poki, segment_index: usize, palette: Palette) -> String {
+    let segment = &poki.segments[segment_index];
+
+    let mut exports: HashMap<u16, Vec<&str>> = HashMap::new();
+    for export_table_entry in &segment.export_table {
+        exports
+            .entry(export_table_entry.offset)
+            .or_default()
+            .push(&export_table_entry.label);
+    }
+    let relocations: HashMap<u16, String> = segment
+        .relocation_table
+        .iter()
+        .map(|relocation_table_entry| {
+            (
+                relocation_table_entry.offset,
+                symbolic::Target::resolve(poki, relocation_table_entry).render(palette),
+            )
+        })
+        .collect();
+
+    let mut rendered = String::new();
+    writeln!(
+        rendered,
+        "{}",
+        palette.header(&format!(
+            "segment {segment_index} ({})",
+            permissions(segment_index)
+        ))
+    )
+    .unwrap();
+
+    let mut offset = 0;
+    while offset < segment.contents.len() {
+        // NOTE: A segment is at most `u16::MAX` words long, so every offset within it fits.
+        let offset_word = offset as u16;
+        for label in exports.get(&offset_word).into_iter().flatten() {
+            writeln!(rendered, "{}:", palette.label(label)).unwrap();
+        }
+
+        let word = segment.contents[offset];
+        let immediate = segment.contents.get(offset + 1).copied();
+        let (assembly, size) =
+            decode(word, immediate).unwrap_or_else(|| (format!(".word {word:#06x}"), 1));
+
+        let words = segment.contents[offset..offset + size]
+            .iter()
+            .map(|word| format!("{word:04x}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let annotations = (offset..offset + size)
+            .filter_map(|offset| relocations.get(&(offset as u16)))
+            .map(|target| format!("  -> {target}"))
+            .collect::<String>();
+        writeln!(
+            rendered,
+            "  {offset_word:#06x}: {words:<9}  {assembly}{annotations}"
+        )
+        .unwrap();
+
+        offset += size;
+    }
+
+    rendered
+}
@@ -0,0 +1,304 @@
+use crate::colour::Palette;
+use crate::symbolic::Target;
+
+use poki::Poki;
+
+use std::fmt::Write;
+
+// The sections of a poki file which may be displayed. When none of them are selected, a summary of
+// the file is displayed instead.
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub struct Sections {
+    pub headers: bool,
+    pub segments: bool,
+    pub relocations: bool,
+    pub exports: bool,
+    pub unresolved: bool,
+}
+
+impl Sections {
+    pub fn all() -> Self {
+        Self {
+            headers: true,
+            segments: true,
+            relocations: true,
+            exports: true,
+            unresolved: true,
+        }
+    }
+
+    fn is_empty(self) -> bool {
+        self == Self::default()
+    }
+}
+
+// Render the selected sections of `poki`, one after another, separated by blank lines.
+pub fn render(poki: &Poki, sections: Sections, palette: Palette) -> String {
+    if sections.is_empty() {
+        return render_summary(poki, palette);
+    }
+
+    let mut rendered_sections = Vec::new();
+    if sections.headers {
+        rendered_sections.push(render_headers(poki, palette));
+    }
+    if sections.segments {
+        rendered_sections.push(render_segments(poki, palette));
+    }
+    if sections.relocations {
+        rendered_sections.push(render_relocations(poki, palette));
+    }
+    if sections.exports {
+        rendered_sections.push(render_exports(poki, palette));
+    }
+    if sections.unresolved {
+        rendered_sections.push(render_unresolved(poki, palette));
+    }
+
+    rendered_sections.join("\n")
+}
+
+// The permissions which the index of a segment encodes, as in `r-x`.
+pub fn permissions(segment_index: usize) -> String {
+    [(0b100, 'r'), (0b010, 'w'), (0b001, 'x')]
+        .iter()
+        .map(|(bit, c)| if segment_index & bit != 0 { *c } else { '-' })
+        .collect()
+}
+
+// Render a table of the sizes of the given segments, along with their permissions.
+fn render_segment_table(
+    poki: &Poki,
+    segment_indices: impl Iterator<Item = usize>,
+    palette: Palette,
+) -> String {
+    render_size_table(
+        segment_indices.map(|segment_index| {
+            let segment = &poki.segments[segment_index];
+            (
+                segment_index,
+                [
+                    segment.contents.len(),
+                    segment.relocation_table.len(),
+                    segment.export_table.len(),
+                ],
+            )
+        }),
+        palette,
+    )
+}
+
+// Render a table of the given sizes, each a count of words, relocations, and exports, along with
+// the index and permissions of the segment to which they belong.
+fn render_size_table(rows: impl Iterator<Item = (usize, [usize; 3])>, palette: Palette) -> String {
+    let mut rendered = String::new();
+    writeln!(
+        rendered,
+        "{}",
+        palette.header("segment  perms     words  relocations  exports")
+    )
+    .unwrap();
+    for (segment_index, [words, relocations, exports]) in rows {
+        writeln!(
+            rendered,
+            "{segment_index:<8} {:<5} {words:>9} {relocations:>12} {exports:>8}",
+            permissions(segment_index),
+        )
+        .unwrap();
+    }
+    rendered
+}
+
+fn is_empty_segment(poki: &Poki, segment_index: usize) -> bool {
+    let segment = &poki.segments[segment_index];
+    segment.contents.is_empty()
+        && segment.relocation_table.is_empty()
+        && segment.export_table.is_empty()
+}
+
+// Render a summary of `poki` which fits on a single screen: its size, the sizes of its non-empty
+// segments, and the number of exports and unresolved symbols.
+fn render_summary(poki: &Poki, palette: Palette) -> String {
+    let mut rendered = String::new();
+    writeln!(
+        rendered,
+        "{}",
+        palette.header(&format!(
+            "poki file of {} bytes, with {} exports and {} unresolved symbols",
+            poki.serialized_size(),
+            poki.find_all_exports().count(),
+            poki.unresolved_table.len()
+        ))
+    )
+    .unwrap();
+    rendered.push_str(&render_segment_table(
+        poki,
+        (0..poki.segments.len()).filter(|segment_index| !is_empty_segment(poki, *segment_index)),
+        palette,
+    ));
+    rendered
+}
+
+// Render the combined sizes of the segments of all of `pokis`, in the same form as the summary of a
+// single poki file, leaving out segments which are empty in every one of them.
+pub fn render_totals(pokis: &[Poki], palette: Palette) -> String {
+    let mut sizes = [[0; 3]; 8];
+    for poki in pokis {
+        for (size, segment) in sizes.iter_mut().zip(&poki.segments) {
+            size[0] += segment.contents.len();
+            size[1] += segment.relocation_table.len();
+            size[2] += segment.export_table.len();
+        }
+    }
+
+    let mut rendered = String::new();
+    writeln!(
+        rendered,
+        "{}",
+        palette.header(&format!(
+            "totals over {} poki files of {} bytes, with {} exports and {} unresolved symbols",
+            pokis.len(),
+            pokis.iter().map(Poki::serialized_size).sum::<usize>(),
+            pokis
+                .iter()
+                .map(|poki| poki.find_all_exports().count())
+                .sum::<usize>(),
+            pokis
+                .iter()
+                .map(|poki| poki.unresolved_table.len())
+                .sum::<usize>()
+        ))
+    )
+    .unwrap();
+    rendered.push_str(&render_size_table(
+        sizes
+            .into_iter()
+            .enumerate()
+            .filter(|(_, size)| *size != [0; 3]),
+        palette,
+    ));
+    rendered
+}
+
+// Render the header of each of the eight segments, including those which are empty.
+fn render_headers(poki: &Poki, palette: Palette) -> String {
+    let mut rendered = String::new();
+    writeln!(
+        rendered,
+        "{}",
+        palette.header(&format!("headers ({} bytes)", poki.serialized_size()))
+    )
+    .unwrap();
+    rendered.push_str(&render_segment_table(poki, 0..poki.segments.len(), palette));
+    rendered
+}
+
+// Render the contents of each non-empty segment, eight words to a line, each line beginning with
+// the offset of its first word.
+fn render_segments(poki: &Poki, palette: Palette) -> String {
+    let mut rendered = String::new();
+    for (segment_index, segment) in poki.segments.iter().enumerate() {
+        if segment.contents.is_empty() {
+            continue;
+        }
+
+        writeln!(
+            rendered,
+            "{}",
+            palette.header(&format!(
+                "contents of segment {segment_index} ({})",
+                permissions(segment_index)
+            ))
+        )
+        .unwrap();
+        for (line_index, words) in segment.contents.chunks(8).enumerate() {
+            let words = words
+                .iter()
+                .map(|word| format!("{word:04x}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(rendered, "  {:#06x}: {words}", line_index * 8).unwrap();
+        }
+    }
+    if rendered.is_empty() {
+        writeln!(rendered, "no segment has any contents").unwrap();
+    }
+    rendered
+}
+
+// Render the relocations of each segment which has any, as `offset -> target`, where the target is
+// named by the export nearest before it where possible.
+fn render_relocations(poki: &Poki, palette: Palette) -> String {
+    let mut rendered = String::new();
+    for (segment_index, segment) in poki.segments.iter().enumerate() {
+        if segment.relocation_table.is_empty() {
+            continue;
+        }
+
+        writeln!(
+            rendered,
+            "{}",
+            palette.header(&format!("relocations in segment {segment_index}"))
+        )
+        .unwrap();
+        for relocation_table_entry in &segment.relocation_table {
+            writeln!(
+                rendered,
+                "  {:#06x} -> {}",
+                relocation_table_entry.offset,
+                Target::resolve(poki, relocation_table_entry).render(palette)
+            )
+            .unwrap();
+        }
+    }
+    if rendered.is_empty() {
+        writeln!(rendered, "no segment has any relocations").unwrap();
+    }
+    rendered
+}
+
+// Render the exports of each segment which has any, as `label @ offset`.
+fn render_exports(poki: &Poki, palette: Palette) -> String {
+    let mut rendered = String::new();
+    for (segment_index, segment) in poki.segments.iter().enumerate() {
+        if segment.export_table.is_empty() {
+            continue;
+        }
+
+        writeln!(
+            rendered,
+            "{}",
+            palette.header(&format!("exports in segment {segment_index}"))
+        )
+        .unwrap();
+        for export_table_entry in &segment.export_table {
+            writeln!(
+                rendered,
+                "  {} @ {:#06x}",
+                palette.label(&export_table_entry.label),
+                export_table_entry.offset
+            )
+            .unwrap();
+        }
+    }
+    if rendered.is_empty() {
+        writeln!(rendered, "no segment has any exports").unwrap();
+    }
+    rendered
+}
+
+// Render the table of unresolved symbols, each along with its index, which is what relocations
+// refer to it by.
+fn render_unresolved(poki: &Poki, palette: Palette) -> String {
+    let mut rendered = String::new();
+    if poki.unresolved_table.is_empty() {
+        writeln!(rendered, "no unresolved symbols").unwrap();
+        return rendered;
+    }
+
+    writeln!(rendered, "{}", palette.header("unresolved symbols")).unwrap();
+    for (index, symbol) in poki.unresolved_table.iter().enumerate() {
+        writeln!(rendered, "  {index:#06x}: {}", palette.unresolved(symbol)).unwrap();
+    }
+    rendered
+}
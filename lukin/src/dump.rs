@@ -0,0 +1,165 @@
+use crate::colour::Palette;
+use crate::display::permissions;
+
+use poki::Poki;
+
+use std::fmt::Write;
+use std::ops::Range;
+use std::str::FromStr;
+
+// The segments whose contents are to be dumped.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Target {
+    Segment(usize),
+    All,
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "all" {
+            return Ok(Self::All);
+        }
+        match s.parse() {
+            Ok(segment_index) if segment_index < 8 => Ok(Self::Segment(segment_index)),
+            _ => Err(format!(
+                "{s} is not a segment index, which must lie between 0 and 7, or all"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Options {
+    // The offsets within each segment of the words to dump, or every word if not given.
+    pub range: Option<Range<usize>>,
+    // The number of words shown on each line.
+    pub width: usize,
+    // The address at which the segment is loaded, which is added to each offset shown.
+    pub base: u16,
+}
+
+// Render the contents of the segments named by `targets` as a classic hexdump, in which each line
+// gives the address of its first word, then the words themselves, and finally the words which are
+// printable ascii characters as those characters, with `.` standing in for the rest.
+//
+// NOTE: Naming a segment explicitly dumps it even if it is empty, so that it's clear that nothing
+// was left out, but dumping all of the segments skips those which are empty.
+pub fn render(poki: &Poki, targets: &[Target], options: &Options, palette: Palette) -> String {
+    let segment_indices: Vec<usize> = if targets.contains(&Target::All) {
+        (0..poki.segments.len())
+            .filter(|segment_index| !poki.segments[*segment_index].contents.is_empty())
+            .collect()
+    } else {
+        targets
+            .iter()
+            .filter_map(|target| match target {
+                Target::Segment(segment_index) => Some(*segment_index),
+                Target::All => None,
+            })
+            .collect()
+    };
+
+    segment_indices
+        .into_iter()
+        .map(|segment_index| render_segment(poki, segment_index, options, palette))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_segment(
+    poki: &Poki,
+    segment_index: usize,
+    options: &Options,
+    palette: Palette,
+) -> String {
+    let contents = &poki.segments[segment_index].contents;
+    let range = options.range.clone().unwrap_or(0..contents.len());
+    let words = &contents[range.start.min(contents.len())..range.end.min(contents.len())];
+
+    let mut rendered = String::new();
+    writeln!(
+        rendered,
+        "{}",
+        palette.header(&format!(
+            "segment {segment_index} ({}), {} words",
+            permissions(segment_index),
+            contents.len()
+        ))
+    )
+    .unwrap();
+
+    for (line_index, line) in words.chunks(options.width).enumerate() {
+        // NOTE: Addresses wrap around, just as they would once the segment is loaded.
+        let address = options
+            .base
+            .wrapping_add((range.start + line_index * options.width) as u16);
+        let hex = line
+            .iter()
+            .map(|word| format!("{word:04x}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let gutter: String = line
+            .iter()
+            .map(|word| match u8::try_from(*word) {
+                Ok(byte) if byte.is_ascii_graphic() || byte == b' ' => char::from(byte),
+                _ => '.',
+            })
+            .collect();
+
+        // The hex of a short final line is padded, so that its gutter lines up with the others.
+        writeln!(
+            rendered,
+            "{address:#06x}  {hex:<hex_width$}  |{gutter}|",
+            hex_width = 5 * options.width - 1
+        )
+        .unwrap();
+    }
+
+    rendered
+}
+
+// Parse the argument to `--range`, which should be of the form `start..end`, where either bound
+// may be omitted, and each is a decimal or hexadecimal number.
+pub fn parse_range(range: &str) -> Result<Range<usize>, String> {
+    let (start, end) = range
+        .split_once("..")
+        .ok_or_else(|| format!("{range} is not a range of the form start..end"))?;
+
+    let start = match start {
+        "" => 0,
+        start => parse_number(start)?,
+    };
+    let end = match end {
+        "" => usize::MAX,
+        end => parse_number(end)?,
+    };
+
+    if start > end {
+        return Err(format!("the range {range} ends before it begins"));
+    }
+    Ok(start..end)
+}
+
+// Parse a decimal or hexadecimal number, as in `16` or `0x10`.
+fn parse_number(number: &str) -> Result<usize, String> {
+    match number.strip_prefix("0x") {
+        Some(digits) => usize::from_str_radix(digits, 16),
+        None => number.parse(),
+    }
+    .map_err(|e| format!("{number} is not a valid number: {e}"))
+}
+
+// Parse the argument to `--width`, which must be a positive number of words.
+pub fn parse_width(width: &str) -> Result<usize, String> {
+    match parse_number(width)? {
+        0 => Err("at least one word must be shown on each line".to_string()),
+        width => Ok(width),
+    }
+}
+
+// Parse the argument to `--base`, which must be a 16-bit address.
+pub fn parse_base(base: &str) -> Result<u16, String> {
+    u16::try_from(parse_number(base)?).map_err(|_| format!("{base} is not a 16-bit address"))
+}
@@ -1,29 +1,368 @@
-use clap::Parser;
+mod colour;
+mod csv;
+mod diff;
+mod disasm;
+mod display;
+mod dump;
+mod pager;
+mod symbolic;
+mod verify;
+mod xref;
+
+use clap::{Parser, Subcommand};
+use colour::Palette;
 use miette::{IntoDiagnostic, Result, WrapErr};
 use poki::Poki;
 
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::{self, IsTerminal, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process;
 
 #[derive(Parser, Debug, Clone, Hash, PartialEq, Eq)]
-#[command(version, about, long_about)]
+#[command(
+    version,
+    about,
+    long_about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    source_path: PathBuf,
-    // TODO: Add some flags to make it possible to granularize what lukin displays.
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// the poki files to examine, or - for stdin
+    #[arg(required = true)]
+    source_paths: Vec<PathBuf>,
+
+    /// after examining every poki file, display the combined sizes of their segments
+    #[arg(long)]
+    totals: bool,
+
+    /// when to colour the output
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        default_value_t,
+        global = true,
+        alias = "colour"
+    )]
+    color: colour::When,
+
+    /// write the output to this file, rather than to stdout
+    #[arg(long, short, value_name = "FILE", global = true)]
+    output: Option<PathBuf>,
+
+    /// write the output straight to stdout, rather than piping it through $PAGER when it doesn't
+    /// fit in the terminal
+    #[arg(long, global = true)]
+    no_pager: bool,
+
+    /// check the structure of the poki file, printing every problem found and failing if there
+    /// are any, rather than displaying the poki file
+    #[arg(long, alias = "validate")]
+    verify: bool,
+
+    /// display the poki file in the line-oriented text format, which is suited to diffing and
+    /// version control
+    #[arg(long)]
+    text_format: bool,
+
+    /// display the size of the poki file, and the header of every segment
+    #[arg(long)]
+    headers: bool,
+
+    /// display the contents of every segment
+    #[arg(long)]
+    segments: bool,
+
+    /// display the relocation table of every segment
+    #[arg(long)]
+    relocations: bool,
+
+    /// display the export table of every segment
+    #[arg(long)]
+    exports: bool,
+
+    /// display the table of unresolved symbols
+    #[arg(long)]
+    unresolved: bool,
+
+    /// display everything, rather than a summary
+    #[arg(long)]
+    all: bool,
+
+    /// display the contents of this segment (or of every segment, with all) as a hexdump, rather
+    /// than displaying the poki file
+    #[arg(
+        long,
+        value_name = "SEGMENT",
+        conflicts_with_all = ["verify", "text_format", "headers", "segments", "relocations", "exports", "unresolved", "all"]
+    )]
+    dump: Vec<dump::Target>,
+
+    /// dump only the words at these offsets within each segment, as in 0x10..0x20
+    #[arg(long, requires = "dump", value_parser = dump::parse_range)]
+    range: Option<Range<usize>>,
+
+    /// the number of words shown on each line of a dump
+    #[arg(long, requires = "dump", default_value = "8", value_parser = dump::parse_width)]
+    width: usize,
+
+    /// the address at which each dumped segment is loaded, which is added to the offsets shown
+    #[arg(long, requires = "dump", default_value = "0", value_parser = dump::parse_base)]
+    base: u16,
+
+    /// disassemble the contents of this segment (or of every executable segment, with all),
+    /// rather than displaying the poki file
+    #[arg(
+        long,
+        value_name = "SEGMENT",
+        conflicts_with_all = ["verify", "text_format", "headers", "segments", "relocations", "exports", "unresolved", "all", "dump"]
+    )]
+    disasm: Vec<dump::Target>,
+
+    /// display where this symbol is exported from, or whether it is unresolved, along with every
+    /// relocation which refers to it, rather than displaying the poki file
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = ["verify", "text_format", "headers", "segments", "relocations", "exports", "unresolved", "all", "dump", "disasm"]
+    )]
+    symbol: Option<String>,
+
+    /// display every exported and unresolved symbol, along with every relocation which refers to
+    /// it, rather than displaying the poki file
+    #[arg(
+        long,
+        conflicts_with_all = ["verify", "text_format", "headers", "segments", "relocations", "exports", "unresolved", "all", "dump", "disasm", "symbol"]
+    )]
+    xref: bool,
+
+    /// write this table of the poki file as CSV, with a header row, rather than displaying the
+    /// poki file
+    #[arg(
+        long,
+        value_name = "TABLE",
+        conflicts_with_all = ["verify", "text_format", "headers", "segments", "relocations", "exports", "unresolved", "all", "dump", "disasm", "symbol", "xref"]
+    )]
+    csv: Option<csv::Table>,
+
+    /// display every unresolved symbol, along with every relocation which refers to it, rather
+    /// than displaying the poki file
+    #[arg(
+        long,
+        conflicts_with_all = ["verify", "text_format", "headers", "segments", "relocations", "exports", "unresolved", "all", "dump", "disasm", "symbol", "xref", "csv"]
+    )]
+    unresolved_uses: bool,
+}
+
+#[derive(Subcommand, Debug, Clone, Hash, PartialEq, Eq)]
+enum Command {
+    /// compare two poki files, printing the differences between them as a unified diff, and
+    /// exiting with status 1 if there are any
+    Diff {
+        /// the original poki file, or - for stdin
+        a_path: PathBuf,
+
+        /// the modified poki file, or - for stdin
+        b_path: PathBuf,
+
+        /// don't compare the relocation tables
+        #[arg(long)]
+        ignore_relocations: bool,
+
+        /// compare only the export tables
+        #[arg(long)]
+        exports_only: bool,
+    },
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    let palette = args
+        .color
+        .palette(args.output.is_none() && io::stdout().is_terminal());
 
-    let mut source = File::open(&args.source_path)
-        .into_diagnostic()
-        .wrap_err_with(|| format!("unable to read source from {}", args.source_path.display()))?;
+    if let Some(Command::Diff {
+        a_path,
+        b_path,
+        ignore_relocations,
+        exports_only,
+    }) = &args.command
+    {
+        let a = read_poki(a_path)?;
+        let b = read_poki(b_path)?;
 
-    let poki = Poki::deserialize(&mut source)
-        .into_diagnostic()
-        .wrap_err("unable to deserialize provided poki file")?;
+        let options = diff::Options {
+            ignore_relocations: *ignore_relocations,
+            exports_only: *exports_only,
+        };
+        let changes = diff::diff(&a, &b, options);
+        if !changes.is_empty() {
+            let rendered = diff::render(
+                &changes,
+                &a_path.display().to_string(),
+                &b_path.display().to_string(),
+                !palette.is_plain(),
+            );
+            emit(&rendered, &args)?;
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // NOTE: When several poki files are examined, a failure to examine one of them doesn't stop
+    // the rest from being examined, but is reported as it happens, and is reflected in the exit
+    // code once all of them have been.
+    let paths = &args.source_paths;
+    if paths.len() == 1 && !args.totals {
+        let output = examine(&args, &paths[0], &read_bytes(&paths[0])?, palette)?;
+        return emit(&output, &args);
+    }
+
+    let mut output = String::new();
+    let mut pokis = Vec::new();
+    let mut failures = 0;
+    for (index, path) in paths.iter().enumerate() {
+        if index != 0 {
+            output.push('\n');
+        }
+        output.push_str(&palette.header(&format!("==> {} <==", path.display())));
+        output.push('\n');
+
+        let result = read_bytes(path).and_then(|bytes| {
+            output.push_str(&examine(&args, path, &bytes, palette)?);
+            Ok(bytes)
+        });
+        match result {
+            Ok(bytes) => pokis.extend(Poki::from_slice(&bytes).ok()),
+            Err(report) => {
+                eprintln!("{report:?}");
+                failures += 1;
+            }
+        }
+    }
 
-    println!("{:?}", poki);
+    if args.totals {
+        output.push('\n');
+        output.push_str(&display::render_totals(&pokis, palette));
+    }
+    emit(&output, &args)?;
 
+    if failures != 0 {
+        miette::bail!("unable to examine {failures} of {} poki files", paths.len());
+    }
     Ok(())
 }
+
+// Examine the poki file at `path`, whose serialization is `bytes`, as `args` asks, returning the
+// output.
+fn examine(args: &Args, path: &Path, bytes: &[u8], palette: Palette) -> Result<String> {
+    // NOTE: A poki file which is being verified may not even deserialize, so it is verified from
+    // its bytes, rather than being read as usual.
+    if args.verify {
+        let problems = verify::verify(bytes);
+        if !problems.is_empty() {
+            for problem in &problems {
+                eprintln!("{problem}");
+            }
+            miette::bail!("poki file has {} structural problems", problems.len());
+        }
+        return Ok(String::new());
+    }
+
+    let poki = Poki::from_slice(bytes)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to deserialize poki file {}", path.display()))?;
+
+    if let Some(table) = args.csv {
+        return Ok(csv::render(&poki, table));
+    }
+
+    if let Some(name) = &args.symbol {
+        return match xref::render_symbol(&poki, name, palette) {
+            Some(rendered) => Ok(rendered),
+            None => miette::bail!("no symbol named {name} is exported or unresolved"),
+        };
+    }
+
+    if args.xref {
+        return Ok(xref::render_xref(&poki, palette));
+    }
+
+    if args.unresolved_uses {
+        return Ok(xref::render_unresolved_uses(&poki, palette));
+    }
+
+    if !args.disasm.is_empty() {
+        return Ok(disasm::render(&poki, &args.disasm, palette));
+    }
+
+    if !args.dump.is_empty() {
+        let options = dump::Options {
+            range: args.range.clone(),
+            width: args.width,
+            base: args.base,
+        };
+        return Ok(dump::render(&poki, &args.dump, &options, palette));
+    }
+
+    // NOTE: The text format is meant to be read by other tools, so it is never coloured.
+    if args.text_format {
+        let mut text = Vec::new();
+        poki.serialize_text(&mut text)
+            .into_diagnostic()
+            .wrap_err("unable to write poki file as text")?;
+        return String::from_utf8(text)
+            .into_diagnostic()
+            .wrap_err("unable to write poki file as text");
+    }
+
+    // NOTE: When nothing in particular is asked for, a summary is displayed.
+    let sections = if args.all {
+        display::Sections::all()
+    } else {
+        display::Sections {
+            headers: args.headers,
+            segments: args.segments,
+            relocations: args.relocations,
+            exports: args.exports,
+            unresolved: args.unresolved,
+        }
+    };
+    Ok(display::render(&poki, sections, palette))
+}
+
+// Write `output` to the file given by `args`, if any, and otherwise to stdout, through the pager
+// if need be.
+fn emit(output: &str, args: &Args) -> Result<()> {
+    match &args.output {
+        Some(path) => std::fs::write(path, output)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("unable to write output to {}", path.display())),
+        None => pager::page(output, !args.no_pager)
+            .into_diagnostic()
+            .wrap_err("unable to write output"),
+    }
+}
+
+fn read_poki(path: &Path) -> Result<Poki> {
+    Poki::from_slice(&read_bytes(path)?)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("unable to deserialize poki file {}", path.display()))
+}
+
+fn read_bytes(path: &Path) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    if path == Path::new("-") {
+        io::stdin().lock().read_to_end(&mut bytes)
+    } else {
+        File::open(path).and_then(|mut file| file.read_to_end(&mut bytes))
+    }
+    .into_diagnostic()
+    .wrap_err_with(|| format!("unable to read source from {}", path.display()))?;
+    Ok(bytes)
+}
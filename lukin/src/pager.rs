@@ -0,0 +1,47 @@
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Child, Command, Stdio};
+
+// Write `output` to stdout, piping it through the user's pager if `paging` is allowed, stdout is a
+// terminal, and `output` has more lines than fit in the terminal, as git does.
+pub fn page(output: &str, paging: bool) -> io::Result<()> {
+    if paging && io::stdout().is_terminal() && exceeds_terminal(output) {
+        if let Some(mut pager) = spawn_pager() {
+            // NOTE: The pager may well be quit before it has read all of the output, which
+            // closes the pipe, and isn't a failure.
+            if let Some(mut stdin) = pager.stdin.take() {
+                let _ = stdin.write_all(output.as_bytes());
+            }
+            pager.wait()?;
+            return Ok(());
+        }
+    }
+
+    io::stdout().lock().write_all(output.as_bytes())
+}
+
+fn exceeds_terminal(output: &str) -> bool {
+    match terminal_size::terminal_size() {
+        Some((_, terminal_size::Height(height))) => output.lines().count() >= usize::from(height),
+        None => false,
+    }
+}
+
+// Spawn the pager named by PAGER, or less if it isn't set, or `None` if it can't be spawned, or
+// there is no pager to speak of.
+//
+// NOTE: As git does, less is told to quit if the output fits on one screen after all, to pass
+// colours through, and not to clear the screen, unless LESS says otherwise.
+fn spawn_pager() -> Option<Child> {
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    if pager.is_empty() || pager == "cat" {
+        return None;
+    }
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(&pager).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    command.spawn().ok()
+}
@@ -0,0 +1,89 @@
+use crate::colour::Palette;
+
+use poki::{Poki, RelocationTableEntry};
+
+// What a relocation refers to, in terms of the symbols of the poki file which contains it.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Target<'a> {
+    // Somewhere within the block beginning at an export, `delta` words after it.
+    Export {
+        label: &'a str,
+        delta: u16,
+    },
+    Unresolved(&'a str),
+    // An index past the end of the table of unresolved symbols, which only a corrupt poki file
+    // contains.
+    Corrupt(u16),
+    // Somewhere which no export precedes.
+    Unnamed {
+        segment_index: u16,
+        segment_offset: u16,
+    },
+}
+
+impl<'a> Target<'a> {
+    pub fn resolve(poki: &'a Poki, relocation_table_entry: &RelocationTableEntry) -> Self {
+        if relocation_table_entry.segment_index == 0xFFFF {
+            return poki
+                .unresolved_table
+                .get(usize::from(relocation_table_entry.segment_offset))
+                .map_or(
+                    Target::Corrupt(relocation_table_entry.segment_offset),
+                    |symbol| Target::Unresolved(symbol),
+                );
+        }
+
+        nearest_export(
+            poki,
+            usize::from(relocation_table_entry.segment_index),
+            relocation_table_entry.segment_offset,
+        )
+        .map_or(
+            Target::Unnamed {
+                segment_index: relocation_table_entry.segment_index,
+                segment_offset: relocation_table_entry.segment_offset,
+            },
+            |(label, delta)| Target::Export { label, delta },
+        )
+    }
+
+    // Render the target as in `_start+0x0003`, `unresolved putc`, or `segment 5 @ 0x0003`.
+    pub fn render(self, palette: Palette) -> String {
+        match self {
+            Target::Export { label, delta } => palette.target(&label_plus_delta(label, delta)),
+            Target::Unresolved(symbol) => format!("unresolved {}", palette.unresolved(symbol)),
+            Target::Corrupt(index) => {
+                palette.unresolved(&format!("unresolved symbol {index} (corrupt)"))
+            }
+            Target::Unnamed {
+                segment_index,
+                segment_offset,
+            } => palette.target(&format!("segment {segment_index} @ {segment_offset:#06x}")),
+        }
+    }
+}
+
+// The export at or nearest before `offset` in the given segment, along with how far `offset` lies
+// beyond it.
+pub fn nearest_export(poki: &Poki, segment_index: usize, offset: u16) -> Option<(&str, u16)> {
+    poki.segments
+        .get(segment_index)?
+        .export_table
+        .iter()
+        .filter(|export_table_entry| export_table_entry.offset <= offset)
+        .max_by_key(|export_table_entry| export_table_entry.offset)
+        .map(|export_table_entry| {
+            (
+                export_table_entry.label.as_str(),
+                offset - export_table_entry.offset,
+            )
+        })
+}
+
+// A label, followed by how far beyond it something lies if it doesn't lie exactly at the label.
+pub fn label_plus_delta(label: &str, delta: u16) -> String {
+    match delta {
+        0 => label.to_string(),
+        delta => format!("{label}+{delta:#06x}"),
+    }
+}
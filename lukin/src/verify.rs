@@ -0,0 +1,61 @@
+use poki::{Poki, PokiDeserializationError};
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+
+// Check the structure of the poki file whose serialization is `bytes`, returning a description of
+// every problem found, or nothing if the file is well-formed.
+//
+// NOTE: The sizes given by the segment headers are checked by deserialization itself, which fails
+// if they claim more data than the file contains, so there is nothing more to check about them
+// once the file has been deserialized.
+pub fn verify(bytes: &[u8]) -> Vec<String> {
+    let poki = match Poki::from_slice(bytes) {
+        Ok(poki) => poki,
+        Err(PokiDeserializationError::IOError(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+            return vec![format!(
+                "the headers claim more data than the file's {} bytes contain",
+                bytes.len()
+            )];
+        }
+        Err(e) => return vec![format!("the file is not a poki file: {e}")],
+    };
+
+    let mut problems = Vec::new();
+
+    if let Err(errors) = poki.verify_relocations() {
+        problems.extend(errors.iter().map(ToString::to_string));
+    }
+
+    // NOTE: A label may be exported from the offset one past the end of a segment, since a label
+    // may be defined at the very end of one.
+    for (segment_index, segment) in poki.segments.iter().enumerate() {
+        for (entry_index, export_table_entry) in segment.export_table.iter().enumerate() {
+            if usize::from(export_table_entry.offset) > segment.contents.len() {
+                problems.push(format!(
+                    "export {entry_index} of segment {segment_index} exports {} from offset {:#06x}, which lies past the end of the segment",
+                    export_table_entry.label, export_table_entry.offset
+                ));
+            }
+        }
+    }
+
+    let mut exporting_segments: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (segment_index, export_table_entry) in poki.find_all_exports() {
+        exporting_segments
+            .entry(&export_table_entry.label)
+            .or_default()
+            .push(segment_index.to_string());
+    }
+    for (label, segment_indices) in exporting_segments {
+        if segment_indices.len() > 1 {
+            problems.push(format!(
+                "{label} is exported {} times, from segments {}",
+                segment_indices.len(),
+                segment_indices.join(", ")
+            ));
+        }
+    }
+
+    problems
+}
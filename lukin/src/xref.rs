@@ -0,0 +1,206 @@
+use crate::colour::Palette;
+use crate::symbolic::{label_plus_delta, nearest_export, Target};
+
+use poki::Poki;
+
+use std::fmt::Write;
+
+// A word which a relocation patches, along with what the relocation refers to.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+struct Reference<'a> {
+    segment_index: usize,
+    offset: u16,
+    target: Target<'a>,
+}
+
+fn references(poki: &Poki) -> Vec<Reference<'_>> {
+    let mut references = Vec::new();
+    for (segment_index, segment) in poki.segments.iter().enumerate() {
+        for relocation_table_entry in &segment.relocation_table {
+            references.push(Reference {
+                segment_index,
+                offset: relocation_table_entry.offset,
+                target: Target::resolve(poki, relocation_table_entry),
+            });
+        }
+    }
+    references
+}
+
+// Render the site of a reference, as in `segment 5 @ 0x0003, in _start+0x0003`, followed by
+// `suffix`.
+fn render_site(poki: &Poki, reference: &Reference, suffix: &str) -> String {
+    let mut rendered = format!(
+        "  segment {} @ {:#06x}",
+        reference.segment_index, reference.offset
+    );
+    if let Some((label, delta)) = nearest_export(poki, reference.segment_index, reference.offset) {
+        write!(rendered, ", in {}", label_plus_delta(label, delta)).unwrap();
+    }
+    writeln!(rendered, "{suffix}").unwrap();
+    rendered
+}
+
+// Render the sites of the references to the export `label`, noting those which refer to somewhere
+// after the label itself, rather than to the label.
+fn render_export_references(poki: &Poki, references: &[Reference], label: &str) -> String {
+    let mut rendered = String::new();
+    for reference in references {
+        if let Target::Export {
+            label: target_label,
+            delta,
+        } = reference.target
+        {
+            if target_label == label {
+                let suffix = match delta {
+                    0 => String::new(),
+                    delta => format!(", to {}", label_plus_delta(label, delta)),
+                };
+                rendered.push_str(&render_site(poki, reference, &suffix));
+            }
+        }
+    }
+    rendered
+}
+
+fn render_unresolved_references(poki: &Poki, references: &[Reference], symbol: &str) -> String {
+    references
+        .iter()
+        .filter(|reference| reference.target == Target::Unresolved(symbol))
+        .map(|reference| render_site(poki, reference, ""))
+        .collect()
+}
+
+// Render where `name` is defined, if it is exported, or whether it is unresolved, along with every
+// site which refers to it, or `None` if there is no such symbol.
+pub fn render_symbol(poki: &Poki, name: &str, palette: Palette) -> Option<String> {
+    let exports: Vec<_> = poki
+        .find_all_exports()
+        .filter(|(_, export_table_entry)| export_table_entry.label == name)
+        .collect();
+    let unresolved = poki.unresolved_table.iter().any(|symbol| symbol == name);
+    if exports.is_empty() && !unresolved {
+        return None;
+    }
+
+    let references = references(poki);
+    let mut sites = String::new();
+    let mut rendered = String::new();
+    for (segment_index, export_table_entry) in exports {
+        writeln!(
+            rendered,
+            "{} is exported from segment {segment_index} @ {:#06x}",
+            palette.label(name),
+            export_table_entry.offset
+        )
+        .unwrap();
+    }
+    if unresolved {
+        writeln!(rendered, "{} is unresolved", palette.unresolved(name)).unwrap();
+    }
+
+    // NOTE: An export and an unresolved symbol may only share a name in a malformed poki file, but
+    // the references to either are listed together all the same.
+    sites.push_str(&render_export_references(poki, &references, name));
+    sites.push_str(&render_unresolved_references(poki, &references, name));
+    if sites.is_empty() {
+        writeln!(rendered, "nothing refers to {name}").unwrap();
+    } else {
+        writeln!(rendered, "{}", palette.header("referenced from")).unwrap();
+        rendered.push_str(&sites);
+    }
+
+    Some(rendered)
+}
+
+// Render every export, followed by every unresolved symbol, each along with every site which
+// refers to it. Sites which refer to something which no export precedes, or to an unresolved
+// symbol which is missing from the table, are listed last.
+pub fn render_xref(poki: &Poki, palette: Palette) -> String {
+    let references = references(poki);
+
+    let mut rendered = String::new();
+    for (segment_index, export_table_entry) in poki.find_all_exports() {
+        writeln!(
+            rendered,
+            "{} (segment {segment_index} @ {:#06x})",
+            palette.label(&export_table_entry.label),
+            export_table_entry.offset
+        )
+        .unwrap();
+        rendered.push_str(&render_export_references(
+            poki,
+            &references,
+            &export_table_entry.label,
+        ));
+    }
+    for symbol in &poki.unresolved_table {
+        writeln!(rendered, "{} (unresolved)", palette.unresolved(symbol)).unwrap();
+        rendered.push_str(&render_unresolved_references(poki, &references, symbol));
+    }
+
+    let unnamed: String = references
+        .iter()
+        .filter_map(|reference| match reference.target {
+            target @ (Target::Corrupt(_) | Target::Unnamed { .. }) => Some(render_site(
+                poki,
+                reference,
+                &format!(", to {}", target.render(palette)),
+            )),
+            _ => None,
+        })
+        .collect();
+    if !unnamed.is_empty() {
+        writeln!(rendered, "{}", palette.header("(no symbol)")).unwrap();
+        rendered.push_str(&unnamed);
+    }
+
+    if rendered.is_empty() {
+        writeln!(rendered, "no symbols are exported or unresolved").unwrap();
+    }
+    rendered
+}
+
+// Render every unresolved symbol, along with its index and every site which refers to it. Sites
+// which refer to an index past the end of the table of unresolved symbols are listed last, as only
+// a corrupt poki file contains them.
+pub fn render_unresolved_uses(poki: &Poki, palette: Palette) -> String {
+    let references = references(poki);
+
+    let mut rendered = String::new();
+    for (index, symbol) in poki.unresolved_table.iter().enumerate() {
+        writeln!(
+            rendered,
+            "{} (unresolved symbol {index})",
+            palette.unresolved(symbol)
+        )
+        .unwrap();
+        let sites = render_unresolved_references(poki, &references, symbol);
+        if sites.is_empty() {
+            writeln!(rendered, "  nothing refers to it").unwrap();
+        } else {
+            rendered.push_str(&sites);
+        }
+    }
+
+    let corrupt: String = references
+        .iter()
+        .filter_map(|reference| match reference.target {
+            target @ Target::Corrupt(_) => Some(render_site(
+                poki,
+                reference,
+                &format!(", to {}", target.render(palette)),
+            )),
+            _ => None,
+        })
+        .collect();
+    if !corrupt.is_empty() {
+        writeln!(rendered, "{}", palette.header("(corrupt)")).unwrap();
+        rendered.push_str(&corrupt);
+    }
+
+    if rendered.is_empty() {
+        writeln!(rendered, "no symbols are unresolved").unwrap();
+    }
+    rendered
+}
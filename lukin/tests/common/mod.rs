@@ -0,0 +1,61 @@
+// NOTE: Each test binary includes this module, but not every one of them uses all of it.
+#![allow(dead_code)]
+
+use poki::{ExportTableEntry, Poki, RelocationTableEntry};
+
+use std::fs::File;
+use std::path::PathBuf;
+use std::process::Command;
+
+// A small poki file with something in every section, as pali would assemble from a program which
+// calls an external `putc`. Tests which need something more particular change it to suit.
+pub fn poki() -> Poki {
+    let mut poki = Poki::new_empty();
+    poki.segments[5].contents = vec![0x0028, 0x0000, 0x0068, 0x0000];
+    poki.segments[5].relocation_table = vec![
+        RelocationTableEntry {
+            offset: 1,
+            segment_index: 5,
+            segment_offset: 0,
+        },
+        RelocationTableEntry {
+            offset: 3,
+            segment_index: 0xFFFF,
+            segment_offset: 0,
+        },
+    ];
+    poki.segments[5].export_table = vec![ExportTableEntry {
+        label: "_start".to_string(),
+        offset: 0,
+    }];
+    poki.unresolved_table = vec!["putc".to_string()];
+    poki
+}
+
+// NOTE: Tests run in parallel, so each of them writes to paths of its own, named after both the
+// test binary and the test.
+pub fn path(name: &str, extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "lukin-{}-{name}-{}.{extension}",
+        env!("CARGO_CRATE_NAME"),
+        std::process::id()
+    ))
+}
+
+pub fn write(name: &str, poki: &Poki) -> PathBuf {
+    let path = path(name, "poki");
+    poki.serialize(&mut File::create(&path).unwrap()).unwrap();
+    path
+}
+
+// Run lukin with `args` on `poki`, which is written to a path of its own, expecting it to succeed,
+// and return what it printed.
+pub fn lukin(name: &str, poki: &Poki, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_lukin"))
+        .arg(write(name, poki))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::path;
+use poki::{ExportTableEntry, Poki};
+
+// The poki file of `common`, but with a label which needs quoting, and a relocation into the middle
+// of a block.
+fn poki() -> Poki {
+    let mut poki = common::poki();
+    poki.segments[5].relocation_table[0].segment_offset = 2;
+    poki.segments[5].export_table.push(ExportTableEntry {
+        label: "a,\"b\"".to_string(),
+        offset: 4,
+    });
+    poki
+}
+
+fn lukin(name: &str, args: &[&str]) -> String {
+    common::lukin(name, &poki(), args)
+}
+
+#[test]
+fn exports_are_written_with_labels_quoted() {
+    assert_eq!(
+        lukin("exports", &["--csv", "exports"]),
+        "segment,offset,label
+5,0,_start
+5,4,\"a,\"\"b\"\"\"
+"
+    );
+}
+
+#[test]
+fn relocations_are_written_with_their_targets() {
+    assert_eq!(
+        lukin("relocations", &["--csv", "relocations"]),
+        "segment,offset,target_segment,target_offset,target_label
+5,1,5,2,_start+0x0002
+5,3,65535,0,putc
+"
+    );
+}
+
+#[test]
+fn segments_are_written() {
+    assert_eq!(
+        lukin("segments", &["--csv", "segments"]),
+        "index,permissions,words,relocs,exports
+0,---,0,0,0
+1,--x,0,0,0
+2,-w-,0,0,0
+3,-wx,0,0,0
+4,r--,0,0,0
+5,r-x,4,2,2
+6,rw-,0,0,0
+7,rwx,0,0,0
+"
+    );
+}
+
+#[test]
+fn tables_are_written_to_the_output_file() {
+    let output = path("output", "csv");
+    let output_arg = output.to_str().unwrap();
+    assert_eq!(
+        lukin("output", &["--csv", "exports", "--output", output_arg]),
+        ""
+    );
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "segment,offset,label
+5,0,_start
+5,4,\"a,\"\"b\"\"\"
+"
+    );
+}
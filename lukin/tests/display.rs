@@ -0,0 +1,225 @@
+mod common;
+
+use common::poki;
+use poki::RelocationTableEntry;
+
+use std::process::Command;
+
+fn lukin(name: &str, args: &[&str]) -> String {
+    common::lukin(name, &poki(), args)
+}
+
+#[test]
+fn summary_is_displayed_by_default() {
+    assert_eq!(
+        lukin("summary", &[]),
+        "poki file of 102 bytes, with 1 exports and 1 unresolved symbols
+segment  perms     words  relocations  exports
+5        r-x           4            2        1
+"
+    );
+}
+
+#[test]
+fn only_the_selected_sections_are_displayed() {
+    assert_eq!(
+        lukin("selected", &["--relocations", "--exports", "--unresolved"]),
+        "relocations in segment 5
+  0x0001 -> _start
+  0x0003 -> unresolved putc
+
+exports in segment 5
+  _start @ 0x0000
+
+unresolved symbols
+  0x0000: putc
+"
+    );
+}
+
+#[test]
+fn all_sections_are_displayed_with_all() {
+    assert_eq!(
+        lukin("all", &["--all"]),
+        "headers (102 bytes)
+segment  perms     words  relocations  exports
+0        ---           0            0        0
+1        --x           0            0        0
+2        -w-           0            0        0
+3        -wx           0            0        0
+4        r--           0            0        0
+5        r-x           4            2        1
+6        rw-           0            0        0
+7        rwx           0            0        0
+
+contents of segment 5 (r-x)
+  0x0000: 0028 0000 0068 0000
+
+relocations in segment 5
+  0x0001 -> _start
+  0x0003 -> unresolved putc
+
+exports in segment 5
+  _start @ 0x0000
+
+unresolved symbols
+  0x0000: putc
+"
+    );
+}
+
+#[test]
+fn segments_are_dumped_with_offsets_and_characters() {
+    assert_eq!(
+        lukin("dump", &["--dump", "5", "--width", "3", "--base", "0x100"]),
+        "segment 5 (r-x), 4 words
+0x0100  0028 0000 0068  |(.h|
+0x0103  0000            |.|
+"
+    );
+}
+
+#[test]
+fn segments_are_disassembled_with_exports_and_relocations() {
+    assert_eq!(
+        lukin("disasm", &["--disasm", "5"]),
+        "segment 5 (r-x)
+_start:
+  0x0000: 0028 0000  jal r0, r0, 0x0000  -> _start
+  0x0002: 0068 0000  jal r1, r0, 0x0000  -> unresolved putc
+"
+    );
+}
+
+#[test]
+fn symbols_are_cross_referenced() {
+    assert_eq!(
+        lukin("xref", &["--xref"]),
+        "_start (segment 5 @ 0x0000)
+  segment 5 @ 0x0001, in _start+0x0001
+putc (unresolved)
+  segment 5 @ 0x0003, in _start+0x0003
+"
+    );
+}
+
+#[test]
+fn a_single_symbol_is_looked_up() {
+    assert_eq!(
+        lukin("symbol", &["--symbol", "putc"]),
+        "putc is unresolved
+referenced from
+  segment 5 @ 0x0003, in _start+0x0003
+"
+    );
+}
+
+#[test]
+fn several_poki_files_are_examined_with_totals() {
+    let path = common::write("several", &poki());
+    let output = Command::new(env!("CARGO_BIN_EXE_lukin"))
+        .args([&path, &path])
+        .arg("--totals")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let summary = "poki file of 102 bytes, with 1 exports and 1 unresolved symbols
+segment  perms     words  relocations  exports
+5        r-x           4            2        1
+";
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "==> {path} <==
+{summary}
+==> {path} <==
+{summary}
+totals over 2 poki files of 204 bytes, with 2 exports and 2 unresolved symbols
+segment  perms     words  relocations  exports
+5        r-x           8            4        2
+",
+            path = path.display()
+        )
+    );
+}
+
+#[test]
+fn output_is_coloured_when_asked() {
+    assert_eq!(
+        lukin(
+            "colour",
+            &["--exports", "--unresolved", "--color", "always"]
+        ),
+        "\x1b[1mexports in segment 5\x1b[0m
+  \x1b[32m_start\x1b[0m @ 0x0000
+
+\x1b[1munresolved symbols\x1b[0m
+  0x0000: \x1b[31mputc\x1b[0m
+"
+    );
+}
+
+#[test]
+fn output_isnt_coloured_when_not_a_terminal() {
+    // NOTE: Tests capture the output of lukin, so stdout is never a terminal.
+    assert_eq!(
+        lukin("plain", &["--unresolved", "--color", "auto"]),
+        "unresolved symbols
+  0x0000: putc
+"
+    );
+}
+
+#[test]
+fn relocation_targets_are_named_by_the_nearest_export() {
+    let mut poki = poki();
+    poki.segments[5].relocation_table = vec![
+        RelocationTableEntry {
+            offset: 1,
+            segment_index: 5,
+            segment_offset: 2,
+        },
+        RelocationTableEntry {
+            offset: 3,
+            segment_index: 6,
+            segment_offset: 0,
+        },
+        RelocationTableEntry {
+            offset: 3,
+            segment_index: 0xFFFF,
+            segment_offset: 1,
+        },
+    ];
+    assert_eq!(
+        common::lukin("nearest", &poki, &["--relocations"]),
+        "relocations in segment 5
+  0x0001 -> _start+0x0002
+  0x0003 -> segment 6 @ 0x0000
+  0x0003 -> unresolved symbol 1 (corrupt)
+"
+    );
+}
+
+#[test]
+fn uses_of_unresolved_symbols_are_listed() {
+    let mut poki = poki();
+    poki.unresolved_table.push("getc".to_string());
+    poki.segments[5]
+        .relocation_table
+        .push(RelocationTableEntry {
+            offset: 2,
+            segment_index: 0xFFFF,
+            segment_offset: 2,
+        });
+    assert_eq!(
+        common::lukin("unresolved-uses", &poki, &["--unresolved-uses"]),
+        "putc (unresolved symbol 0)
+  segment 5 @ 0x0003, in _start+0x0003
+getc (unresolved symbol 1)
+  nothing refers to it
+(corrupt)
+  segment 5 @ 0x0002, in _start+0x0002, to unresolved symbol 2 (corrupt)
+"
+    );
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::poki;
+use poki::{ExportTableEntry, Poki};
+
+use std::process::{Command, Output};
+
+fn serialize(poki: &Poki) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    poki.serialize(&mut bytes).unwrap();
+    bytes
+}
+
+fn verify(name: &str, bytes: &[u8]) -> Output {
+    let path = common::path(name, "poki");
+    std::fs::write(&path, bytes).unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_lukin"))
+        .arg(&path)
+        .arg("--verify")
+        .output()
+        .unwrap()
+}
+
+// Verify `bytes`, expecting it to fail with `problem` as the first line of stderr.
+fn assert_problem(name: &str, bytes: &[u8], problem: &str) {
+    let output = verify(name, bytes);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.lines().next(), Some(problem));
+}
+
+#[test]
+fn well_formed_poki_file_passes() {
+    let output = verify("clean", &serialize(&poki()));
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn relocation_past_end_of_segment_is_reported() {
+    let mut poki = poki();
+    poki.segments[5].relocation_table[0].offset = 4;
+    assert_problem(
+        "relocation",
+        &serialize(&poki),
+        "relocation 0 of segment 5 patches the word at offset 0x0004, which lies past the end of the segment",
+    );
+}
+
+#[test]
+fn export_past_end_of_segment_is_reported() {
+    let mut poki = poki();
+    poki.segments[5].export_table[0].offset = 5;
+    assert_problem(
+        "export",
+        &serialize(&poki),
+        "export 0 of segment 5 exports _start from offset 0x0005, which lies past the end of the segment",
+    );
+}
+
+#[test]
+fn invalid_unresolved_index_is_reported() {
+    let mut poki = poki();
+    poki.segments[5].relocation_table[1].segment_offset = 1;
+    assert_problem(
+        "unresolved",
+        &serialize(&poki),
+        "relocation 1 of segment 5 refers to unresolved symbol 1, but there are not that many unresolved symbols",
+    );
+}
+
+#[test]
+fn duplicate_exports_are_reported() {
+    let mut poki = poki();
+    poki.segments[6].export_table = vec![ExportTableEntry {
+        label: "_start".to_string(),
+        offset: 0,
+    }];
+    assert_problem(
+        "duplicate",
+        &serialize(&poki),
+        "_start is exported 2 times, from segments 5, 6",
+    );
+}
+
+#[test]
+fn headers_claiming_too_much_data_are_reported() {
+    // NOTE: Cutting the file off partway through the contents of segment 5 leaves its header
+    // claiming more words than remain.
+    let bytes = serialize(&poki());
+    assert_problem(
+        "truncated",
+        &bytes[..60],
+        "the headers claim more data than the file's 60 bytes contain",
+    );
+}
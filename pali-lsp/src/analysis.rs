@@ -0,0 +1,279 @@
+use crate::docs;
+
+use pali::assemble::Assembler;
+use pali::lex::{ControlStatusRegister, Lexer, Register, Token, TokenKind};
+use pali::parse::{opcode_name, opcode_shape, Code, CodeKind, Defines, Parser};
+use pali::source::{SourceFile, Sources};
+
+use miette::{Report, SourceSpan};
+use strum::{EnumMessage, IntoEnumIterator};
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+// The kind of a completion, which an editor may use to decide how to show it.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum CompletionKind {
+    Opcode,
+    Register,
+    ControlStatusRegister,
+    Constant,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    // A brief note shown alongside the label, such as the form in which an opcode is written.
+    pub detail: String,
+}
+
+// NOTE: Each document is analyzed on its own, as though it were the only file being assembled, so
+// every span refers to a byte offset within the document itself.
+fn sources(text: &str) -> Sources {
+    Sources::new([("document.pali".to_string(), text.to_string())])
+}
+
+// Assemble `text`, returning every error and warning reported while doing so.
+pub fn check(text: &str, defines: &Defines) -> Vec<Report> {
+    let sources = sources(text);
+    match Assembler::try_new(&sources, defines).and_then(|assembler| assembler.assemble()) {
+        Ok(assembly) => assembly.warnings,
+        Err(errors) => errors,
+    }
+}
+
+// Find the markdown documentation of the opcode at `offset` in `text`, if there is one, along with
+// the span of the opcode.
+pub fn hover(text: &str, offset: usize) -> Option<(Range<usize>, String)> {
+    let sources = sources(text);
+    let token = token_at(&sources, &sources.files()[0], offset)?;
+    let documentation = match token.token_kind {
+        TokenKind::Opcode(opcode) => docs::document_opcode(opcode),
+        TokenKind::PseudoOpcode(pseudo_opcode) => docs::document_pseudo_opcode(pseudo_opcode),
+        _ => return None,
+    };
+    Some((range(token.source_span), documentation))
+}
+
+// Find the span of the label of the block to which the label at `offset` in `text` refers, if there
+// is one. Labels are resolved just as the assembler resolves them, so a label is looked for within
+// the blocks enclosing `offset`, from the innermost outwards, before it is looked for as a full
+// label.
+//
+// NOTE: Blocks can only be found once the whole document has been parsed, so nothing can be found
+// while the document contains errors.
+pub fn definition(text: &str, defines: &Defines, offset: usize) -> Option<Range<usize>> {
+    let sources = sources(text);
+    let file = &sources.files()[0];
+    let TokenKind::Label(label) = token_at(&sources, file, offset)?.token_kind else {
+        return None;
+    };
+    let program = Parser::new(&sources, file, defines).parse().ok()?;
+
+    let mut definitions = HashMap::new();
+    let mut ctx = String::new();
+    for code in program.segments.iter().flatten() {
+        collect_definitions(code, "", offset, &mut definitions, &mut ctx);
+    }
+
+    // NOTE: The label of an export lies outside of any block, and is resolved relative to the
+    // module of the file in which it appears.
+    if let Some(export) = program
+        .exports
+        .iter()
+        .find(|export| contains(export.label.source_span, offset))
+    {
+        ctx = export.module.unwrap_or_default().to_string();
+    }
+
+    let mut ctx = ctx.as_str();
+    while !ctx.is_empty() {
+        if let Some(source_span) = definitions.get(&format!("{ctx}.{label}")) {
+            return Some(range(*source_span));
+        }
+        ctx = ctx.rsplit_once('.').map_or("", |(parent, _)| parent);
+    }
+    definitions
+        .get(label)
+        .map(|source_span| range(*source_span))
+}
+
+// Every opcode, register, control/status register, and constant, in that order.
+pub fn completions(defines: &Defines) -> Vec<Completion> {
+    let opcodes = docs::OPCODES.iter().map(|(opcode, _)| Completion {
+        label: opcode_name(*opcode),
+        kind: CompletionKind::Opcode,
+        detail: opcode_shape(*opcode),
+    });
+    let pseudo_opcodes = docs::PSEUDO_OPCODES
+        .iter()
+        .map(|(pseudo_opcode, shapes, _)| Completion {
+            label: docs::pseudo_opcode_name(*pseudo_opcode),
+            kind: CompletionKind::Opcode,
+            detail: shapes.join(", "),
+        });
+    // NOTE: The first name of each register is the one by which it is numbered, and any others are
+    // aliases, which are shown alongside the register for which they stand.
+    let registers = Register::iter().flat_map(|register| {
+        let names = register.get_serializations();
+        names
+            .iter()
+            .enumerate()
+            .map(move |(index, name)| Completion {
+                label: name.to_lowercase(),
+                kind: CompletionKind::Register,
+                detail: if index == 0 {
+                    String::new()
+                } else {
+                    names[0].to_lowercase()
+                },
+            })
+    });
+    let control_status_registers =
+        ControlStatusRegister::iter().map(|control_status_register| Completion {
+            label: control_status_register.get_serializations()[0].to_lowercase(),
+            kind: CompletionKind::ControlStatusRegister,
+            detail: control_status_register
+                .get_message()
+                .unwrap_or_default()
+                .to_string(),
+        });
+
+    // NOTE: Defines are kept in a HashMap, so they are sorted to keep completions in a stable order.
+    let mut constants: Vec<_> = defines.iter().collect();
+    constants.sort();
+    let constants = constants.into_iter().map(|(name, value)| Completion {
+        label: name.clone(),
+        kind: CompletionKind::Constant,
+        detail: format!("{value:#06x}"),
+    });
+
+    opcodes
+        .chain(pseudo_opcodes)
+        .chain(registers)
+        .chain(control_status_registers)
+        .chain(constants)
+        .collect()
+}
+
+// Record the span of the label of every block within `code`, which lies within the block `ctx`, by
+// its full label, keeping only the first definition of each label, as the assembler does. Should
+// `code` contain `offset`, `enclosing` is set to the full label of the innermost block or module
+// which does.
+fn collect_definitions(
+    code: &Code,
+    ctx: &str,
+    offset: usize,
+    definitions: &mut HashMap<String, SourceSpan>,
+    enclosing: &mut String,
+) {
+    let (name, source_span, contents) = match &code.code_kind {
+        CodeKind::Block { label, contents } => (label.label, Some(label.source_span), contents),
+        // NOTE: A module places the labels defined within it inside of it, just as a block would,
+        // but defines no label of its own.
+        CodeKind::Module { name, contents } => (*name, None, contents),
+        _ => return,
+    };
+
+    let absolute_label = if ctx.is_empty() {
+        name.to_string()
+    } else {
+        format!("{ctx}.{name}")
+    };
+    if let Some(source_span) = source_span {
+        definitions
+            .entry(absolute_label.clone())
+            .or_insert(source_span);
+    }
+    if contains(code.source_span, offset) {
+        enclosing.clone_from(&absolute_label);
+    }
+
+    for code in contents {
+        collect_definitions(code, &absolute_label, offset, definitions, enclosing);
+    }
+}
+
+// Find the token of `file` which contains `offset`, or which ends at it, so that a cursor just past
+// the end of a word still refers to the word.
+fn token_at<'a>(sources: &Sources, file: &'a SourceFile, offset: usize) -> Option<Token<'a>> {
+    // NOTE: The lexer stops at the first error, but any tokens before it are still worth looking
+    // at.
+    Lexer::new(sources, file)
+        .map_while(Result::ok)
+        .find(|token| contains(token.source_span, offset))
+}
+
+fn contains(source_span: SourceSpan, offset: usize) -> bool {
+    (source_span.offset()..=source_span.offset() + source_span.len()).contains(&offset)
+}
+
+fn range(source_span: SourceSpan) -> Range<usize> {
+    source_span.offset()..source_span.offset() + source_span.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completion(label: &str) -> Option<Completion> {
+        completions(&Defines::from([("STACK_SIZE".to_string(), 0x400)]))
+            .into_iter()
+            .find(|completion| completion.label == label)
+    }
+
+    #[test]
+    fn completes_registers_by_name_and_alias() {
+        assert_eq!(
+            completion("r27"),
+            Some(Completion {
+                label: "r27".to_string(),
+                kind: CompletionKind::Register,
+                detail: String::new(),
+            })
+        );
+        assert_eq!(
+            completion("sp"),
+            Some(Completion {
+                label: "sp".to_string(),
+                kind: CompletionKind::Register,
+                detail: "r31".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn completes_control_status_registers_with_their_descriptions() {
+        assert_eq!(
+            completion("im1").map(|completion| completion.detail),
+            Some("interrupt mask of devices 16 to 31".to_string())
+        );
+        assert_eq!(
+            completion("ipc").map(|completion| completion.detail),
+            Some("interrupted program counter".to_string())
+        );
+    }
+
+    #[test]
+    fn completes_opcodes_and_constants() {
+        assert_eq!(
+            completion("ldio").map(|completion| completion.detail),
+            Some("(ldio dst src imm)".to_string())
+        );
+        assert_eq!(
+            completion("call").map(|completion| completion.detail),
+            Some("(call label), (call dst label)".to_string())
+        );
+        assert_eq!(
+            completion("STACK_SIZE").map(|completion| completion.detail),
+            Some("0x0400".to_string())
+        );
+    }
+
+    #[test]
+    fn resolves_exports_within_their_module() {
+        let text = "(module m) (export f) (segment rx (block f (nop)))";
+        assert_eq!(definition(text, &Defines::new(), 19), Some(41..42));
+    }
+}
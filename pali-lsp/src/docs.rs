@@ -0,0 +1,165 @@
+use pali::lex::{Opcode, PseudoOpcode};
+use pali::parse::opcode_shape;
+
+// Every opcode, along with a brief description of what it does.
+pub const OPCODES: &[(Opcode, &str)] = &[
+    (Opcode::ADD, "add src to dst"),
+    (Opcode::SUB, "subtract src from dst"),
+    (Opcode::AND, "bitwise and dst with src"),
+    (Opcode::OR, "bitwise or dst with src"),
+    (Opcode::XOR, "bitwise xor dst with src"),
+    (
+        Opcode::SLL,
+        "shift dst left by src bits, or right if src is negative",
+    ),
+    (
+        Opcode::SRL,
+        "shift dst right by src bits, filling with zeroes, or left if src is negative",
+    ),
+    (
+        Opcode::SRA,
+        "shift dst right by src bits, filling with its sign bit, or left if src is negative",
+    ),
+    (Opcode::ADDI, "set dst to the sum of src and imm"),
+    (Opcode::ANDI, "set dst to the bitwise and of src and imm"),
+    (Opcode::ORI, "set dst to the bitwise or of src and imm"),
+    (Opcode::XORI, "set dst to the bitwise xor of src and imm"),
+    (
+        Opcode::SLLI,
+        "set dst to src shifted left by imm bits, or right if imm is negative",
+    ),
+    (
+        Opcode::SRAI,
+        "set dst to src shifted right by imm bits, filling with its sign bit",
+    ),
+    (Opcode::LD, "load the word at the address in src into dst"),
+    (Opcode::ST, "store dst to the address in src"),
+    (
+        Opcode::DEI,
+        "read input into dst from the device whose index is the high byte of src, in the context given by its low byte, only in privileged mode",
+    ),
+    (
+        Opcode::DEO,
+        "write dst as output to the device whose index is the high byte of src, in the context given by its low byte, only in privileged mode",
+    ),
+    (
+        Opcode::RCSR,
+        "read the control/status register csr into dst, only in privileged mode",
+    ),
+    (
+        Opcode::WCSR,
+        "write src to the control/status register csr, only in privileged mode",
+    ),
+    (
+        Opcode::SWPR,
+        "return from an interrupt, jumping to ipc and leaving privileged mode",
+    ),
+    (Opcode::WFI, "wait until an interrupt is taken"),
+    (
+        Opcode::LDIO,
+        "load the word at the address src + imm into dst",
+    ),
+    (Opcode::STIO, "store dst to the address src + imm"),
+    (
+        Opcode::JAL,
+        "jump to the address src + imm, writing the return address to dst",
+    ),
+    (
+        Opcode::JSH,
+        "jump by imm words relative to the program counter, where imm lies between -512 and 511",
+    ),
+    (Opcode::BEQ, "branch to imm if dst equals src"),
+    (Opcode::BNE, "branch to imm if dst does not equal src"),
+    (
+        Opcode::BLT,
+        "branch to imm if dst is less than src, comparing them as signed numbers",
+    ),
+    (
+        Opcode::BGE,
+        "branch to imm if dst is at least src, comparing them as signed numbers",
+    ),
+    (
+        Opcode::BLTU,
+        "branch to imm if dst is less than src, comparing them as unsigned numbers",
+    ),
+    (
+        Opcode::BGEU,
+        "branch to imm if dst is at least src, comparing them as unsigned numbers",
+    ),
+];
+
+// Every pseudo-opcode, along with the forms in which it may be written, and a brief description of
+// what it does.
+pub const PSEUDO_OPCODES: &[(PseudoOpcode, &[&str], &str)] = &[
+    (PseudoOpcode::NOP, &["(nop)"], "do nothing, as (add r0 r0)"),
+    (
+        PseudoOpcode::MOV,
+        &["(mov dst src)"],
+        "copy src to dst, as (ori dst src 0)",
+    ),
+    (
+        PseudoOpcode::NOT,
+        &["(not dst src)"],
+        "set dst to the bitwise complement of src, as (xori dst src 0xFFFF)",
+    ),
+    (
+        PseudoOpcode::NEG,
+        &["(neg dst src)"],
+        "set dst to the negation of src, as (xori dst src 0xFFFF) (addi dst dst 1)",
+    ),
+    (
+        PseudoOpcode::LI,
+        &["(li dst imm)"],
+        "load imm into dst, as (addi dst r0 imm)",
+    ),
+    (
+        PseudoOpcode::LA,
+        &["(la dst label)"],
+        "load the address of label into dst, as (addi dst r0 label)",
+    ),
+    (
+        PseudoOpcode::CALL,
+        &["(call label)", "(call dst label)"],
+        "call label, writing the return address to dst, or to r1 if it is omitted, as (jal dst r0 label)",
+    ),
+    (
+        PseudoOpcode::RET,
+        &["(ret)", "(ret src)"],
+        "return to the address in src, or in r1 if it is omitted, as (jal r0 src 0)",
+    ),
+    (
+        PseudoOpcode::PUSH,
+        &["(push src)"],
+        "push src onto the stack, as (stio src r31 0xFFFF) (addi r31 r31 0xFFFF)",
+    ),
+    (
+        PseudoOpcode::POP,
+        &["(pop dst)"],
+        "pop the top of the stack into dst, as (ldio dst r31 0) (addi r31 r31 1)",
+    ),
+];
+
+// The name of `pseudo_opcode`, as it is written in the source.
+pub fn pseudo_opcode_name(pseudo_opcode: PseudoOpcode) -> String {
+    format!("{pseudo_opcode:?}").to_lowercase()
+}
+
+// Document `opcode` in markdown, with the form in which it is written followed by its description.
+pub fn document_opcode(opcode: Opcode) -> String {
+    let description = OPCODES
+        .iter()
+        .find(|(documented, _)| *documented == opcode)
+        .map_or("", |(_, description)| description);
+    format!("```pali\n{}\n```\n{description}", opcode_shape(opcode))
+}
+
+// Document `pseudo_opcode` in markdown, in the same way as `document_opcode`.
+pub fn document_pseudo_opcode(pseudo_opcode: PseudoOpcode) -> String {
+    let (shapes, description) = PSEUDO_OPCODES
+        .iter()
+        .find(|(documented, _, _)| *documented == pseudo_opcode)
+        .map_or((&[][..], ""), |(_, shapes, description)| {
+            (*shapes, *description)
+        });
+    format!("```pali\n{}\n```\n{description}", shapes.join("\n"))
+}
@@ -0,0 +1,404 @@
+// NOTE: The language server protocol only needs a small part of what a full JSON library offers, so
+// rather than depending on one, values are parsed into and serialized from this simple tree.
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    // The members of an object, in the order in which they were written.
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    // Construct an object from its members.
+    pub fn object<const N: usize>(members: [(&str, Value); N]) -> Self {
+        Self::Object(
+            members
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    // Find the member `key` of an object, if this is an object with such a member.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Object(members) => members
+                .iter()
+                .find(|(member, _)| member == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    // The value of a number which is a non-negative integer.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            // NOTE: Every integer up to 2^53 is represented exactly, which is far more than is
+            // needed for positions and versions.
+            Self::Number(n) if n >= 0.0 && n.fract() == 0.0 && n <= u64::MAX as f64 => {
+                Some(n as u64)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Self::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Self::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Self::Bool(b)
+    }
+}
+
+impl From<u32> for Value {
+    fn from(n: u32) -> Self {
+        Self::Number(f64::from(n))
+    }
+}
+
+impl From<i32> for Value {
+    fn from(n: i32) -> Self {
+        Self::Number(f64::from(n))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Self::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Self::String(s)
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(values: Vec<Value>) -> Self {
+        Self::Array(values)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Bool(b) => write!(f, "{b}"),
+            // NOTE: Integers are written without a fractional part, since clients may insist on
+            // integers where the protocol calls for them.
+            Self::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Self::Number(n) if n.is_finite() => write!(f, "{n}"),
+            // NOTE: JSON has no way to write infinities or NaN, so they are written as null.
+            Self::Number(_) => write!(f, "null"),
+            Self::String(s) => write_string(f, s),
+            Self::Array(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Self::Object(members) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c < ' ' => write!(f, "\\u{:04x}", u32::from(c))?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+// An error encountered while parsing JSON, at the given byte offset.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct ParseError(pub usize);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid JSON at byte {}", self.0)
+    }
+}
+
+impl Error for ParseError {}
+
+// Parse `text`, which must consist of a single JSON value, optionally surrounded by whitespace.
+pub fn parse(text: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser { text, offset: 0 };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.offset == text.len() {
+        Ok(value)
+    } else {
+        Err(ParseError(parser.offset))
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    offset: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.offset).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.offset += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), ParseError> {
+        if self.text[self.offset..].starts_with(expected) {
+            self.offset += expected.len();
+            Ok(())
+        } else {
+            Err(ParseError(self.offset))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'n') => self.expect("null").map(|()| Value::Null),
+            Some(b't') => self.expect("true").map(|()| Value::Bool(true)),
+            Some(b'f') => self.expect("false").map(|()| Value::Bool(false)),
+            Some(b'"') => self.parse_string().map(Value::String),
+            Some(b'[') => {
+                self.offset += 1;
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.offset += 1;
+                    return Ok(Value::Array(values));
+                }
+                loop {
+                    values.push(self.parse_value()?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.offset += 1,
+                        Some(b']') => {
+                            self.offset += 1;
+                            return Ok(Value::Array(values));
+                        }
+                        _ => return Err(ParseError(self.offset)),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.offset += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.offset += 1;
+                    return Ok(Value::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() != Some(b'"') {
+                        return Err(ParseError(self.offset));
+                    }
+                    let key = self.parse_string()?;
+                    self.skip_whitespace();
+                    self.expect(":")?;
+                    members.push((key, self.parse_value()?));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.offset += 1,
+                        Some(b'}') => {
+                            self.offset += 1;
+                            return Ok(Value::Object(members));
+                        }
+                        _ => return Err(ParseError(self.offset)),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => {
+                let start = self.offset;
+                while matches!(
+                    self.peek(),
+                    Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')
+                ) {
+                    self.offset += 1;
+                }
+                self.text[start..self.offset]
+                    .parse()
+                    .map(Value::Number)
+                    .map_err(|_| ParseError(start))
+            }
+            _ => Err(ParseError(self.offset)),
+        }
+    }
+
+    // Parse a string, beginning at its opening quote.
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.offset += 1;
+        let mut s = String::new();
+        loop {
+            let start = self.offset;
+            let c = self.text[self.offset..]
+                .chars()
+                .next()
+                .ok_or(ParseError(self.offset))?;
+            self.offset += c.len_utf8();
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let escape = self.peek().ok_or(ParseError(self.offset))?;
+                    self.offset += 1;
+                    match escape {
+                        b'"' => s.push('"'),
+                        b'\\' => s.push('\\'),
+                        b'/' => s.push('/'),
+                        b'b' => s.push('\u{8}'),
+                        b'f' => s.push('\u{c}'),
+                        b'n' => s.push('\n'),
+                        b'r' => s.push('\r'),
+                        b't' => s.push('\t'),
+                        b'u' => {
+                            let unit = self.parse_code_unit()?;
+                            // NOTE: Characters outside of the basic multilingual plane are
+                            // written as a pair of escaped UTF-16 surrogates.
+                            let c = if (0xD800..0xDC00).contains(&unit) {
+                                self.expect("\\u")?;
+                                let low = self.parse_code_unit()?;
+                                char::decode_utf16([unit, low]).next().and_then(Result::ok)
+                            } else {
+                                char::from_u32(u32::from(unit))
+                            };
+                            s.push(c.ok_or(ParseError(start))?);
+                        }
+                        _ => return Err(ParseError(start)),
+                    }
+                }
+                c if c < ' ' => return Err(ParseError(start)),
+                c => s.push(c),
+            }
+        }
+    }
+
+    // Parse the four hexadecimal digits of a `\u` escape.
+    fn parse_code_unit(&mut self) -> Result<u16, ParseError> {
+        let digits = self
+            .text
+            .get(self.offset..self.offset + 4)
+            .ok_or(ParseError(self.offset))?;
+        let unit = u16::from_str_radix(digits, 16).map_err(|_| ParseError(self.offset))?;
+        self.offset += 4;
+        Ok(unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_values() {
+        let value = parse(r#" {"id": 1, "params": {"a": [true, null, -2.5e1]}, "b": false} "#);
+        assert_eq!(
+            value,
+            Ok(Value::object([
+                ("id", Value::Number(1.0)),
+                (
+                    "params",
+                    Value::object([(
+                        "a",
+                        Value::Array(vec![Value::Bool(true), Value::Null, Value::Number(-25.0)])
+                    )])
+                ),
+                ("b", Value::Bool(false)),
+            ]))
+        );
+    }
+
+    #[test]
+    fn parses_escapes() {
+        assert_eq!(
+            parse(r#""a\"b\\c\/\n\u0041\ud834\udd1e""#),
+            Ok(Value::from("a\"b\\c/\nA\u{1D11E}"))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        assert_eq!(parse(r#"{"a": 1,}"#), Err(ParseError(8)));
+        assert_eq!(parse("[1 2]"), Err(ParseError(3)));
+        assert_eq!(parse(r#""unterminated"#), Err(ParseError(13)));
+        assert_eq!(parse("1 1"), Err(ParseError(2)));
+    }
+
+    #[test]
+    fn serializes_what_it_parses() {
+        let value = Value::object([
+            ("jsonrpc", Value::from("2.0")),
+            ("id", Value::from(7)),
+            ("text", Value::from("tab\there \"quoted\" \u{1}")),
+            ("items", Value::from(vec![Value::Number(0.5), Value::Null])),
+        ]);
+        let text = value.to_string();
+        assert_eq!(
+            text,
+            r#"{"jsonrpc":"2.0","id":7,"text":"tab\there \"quoted\" \u0001","items":[0.5,null]}"#
+        );
+        assert_eq!(parse(&text), Ok(value));
+    }
+}
@@ -0,0 +1,502 @@
+mod analysis;
+mod docs;
+mod json;
+mod position;
+mod rpc;
+
+use analysis::CompletionKind;
+use json::Value;
+
+use miette::{Report, Severity, SourceSpan};
+use pali::parse::Defines;
+
+use std::collections::HashMap;
+use std::io;
+use std::ops::Range;
+use std::process::ExitCode;
+
+// The error codes of JSON-RPC which the server may respond with.
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+// NOTE: The protocol numbers the kinds of many things, rather than naming them. Only those which the
+// server uses are given here.
+const TEXT_DOCUMENT_SYNC_FULL: u32 = 1;
+const MESSAGE_TYPE_WARNING: u32 = 2;
+const SEVERITY_ERROR: u32 = 1;
+const SEVERITY_WARNING: u32 = 2;
+const SEVERITY_HINT: u32 = 4;
+const COMPLETION_VARIABLE: u32 = 6;
+const COMPLETION_PROPERTY: u32 = 10;
+const COMPLETION_KEYWORD: u32 = 14;
+const COMPLETION_CONSTANT: u32 = 21;
+
+#[derive(Debug, Default)]
+struct Server {
+    // The text of every open document, by its uri.
+    documents: HashMap<String, String>,
+    // The constants with which every document is assembled, which are given by the editor, as it
+    // has no command line to give them on.
+    defines: Defines,
+    // Whether the editor has asked the server to shut down, after which it may only exit.
+    shut_down: bool,
+    // The messages waiting to be sent to the editor.
+    outgoing: Vec<Value>,
+}
+
+impl Server {
+    // Handle a single message from the editor, returning whether the server should now exit.
+    fn handle(&mut self, message: &Value) -> bool {
+        // NOTE: The server never sends requests of its own, so any message without a method is a
+        // response which nothing is waiting for, and is ignored.
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            return false;
+        };
+        let params = message.get("params").unwrap_or(&Value::Null);
+
+        match message.get("id") {
+            Some(id) => {
+                let response = match self.request(method, params) {
+                    Ok(result) => Value::object([
+                        ("jsonrpc", Value::from("2.0")),
+                        ("id", id.clone()),
+                        ("result", result),
+                    ]),
+                    Err((code, message)) => error_response(id.clone(), code, message),
+                };
+                self.outgoing.push(response);
+                false
+            }
+            None => self.notify(method, params),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i32, String)> {
+        if self.shut_down {
+            return Err((
+                INVALID_REQUEST,
+                format!("{method} requested after shutdown"),
+            ));
+        }
+
+        match method {
+            "initialize" => Ok(self.initialize(params)),
+            "shutdown" => {
+                self.shut_down = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => {
+                let (_, text, offset) = self.document_position(params)?;
+                Ok(
+                    analysis::hover(text, offset).map_or(Value::Null, |(span, documentation)| {
+                        Value::object([
+                            (
+                                "contents",
+                                Value::object([
+                                    ("kind", Value::from("markdown")),
+                                    ("value", Value::from(documentation)),
+                                ]),
+                            ),
+                            ("range", range(text, span)),
+                        ])
+                    }),
+                )
+            }
+            "textDocument/definition" => {
+                let (uri, text, offset) = self.document_position(params)?;
+                Ok(analysis::definition(text, &self.defines, offset)
+                    .map_or(Value::Null, |span| location(uri, text, span)))
+            }
+            "textDocument/completion" => Ok(Value::from(
+                analysis::completions(&self.defines)
+                    .into_iter()
+                    .map(|completion| {
+                        let kind = match completion.kind {
+                            CompletionKind::Opcode => COMPLETION_KEYWORD,
+                            CompletionKind::Register => COMPLETION_VARIABLE,
+                            CompletionKind::ControlStatusRegister => COMPLETION_PROPERTY,
+                            CompletionKind::Constant => COMPLETION_CONSTANT,
+                        };
+                        let mut item = vec![
+                            ("label".to_string(), Value::from(completion.label)),
+                            ("kind".to_string(), Value::from(kind)),
+                        ];
+                        if !completion.detail.is_empty() {
+                            item.push(("detail".to_string(), Value::from(completion.detail)));
+                        }
+                        Value::Object(item)
+                    })
+                    .collect::<Vec<_>>(),
+            )),
+            _ => Err((METHOD_NOT_FOUND, format!("{method} is not supported"))),
+        }
+    }
+
+    // Handle a notification, which is never responded to, returning whether the server should now
+    // exit.
+    fn notify(&mut self, method: &str, params: &Value) -> bool {
+        let document = params.get("textDocument");
+        let uri = document.and_then(|document| document.get("uri"));
+        let version = document.and_then(|document| document.get("version"));
+
+        match (method, uri.and_then(Value::as_str)) {
+            ("exit", _) => return true,
+            ("textDocument/didOpen", Some(uri)) => {
+                if let Some(text) = document
+                    .and_then(|document| document.get("text"))
+                    .and_then(Value::as_str)
+                {
+                    self.update(uri, text.to_string(), version);
+                }
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                // NOTE: Documents are synchronized in full, so the last change holds the whole
+                // text.
+                if let Some(text) = params
+                    .get("contentChanges")
+                    .and_then(Value::as_array)
+                    .and_then(<[Value]>::last)
+                    .and_then(|change| change.get("text"))
+                    .and_then(Value::as_str)
+                {
+                    self.update(uri, text.to_string(), version);
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(uri);
+                self.publish_diagnostics(uri, Vec::new(), None);
+            }
+            // NOTE: Any other notification, such as `initialized`, asks nothing of the server.
+            _ => {}
+        }
+        false
+    }
+
+    fn initialize(&mut self, params: &Value) -> Value {
+        // NOTE: Constants are given as the `defines` object of the initialization options, as in
+        // `{"defines": {"STACK_SIZE": 1024}}`.
+        if let Some(defines) = params
+            .get("initializationOptions")
+            .and_then(|options| options.get("defines"))
+            .and_then(Value::as_object)
+        {
+            for (name, value) in defines {
+                match value.as_u64().and_then(|value| u16::try_from(value).ok()) {
+                    Some(value) => {
+                        self.defines.insert(name.clone(), value);
+                    }
+                    None => self.outgoing.push(notification(
+                        "window/showMessage",
+                        Value::object([
+                            ("type", Value::from(MESSAGE_TYPE_WARNING)),
+                            (
+                                "message",
+                                Value::from(format!(
+                                    "ignoring constant {name}, whose value isn't a 16-bit number"
+                                )),
+                            ),
+                        ]),
+                    )),
+                }
+            }
+        }
+
+        Value::object([
+            (
+                "capabilities",
+                Value::object([
+                    ("textDocumentSync", Value::from(TEXT_DOCUMENT_SYNC_FULL)),
+                    ("hoverProvider", Value::from(true)),
+                    ("definitionProvider", Value::from(true)),
+                    ("completionProvider", Value::object([])),
+                ]),
+            ),
+            (
+                "serverInfo",
+                Value::object([
+                    ("name", Value::from(env!("CARGO_PKG_NAME"))),
+                    ("version", Value::from(env!("CARGO_PKG_VERSION"))),
+                ]),
+            ),
+        ])
+    }
+
+    // Record the new text of the document `uri`, and publish the diagnostics produced by assembling
+    // it.
+    fn update(&mut self, uri: &str, text: String, version: Option<&Value>) {
+        let diagnostics = analysis::check(&text, &self.defines)
+            .iter()
+            .map(|report| diagnostic(uri, &text, report))
+            .collect();
+        self.documents.insert(uri.to_string(), text);
+        self.publish_diagnostics(uri, diagnostics, version);
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str, diagnostics: Vec<Value>, version: Option<&Value>) {
+        let mut params = vec![
+            ("uri".to_string(), Value::from(uri)),
+            ("diagnostics".to_string(), Value::from(diagnostics)),
+        ];
+        if let Some(version) = version {
+            params.push(("version".to_string(), version.clone()));
+        }
+        self.outgoing.push(notification(
+            "textDocument/publishDiagnostics",
+            Value::Object(params),
+        ));
+    }
+
+    // Find the uri and text of the document to which the position `params` refer, along with the
+    // byte offset of the position.
+    fn document_position(&self, params: &Value) -> Result<(&str, &str, usize), (i32, String)> {
+        let uri = params
+            .get("textDocument")
+            .and_then(|document| document.get("uri"))
+            .and_then(Value::as_str);
+        let position = params.get("position");
+        let coordinate = |name| {
+            position
+                .and_then(|position| position.get(name))
+                .and_then(Value::as_u64)
+                .map(|coordinate| u32::try_from(coordinate).unwrap_or(u32::MAX))
+        };
+        let (Some(uri), Some(line), Some(character)) =
+            (uri, coordinate("line"), coordinate("character"))
+        else {
+            return Err((
+                INVALID_PARAMS,
+                "expected a document and position".to_string(),
+            ));
+        };
+
+        let (uri, text) = self
+            .documents
+            .get_key_value(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("{uri} is not open")))?;
+        Ok((uri, text, position::offset(text, line, character)))
+    }
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    Value::object([
+        ("jsonrpc", Value::from("2.0")),
+        ("method", Value::from(method)),
+        ("params", params),
+    ])
+}
+
+fn error_response(id: Value, code: i32, message: String) -> Value {
+    Value::object([
+        ("jsonrpc", Value::from("2.0")),
+        ("id", id),
+        (
+            "error",
+            Value::object([
+                ("code", Value::from(code)),
+                ("message", Value::from(message)),
+            ]),
+        ),
+    ])
+}
+
+// Convert `report`, produced by assembling `text`, the document `uri`, into an LSP diagnostic. The
+// diagnostic is placed at the primary label of the report, or at its first label if none is
+// primary, and every other label is attached to it as related information.
+fn diagnostic(uri: &str, text: &str, report: &Report) -> Value {
+    let severity = match report.severity().unwrap_or(Severity::Error) {
+        Severity::Advice => SEVERITY_HINT,
+        Severity::Warning => SEVERITY_WARNING,
+        Severity::Error => SEVERITY_ERROR,
+    };
+
+    // NOTE: LSP has no place for help, so it is appended to the message, as miette would render
+    // it.
+    let mut message = report
+        .chain()
+        .map(|error| error.to_string())
+        .collect::<Vec<_>>()
+        .join(": ");
+    if let Some(help) = report.help() {
+        message.push_str(&format!("\nhelp: {help}"));
+    }
+
+    let labels: Vec<_> = report.labels().into_iter().flatten().collect();
+    let primary = labels
+        .iter()
+        .position(|label| label.primary())
+        .or((!labels.is_empty()).then_some(0));
+    let related_information: Vec<_> = labels
+        .iter()
+        .enumerate()
+        .filter(|(index, label)| Some(*index) != primary && label.label().is_some())
+        .map(|(_, label)| {
+            Value::object([
+                (
+                    "location",
+                    location(uri, text, source_range(*label.inner())),
+                ),
+                ("message", Value::from(label.label().unwrap_or_default())),
+            ])
+        })
+        .collect();
+
+    let mut diagnostic = vec![
+        (
+            "range".to_string(),
+            range(
+                text,
+                primary.map_or(0..0, |index| source_range(*labels[index].inner())),
+            ),
+        ),
+        ("severity".to_string(), Value::from(severity)),
+        ("source".to_string(), Value::from("pali")),
+        ("message".to_string(), Value::from(message)),
+    ];
+    if !related_information.is_empty() {
+        diagnostic.push((
+            "relatedInformation".to_string(),
+            Value::from(related_information),
+        ));
+    }
+    Value::Object(diagnostic)
+}
+
+fn source_range(source_span: SourceSpan) -> Range<usize> {
+    source_span.offset()..source_span.offset() + source_span.len()
+}
+
+fn location(uri: &str, text: &str, span: Range<usize>) -> Value {
+    Value::object([("uri", Value::from(uri)), ("range", range(text, span))])
+}
+
+// Convert the byte range `span` of `text` into an LSP range.
+fn range(text: &str, span: Range<usize>) -> Value {
+    let position = |offset| {
+        let (line, character) = position::line_column(text, offset);
+        Value::object([
+            ("line", Value::from(line)),
+            ("character", Value::from(character)),
+        ])
+    };
+    Value::object([("start", position(span.start)), ("end", position(span.end))])
+}
+
+fn main() -> io::Result<ExitCode> {
+    let mut reader = io::stdin().lock();
+    let mut writer = io::stdout().lock();
+    let mut server = Server::default();
+
+    // NOTE: The editor may close the connection without asking the server to exit, which is treated
+    // just as an exit would be.
+    while let Some(body) = rpc::read_message(&mut reader)? {
+        let exit = match json::parse(&body) {
+            Ok(message) => server.handle(&message),
+            Err(error) => {
+                server
+                    .outgoing
+                    .push(error_response(Value::Null, PARSE_ERROR, error.to_string()));
+                false
+            }
+        };
+        for message in server.outgoing.drain(..) {
+            rpc::write_message(&mut writer, &message)?;
+        }
+        if exit {
+            break;
+        }
+    }
+
+    // NOTE: The protocol asks that the server exit with an error should it not have been shut down
+    // first.
+    Ok(if server.shut_down {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Handle each of `messages`, written as JSON, returning everything the server sends in reply.
+    fn exchange(server: &mut Server, messages: &[&str]) -> Vec<String> {
+        for message in messages {
+            server.handle(&json::parse(message).unwrap());
+        }
+        server
+            .outgoing
+            .drain(..)
+            .map(|message| message.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn publishes_diagnostics_for_each_change() {
+        let mut server = Server::default();
+        let replies = exchange(
+            &mut server,
+            &[
+                r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.pali","languageId":"pali","version":1,"text":"(segment rx\n  (jsh 600))"}}}"#,
+                r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":{"uri":"file:///a.pali","version":2},"contentChanges":[{"text":"(segment rx (nop))"}]}}"#,
+            ],
+        );
+
+        assert_eq!(
+            replies,
+            [
+                r#"{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///a.pali","diagnostics":[{"range":{"start":{"line":1,"character":7},"end":{"line":1,"character":10}},"severity":1,"source":"pali","message":"jsh immediate 0x0258 is out of range\nhelp: the immediate of jsh must lie between -512 and 511, or equivalently be at most 0x01FF or at least 0xFE00"}],"version":1}}"#,
+                r#"{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///a.pali","diagnostics":[],"version":2}}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_definitions_within_enclosing_blocks() {
+        let mut server = Server::default();
+        exchange(
+            &mut server,
+            &[
+                r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.pali","languageId":"pali","version":1,"text":"(segment rx\n  (block loop (nop))\n  (block f (block loop (nop)) (jal r0 r0 loop)))"}}}"#,
+            ],
+        );
+
+        // NOTE: The reference to `loop` within `f` refers to `f.loop`, rather than to the `loop`
+        // outside of it.
+        let replies = exchange(
+            &mut server,
+            &[
+                r#"{"jsonrpc":"2.0","id":1,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///a.pali"},"position":{"line":2,"character":42}}}"#,
+            ],
+        );
+        assert_eq!(
+            replies,
+            [
+                r#"{"jsonrpc":"2.0","id":1,"result":{"uri":"file:///a.pali","range":{"start":{"line":2,"character":18},"end":{"line":2,"character":22}}}}"#
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_requests_after_shutdown() {
+        let mut server = Server::default();
+        let replies = exchange(
+            &mut server,
+            &[
+                r#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#,
+                r#"{"jsonrpc":"2.0","id":"2","method":"textDocument/completion","params":{}}"#,
+            ],
+        );
+
+        assert_eq!(
+            replies,
+            [
+                r#"{"jsonrpc":"2.0","id":1,"result":null}"#,
+                r#"{"jsonrpc":"2.0","id":"2","error":{"code":-32600,"message":"textDocument/completion requested after shutdown"}}"#,
+            ]
+        );
+        assert!(server.handle(&json::parse(r#"{"jsonrpc":"2.0","method":"exit"}"#).unwrap()));
+    }
+}
@@ -0,0 +1,82 @@
+// NOTE: LSP locates text by line and column, both counted from 0, with columns counted in UTF-16
+// code units, whereas pali locates it by byte offset, so every location passed between the two
+// must be translated.
+
+// Find the line and column at which the byte `offset` of `text` lies. An offset beyond the end of
+// `text`, such as that of an unexpected EOF, is taken to lie at its end.
+pub fn line_column(text: &str, offset: usize) -> (u32, u32) {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    let column = before[line_start..].encode_utf16().count();
+    (
+        u32::try_from(line).unwrap_or(u32::MAX),
+        u32::try_from(column).unwrap_or(u32::MAX),
+    )
+}
+
+// Find the byte offset of the given line and column of `text`. A column beyond the end of its line
+// is taken to lie at the end of the line, and a line beyond the end of `text` at the end of `text`.
+pub fn offset(text: &str, line: u32, column: u32) -> usize {
+    let line = usize::try_from(line).unwrap_or(usize::MAX);
+    let column = usize::try_from(column).unwrap_or(usize::MAX);
+
+    let line_start: usize = text.split_inclusive('\n').take(line).map(str::len).sum();
+    let mut units = 0;
+    for (index, c) in text[line_start..].char_indices() {
+        if c == '\n' || units >= column {
+            return line_start + index;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NOTE: `é` is two bytes but a single UTF-16 code unit, while `𝄞` is four bytes and two code
+    // units, so that the two kinds of column differ on the second line.
+    const TEXT: &str = "(nop)\n(é 𝄞 x)\n";
+
+    #[test]
+    fn line_column_counts_utf16_code_units() {
+        assert_eq!(line_column(TEXT, 0), (0, 0));
+        assert_eq!(line_column(TEXT, 5), (0, 5));
+        assert_eq!(line_column(TEXT, 6), (1, 0));
+        assert_eq!(line_column(TEXT, 9), (1, 2));
+        assert_eq!(line_column(TEXT, 14), (1, 5));
+        assert_eq!(line_column(TEXT, 17), (1, 8));
+        assert_eq!(line_column(TEXT, 18), (2, 0));
+    }
+
+    #[test]
+    fn line_column_clamps_offsets() {
+        // NOTE: An offset within a character is taken to lie at its beginning.
+        assert_eq!(line_column(TEXT, 8), (1, 1));
+        assert_eq!(line_column(TEXT, 11), (1, 3));
+        assert_eq!(line_column(TEXT, 100), (2, 0));
+    }
+
+    #[test]
+    fn offset_inverts_line_column() {
+        for offset in [0, 5, 6, 7, 9, 10, 14, 15, 16, 17, 18] {
+            let (line, column) = line_column(TEXT, offset);
+            assert_eq!(super::offset(TEXT, line, column), offset);
+        }
+    }
+
+    #[test]
+    fn offset_clamps_positions() {
+        assert_eq!(offset(TEXT, 0, 100), 5);
+        assert_eq!(offset(TEXT, 1, 100), 17);
+        assert_eq!(offset(TEXT, 2, 3), 18);
+        assert_eq!(offset(TEXT, 100, 0), 18);
+    }
+}
@@ -0,0 +1,78 @@
+// NOTE: Messages are exchanged with the editor as JSON-RPC, with each message preceded by a header
+// giving its length in bytes, as in `Content-Length: 52\r\n\r\n{"jsonrpc":"2.0",...}`.
+
+use crate::json::Value;
+
+use std::io::{self, BufRead, ErrorKind, Write};
+
+// Read the body of the next message from `reader`, or `None` should the editor have closed it.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        // NOTE: The headers end at the first empty line. Any header other than the length, such
+        // as the content type, is ignored.
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            "message has no valid Content-Length header",
+        )
+    })?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_consecutive_messages() {
+        let mut input =
+            "Content-Length: 2\r\n\r\n{}content-length: 4\r\nContent-Type: x\r\n\r\nnull"
+                .as_bytes();
+        assert_eq!(read_message(&mut input).unwrap().as_deref(), Some("{}"));
+        assert_eq!(read_message(&mut input).unwrap().as_deref(), Some("null"));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_messages_without_a_length() {
+        let mut input = "Content-Type: x\r\n\r\n{}".as_bytes();
+        assert_eq!(
+            read_message(&mut input).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn writes_the_length_in_bytes() {
+        let mut output = Vec::new();
+        write_message(&mut output, &Value::from("é")).unwrap();
+        assert_eq!(output, "Content-Length: 4\r\n\r\n\"é\"".as_bytes());
+    }
+}
@@ -1,237 +1,856 @@
-use crate::lex::Opcode;
-use crate::parse::{Code, Immediate, Parser, Program};
+use crate::lex::{Opcode, SegmentPermissions};
+use crate::listing::{Listing, ListingEntry};
+use crate::parse::{
+    Code, CodeKind, Defines, Export, Immediate, Label, Parser, Program, SegmentForm,
+};
+use crate::source::Sources;
 
-use miette::{LabeledSpan, Result, SourceSpan};
+use miette::{LabeledSpan, Report, Result, Severity, SourceSpan};
 
 use poki::{ExportTableEntry, Poki, RelocationTableEntry};
 
-use std::collections::HashMap;
+use std::cmp;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashSet};
+use std::iter;
+
+// The maximum length of an absolute label, in UTF-16 code units, which is the unit in which poki
+// files store labels.
+//
+// NOTE: Poki files can hold labels up to `u16::MAX` code units long, but an export table as a whole
+// is limited to the same size, so a far smaller limit keeps any one label from crowding out the
+// rest, and is still well beyond the length of any label written by hand.
+const MAX_LABEL_LENGTH: usize = 256;
 
 #[derive(Debug)]
 pub struct Assembler<'a> {
-    source: &'a str,
+    sources: &'a Sources,
     program: Program<'a>,
+    symbol_table: SymbolTable,
     partial_poki: Poki,
+    listing: Listing,
     segment_index: u16,
     segment_offset: u16,
+
+    // Whether references to labels in the same segment should be encoded directly as the offsets
+    // of those labels, rather than as relocations.
+    resolve_local: bool,
+
+    // Whether labels which are defined but never used should be reported as errors, rather than as
+    // warnings.
+    deny_unused: bool,
+
+    // Whether branches to numeric literals should be warned about.
+    warn_numeric_branch: bool,
+
+    // Whether instructions which write to r0, and so have no effect, should be warned about.
+    warn_discarded_write: bool,
+
+    // Whether references to labels which aren't defined anywhere in the program should be reported
+    // as errors, rather than added to the table of unresolved symbols.
+    error_unresolved: bool,
+
+    // Whether references to labels which aren't defined anywhere in the program, but which closely
+    // resemble labels which are, should be pointed out, even if they aren't reported as errors.
+    suggest_unresolved: bool,
+
+    // The exports of the program, each along with the absolute label of the block which it
+    // exports, once they have been resolved.
+    exports: Vec<(Export<'a>, String)>,
+
+    // The absolute labels of the blocks which are referred to anywhere in the program.
+    referenced_labels: HashSet<String>,
+
+    // The references to labels which aren't defined anywhere in the program, in the order in which
+    // they appear.
+    unresolved_references: Vec<Label<'a>>,
+
+    // The relocations added since the current listing entry was begun, along with the labels to
+    // which they refer.
+    pending_relocations: Vec<(u16, String)>,
+
+    warnings: Vec<Report>,
+}
+
+// The results of assembling a program.
+#[derive(Debug)]
+pub struct Assembly {
+    pub poki: Poki,
+    pub listing: Listing,
+    pub symbol_table: SymbolTable,
+    // The name under which each exported label is exported, keyed by its absolute label.
+    pub exports: BTreeMap<String, Vec<String>>,
+    pub warnings: Vec<Report>,
 }
 
 impl<'a> Assembler<'a> {
-    pub fn try_new(source: &'a str) -> Result<Self> {
-        let program = Parser::new(source).parse()?;
+    pub fn try_new(sources: &'a Sources, defines: &'a Defines) -> Result<Self, Vec<Report>> {
+        // Each file is parsed separately, and the resulting programs are then merged, so that the
+        // segments of each file are laid out one after another, in the order in which the files
+        // were given.
+        let mut program = Program {
+            exports: Vec::new(),
+            segments: [const { Vec::new() }; 8],
+            segment_forms: [const { Vec::new() }; 8],
+            numeric_branches: Vec::new(),
+            discarded_writes: Vec::new(),
+            constant_addresses: Vec::new(),
+        };
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+        for file in sources.files() {
+            match Parser::new(sources, file, defines).parse() {
+                Ok(file_program) => {
+                    // NOTE: Exporting a label more than once is harmless, so only the first export
+                    // of each label is kept, and any others are merely warned about.
+                    for export in file_program.exports {
+                        match program.exports.iter().find(|e| **e == export) {
+                            Some(previous_export) => warnings.push(
+                                miette::miette!(
+                                    severity = Severity::Warning,
+                                    labels = vec![
+                                        LabeledSpan::at(
+                                            previous_export.name.source_span,
+                                            "label first exported here"
+                                        ),
+                                        LabeledSpan::at(export.name.source_span, "and again here")
+                                    ],
+                                    "label {export} is exported more than once"
+                                )
+                                .with_source_code(sources.clone()),
+                            ),
+                            None => program.exports.push(export),
+                        }
+                    }
+                    for ((segment, segment_forms), (file_segment, file_segment_forms)) in program
+                        .segments
+                        .iter_mut()
+                        .zip(&mut program.segment_forms)
+                        .zip(
+                            file_program
+                                .segments
+                                .into_iter()
+                                .zip(file_program.segment_forms),
+                        )
+                    {
+                        segment_forms.extend(file_segment_forms.into_iter().map(|form| {
+                            SegmentForm {
+                                start: form.start + segment.len(),
+                                ..form
+                            }
+                        }));
+                        segment.extend(file_segment);
+                    }
+                    program
+                        .numeric_branches
+                        .extend(file_program.numeric_branches);
+                    program
+                        .discarded_writes
+                        .extend(file_program.discarded_writes);
+                    program
+                        .constant_addresses
+                        .extend(file_program.constant_addresses);
+                }
+                Err(file_errors) => errors.extend(file_errors),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        // A segment form with nothing in it is harmless, but is almost certainly not what was
+        // intended, so we warn about it.
+        for (segment, segment_forms) in program.segments.iter().zip(&program.segment_forms) {
+            for (i, form) in segment_forms.iter().enumerate() {
+                let end = segment_forms
+                    .get(i + 1)
+                    .map_or(segment.len(), |next_form| next_form.start);
+                if form.start == end {
+                    warnings.push(
+                        miette::miette!(
+                            severity = Severity::Warning,
+                            labels = vec![LabeledSpan::underline(form.source_span)],
+                            "segment form is empty"
+                        )
+                        .with_source_code(sources.clone()),
+                    );
+                }
+            }
+        }
+
         let partial_poki = Poki::new_empty();
 
         Ok(Self {
-            source,
+            sources,
             program,
+            symbol_table: SymbolTable::new(),
             partial_poki,
+            listing: Listing::default(),
             segment_index: 0,
             segment_offset: 0,
+            resolve_local: false,
+            deny_unused: false,
+            warn_numeric_branch: true,
+            warn_discarded_write: true,
+            error_unresolved: false,
+            suggest_unresolved: false,
+            exports: Vec::new(),
+            referenced_labels: HashSet::new(),
+            unresolved_references: Vec::new(),
+            pending_relocations: Vec::new(),
+            warnings,
         })
     }
 
-    fn symbol_table(&self) -> Result<SymbolTable> {
-        // HACK: This is another hack. We only really need this method because the `symbol_table`
-        // method defined on `Program<'a>` doesn't have access to the source code, and so it can't
-        // attach it itself, so we do that here instead.
-        self.program
-            .symbol_table()
-            .map_err(|e| e.with_source_code(self.source.to_string()))
+    // NOTE: A reference which is resolved locally is only correct if its segment is loaded at
+    // address 0, since no relocation remains to adjust it for wherever the segment is actually
+    // loaded. This is why it isn't the default.
+    pub fn resolve_local(mut self, resolve_local: bool) -> Self {
+        self.resolve_local = resolve_local;
+        self
+    }
+
+    pub fn deny_unused(mut self, deny_unused: bool) -> Self {
+        self.deny_unused = deny_unused;
+        self
+    }
+
+    pub fn warn_numeric_branch(mut self, warn_numeric_branch: bool) -> Self {
+        self.warn_numeric_branch = warn_numeric_branch;
+        self
+    }
+
+    pub fn warn_discarded_write(mut self, warn_discarded_write: bool) -> Self {
+        self.warn_discarded_write = warn_discarded_write;
+        self
     }
 
-    pub fn assemble(mut self) -> Result<Poki> {
+    // NOTE: When a single file is assembled on its own, nothing could ever resolve a reference to
+    // a label which it doesn't define, so such a reference is almost certainly a typo.
+    pub fn error_unresolved(mut self, error_unresolved: bool) -> Self {
+        self.error_unresolved = error_unresolved;
+        self
+    }
+
+    // NOTE: Finding similar labels means comparing every unresolved label against every defined
+    // one, so it is only done when asked for, or when unresolved labels are errors anyway.
+    pub fn suggest_unresolved(mut self, suggest_unresolved: bool) -> Self {
+        self.suggest_unresolved = suggest_unresolved;
+        self
+    }
+
+    pub fn assemble(mut self) -> Result<Assembly, Vec<Report>> {
+        // Errors are collected, rather than returned immediately, so that as many of them as
+        // possible can be reported at once.
+        //
+        // HACK: The `symbol_table` method defined on `Program<'a>` doesn't have access to the
+        // source code, and so it can't attach it itself, so we do that here instead.
+        let (symbol_table, errors) = self.program.symbol_table();
+        self.symbol_table = symbol_table;
+        let mut errors: Vec<_> = errors
+            .into_iter()
+            .map(|e| e.with_source_code(self.sources.clone()))
+            .collect();
+
         // At some point I need to run a quick scan to check that I'm not exporting any labels that
         // I haven't defined.
+        //
+        // NOTE: The label of an export is resolved relative to the module of the file in which it
+        // appears, as a reference from that file would be, and otherwise as an absolute label.
         for export in &self.program.exports {
-            if !self.symbol_table()?.contains_key(export.label) {
-                return Err(miette::miette!(
-                    labels = vec![LabeledSpan::underline(export.source_span)],
-                    "label {0} exported, but is not defined",
-                    export.label
-                )
-                .with_source_code(self.source.to_string()));
+            if let Some(absolute_label) =
+                self.absolute_label(export.label.label, export.module.unwrap_or_default())
+            {
+                // Two files may export the same label under the same name without issue, but not
+                // two different labels.
+                match self.exports.iter().find(|(e, _)| e.name == export.name) {
+                    Some((previous_export, previous_label))
+                        if *previous_label != absolute_label =>
+                    {
+                        errors.push(
+                            miette::miette!(
+                                labels = vec![
+                                    LabeledSpan::at(
+                                        previous_export.name.source_span,
+                                        format!("{previous_label} first exported as {} here", export.name)
+                                    ),
+                                    LabeledSpan::at(
+                                        export.name.source_span,
+                                        format!("and {absolute_label} here")
+                                    ),
+                                ],
+                                help = "export one of them by another name, as in (export (as name label))",
+                                "two different labels are exported as {}",
+                                export.name
+                            )
+                            .with_source_code(self.sources.clone()),
+                        );
+                    }
+                    Some(_) => {}
+                    None => self.exports.push((*export, absolute_label)),
+                }
+            } else {
+                // Since nested blocks are exported by their full dotted names, it is an easy
+                // mistake to export one by its unqualified label, so we point out any nested
+                // blocks which the export might have been intended to refer to.
+                let suffix = format!(".{}", export.label);
+                let mut candidates: Vec<_> = self
+                    .symbol_table
+                    .keys()
+                    .filter(|label| label.ends_with(&suffix))
+                    .collect();
+                candidates.sort();
+
+                let error = match candidates.as_slice() {
+                    [] => miette::miette!(
+                        labels = vec![LabeledSpan::underline(export.label.source_span)],
+                        "label {0} exported, but is not defined",
+                        export.label
+                    ),
+                    [candidate] => miette::miette!(
+                        labels = vec![LabeledSpan::underline(export.label.source_span)],
+                        help = format!("nested blocks are exported by their full dotted names, so perhaps you meant {candidate}"),
+                        "label {0} exported, but is not defined",
+                        export.label
+                    ),
+                    candidates => miette::miette!(
+                        labels = vec![LabeledSpan::underline(export.label.source_span)],
+                        help = format!(
+                            "nested blocks are exported by their full dotted names, so perhaps you meant one of {}",
+                            candidates.iter().map(|c| c.as_str()).collect::<Vec<_>>().join(", ")
+                        ),
+                        "label {0} exported, but is not defined",
+                        export.label
+                    ),
+                };
+                errors.push(error.with_source_code(self.sources.clone()));
             }
         }
 
+        // There is no point in emitting any code if the symbol table is in error, and doing so
+        // could even overflow a segment, so we stop here if anything has gone wrong so far.
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
         // HACK: This is a total hack. I managed to restructure things in a way that the borrow
         // checker did not appreciate, so I'm just using `clone` as a bandage here until I actually
         // solve the problem.
-        for segment in &self.program.segments.clone() {
-            for code in segment {
-                self.add_code(&code)?;
+        //
+        // NOTE: Each segment form is recorded in the listing with no words of its own, so that the
+        // listing shows the offset at which the contents of each form begin.
+        for (segment, segment_forms) in self
+            .program
+            .segments
+            .clone()
+            .iter()
+            .zip(self.program.segment_forms.clone())
+        {
+            let mut segment_forms = segment_forms.into_iter().peekable();
+            for (i, code) in segment.iter().enumerate() {
+                while let Some(form) = segment_forms.next_if(|form| form.start == i) {
+                    self.add_segment_form_to_listing(&form);
+                }
+                if let Err(e) = self.add_code(code, "") {
+                    errors.push(e);
+                }
+            }
+            for form in segment_forms {
+                self.add_segment_form_to_listing(&form);
             }
             self.segment_index += 1;
+            self.segment_offset = 0;
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        if self.error_unresolved && !self.unresolved_references.is_empty() {
+            return Err(self.unresolved_labels());
+        }
+
+        if self.suggest_unresolved {
+            let suggestions = self.unresolved_label_suggestions();
+            self.warnings.extend(suggestions);
+        }
+
+        if self.warn_numeric_branch {
+            for source_span in &self.program.numeric_branches {
+                self.warnings.push(
+                    miette::miette!(
+                        severity = Severity::Warning,
+                        labels = vec![LabeledSpan::at(*source_span, "branches to this address")],
+                        help = "refer to a label instead, or pass --no-warn-numeric-branch if the address is intended",
+                        "branch to a numeric address",
+                    )
+                    .with_source_code(self.sources.clone()),
+                );
+            }
+        }
+
+        if self.warn_discarded_write {
+            for source_span in &self.program.discarded_writes {
+                self.warnings.push(
+                    miette::miette!(
+                        severity = Severity::Warning,
+                        labels = vec![LabeledSpan::at(*source_span, "r0 is always zero")],
+                        help = "use (nop) if this is intended, or pass --no-warn-discarded-write",
+                        "instruction writes to r0, so its result is discarded",
+                    )
+                    .with_source_code(self.sources.clone()),
+                );
+            }
+        }
+
+        // Loading a constant with `la` works just as `li` would, but a constant is hardly ever the
+        // address which was meant to be loaded, so we warn about it.
+        for label in &self.program.constant_addresses {
+            self.warnings.push(
+                miette::miette!(
+                    severity = Severity::Warning,
+                    labels = vec![LabeledSpan::at(label.source_span, "this is a constant")],
+                    help = "use li to load a constant",
+                    "la loads the constant {label}, rather than the address of a label",
+                )
+                .with_source_code(self.sources.clone()),
+            );
+        }
+
+        // Now that every reference has been seen, we can check for labels which are never used.
+        // NOTE: This is only done once the code has been emitted without error, since an error
+        // could have prevented some of the references from being seen.
+        let unused_labels = self.unused_labels();
+        if self.deny_unused && !unused_labels.is_empty() {
+            return Err(unused_labels);
         }
+        self.warnings.extend(unused_labels);
 
-        Ok(self.partial_poki)
+        let mut exports: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (export, absolute_label) in &self.exports {
+            exports
+                .entry(absolute_label.clone())
+                .or_default()
+                .push(export.name.to_string());
+        }
+
+        Ok(Assembly {
+            symbol_table: self.symbol_table,
+            poki: self.partial_poki,
+            listing: self.listing,
+            exports,
+            warnings: self.warnings,
+        })
+    }
+
+    // Resolve a label referenced from within the block `ctx`, returning the symbol table entry of
+    // the block to which it refers, if any. Labels are resolved relative to the block in which
+    // they are referenced first, then relative to each enclosing block in turn, and finally as an
+    // absolute label. As such, labels defined in inner blocks shadow those defined further out.
+    fn resolve_label(&self, label: &str, ctx: &str) -> Option<SymbolTableEntry> {
+        self.absolute_label(label, ctx)
+            .map(|absolute_label| self.symbol_table[&absolute_label])
+    }
+
+    // Find the absolute label of the block to which `label`, referenced from within the block
+    // `ctx`, refers, in the same way as `resolve_label`.
+    fn absolute_label(&self, label: &str, ctx: &str) -> Option<String> {
+        let mut ctx = ctx;
+        while !ctx.is_empty() {
+            let absolute_label = format!("{ctx}.{label}");
+            if self.symbol_table.contains_key(&absolute_label) {
+                return Some(absolute_label);
+            }
+            ctx = ctx.rsplit_once('.').map_or("", |(parent, _)| parent);
+        }
+
+        self.symbol_table
+            .contains_key(label)
+            .then(|| label.to_string())
+    }
+
+    // Report each label which is defined, but neither referred to nor exported. A block which only
+    // serves to group other blocks is used whenever any of the blocks nested within it are, and
+    // labels beginning with `_` are never reported, so that they may be left unused deliberately.
+    fn unused_labels(&self) -> Vec<Report> {
+        let is_used = |absolute_label: &str| {
+            let nested_prefix = format!("{absolute_label}.");
+            self.referenced_labels
+                .iter()
+                .map(String::as_str)
+                .chain(self.exports.iter().map(|(_, label)| label.as_str()))
+                .any(|used_label| {
+                    used_label == absolute_label || used_label.starts_with(&nested_prefix)
+                })
+        };
+
+        let mut unused_labels: Vec<_> = self
+            .symbol_table
+            .iter()
+            .filter(|(absolute_label, _)| {
+                let label = absolute_label
+                    .rsplit_once('.')
+                    .map_or(absolute_label.as_str(), |(_, label)| label);
+                !label.starts_with('_') && !is_used(absolute_label)
+            })
+            .collect();
+        unused_labels.sort_by_key(|(_, entry)| entry.source_span.offset());
+
+        unused_labels
+            .into_iter()
+            .map(|(absolute_label, entry)| {
+                miette::miette!(
+                    severity = if self.deny_unused {
+                        Severity::Error
+                    } else {
+                        Severity::Warning
+                    },
+                    labels = vec![LabeledSpan::at(entry.source_span, "defined here")],
+                    help = "refer to it, export it, or rename it to begin with _ if it is unused deliberately",
+                    "label {absolute_label} is never used"
+                )
+                .with_source_code(self.sources.clone())
+            })
+            .collect()
     }
 
-    fn add_code(&mut self, code: &Code<'a>) -> Result<()> {
-        match code {
-            Code::Block { label, contents } => {
-                if self.program.exports.contains(label) {
+    // Report each label which is referred to, but not defined anywhere in the program, pointing out
+    // every reference to it, along with any similar label which is defined.
+    fn unresolved_labels(&self) -> Vec<Report> {
+        self.partial_poki
+            .unresolved_table
+            .iter()
+            .map(|label| {
+                let mut labels = self.unresolved_reference_spans(label);
+                let help = match self.similar_label(label) {
+                    Some((similar_label, entry)) => {
+                        labels.push(LabeledSpan::at(entry.source_span, "similar label defined here"));
+                        format!("perhaps you meant {similar_label}")
+                    }
+                    None => "define the label, or assemble without --error-unresolved if it is defined elsewhere".to_string(),
+                };
+                miette::miette!(
+                    labels = labels,
+                    help = help,
+                    "label {label} is referred to, but is not defined",
+                )
+                .with_source_code(self.sources.clone())
+            })
+            .collect()
+    }
+
+    // Point out each label which is referred to, but not defined anywhere in the program, and which
+    // closely resembles a label which is defined, since such a reference is most likely a typo
+    // which would otherwise go unnoticed until the program is linked.
+    fn unresolved_label_suggestions(&self) -> Vec<Report> {
+        self.partial_poki
+            .unresolved_table
+            .iter()
+            .filter_map(|label| {
+                let (similar_label, entry) = self.similar_label(label)?;
+                let mut labels = self.unresolved_reference_spans(label);
+                labels.push(LabeledSpan::at(
+                    entry.source_span,
+                    "similar label defined here",
+                ));
+                Some(
+                    miette::miette!(
+                        severity = Severity::Advice,
+                        labels = labels,
+                        help = format!("perhaps you meant {similar_label}"),
+                        "label {label} is not defined, and is left to be resolved by linking",
+                    )
+                    .with_source_code(self.sources.clone()),
+                )
+            })
+            .collect()
+    }
+
+    fn unresolved_reference_spans(&self, label: &str) -> Vec<LabeledSpan> {
+        self.unresolved_references
+            .iter()
+            .filter(|reference| reference.label == label)
+            .map(|reference| LabeledSpan::at(reference.source_span, "referred to here"))
+            .collect()
+    }
+
+    // Find the defined label which most closely resembles `label`, if any resembles it closely
+    // enough. Labels are compared without regard to case or underscores, so that `uart_init`
+    // resembles `UartInit`, and are otherwise allowed to differ by a few edits, in proportion to
+    // their length. Since labels are resolved relative to the block in which they're referred to,
+    // the label of a nested block alone is compared as well as its full dotted name. Ties are
+    // broken in favour of the first label in the symbol table.
+    fn similar_label(&self, label: &str) -> Option<(&String, &SymbolTableEntry)> {
+        let normalize = |label: &str| -> Vec<char> {
+            label
+                .chars()
+                .filter(|c| *c != '_')
+                .flat_map(char::to_lowercase)
+                .collect()
+        };
+
+        let normalized_label = normalize(label);
+        let max_distance = cmp::max(normalized_label.len() / 3, 1);
+        self.symbol_table
+            .iter()
+            .map(|(defined_label, entry)| {
+                let nested_label = defined_label.rsplit('.').next().unwrap_or(defined_label);
+                let distance = cmp::min(
+                    edit_distance(&normalized_label, &normalize(defined_label)),
+                    edit_distance(&normalized_label, &normalize(nested_label)),
+                );
+                (distance, defined_label, entry)
+            })
+            .filter(|(distance, _, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _, _)| *distance)
+            .map(|(_, defined_label, entry)| (defined_label, entry))
+    }
+
+    // Add an entry to the relocation table of the current segment, so that the word at `offset`
+    // refers to `label`. If `label` is not defined anywhere in the program, it is added to the
+    // table of unresolved symbols.
+    fn add_relocation(&mut self, offset: u16, label: &str, ctx: &str) {
+        let relocation_table_entry = match self.resolve_label(label, ctx) {
+            Some(SymbolTableEntry {
+                segment_index,
+                segment_offset,
+                ..
+            }) => RelocationTableEntry {
+                offset,
+                segment_index,
+                segment_offset,
+            },
+            None => {
+                let segment_offset = match self
+                    .partial_poki
+                    .unresolved_table
+                    .iter()
+                    .position(|s| s == label)
+                {
+                    Some(segment_offset) => segment_offset,
+                    None => {
+                        self.partial_poki.unresolved_table.push(label.to_string());
+                        self.partial_poki.unresolved_table.len() - 1
+                    }
+                };
+                RelocationTableEntry {
+                    offset,
+                    segment_index: 0xFFFF,
+                    segment_offset: u16::try_from(segment_offset).unwrap(),
+                }
+            }
+        };
+        self.partial_poki.segments[usize::from(self.segment_index)]
+            .relocation_table
+            .push(relocation_table_entry);
+        self.pending_relocations.push((offset, label.to_string()));
+    }
+
+    fn add_segment_form_to_listing(&mut self, form: &SegmentForm) {
+        self.listing.0.push(ListingEntry {
+            source_span: form.source_span,
+            segment_index: self.segment_index,
+            segment_offset: self.segment_offset,
+            words: Vec::new(),
+            relocations: Vec::new(),
+        });
+    }
+
+    fn add_code(&mut self, code: &Code<'a>, ctx: &str) -> Result<()> {
+        // Blocks don't emit any words themselves, so only the code which they contain is recorded
+        // in the listing. The same goes for modules.
+        if let CodeKind::Block { .. } | CodeKind::Module { .. } = code.code_kind {
+            return self.add_code_kind(&code.code_kind, ctx);
+        }
+
+        let segment_offset = self.segment_offset;
+        let contents_length = self.partial_poki.segments[usize::from(self.segment_index)]
+            .contents
+            .len();
+
+        self.add_code_kind(&code.code_kind, ctx)?;
+
+        self.listing.0.push(ListingEntry {
+            source_span: code.source_span,
+            segment_index: self.segment_index,
+            segment_offset,
+            words: self.partial_poki.segments[usize::from(self.segment_index)].contents
+                [contents_length..]
+                .to_vec(),
+            relocations: self.pending_relocations.drain(..).collect(),
+        });
+
+        Ok(())
+    }
+
+    fn add_code_kind(&mut self, code_kind: &CodeKind<'a>, ctx: &str) -> Result<()> {
+        match code_kind {
+            CodeKind::Block { label, contents } => {
+                let ctx = if ctx.is_empty() {
+                    label.to_string()
+                } else {
+                    format!("{ctx}.{label}")
+                };
+
+                // NOTE: Exports name blocks by their absolute labels, so that a block nested
+                // inside of another is only exported if it is exported by its full dotted name,
+                // and not whenever a block with the same unqualified label is exported.
+                for (export, _) in self.exports.iter().filter(|(_, label)| *label == ctx) {
                     self.partial_poki.segments[usize::from(self.segment_index)]
                         .export_table
                         .push(ExportTableEntry {
-                            label: label.label.to_string(),
+                            label: export.name.to_string(),
                             offset: self.segment_offset,
                         });
                 }
 
                 for code in contents {
-                    self.add_code(code)?;
+                    self.add_code(code, &ctx)?;
                 }
             }
-            Code::String(s) => {
+            CodeKind::Module { name, contents } => {
+                let ctx = if ctx.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{ctx}.{name}")
+                };
+
+                for code in contents {
+                    self.add_code(code, &ctx)?;
+                }
+            }
+            CodeKind::String(s) => {
                 self.partial_poki.segments[usize::from(self.segment_index)]
                     .contents
                     .extend(s.encode_utf16());
             }
-            Code::Number(n) => {
+            CodeKind::Number(n) => {
                 self.partial_poki.segments[usize::from(self.segment_index)]
                     .contents
                     .push(*n);
             }
-            Code::Instruction { opcode, dst, src } => {
+            CodeKind::Instruction { opcode, dst, src } => {
                 let instruction = (*opcode as u16) | ((*dst as u16) << 6) | ((*src as u16) << 11);
                 self.partial_poki.segments[usize::from(self.segment_index)]
                     .contents
                     .push(instruction);
             }
-            Code::ImmediateInstruction {
+            CodeKind::ImmediateInstruction {
                 opcode,
                 dst,
                 src,
                 imm,
             } => {
+                // NOTE: The offset of a label within its segment is fixed as soon as the program is
+                // assembled, so unlike the address of a label, it can't be left to be resolved by
+                // linking, and the label must be defined in the program.
+                if let Immediate::OffsetOf(label) = imm {
+                    if self.resolve_label(label.label, ctx).is_none() {
+                        return Err(miette::miette!(
+                            labels = vec![LabeledSpan::underline(label.source_span)],
+                            help = "offsetof may only refer to labels defined in the program being assembled",
+                            "offsetof refers to label {label}, which is not defined",
+                        )
+                        .with_source_code(self.sources.clone()));
+                    }
+                }
+
                 let instruction = (*opcode as u16) | ((*dst as u16) << 6) | ((*src as u16) << 11);
                 self.partial_poki.segments[usize::from(self.segment_index)]
                     .contents
                     .push(instruction);
+                if let Immediate::Label(label) | Immediate::OffsetOf(label) = imm {
+                    if let Some(absolute_label) = self.absolute_label(label.label, ctx) {
+                        self.referenced_labels.insert(absolute_label);
+                    }
+                }
                 let immediate = match imm {
-                    Immediate::Label(label) => {
-                        match self.symbol_table()?.get(label.label) {
-                            Some(SymbolTableEntry {
-                                segment_index,
-                                segment_offset,
-                                ..
-                            }) => {
-                                let relocation_table_entry = RelocationTableEntry {
-                                    offset: self.segment_offset + 1,
-                                    segment_index: *segment_index,
-                                    segment_offset: *segment_offset,
-                                };
-                                self.partial_poki.segments[usize::from(self.segment_index)]
-                                    .relocation_table
-                                    .push(relocation_table_entry);
-                            }
-                            None => {
-                                let segment_offset = match self
-                                    .partial_poki
-                                    .unresolved_table
-                                    .iter()
-                                    .position(|s| s == &label.label)
-                                {
-                                    Some(segment_offset) => segment_offset,
-                                    None => {
-                                        self.partial_poki
-                                            .unresolved_table
-                                            .push(label.label.to_string());
-                                        self.partial_poki.unresolved_table.len() - 1
-                                    }
-                                };
-                                let relocation_table_entry = RelocationTableEntry {
-                                    offset: self.segment_offset + 1,
-                                    segment_index: 0xFFFF,
-                                    segment_offset: u16::try_from(segment_offset).unwrap(),
-                                };
-                                self.partial_poki.segments[usize::from(self.segment_index)]
-                                    .relocation_table
-                                    .push(relocation_table_entry);
+                    Immediate::Label(label) => match self.resolve_label(label.label, ctx) {
+                        Some(entry)
+                            if self.resolve_local && entry.segment_index == self.segment_index =>
+                        {
+                            entry.segment_offset
+                        }
+                        entry => {
+                            if entry.is_none() {
+                                self.unresolved_references.push(*label);
                             }
+                            self.add_relocation(self.segment_offset + 1, label.label, ctx);
+                            0
                         }
+                    },
+                    Immediate::Number(n) => n.value,
+                    // NOTE: The label was checked to be defined above.
+                    Immediate::OffsetOf(label) => {
+                        self.resolve_label(label.label, ctx).unwrap().segment_offset
+                    }
+                    // NOTE: `$` refers to the address of the immediate word itself, which, like
+                    // the address of any label, is only known once the segment has been loaded, so
+                    // it is encoded as a relocation referring to the current segment.
+                    Immediate::Here if self.resolve_local => self.segment_offset + 1,
+                    Immediate::Here => {
+                        let offset = self.segment_offset + 1;
+                        self.partial_poki.segments[usize::from(self.segment_index)]
+                            .relocation_table
+                            .push(RelocationTableEntry {
+                                offset,
+                                segment_index: self.segment_index,
+                                segment_offset: offset,
+                            });
+                        self.pending_relocations.push((offset, "$".to_string()));
                         0
                     }
-                    Immediate::Number(n) => *n,
                 };
                 self.partial_poki.segments[usize::from(self.segment_index)]
                     .contents
                     .push(immediate);
             }
-            Code::RCSR { dst, src } => {
+            CodeKind::RCSR { dst, src } => {
                 let instruction =
                     (Opcode::RCSR as u16) | ((*dst as u16) << 6) | ((*src as u16) << 11);
                 self.partial_poki.segments[usize::from(self.segment_index)]
                     .contents
                     .push(instruction);
             }
-            Code::WCSR { dst, src } => {
+            CodeKind::WCSR { dst, src } => {
                 let instruction =
                     (Opcode::WCSR as u16) | ((*dst as u16) << 6) | ((*src as u16) << 11);
                 self.partial_poki.segments[usize::from(self.segment_index)]
                     .contents
                     .push(instruction);
             }
-            Code::JSH { imm } => {
-                let immediate = match imm {
-                    Immediate::Label(label) => {
-                        match self.symbol_table()?.get(label.label) {
-                            Some(SymbolTableEntry {
-                                segment_index,
-                                segment_offset,
-                                ..
-                            }) => {
-                                let relocation_table_entry = RelocationTableEntry {
-                                    offset: self.segment_offset,
-                                    segment_index: *segment_index,
-                                    segment_offset: *segment_offset,
-                                };
-                                self.partial_poki.segments[usize::from(self.segment_index)]
-                                    .relocation_table
-                                    .push(relocation_table_entry);
-                            }
-                            None => {
-                                let segment_offset = match self
-                                    .partial_poki
-                                    .unresolved_table
-                                    .iter()
-                                    .position(|s| s == &label.label)
-                                {
-                                    Some(segment_offset) => segment_offset,
-                                    None => {
-                                        self.partial_poki
-                                            .unresolved_table
-                                            .push(label.label.to_string());
-                                        self.partial_poki.unresolved_table.len() - 1
-                                    }
-                                };
-                                let relocation_table_entry = RelocationTableEntry {
-                                    offset: self.segment_offset,
-                                    segment_index: 0xFFFF,
-                                    segment_offset: u16::try_from(segment_offset).unwrap(),
-                                };
-                                self.partial_poki.segments[usize::from(self.segment_index)]
-                                    .relocation_table
-                                    .push(relocation_table_entry);
-                            }
-                        }
-                        0
-                    }
-                    Immediate::Number(n) => *n,
-                };
-                let instruction = (Opcode::JSH as u16) | (immediate << 6);
+            CodeKind::IncBin { contents, .. } => {
+                self.partial_poki.segments[usize::from(self.segment_index)]
+                    .contents
+                    .extend(contents);
+            }
+            CodeKind::Sequence(contents) => {
+                for code_kind in contents {
+                    self.add_code_kind(code_kind, ctx)?;
+                }
+            }
+            CodeKind::JSH { imm } => {
+                let instruction = (Opcode::JSH as u16) | (imm << 6);
                 self.partial_poki.segments[usize::from(self.segment_index)]
                     .contents
                     .push(instruction);
             }
+            CodeKind::Org(_) | CodeKind::Skip(_) => {
+                let padding = code_kind.size(self.segment_offset);
+                self.partial_poki.segments[usize::from(self.segment_index)]
+                    .contents
+                    .extend(iter::repeat_n(0, usize::from(padding)));
+            }
         }
 
         // this is a bit weird-looking, but we should only be updating the segment offset at the
         // leaves of the ast, or we end up adding offsets twice and throwing off the resultant
         // relocation table
-        if let Code::Block { .. } = code {
-        } else {
-            self.segment_offset += code.size();
+        if !matches!(
+            code_kind,
+            CodeKind::Block { .. } | CodeKind::Module { .. } | CodeKind::Sequence(_)
+        ) {
+            self.segment_offset += code_kind.size(self.segment_offset);
         }
 
         Ok(())
@@ -239,75 +858,250 @@ impl<'a> Assembler<'a> {
 }
 
 impl<'a> Program<'a> {
-    pub fn symbol_table(&self) -> Result<SymbolTable> {
+    // Compute the symbol table of the program, along with any errors encountered while doing so.
+    // Should a label be defined more than once, only its first definition is included.
+    pub fn symbol_table(&self) -> (SymbolTable, Vec<Report>) {
+        // Add the labels defined in `segment` to `partial_symbol_table`, returning the offset at
+        // which `segment` ends. If the segment grows too large to be addressed, this is reported
+        // and `None` is returned, since the offsets of any further labels would be meaningless.
         fn symbol_table_helper<'a>(
+            program: &Program<'a>,
             segment: &Vec<Code<'a>>,
             segment_index: u16,
-            mut segment_offset: u16,
+            mut segment_offset: u32,
             partial_symbol_table: &mut SymbolTable,
+            errors: &mut Vec<Report>,
             ctx: String,
-        ) -> Result<()> {
+        ) -> Option<u32> {
             for code in segment {
-                if let Code::Block { label, contents } = code {
+                if let CodeKind::Block { label, contents } = &code.code_kind {
                     let absolute_label = if ctx.is_empty() {
                         label.to_string()
                     } else {
                         format!("{ctx}.{label}")
                     };
-                    if let Some(previous_definition) = partial_symbol_table.insert(
-                        absolute_label.clone(),
-                        SymbolTableEntry {
+
+                    // NOTE: Only the outermost block whose label is too long is reported, since
+                    // the labels of the blocks nested within it are necessarily too long as well.
+                    let label_length = absolute_label.encode_utf16().count();
+                    if label_length > MAX_LABEL_LENGTH {
+                        if ctx.encode_utf16().count() <= MAX_LABEL_LENGTH {
+                            errors.push(miette::miette!(
+                                labels = vec![LabeledSpan::at(
+                                    label.source_span,
+                                    format!("this block's label is {label_length} characters long")
+                                )],
+                                help = format!(
+                                    "labels, including the labels of the blocks enclosing them, may be at most {MAX_LABEL_LENGTH} characters long"
+                                ),
+                                "label {} is too long",
+                                label.label,
+                            ));
+                        }
+
+                        // NOTE: The label isn't added to the symbol table, but the contents of the
+                        // block are still walked, so that the offsets of the labels which follow it
+                        // remain correct.
+                        segment_offset = symbol_table_helper(
+                            program,
+                            contents,
                             segment_index,
                             segment_offset,
-                            source_span: label.source_span,
-                        },
-                    ) {
-                        miette::bail!(
-                            labels = vec![
+                            partial_symbol_table,
+                            errors,
+                            absolute_label,
+                        )?;
+                        continue;
+                    }
+
+                    match partial_symbol_table.entry(absolute_label.clone()) {
+                        Entry::Occupied(previous_definition) => {
+                            let previous_definition = previous_definition.get();
+                            let mut labels = vec![
                                 LabeledSpan::at(
                                     previous_definition.source_span,
-                                    "label first defined here"
+                                    "label first defined here",
                                 ),
-                                LabeledSpan::at(label.source_span, "and again here")
-                            ],
-                            "label {absolute_label} is defined more than once"
-                        );
+                                LabeledSpan::at(label.source_span, "and again here"),
+                            ];
+
+                            // Since a segment may be given in several forms, the two definitions
+                            // may lie far apart, so we point out the segment forms which contain
+                            // them when they differ.
+                            let previous_form = program.segment_form_containing(
+                                previous_definition.segment_index,
+                                previous_definition.source_span,
+                            );
+                            let form =
+                                program.segment_form_containing(segment_index, label.source_span);
+                            if let (Some(previous_form), Some(form)) = (previous_form, form) {
+                                if previous_form != form {
+                                    labels.push(LabeledSpan::at(
+                                        previous_form.source_span,
+                                        "in this segment",
+                                    ));
+                                    labels
+                                        .push(LabeledSpan::at(form.source_span, "and in this one"));
+                                }
+                            }
+
+                            errors.push(miette::miette!(
+                                labels = labels,
+                                "label {absolute_label} is defined more than once"
+                            ));
+                        }
+                        Entry::Vacant(entry) => {
+                            // NOTE: Offsets never exceed `u16::MAX`, since we bail out below as
+                            // soon as a segment grows past that size.
+                            let segment_offset = u16::try_from(segment_offset).unwrap();
+                            entry.insert(SymbolTableEntry {
+                                segment_index,
+                                segment_offset,
+                                size: code.size(segment_offset),
+                                source_span: label.source_span,
+                            });
+                        }
                     }
 
-                    symbol_table_helper(
+                    segment_offset = symbol_table_helper(
+                        program,
                         contents,
                         segment_index,
                         segment_offset,
                         partial_symbol_table,
+                        errors,
                         absolute_label,
                     )?;
+                } else if let CodeKind::Module { name, contents } = &code.code_kind {
+                    // A module places the labels defined within it inside of it, just as a block
+                    // would, but defines no label of its own.
+                    let ctx = if ctx.is_empty() {
+                        name.to_string()
+                    } else {
+                        format!("{ctx}.{name}")
+                    };
+                    segment_offset = symbol_table_helper(
+                        program,
+                        contents,
+                        segment_index,
+                        segment_offset,
+                        partial_symbol_table,
+                        errors,
+                        ctx,
+                    )?;
+                } else {
+                    if let CodeKind::Org(target) = code.code_kind {
+                        if segment_offset > u32::from(target.value) {
+                            let permissions = SegmentPermissions::from(segment_index);
+                            errors.push(miette::miette!(
+                                labels = vec![LabeledSpan::at(
+                                    target.source_span,
+                                    format!(
+                                        "this would place the following code at {:#06x}",
+                                        target.value
+                                    )
+                                )],
+                                help = format!(
+                                    "segment {permissions} is already {segment_offset:#06x} words long, so the following code would overlap the code before it"
+                                ),
+                                "org moves backwards in segment {permissions}",
+                            ));
+                        }
+                    }
+
+                    // NOTE: The number of words in a segment is stored in a single word of the
+                    // poki file which contains it, so a segment can be at most `u16::MAX` words
+                    // long. The offsets are tracked as `u32`s so that we notice this limit being
+                    // exceeded, rather than silently wrapping around.
+                    //
+                    // NOTE: Offsets never exceed `u16::MAX`, since we bail out below as soon as a
+                    // segment grows past that size.
+                    let size = u32::from(code.size(u16::try_from(segment_offset).unwrap()));
+                    if segment_offset + size > u32::from(u16::MAX) {
+                        let permissions = SegmentPermissions::from(segment_index);
+                        errors.push(miette::miette!(
+                            labels = vec![LabeledSpan::at(
+                                code.source_span,
+                                format!(
+                                    "this brings segment {permissions} to {} words",
+                                    segment_offset + size
+                                )
+                            )],
+                            help = format!(
+                                "segment {permissions} is already {segment_offset} words long, but segments may be at most {} words long",
+                                u16::MAX
+                            ),
+                            "segment {permissions} is too large",
+                        ));
+                        return None;
+                    }
+                    segment_offset += size;
                 }
-                segment_offset += code.size();
             }
 
-            Ok(())
+            Some(segment_offset)
         }
 
-        let mut symbol_table = HashMap::new();
+        let mut symbol_table = BTreeMap::new();
+        let mut errors = Vec::new();
         for i in 0u16..8 {
             symbol_table_helper(
+                self,
                 &self.segments[usize::from(i)],
                 i,
                 0,
                 &mut symbol_table,
+                &mut errors,
                 String::new(),
-            )?;
+            );
         }
 
-        Ok(symbol_table)
+        (symbol_table, errors)
+    }
+
+    // Find the segment form of the segment `segment_index` from which the code at `source_span`
+    // came.
+    fn segment_form_containing(
+        &self,
+        segment_index: u16,
+        source_span: SourceSpan,
+    ) -> Option<&SegmentForm> {
+        // NOTE: The segment forms of each segment are recorded in the order in which they appear,
+        // which, since the sources of every file are laid end to end, is also the order of their
+        // offsets. The form containing the code is therefore the last which begins before it.
+        self.segment_forms[usize::from(segment_index)]
+            .iter()
+            .take_while(|form| form.source_span.offset() <= source_span.offset())
+            .last()
+    }
+}
+
+// The number of insertions, deletions, and substitutions needed to turn `a` into `b`.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous_row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous_row[j] + usize::from(a_char != b_char);
+            row.push(cmp::min(
+                substitution,
+                cmp::min(previous_row[j + 1], row[j]) + 1,
+            ));
+        }
+        previous_row = row;
     }
+    previous_row[b.len()]
 }
 
-type SymbolTable = HashMap<String, SymbolTableEntry>;
+// NOTE: The symbol table is ordered, so that anything which iterates over it does so in the same
+// order every time, and so assembling the same sources twice produces identical output.
+pub type SymbolTable = BTreeMap<String, SymbolTableEntry>;
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct SymbolTableEntry {
     pub segment_index: u16,
     pub segment_offset: u16,
+    // The number of words occupied by the block which the label names.
+    pub size: u16,
     source_span: SourceSpan,
 }
@@ -0,0 +1,91 @@
+use crate::source::Sources;
+use crate::symbols::json_string;
+
+use miette::{LabeledSpan, Report, Severity};
+
+// Render `report` as a single line of JSON, of the form
+// `{"severity":...,"message":...,"help":...,"file":...,"line":...,"column":...,"start":...,
+// "len":...,"labels":[...]}`, where each label is of the form
+// `{"text":...,"file":...,"line":...,"column":...,"start":...,"len":...}`.
+//
+// NOTE: Spans are given as byte offsets within the file to which they refer, rather than within
+// the combined source, since that is what an editor will know, along with the line and column at
+// which they begin, both counted from 1, for tools which only understand those. The location of
+// the diagnostic as a whole is taken to be that of its primary label, or of its first label if
+// none is primary, and its location is null if it has no labels at all, as is the case for I/O
+// errors.
+pub fn render_json(report: &Report, sources: &Sources) -> String {
+    let severity = match report.severity().unwrap_or(Severity::Error) {
+        Severity::Advice => "advice",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    };
+
+    // NOTE: Errors which wrap others, such as those arising from I/O, say little on their own, so
+    // the whole chain of causes is included in the message, as miette would do when rendering it.
+    let message = report
+        .chain()
+        .map(|error| error.to_string())
+        .collect::<Vec<_>>()
+        .join(": ");
+
+    let help = report
+        .help()
+        .map_or("null".to_string(), |help| json_string(&help.to_string()));
+
+    let labels: Vec<_> = report.labels().into_iter().flatten().collect();
+    let location = labels
+        .iter()
+        .find(|label| label.primary())
+        .or(labels.first())
+        .map_or(
+            r#""file":null,"line":null,"column":null,"start":null,"len":null"#.to_string(),
+            |label| locate(label, sources),
+        );
+
+    let rendered_labels = labels
+        .iter()
+        .map(|label| {
+            format!(
+                r#"{{"text":{},{}}}"#,
+                label.label().map_or("null".to_string(), json_string),
+                locate(label, sources)
+            )
+        })
+        .collect::<Vec<_>>();
+
+    format!(
+        r#"{{"severity":"{severity}","message":{},"help":{help},{location},"labels":[{}]}}"#,
+        json_string(&message),
+        rendered_labels.join(",")
+    )
+}
+
+// Render the location of `label` as the fields `"file":...,"line":...,"column":...,"start":...,
+// "len":...` of a JSON object.
+//
+// NOTE: Columns are counted in characters, rather than bytes, since that is what an editor shows.
+fn locate(label: &LabeledSpan, sources: &Sources) -> String {
+    match sources.file_containing(label.offset()) {
+        Some(file) => {
+            let start = label.offset() - file.offset;
+            let before = &file.contents[..start];
+            let line = before.matches('\n').count() + 1;
+            let column = before
+                .rsplit('\n')
+                .next()
+                .map_or(0, |line| line.chars().count())
+                + 1;
+            format!(
+                r#""file":{},"line":{line},"column":{column},"start":{start},"len":{}"#,
+                json_string(&file.name),
+                label.len()
+            )
+        }
+        None => format!(
+            r#""file":null,"line":null,"column":null,"start":{},"len":{}"#,
+            label.offset(),
+            label.len()
+        ),
+    }
+}
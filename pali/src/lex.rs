@@ -1,10 +1,13 @@
-use miette::{IntoDiagnostic, LabeledSpan, Result, SourceSpan, WrapErr};
-use strum::{Display, EnumString};
+use crate::source::{SourceFile, Sources};
+
+use miette::{LabeledSpan, Result, SourceSpan};
+use strum::{Display, EnumIter, EnumMessage, EnumString};
 
 use std::error::Error;
 use std::fmt;
+use std::num::IntErrorKind;
+use std::ops::Range;
 use std::str::FromStr;
-use std::string::ToString;
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, EnumString)]
 #[strum(ascii_case_insensitive)]
@@ -34,6 +37,7 @@ pub enum Opcode {
     RCSR = 0b010100,
     WCSR = 0b010101,
     SWPR = 0b010110,
+    WFI = 0b010111,
 
     LDIO = 0b011000,
     STIO = 0b011001,
@@ -52,79 +56,200 @@ impl Opcode {
     pub fn takes_immediate(self) -> bool {
         (((self as usize) & 0b001000) != 0) & (self != Self::JSH)
     }
+
+    // Whether the instruction writes its result to its destination register. Jumps are excluded,
+    // even though jal writes the return address to its destination, since discarding the return
+    // address by writing it to r0 is the usual way of jumping without linking.
+    pub fn writes_destination(self) -> bool {
+        matches!(
+            self,
+            Self::ADD
+                | Self::SUB
+                | Self::AND
+                | Self::OR
+                | Self::XOR
+                | Self::SLL
+                | Self::SRL
+                | Self::SRA
+                | Self::ADDI
+                | Self::ANDI
+                | Self::ORI
+                | Self::XORI
+                | Self::SLLI
+                | Self::SRAI
+                | Self::LD
+                | Self::DEI
+                | Self::RCSR
+                | Self::LDIO
+        )
+    }
+
+    // Whether the instruction transfers control to the address given by its immediate.
+    pub fn is_branch(self) -> bool {
+        matches!(
+            self,
+            Self::JAL | Self::BEQ | Self::BNE | Self::BLT | Self::BGE | Self::BLTU | Self::BGEU
+        )
+    }
 }
 
+// NOTE: Pseudo-opcodes do not correspond to any real instruction in the lawa isa. Instead, the
+// parser expands each of them into the real instruction (or instructions) which they stand for.
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, EnumString)]
 #[strum(ascii_case_insensitive)]
+pub enum PseudoOpcode {
+    NOP,
+    MOV,
+    NOT,
+    NEG,
+    LI,
+    LA,
+    CALL,
+    RET,
+    PUSH,
+    POP,
+}
+
+// NOTE: In addition to their names, most registers have an alias describing the role which they
+// conventionally play. r0 is `zero`, r1 holds the return address (`ra`), r2 is the global pointer
+// (`gp`), r3 through r10 hold arguments (`a0` through `a7`), r11 through r18 are temporaries (`t0`
+// through `t7`), r19 through r26 are saved registers (`s0` through `s7`), and r31 is the stack
+// pointer (`sp`). r27 through r30 have no conventional role.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, EnumString, EnumIter, EnumMessage)]
+#[strum(ascii_case_insensitive)]
 #[repr(u16)]
 pub enum Register {
+    #[strum(serialize = "r0", serialize = "zero")]
     R0 = 0b00000,
+    #[strum(serialize = "r1", serialize = "ra")]
     R1 = 0b00001,
+    #[strum(serialize = "r2", serialize = "gp")]
     R2 = 0b00010,
+    #[strum(serialize = "r3", serialize = "a0")]
     R3 = 0b00011,
+    #[strum(serialize = "r4", serialize = "a1")]
     R4 = 0b00100,
+    #[strum(serialize = "r5", serialize = "a2")]
     R5 = 0b00101,
+    #[strum(serialize = "r6", serialize = "a3")]
     R6 = 0b00110,
+    #[strum(serialize = "r7", serialize = "a4")]
     R7 = 0b00111,
+    #[strum(serialize = "r8", serialize = "a5")]
     R8 = 0b01000,
+    #[strum(serialize = "r9", serialize = "a6")]
     R9 = 0b01001,
+    #[strum(serialize = "r10", serialize = "a7")]
     R10 = 0b01010,
+    #[strum(serialize = "r11", serialize = "t0")]
     R11 = 0b01011,
+    #[strum(serialize = "r12", serialize = "t1")]
     R12 = 0b01100,
+    #[strum(serialize = "r13", serialize = "t2")]
     R13 = 0b01101,
+    #[strum(serialize = "r14", serialize = "t3")]
     R14 = 0b01110,
+    #[strum(serialize = "r15", serialize = "t4")]
     R15 = 0b01111,
+    #[strum(serialize = "r16", serialize = "t5")]
     R16 = 0b10000,
+    #[strum(serialize = "r17", serialize = "t6")]
     R17 = 0b10001,
+    #[strum(serialize = "r18", serialize = "t7")]
     R18 = 0b10010,
+    #[strum(serialize = "r19", serialize = "s0")]
     R19 = 0b10011,
+    #[strum(serialize = "r20", serialize = "s1")]
     R20 = 0b10100,
+    #[strum(serialize = "r21", serialize = "s2")]
     R21 = 0b10101,
+    #[strum(serialize = "r22", serialize = "s3")]
     R22 = 0b10110,
+    #[strum(serialize = "r23", serialize = "s4")]
     R23 = 0b10111,
+    #[strum(serialize = "r24", serialize = "s5")]
     R24 = 0b11000,
+    #[strum(serialize = "r25", serialize = "s6")]
     R25 = 0b11001,
+    #[strum(serialize = "r26", serialize = "s7")]
     R26 = 0b11010,
     R27 = 0b11011,
     R28 = 0b11100,
     R29 = 0b11101,
     R30 = 0b11110,
+    #[strum(serialize = "r31", serialize = "sp")]
     R31 = 0b11111,
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, EnumString)]
+// NOTE: Each control/status register carries a brief description of its purpose, for the benefit
+// of tools such as editors which describe them.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, EnumString, EnumIter, EnumMessage)]
 #[strum(ascii_case_insensitive)]
 #[repr(u16)]
 pub enum ControlStatusRegister {
+    #[strum(message = "interrupt mask of devices 0 to 15")]
     IM0 = 0b00000,
+    #[strum(message = "interrupt mask of devices 16 to 31")]
     IM1 = 0b00001,
+    #[strum(message = "interrupt mask of devices 32 to 47")]
     IM2 = 0b00010,
+    #[strum(message = "interrupt mask of devices 48 to 63")]
     IM3 = 0b00011,
+    #[strum(message = "interrupt mask of devices 64 to 79")]
     IM4 = 0b00100,
+    #[strum(message = "interrupt mask of devices 80 to 95")]
     IM5 = 0b00101,
+    #[strum(message = "interrupt mask of devices 96 to 111")]
     IM6 = 0b00110,
+    #[strum(message = "interrupt mask of devices 112 to 127")]
     IM7 = 0b00111,
+    #[strum(message = "interrupt mask of devices 128 to 143")]
     IM8 = 0b01000,
+    #[strum(message = "interrupt mask of devices 144 to 159")]
     IM9 = 0b01001,
+    #[strum(message = "interrupt mask of devices 160 to 175")]
     IM10 = 0b01010,
+    #[strum(message = "interrupt mask of devices 176 to 191")]
     IM11 = 0b01011,
+    #[strum(message = "interrupt mask of devices 192 to 207")]
     IM12 = 0b01100,
+    #[strum(message = "interrupt mask of devices 208 to 223")]
     IM13 = 0b01101,
+    #[strum(message = "interrupt mask of devices 224 to 239")]
     IM14 = 0b01110,
+    #[strum(message = "interrupt mask of devices 240 to 255")]
     IM15 = 0b01111,
+    #[strum(message = "interrupt vector")]
     IV = 0b10000,
+    #[strum(message = "interrupted program counter")]
     IPC = 0b10001,
+    #[strum(message = "interrupt cause")]
     IC = 0b10010,
-
+    #[strum(message = "low bits of the cycle counter")]
+    CYCLE = 0b10011,
+    #[strum(message = "low bits of the count of instructions retired")]
+    INSTRET = 0b10100,
+    #[strum(message = "next bits of the counter last read")]
+    COUNTERH = 0b10101,
+    #[strum(message = "memory protection configuration")]
     MPC0 = 0b10110,
+    #[strum(message = "memory protection configuration")]
     MPC1 = 0b10111,
+    #[strum(message = "memory protection address")]
     MPA0 = 0b11000,
+    #[strum(message = "memory protection address")]
     MPA1 = 0b11001,
+    #[strum(message = "memory protection address")]
     MPA2 = 0b11010,
+    #[strum(message = "memory protection address")]
     MPA3 = 0b11011,
+    #[strum(message = "memory protection address")]
     MPA4 = 0b11100,
+    #[strum(message = "memory protection address")]
     MPA5 = 0b11101,
+    #[strum(message = "memory protection address")]
     MPA6 = 0b11110,
+    #[strum(message = "memory protection address")]
     MPA7 = 0b11111,
 }
 
@@ -154,6 +279,32 @@ impl From<SegmentPermissions> for u16 {
     }
 }
 
+impl From<u16> for SegmentPermissions {
+    fn from(segment_index: u16) -> Self {
+        Self {
+            readable: segment_index & 0b100 != 0,
+            writable: segment_index & 0b010 != 0,
+            executable: segment_index & 0b001 != 0,
+        }
+    }
+}
+
+impl fmt::Display for SegmentPermissions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.readable {
+            write!(f, "r")?;
+        }
+        if self.writable {
+            write!(f, "w")?;
+        }
+        if self.executable {
+            write!(f, "x")?;
+        }
+
+        Ok(())
+    }
+}
+
 impl FromStr for SegmentPermissions {
     type Err = ParseSegmentPermissionsError;
 
@@ -185,14 +336,26 @@ pub enum TokenKind<'a> {
     // Single-character lexemes.
     LeftParen,
     RightParen,
+    Here,
 
     // Keywords.
     Opcode(Opcode),
+    PseudoOpcode(PseudoOpcode),
     Register(Register),
     ControlStatusRegister(ControlStatusRegister),
     Segment,
     Block,
     Export,
+    As,
+    Module,
+    RegAlias,
+    IncBin,
+    StringZ,
+    Org,
+    Skip,
+    OffsetOf,
+    IfDef,
+    IfNDef,
 
     // Literals.
     Number(u16),
@@ -218,13 +381,13 @@ impl<'a> Token<'a> {
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct Lexer<'a> {
     // The source being lexed.
-    source: &'a str,
+    sources: Sources,
     // The portion of the source which remains unlexed.
     unlexed: &'a str,
 
-    // The current byte (not character) index of the lexer in the source. This needs to be a byte
-    // index instead of a character index for `miette`. Note, in particular, that it is not in
-    // general the case that `unlexed == source[index..]`.
+    // The current byte (not character) index of the lexer in the combined source. This needs to be
+    // a byte index instead of a character index for `miette`. Note, in particular, that it is not
+    // in general the case that `unlexed == source[index..]`.
     index: usize,
 
     // Whether or not the lexer has errored. If it has, we should always return None.
@@ -232,12 +395,12 @@ pub struct Lexer<'a> {
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(source: &'a str) -> Self {
+    pub fn new(sources: &Sources, file: &'a SourceFile) -> Self {
         Self {
-            source,
-            unlexed: source,
+            sources: sources.clone(),
+            unlexed: &file.contents,
 
-            index: 0,
+            index: file.offset,
 
             errored: false,
         }
@@ -260,6 +423,7 @@ impl<'a> Iterator for Lexer<'a> {
             // The types of multi-character tokens that we support.
             enum Started {
                 String,
+                Char,
                 Number,
                 Label,
             }
@@ -280,6 +444,12 @@ impl<'a> Iterator for Lexer<'a> {
                     self.unlexed = &self.unlexed[1..];
                     return Some(Ok(Token::new(TokenKind::RightParen, source_span)));
                 }
+                '$' => {
+                    let source_span = self.index..self.index + 1;
+                    self.index += 1;
+                    self.unlexed = &self.unlexed[1..];
+                    return Some(Ok(Token::new(TokenKind::Here, source_span)));
+                }
                 ';' => {
                     // NOTE: Unwrapping here is infallible, as we know that `self.unlexed` is
                     // non-empty, containing at least ';'.
@@ -288,6 +458,51 @@ impl<'a> Iterator for Lexer<'a> {
                     self.unlexed = &self.unlexed[line.len()..];
                     continue;
                 }
+                '#' if self.unlexed[1..].starts_with('|') => {
+                    // Block comments begin with `#|` and end with `|#`, and may be nested, so that
+                    // code which already contains block comments may itself be commented out.
+                    //
+                    // NOTE: Since they may span several lines, we have to be careful that the
+                    // entire comment, and not just its first line, is accounted for in the index.
+                    let mut depth = 0;
+                    let mut end_index = None;
+                    let mut i = 0;
+                    while i < self.unlexed.len() {
+                        if self.unlexed[i..].starts_with("#|") {
+                            depth += 1;
+                            i += 2;
+                        } else if self.unlexed[i..].starts_with("|#") {
+                            depth -= 1;
+                            i += 2;
+                            if depth == 0 {
+                                end_index = Some(i);
+                                break;
+                            }
+                        } else {
+                            // NOTE: Unwrapping here is infallible, as `i` is always less than the
+                            // length of `self.unlexed`, and always lies on a character boundary.
+                            i += self.unlexed[i..].chars().next().unwrap().len_utf8();
+                        }
+                    }
+
+                    let Some(end_index) = end_index else {
+                        // Like an unterminated string literal, an unterminated block comment
+                        // swallows the rest of the source, so there is nothing left to lex.
+                        self.errored = true;
+                        return Some(Err(miette::miette!(
+                            labels = vec![LabeledSpan::at(
+                                self.index..self.index + 2,
+                                "this comment is never closed"
+                            )],
+                            help = "block comments are closed with |#, and each nested block comment must be closed separately",
+                            "unterminated block comment",
+                        )
+                        .with_source_code(self.sources.clone())));
+                    };
+                    self.index += end_index;
+                    self.unlexed = &self.unlexed[end_index..];
+                    continue;
+                }
                 _ if c.is_whitespace() => {
                     // NOTE: We need to be a bit careful here. Rust's `is_whitespace` method
                     // returns true if the character is a Unicode whitespace codepoint, not all of
@@ -297,17 +512,25 @@ impl<'a> Iterator for Lexer<'a> {
                     continue;
                 }
                 '"' => Started::String,
+                '\'' => Started::Char,
                 _ if c.is_ascii_digit() => Started::Number,
+                '-' if self.unlexed[1..].starts_with(|c: char| c.is_ascii_digit()) => {
+                    Started::Number
+                }
                 _ if unicode_ident::is_xid_start(c) | (c == '_') => Started::Label,
                 _ => {
                     // We have encountered some invalid character in `source`.
+                    // NOTE: Unlike an unterminated string literal, an invalid character doesn't
+                    // affect how the remainder of the source is lexed, so we skip over it and
+                    // carry on, allowing the parser to report any further errors.
                     let source_span = self.index..self.index + c.len_utf8();
-                    self.errored = true;
+                    self.index += c.len_utf8();
+                    self.unlexed = &self.unlexed[c.len_utf8()..];
                     return Some(Err(miette::miette!(
                         labels = vec![LabeledSpan::underline(source_span)],
                         "encountered invalid character '{c}' in input",
                     )
-                    .with_source_code(self.source.to_string())));
+                    .with_source_code(self.sources.clone())));
                 }
             };
 
@@ -331,7 +554,7 @@ impl<'a> Iterator for Lexer<'a> {
                                         LabeledSpan::underline(self.index..self.index+self.unlexed.len())
                                     ],
                                     "unterminated string literal",
-                                }.with_source_code(self.source.to_string())));
+                                }.with_source_code(self.sources.clone())));
                         }
                     };
                     let literal = &self.unlexed[..end_index];
@@ -345,6 +568,76 @@ impl<'a> Iterator for Lexer<'a> {
                     let literal = unescape(literal);
                     return Some(Ok(Token::new(TokenKind::String(literal), source_span)));
                 }
+                Started::Char => {
+                    // Find the end of the character literal, skipping over any escaped quotes.
+                    //
+                    // NOTE: Unlike a string literal, a character literal can't span several lines,
+                    // so an unterminated one only swallows the remainder of its line, and the rest
+                    // of the source may be lexed as usual.
+                    let mut end_index = None;
+                    let mut chars = self.unlexed.char_indices().skip(1);
+                    while let Some((i, c)) = chars.next() {
+                        match c {
+                            '\\' => {
+                                chars.next();
+                            }
+                            '\'' => {
+                                end_index = Some(i + 1);
+                                break;
+                            }
+                            '\n' => break,
+                            _ => {}
+                        }
+                    }
+                    let Some(end_index) = end_index else {
+                        let line_length = self.unlexed.find('\n').unwrap_or(self.unlexed.len());
+                        let source_span = self.index..self.index + line_length;
+                        self.index += line_length;
+                        self.unlexed = &self.unlexed[line_length..];
+                        return Some(Err(miette::miette!(
+                            labels = vec![LabeledSpan::underline(source_span)],
+                            help =
+                                "a quote within a character literal must be escaped, as in '\\''",
+                            "unterminated character literal",
+                        )
+                        .with_source_code(self.sources.clone())));
+                    };
+
+                    let literal = &self.unlexed[..end_index];
+                    let source_span = self.index..self.index + end_index;
+                    let contents_index = self.index + 1;
+                    self.index += end_index;
+                    self.unlexed = &self.unlexed[end_index..];
+
+                    let c = match unescape_char(&literal[1..literal.len() - 1]) {
+                        Ok(c) => c,
+                        Err((span, message)) => {
+                            return Some(Err(miette::miette!(
+                                labels = vec![LabeledSpan::underline(
+                                    contents_index + span.start..contents_index + span.end
+                                )],
+                                help = "a character literal must contain exactly one character, or one of the escape sequences \\\\, \\', \\\", \\n, \\r, \\t, \\0, or \\uXXXX",
+                                "{message}",
+                            )
+                            .with_source_code(self.sources.clone())));
+                        }
+                    };
+
+                    // NOTE: A character outside of the basic multilingual plane is encoded in
+                    // UTF-16 as a surrogate pair, which doesn't fit in a single word.
+                    return Some(match u16::try_from(u32::from(c)) {
+                        Ok(n) => Ok(Token::new(TokenKind::Number(n), source_span)),
+                        Err(_) => Err(miette::miette!(
+                            labels = vec![LabeledSpan::at(
+                                source_span,
+                                "this character does not fit in 16 bits"
+                            )],
+                            help = "characters outside of the basic multilingual plane occupy two words in utf-16, and so may only appear in strings",
+                            "character literal {literal} is out of range",
+                        )
+                        .with_source_code(self.sources.clone())),
+                    });
+                }
                 Started::Number => {
                     // Find the end of the numeric literal, extract it, and update the state of the
                     // lexer as appropriate.
@@ -354,20 +647,43 @@ impl<'a> Iterator for Lexer<'a> {
                     // that numeric literals in other radices have. This is probably mostly
                     // uneliminable messiness.
 
+                    // A negative literal is lexed as the literal which follows its `-`, and then
+                    // negated, so that `-0x8000` is written just as `0x8000` would be.
+                    let start_index = self.index;
+                    let signed_unlexed = self.unlexed;
+                    let negative = c == '-';
+                    let c = if negative {
+                        self.index += 1;
+                        self.unlexed = &self.unlexed[1..];
+                        // NOTE: Unwrapping here is infallible, as a `-` only begins a numeric
+                        // literal when it is followed by a digit.
+                        self.unlexed.chars().next().unwrap()
+                    } else {
+                        c
+                    };
+
                     let radix = match self.unlexed.chars().nth(1) {
                         Some('b') if c == '0' => 2,
                         Some('o') if c == '0' => 8,
                         Some('x') if c == '0' => 16,
                         _ => 10,
                     };
+                    let (radix_name, max_literal) = match radix {
+                        2 => ("binary", "0b1111111111111111"),
+                        8 => ("octal", "0o177777"),
+                        16 => ("hexadecimal", "0xFFFF"),
+                        _ => ("decimal", "65535"),
+                    };
+                    // NOTE: `_` may appear among the digits of a literal in any radix to separate
+                    // groups of digits, and is stripped out before the literal is parsed.
                     let end_index = if radix == 10 {
                         self.unlexed
-                            .find(|c: char| !c.is_digit(radix))
+                            .find(|c: char| !(c.is_digit(radix) || c == '_'))
                             .unwrap_or(self.unlexed.len())
                     } else {
                         self.unlexed[2..]
-                            .find(|c: char| !c.is_digit(radix))
-                            .unwrap_or(self.unlexed.len())
+                            .find(|c: char| !(c.is_digit(radix) || c == '_'))
+                            .unwrap_or(self.unlexed.len() - 2)
                             + 2
                     };
                     let literal = if radix == 10 {
@@ -375,17 +691,122 @@ impl<'a> Iterator for Lexer<'a> {
                     } else {
                         &self.unlexed[2..end_index]
                     };
+                    let text = &signed_unlexed[..end_index + usize::from(negative)];
                     let source_span =
-                        self.index..self.index + literal.len() + if radix == 10 { 0 } else { 2 };
+                        start_index..self.index + literal.len() + if radix == 10 { 0 } else { 2 };
                     self.index += literal.len() + if radix == 10 { 0 } else { 2 };
                     self.unlexed = &self.unlexed[end_index..];
 
-                    return Some(
-                        u16::from_str_radix(literal, radix)
-                            .into_diagnostic()
-                            .wrap_err("invalid numeric literal")
-                            .map(|n| Token::new(TokenKind::Number(n), source_span)),
-                    );
+                    // A literal which runs straight into letters or digits which are not valid in
+                    // its radix, as in `0b102` or `0xFG`, would otherwise be silently split into a
+                    // number and whatever follows it, so the whole run is rejected instead.
+                    if let Some(invalid) = self
+                        .unlexed
+                        .chars()
+                        .next()
+                        .filter(|c| unicode_ident::is_xid_continue(*c))
+                    {
+                        let run_length = self
+                            .unlexed
+                            .find(|c| !unicode_ident::is_xid_continue(c))
+                            .unwrap_or(self.unlexed.len());
+                        let invalid_span = self.index..self.index + invalid.len_utf8();
+                        self.index += run_length;
+                        self.unlexed = &self.unlexed[run_length..];
+                        return Some(Err(miette::miette!(
+                            labels = vec![LabeledSpan::at(
+                                invalid_span,
+                                format!("'{invalid}' is not a digit in {radix_name}")
+                            )],
+                            help = match radix {
+                                2 => "binary literals may only contain the digits 0 and 1",
+                                8 => "octal literals may only contain the digits 0 through 7",
+                                16 => "hexadecimal literals may only contain the digits 0 through 9 and a through f",
+                                _ => "decimal literals may only contain the digits 0 through 9, or may be given a radix prefix of 0b, 0o, or 0x",
+                            },
+                            "invalid {radix_name} numeric literal",
+                        )
+                        .with_source_code(self.sources.clone())));
+                    }
+
+                    // A separator must lie between two digits, so reject literals which consist of
+                    // nothing but separators, or which begin or end with one, pointing at the
+                    // offending separator.
+                    let digits_offset = source_span.end - literal.len();
+                    if !literal.is_empty() && literal.chars().all(|c| c == '_') {
+                        return Some(Err(miette::miette!(
+                            labels = vec![LabeledSpan::underline(source_span)],
+                            help = "a radix prefix must be followed by at least one digit",
+                            "numeric literal {} has no digits, only digit separators",
+                            text,
+                        )
+                        .with_source_code(self.sources.clone())));
+                    }
+                    if literal.starts_with('_') {
+                        return Some(Err(miette::miette!(
+                            labels = vec![LabeledSpan::at(
+                                digits_offset..digits_offset + 1,
+                                "this separator does not follow a digit"
+                            )],
+                            help = "digit separators may only appear between two digits",
+                            "numeric literal {} begins with a digit separator",
+                            text,
+                        )
+                        .with_source_code(self.sources.clone())));
+                    }
+                    if literal.ends_with('_') {
+                        return Some(Err(miette::miette!(
+                            labels = vec![LabeledSpan::at(
+                                source_span.end - 1..source_span.end,
+                                "this separator is not followed by a digit"
+                            )],
+                            help = "digit separators may only appear between two digits",
+                            "numeric literal {} ends with a digit separator",
+                            text,
+                        )
+                        .with_source_code(self.sources.clone())));
+                    }
+                    let digits = literal.replace('_', "");
+
+                    // NOTE: Negative literals are stored in two's complement, as the isa expects,
+                    // so they must lie within the range of an `i16`.
+                    let out_of_range = || {
+                        miette::miette!(
+                            labels = vec![LabeledSpan::at(
+                                source_span.clone(),
+                                "this literal does not fit in 16 bits"
+                            )],
+                            help = if negative {
+                                "negative numeric literals must lie in the range -32768..=-1, since they are stored in two's complement".to_string()
+                            } else {
+                                format!("numeric literals must lie in the range 0..=65535, so a {radix_name} literal may be at most {max_literal}")
+                            },
+                            "numeric literal {} is out of range",
+                            text,
+                        )
+                        .with_source_code(self.sources.clone())
+                    };
+                    return Some(match u16::from_str_radix(&digits, radix) {
+                        Ok(n) if negative && n > 0x8000 => Err(out_of_range()),
+                        Ok(n) if negative => {
+                            Ok(Token::new(TokenKind::Number(n.wrapping_neg()), source_span))
+                        }
+                        Ok(n) => Ok(Token::new(TokenKind::Number(n), source_span)),
+                        Err(e) => match e.kind() {
+                            IntErrorKind::PosOverflow => Err(out_of_range()),
+                            IntErrorKind::Empty => Err(miette::miette!(
+                                labels = vec![LabeledSpan::underline(source_span)],
+                                help = "a radix prefix must be followed by at least one digit",
+                                "numeric literal has no digits",
+                            )
+                            .with_source_code(self.sources.clone())),
+                            _ => Err(miette::miette!(
+                                labels = vec![LabeledSpan::underline(source_span)],
+                                "invalid {radix_name} numeric literal: {e}",
+                            )
+                            .with_source_code(self.sources.clone())),
+                        },
+                    });
                 }
                 Started::Label => {
                     // Find the end of the label (or keyword) literal, extract it, and update the
@@ -404,6 +825,8 @@ impl<'a> Iterator for Lexer<'a> {
                     // rather than an identifier.
                     return Some(Ok(if let Ok(opcode) = Opcode::from_str(literal) {
                         Token::new(TokenKind::Opcode(opcode), source_span)
+                    } else if let Ok(pseudo_opcode) = PseudoOpcode::from_str(literal) {
+                        Token::new(TokenKind::PseudoOpcode(pseudo_opcode), source_span)
                     } else if let Ok(register) = Register::from_str(literal) {
                         Token::new(TokenKind::Register(register), source_span)
                     } else if let Ok(control_status_register) =
@@ -424,6 +847,26 @@ impl<'a> Iterator for Lexer<'a> {
                         Token::new(TokenKind::Block, source_span)
                     } else if literal.eq_ignore_ascii_case("export") {
                         Token::new(TokenKind::Export, source_span)
+                    } else if literal.eq_ignore_ascii_case("as") {
+                        Token::new(TokenKind::As, source_span)
+                    } else if literal.eq_ignore_ascii_case("module") {
+                        Token::new(TokenKind::Module, source_span)
+                    } else if literal.eq_ignore_ascii_case("regalias") {
+                        Token::new(TokenKind::RegAlias, source_span)
+                    } else if literal.eq_ignore_ascii_case("incbin") {
+                        Token::new(TokenKind::IncBin, source_span)
+                    } else if literal.eq_ignore_ascii_case("stringz") {
+                        Token::new(TokenKind::StringZ, source_span)
+                    } else if literal.eq_ignore_ascii_case("org") {
+                        Token::new(TokenKind::Org, source_span)
+                    } else if literal.eq_ignore_ascii_case("skip") {
+                        Token::new(TokenKind::Skip, source_span)
+                    } else if literal.eq_ignore_ascii_case("offsetof") {
+                        Token::new(TokenKind::OffsetOf, source_span)
+                    } else if literal.eq_ignore_ascii_case("ifdef") {
+                        Token::new(TokenKind::IfDef, source_span)
+                    } else if literal.eq_ignore_ascii_case("ifndef") {
+                        Token::new(TokenKind::IfNDef, source_span)
                     } else {
                         Token::new(TokenKind::Label(literal), source_span)
                     }));
@@ -433,7 +876,71 @@ impl<'a> Iterator for Lexer<'a> {
     }
 }
 
+// Check whether `s` would be lexed as a single label, rather than as a keyword or anything else.
+pub fn is_label(s: &str) -> bool {
+    let sources = Sources::new([(String::new(), s.to_string())]);
+    let mut lexer = Lexer::new(&sources, &sources.files()[0]);
+    matches!(
+        (lexer.next(), lexer.next()),
+        (Some(Ok(Token { token_kind: TokenKind::Label(label), .. })), None) if label == s
+    )
+}
+
+// Decode the contents of a character literal, without its quotes, into the single character for
+// which it stands. If it doesn't stand for exactly one character, return the span within
+// `contents` which is at fault, along with a description of the problem.
+fn unescape_char(contents: &str) -> Result<char, (Range<usize>, &'static str)> {
+    let mut chars = contents.char_indices();
+    let c = match chars.next() {
+        None => return Err((0..0, "empty character literal")),
+        Some((_, '\\')) => {
+            let escape_length = match chars.next() {
+                Some((_, 'u')) => 6,
+                Some((_, c)) => 1 + c.len_utf8(),
+                None => 1,
+            };
+            let escape = contents.get(..escape_length).unwrap_or(contents);
+            let c = match escape {
+                "\\\\" => Some('\\'),
+                "\\'" => Some('\''),
+                "\\\"" => Some('"'),
+                "\\n" => Some('\n'),
+                "\\r" => Some('\r'),
+                "\\t" => Some('\t'),
+                "\\0" => Some('\0'),
+                _ => escape
+                    .strip_prefix("\\u")
+                    .filter(|digits| {
+                        digits.len() == 4 && digits.chars().all(|c| c.is_ascii_hexdigit())
+                    })
+                    .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                    .and_then(char::from_u32),
+            };
+            chars = contents[escape.len()..].char_indices();
+            c.ok_or((
+                0..escape.len(),
+                "invalid escape sequence in character literal",
+            ))?
+        }
+        Some((_, c)) => {
+            chars = contents[c.len_utf8()..].char_indices();
+            c
+        }
+    };
+
+    match chars.next() {
+        None => Ok(c),
+        Some(_) => Err((
+            0..contents.len(),
+            "character literal contains more than one character",
+        )),
+    }
+}
+
 // Given a slice which refers to a string literal, unescape it.
+//
+// NOTE: Unlike character literals, string literals don't yet support escape sequences, so `"\n"`
+// stands for a backslash followed by an n, as the README points out.
 fn unescape(string_literal: &str) -> &str {
     // TODO: Support proper unescaping. For now, we just trim off the quotation marks.
     &string_literal[1..string_literal.len() - 1]
@@ -0,0 +1,11 @@
+//! An assembler for the lawa isa, as a library, so that other tools, such as editors and
+//! emulators, may assemble and analyze lawa assembly themselves.
+
+pub mod assemble;
+pub mod diagnostics;
+pub mod lex;
+pub mod listing;
+pub mod parse;
+pub mod source;
+pub mod summary;
+pub mod symbols;
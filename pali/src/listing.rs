@@ -0,0 +1,109 @@
+use crate::source::{SourceFile, Sources};
+
+use miette::SourceSpan;
+
+use std::fmt::Write;
+
+// The number of words which are displayed on each line of a listing.
+const WORDS_PER_ROW: usize = 4;
+
+// The width of the column in which the addresses and words of a listing are displayed. Each row
+// begins with the segment index and offset (`0:0000: `), and each word takes up 6 columns, 4 for
+// the word itself, 1 for the marker signifying that it is relocated, and 1 for the space.
+const ANNOTATION_WIDTH: usize = 8 + 6 * WORDS_PER_ROW;
+
+// A record of the words emitted by the assembler for a single piece of code.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct ListingEntry {
+    pub source_span: SourceSpan,
+    pub segment_index: u16,
+    pub segment_offset: u16,
+    pub words: Vec<u16>,
+    // The offsets of the words which are patched by relocations, along with the labels to which
+    // those relocations refer.
+    pub relocations: Vec<(u16, String)>,
+}
+
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq)]
+pub struct Listing(pub Vec<ListingEntry>);
+
+impl Listing {
+    // Render the listing as text, interleaving the lines of each of the `sources` with the words
+    // emitted for the code which begins on each line.
+    pub fn render(&self, sources: &Sources) -> String {
+        let mut rendered = String::new();
+        for file in sources.files() {
+            // When there is more than one file, each is preceded by its name, so that it is clear
+            // where one ends and the next begins.
+            if sources.files().len() > 1 {
+                writeln!(rendered, "{:ANNOTATION_WIDTH$} | ; {}", "", file.name).unwrap();
+            }
+
+            self.render_file(file, &mut rendered);
+        }
+
+        rendered
+    }
+
+    fn render_file(&self, file: &SourceFile, rendered: &mut String) {
+        // Group the entries of the listing by the line of the file on which they begin.
+        let mut line_starts = vec![file.offset];
+        line_starts.extend(
+            file.contents
+                .match_indices('\n')
+                .map(|(i, _)| file.offset + i + 1),
+        );
+        let mut entries_by_line = vec![Vec::new(); line_starts.len()];
+        for entry in &self.0 {
+            let offset = entry.source_span.offset();
+            if !(file.offset..=file.offset + file.contents.len()).contains(&offset) {
+                continue;
+            }
+            let line_index = line_starts.partition_point(|&i| i <= offset) - 1;
+            entries_by_line[line_index].push(entry);
+        }
+
+        for (line, entries) in file.contents.lines().zip(entries_by_line) {
+            let mut rows = Vec::new();
+            let has_words = entries.iter().any(|entry| !entry.words.is_empty());
+            for entry in entries {
+                // An entry with no words, such as that of a segment form, still shows the offset
+                // at which it lies, unless other entries on the same line already show it.
+                if entry.words.is_empty() && !has_words {
+                    rows.push(format!(
+                        "{}:{:04x}:",
+                        entry.segment_index, entry.segment_offset
+                    ));
+                }
+                for (row_index, row) in entry.words.chunks(WORDS_PER_ROW).enumerate() {
+                    let row_offset = entry.segment_offset + (row_index * WORDS_PER_ROW) as u16;
+                    let mut annotation = format!("{}:{:04x}:", entry.segment_index, row_offset);
+                    for (word_index, word) in row.iter().enumerate() {
+                        let offset = row_offset + word_index as u16;
+                        let relocated = entry.relocations.iter().any(|(o, _)| *o == offset);
+                        write!(
+                            annotation,
+                            " {word:04x}{}",
+                            if relocated { "*" } else { "" }
+                        )
+                        .unwrap();
+                    }
+                    rows.push(annotation);
+                }
+
+                for (offset, label) in &entry.relocations {
+                    rows.push(format!("        {offset:04x}* -> {label}"));
+                }
+            }
+
+            // The source line is displayed alongside the first row of annotations, and any
+            // remaining rows are displayed on their own beneath it.
+            let mut rows = rows.into_iter();
+            let first_row = rows.next().unwrap_or_default();
+            writeln!(rendered, "{first_row:ANNOTATION_WIDTH$} | {line}").unwrap();
+            for row in rows {
+                writeln!(rendered, "{row:ANNOTATION_WIDTH$} |").unwrap();
+            }
+        }
+    }
+}
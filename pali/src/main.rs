@@ -1,41 +1,600 @@
-mod assemble;
-mod lex;
-mod parse;
+use clap::{ArgAction, Parser, ValueEnum};
+use miette::{IntoDiagnostic, Report, Result, Severity, WrapErr};
 
-use clap::Parser;
-use miette::{IntoDiagnostic, Result, WrapErr};
+use std::ffi::OsString;
+use std::fs::{
+    create_dir_all, metadata, read_to_string, remove_file, rename, set_permissions,
+    symlink_metadata, write,
+};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
 
-use std::fs::{read_to_string, File};
-use std::path::PathBuf;
-
-use assemble::Assembler;
+use pali::assemble::Assembler;
+use pali::lex::{Lexer, Token, TokenKind};
+use pali::source::Sources;
+use pali::{diagnostics, lex, summary, symbols};
+use poki::Poki;
 
+/// an assembler for the lawa isa
+///
+/// assembly is reproducible: assembling the same sources with the same options always produces
+/// byte-identical poki files, listings, and symbol tables
 #[derive(Parser, Debug, Clone, Hash, PartialEq, Eq)]
 #[command(version, about, long_about)]
 struct Args {
-    source_path: PathBuf,
-    output_path: Option<PathBuf>,
+    /// the source files to assemble, whose segments are laid out in the order given, or - for stdin
+    #[arg(required = true)]
+    source_paths: Vec<PathBuf>,
+
+    /// write the assembled code to this path (or - for stdout), rather than alongside the source
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// create any missing directories in which output is to be written
+    #[arg(long)]
+    create_dirs: bool,
+
+    /// the format in which to write the assembled code
+    #[arg(long, value_enum, default_value_t = Format::Poki)]
+    format: Format,
+
+    /// the addresses at which to place each of the eight segments when writing a flat binary,
+    /// rather than placing the segments one after another
+    #[arg(long, value_name = "ADDRS", value_parser = parse_base_addrs)]
+    base_addrs: Option<[u16; 8]>,
+
+    /// write a listing of the assembled code, annotated with the emitted words, to this path
+    #[arg(short, long)]
+    listing: Option<PathBuf>,
+
+    /// write the symbol table, along with any unresolved symbols, to this path
+    #[arg(long)]
+    symbols: Option<PathBuf>,
+
+    /// write the symbol table, along with any unresolved symbols, to this path as json
+    #[arg(long)]
+    symbols_json: Option<PathBuf>,
+
+    /// write a map of the assembled program, giving the location and size of every label, and
+    /// which labels are exported or unresolved, to this path
+    #[arg(long)]
+    map: Option<PathBuf>,
+
+    /// the format in which to write the map
+    #[arg(long, value_enum, requires = "map", default_value_t = MapFormat::Text)]
+    map_format: MapFormat,
+
+    /// print the size of each segment, along with the number of unresolved symbols, to stderr
+    /// once assembly succeeds, and point out unresolved symbols which resemble defined labels
+    #[arg(long)]
+    summary: bool,
+
+    /// assemble the source and report any errors and warnings, but don't write any files
+    #[arg(long, conflicts_with_all = ["output", "create_dirs", "listing", "symbols", "symbols_json", "map"])]
+    check: bool,
+
+    /// print the size of each segment, along with the number of unresolved symbols, to stdout
+    /// once assembly succeeds
+    #[arg(long)]
+    stats: bool,
+
+    /// warn if the assembled poki file would be larger than this many bytes
+    #[arg(long)]
+    max_size: Option<usize>,
+
+    /// encode references to labels in the same segment as their offsets, rather than as
+    /// relocations, which is only correct if the segment is loaded at address 0
+    #[arg(long)]
+    resolve_local: bool,
+
+    /// report labels which are defined but never referred to or exported as errors, rather than
+    /// as warnings
+    #[arg(long)]
+    deny_unused: bool,
+
+    /// don't warn about branches whose targets are given as numbers, rather than as labels
+    #[arg(long = "no-warn-numeric-branch", action = ArgAction::SetFalse)]
+    warn_numeric_branch: bool,
+
+    /// don't warn about instructions which write to r0, and so discard their results
+    #[arg(long = "no-warn-discarded-write", action = ArgAction::SetFalse)]
+    warn_discarded_write: bool,
+
+    /// treat warnings as errors, failing once they have all been reported
+    #[arg(long)]
+    deny_warnings: bool,
+
+    /// report references to labels which aren't defined anywhere as errors, rather than leaving
+    /// them to be resolved by linking
+    #[arg(long)]
+    error_unresolved: bool,
+
+    /// the format in which to print errors and warnings
+    #[arg(long, alias = "diagnostic-format", value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+
+    /// the maximum number of errors to report before giving up
+    #[arg(long, default_value_t = 20)]
+    max_errors: usize,
+
+    /// define a constant, which may be used in place of a number and tested by ifdef and ifndef
+    #[arg(short = 'D', long = "define", value_name = "NAME=VALUE", value_parser = parse_define)]
+    defines: Vec<(String, u16)>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum Format {
+    /// a relocatable poki file
+    Poki,
+    /// a flat memory image, with every relocation applied and every word written little-endian
+    Bin,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Poki => "poki",
+            Self::Bin => "bin",
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum MapFormat {
+    /// one label per line, meant to be read by humans and simple scripts
+    Text,
+    /// a single json object, meant to be read by other tools
+    Json,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum MessageFormat {
+    /// graphical diagnostics, meant to be read by humans
+    #[value(alias = "text")]
+    Human,
+    /// a single line of json per diagnostic, meant to be read by editors and other tools
+    Json,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    let message_format = args.message_format;
 
-    let source_path = args.source_path;
-    let output_path = args
-        .output_path
-        .unwrap_or_else(|| source_path.with_extension("poki"));
+    // NOTE: When printing json, errors which prevent pali from going any further are printed as
+    // json too, and the exit code alone conveys failure.
+    let result = run(args);
+    if let (MessageFormat::Json, Err(report)) = (message_format, &result) {
+        emit(report, message_format, &Sources::new([]));
+        process::exit(1);
+    }
+    result
+}
 
-    let source = read_to_string(&source_path)
-        .into_diagnostic()
-        .wrap_err_with(|| format!("unable to read source from {}", source_path.display()))?;
+fn run(args: Args) -> Result<()> {
+    // NOTE: When only a single file is assembled, the output is written alongside it by default.
+    // There is no sensible default when several files are assembled together, or when the source
+    // is read from stdin, so an output path must be given explicitly, unless nothing is to be
+    // written at all.
+    let output_path = match (args.output, args.source_paths.as_slice()) {
+        _ if args.check => None,
+        (Some(output_path), _) => Some(output_path),
+        (None, [source_path]) if source_path == Path::new("-") => miette::bail!(
+            help = "specify an output path with --output, or use `--output -` to write to stdout",
+            "no output path given for source read from stdin"
+        ),
+        (None, [source_path]) => Some(source_path.with_extension(args.format.extension())),
+        (None, _) => miette::bail!(
+            help = "specify an output path with --output",
+            "no output path given for multiple source files"
+        ),
+    };
+
+    if args.stats && output_path.as_deref() == Some(Path::new("-")) {
+        miette::bail!(
+            help = "use --summary to print the same information to stderr",
+            "unable to print statistics to stdout, since the output is written there"
+        );
+    }
+
+    let mut files = Vec::new();
+    for source_path in &args.source_paths {
+        if source_path == Path::new("-") {
+            let source = io::read_to_string(io::stdin())
+                .into_diagnostic()
+                .wrap_err("unable to read source from stdin")?;
+            files.push(("<stdin>".to_string(), source));
+        } else {
+            let source = read_to_string(source_path)
+                .into_diagnostic()
+                .wrap_err_with(|| {
+                    format!("unable to read source from {}", source_path.display())
+                })?;
+            files.push((source_path.display().to_string(), source));
+        }
+    }
+    let sources = Sources::new(files);
+    let defines = args.defines.into_iter().collect();
+
+    // NOTE: Missing directories are dealt with before assembling, so that a typo in an output path
+    // is reported without first waiting for the whole program to be assembled.
+    let output_paths = [
+        output_path
+            .as_ref()
+            .filter(|output_path| *output_path != Path::new("-")),
+        args.listing.as_ref(),
+        args.symbols.as_ref(),
+        args.symbols_json.as_ref(),
+        args.map.as_ref(),
+    ];
+    for path in output_paths.into_iter().flatten() {
+        prepare_directory(path, args.create_dirs)?;
+    }
+
+    let mut assembly = Assembler::try_new(&sources, &defines)
+        .and_then(|assembler| {
+            assembler
+                .resolve_local(args.resolve_local)
+                .deny_unused(args.deny_unused)
+                .warn_numeric_branch(args.warn_numeric_branch)
+                .warn_discarded_write(args.warn_discarded_write)
+                .error_unresolved(args.error_unresolved)
+                .suggest_unresolved(args.summary)
+                .assemble()
+        })
+        .map_err(|errors| report_errors(errors, args.max_errors, args.message_format, &sources))?;
+
+    for warning in &assembly.warnings {
+        emit(warning, args.message_format, &sources);
+    }
+
+    // NOTE: Advice, such as the suggestions made by --summary, is never fatal.
+    let warning_count = assembly
+        .warnings
+        .iter()
+        .filter(|warning| warning.severity() == Some(Severity::Warning))
+        .count();
+    if args.deny_warnings && warning_count > 0 {
+        miette::bail!(
+            help = "warnings are treated as errors, since --deny-warnings was passed",
+            "unable to assemble due to {}",
+            if warning_count == 1 {
+                "the previous warning".to_string()
+            } else {
+                format!("{warning_count} previous warnings")
+            }
+        );
+    }
+
+    assembly.poki.normalize();
 
-    let mut output_file = File::create(&output_path)
+    if let Some(listing_path) = args.listing {
+        write_atomically(&listing_path, assembly.listing.render(&sources).as_bytes())
+            .into_diagnostic()
+            .wrap_err_with(|| format!("unable to write listing to {}", listing_path.display()))?;
+    }
+
+    if let Some(symbols_path) = args.symbols {
+        write_atomically(
+            &symbols_path,
+            symbols::render(&assembly.symbol_table, &assembly.poki.unresolved_table).as_bytes(),
+        )
         .into_diagnostic()
-        .wrap_err_with(|| format!("unable to write output to {}", output_path.display()))?;
+        .wrap_err_with(|| format!("unable to write symbols to {}", symbols_path.display()))?;
+    }
 
-    Assembler::try_new(&source)?
-        .assemble()?
-        .serialize(&mut output_file)
+    if let Some(symbols_path) = args.symbols_json {
+        write_atomically(
+            &symbols_path,
+            symbols::render_json(&assembly.symbol_table, &assembly.poki.unresolved_table)
+                .as_bytes(),
+        )
         .into_diagnostic()
-        .wrap_err("unable to serialize assembled poki file")
+        .wrap_err_with(|| format!("unable to write symbols to {}", symbols_path.display()))?;
+    }
+
+    if let Some(map_path) = args.map {
+        let map = match args.map_format {
+            MapFormat::Text => symbols::render_map(
+                &assembly.symbol_table,
+                &assembly.exports,
+                &assembly.poki.unresolved_table,
+            ),
+            MapFormat::Json => symbols::render_map_json(
+                &assembly.symbol_table,
+                &assembly.exports,
+                &assembly.poki.unresolved_table,
+            ),
+        };
+        write_atomically(&map_path, map.as_bytes())
+            .into_diagnostic()
+            .wrap_err_with(|| format!("unable to write map to {}", map_path.display()))?;
+    }
+
+    let bytes = match args.format {
+        Format::Poki => {
+            let mut bytes = Vec::with_capacity(assembly.poki.serialized_size());
+            assembly
+                .poki
+                .serialize(&mut bytes)
+                .into_diagnostic()
+                .wrap_err("unable to serialize assembled poki file")?;
+            bytes
+        }
+        Format::Bin => flatten(&assembly.poki, args.base_addrs)?,
+    };
+
+    if let Some(max_size) = args.max_size {
+        let size = bytes.len();
+        if size > max_size {
+            let warning = miette::miette!(
+                severity = Severity::Warning,
+                "assembled {} file is {size} bytes, exceeding the maximum of {max_size} bytes",
+                args.format.extension()
+            );
+            emit(&warning, args.message_format, &sources);
+            if args.deny_warnings {
+                miette::bail!(
+                    help = "warnings are treated as errors, since --deny-warnings was passed",
+                    "unable to assemble due to the previous warning"
+                );
+            }
+        }
+    }
+
+    // NOTE: The assembled code is serialized even when it isn't to be written, since doing so may
+    // itself fail, as when a flat binary refers to unresolved symbols.
+    match output_path {
+        None => {}
+        Some(output_path) if output_path == Path::new("-") => io::stdout()
+            .lock()
+            .write_all(&bytes)
+            .into_diagnostic()
+            .wrap_err("unable to write output to stdout")?,
+        Some(output_path) => write_atomically(&output_path, &bytes)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("unable to write output to {}", output_path.display()))?,
+    }
+
+    if args.summary {
+        eprint!("{}", summary::render(&assembly.poki));
+    }
+
+    if args.stats {
+        print!("{}", summary::render(&assembly.poki));
+    }
+
+    Ok(())
+}
+
+// Apply every relocation of `poki` against the given base addresses, or against the addresses at
+// which the segments are placed one after another if none are given, producing a flat image which
+// extends up to the end of the last segment.
+fn flatten(poki: &Poki, base_addrs: Option<[u16; 8]>) -> Result<Vec<u8>> {
+    // NOTE: `Poki::flatten` gives up at the first unresolved symbol it encounters, but it's much
+    // more useful to report all of them at once.
+    if !poki.unresolved_table.is_empty() {
+        miette::bail!(
+            help = "a flat binary can't refer to labels which aren't defined in the source",
+            "unable to write flat binary due to unresolved symbols: {}",
+            poki.unresolved_table.join(", ")
+        );
+    }
+
+    let base_addrs = match base_addrs {
+        Some(base_addrs) => base_addrs,
+        None => {
+            let mut base_addrs = [0; 8];
+            let mut next_base = 0usize;
+            for (base_addr, segment) in base_addrs.iter_mut().zip(&poki.segments) {
+                *base_addr = u16::try_from(next_base).map_err(|_| {
+                    miette::miette!("segments are too large to be placed one after another")
+                })?;
+                next_base += segment.contents.len();
+            }
+            base_addrs
+        }
+    };
+
+    let mut image = poki
+        .flatten(&base_addrs.map(Some))
+        .map_err(|e| miette::miette!("unable to write flat binary: {e}"))?;
+
+    let end = base_addrs
+        .iter()
+        .zip(&poki.segments)
+        .map(|(base_addr, segment)| usize::from(*base_addr) + segment.contents.len())
+        .max()
+        .unwrap_or(0);
+    image.truncate(2 * end);
+
+    // NOTE: `Poki::flatten` writes words in native byte order, as poki files are, but a flat
+    // binary has no header from which the byte order could be recovered, so it's always written
+    // little-endian.
+    for word in image.chunks_exact_mut(2) {
+        let value = u16::from_ne_bytes([word[0], word[1]]);
+        word.copy_from_slice(&value.to_le_bytes());
+    }
+
+    Ok(image)
+}
+
+// Check that the directory in which `path` is to be written exists, creating it if `create_dirs`
+// is set.
+fn prepare_directory(path: &Path, create_dirs: bool) -> Result<()> {
+    let Some(directory) = path
+        .parent()
+        .filter(|directory| *directory != Path::new(""))
+    else {
+        return Ok(());
+    };
+    if directory.is_dir() {
+        return Ok(());
+    }
+
+    if create_dirs {
+        create_dir_all(directory)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("unable to create directory {}", directory.display()))
+    } else {
+        miette::bail!(
+            help = "create the directory, or pass --create-dirs to have it created",
+            "unable to write to {}, since the directory {} does not exist",
+            path.display(),
+            directory.display()
+        )
+    }
+}
+
+// Write `contents` to `path` by writing them to a temporary file alongside it, and then renaming
+// that over `path`, so that `path` is never left partially written, and is left untouched if
+// anything goes wrong.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    // NOTE: Renaming over something other than a regular file, such as /dev/null or a named pipe,
+    // would replace it, rather than write to it, so those are written to directly. The same goes
+    // for symbolic links, such as /dev/stdout, which would be replaced by the file itself.
+    if symlink_metadata(path).is_ok_and(|metadata| !metadata.is_file()) {
+        return write(path, contents);
+    }
+
+    let Some(file_name) = path.file_name() else {
+        return write(path, contents);
+    };
+    let mut temporary_file_name = OsString::from(".");
+    temporary_file_name.push(file_name);
+    temporary_file_name.push(format!(".{}.tmp", process::id()));
+    let temporary_path = path.with_file_name(temporary_file_name);
+
+    // NOTE: The temporary file is created with the default permissions, so those of the file it
+    // replaces, if any, are copied to it, lest an executable or private output lose them.
+    let result = write(&temporary_path, contents)
+        .and_then(|()| match metadata(path) {
+            Ok(metadata) => set_permissions(&temporary_path, metadata.permissions()),
+            Err(_) => Ok(()),
+        })
+        .and_then(|()| rename(&temporary_path, path));
+    if result.is_err() {
+        let _ = remove_file(&temporary_path);
+    }
+    result
+}
+
+// Parse the argument to `--define`, which should be of the form `NAME=VALUE`, where `NAME` is a
+// label and `VALUE` is a numeric literal, written as it would be in source.
+fn parse_define(define: &str) -> Result<(String, u16), String> {
+    let (name, value) = define
+        .split_once('=')
+        .ok_or_else(|| "expected a definition of the form NAME=VALUE".to_string())?;
+
+    if !lex::is_label(name) {
+        return Err(format!("{name} is not a valid label"));
+    }
+
+    Ok((name.to_string(), parse_number(value)?))
+}
+
+// Parse the argument to `--base-addrs`, which should be eight comma-separated numeric literals.
+fn parse_base_addrs(base_addrs: &str) -> Result<[u16; 8], String> {
+    let base_addrs = base_addrs
+        .split(',')
+        .map(|base_addr| parse_number(base_addr.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    base_addrs.try_into().map_err(|base_addrs: Vec<_>| {
+        format!(
+            "expected a base address for each of the 8 segments, but got {}",
+            base_addrs.len()
+        )
+    })
+}
+
+// Parse a numeric literal, written as it would be in source.
+//
+// NOTE: The literal is lexed just as it would be in source, so that it may use any of the forms
+// which are allowed there, such as `_` separators or negative and character literals.
+fn parse_number(value: &str) -> Result<u16, String> {
+    let sources = Sources::new([(String::new(), value.to_string())]);
+    let mut lexer = Lexer::new(&sources, &sources.files()[0]);
+    match (lexer.next(), lexer.next()) {
+        (
+            Some(Ok(Token {
+                token_kind: TokenKind::Number(n),
+                ..
+            })),
+            None,
+        ) => Ok(n),
+        (Some(Err(e)), _) => Err(format!("{value} is not a valid numeric literal: {e}")),
+        _ => Err(format!("{value} is not a valid numeric literal")),
+    }
+}
+
+// Print `report` to stderr in the given format.
+fn emit(report: &Report, message_format: MessageFormat, sources: &Sources) {
+    match message_format {
+        MessageFormat::Human => eprintln!("{report:?}"),
+        MessageFormat::Json => eprintln!("{}", diagnostics::render_json(report, sources)),
+    }
+}
+
+// Print at most `max_errors` of `errors`, returning an error summarizing how many there were.
+fn report_errors(
+    errors: Vec<Report>,
+    max_errors: usize,
+    message_format: MessageFormat,
+    sources: &Sources,
+) -> Report {
+    let error_count = errors.len();
+    for error in errors.iter().take(max_errors) {
+        emit(error, message_format, sources);
+    }
+
+    if error_count > max_errors {
+        miette::miette!(
+            help = "use --max-errors to report more errors",
+            "unable to assemble due to {error_count} errors, of which only the first {max_errors} were reported"
+        )
+    } else if error_count == 1 {
+        miette::miette!("unable to assemble due to the previous error")
+    } else {
+        miette::miette!("unable to assemble due to {error_count} previous errors")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_are_parsed_as_they_are_in_source() {
+        assert_eq!(parse_number("0x1_000"), Ok(0x1000));
+        assert_eq!(parse_number("'A'"), Ok(0x0041));
+        assert_eq!(parse_number("-1"), Ok(0xFFFF));
+        assert_eq!(parse_number("-0x8000"), Ok(0x8000));
+    }
+
+    #[test]
+    fn invalid_numbers_are_rejected() {
+        assert_eq!(
+            parse_number("-0x8001"),
+            Err(
+                "-0x8001 is not a valid numeric literal: numeric literal -0x8001 is out of range"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse_number("-"),
+            Err(
+                "- is not a valid numeric literal: encountered invalid character '-' in input"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse_number("1 2"),
+            Err("1 2 is not a valid numeric literal".to_string())
+        );
+        assert_eq!(
+            parse_number("FOO"),
+            Err("FOO is not a valid numeric literal".to_string())
+        );
+    }
 }
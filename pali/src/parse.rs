@@ -1,130 +1,576 @@
-use crate::lex::{ControlStatusRegister, Lexer, Opcode, Register, TokenKind};
+use crate::lex::{ControlStatusRegister, Lexer, Opcode, PseudoOpcode, Register, Token, TokenKind};
+use crate::source::{SourceFile, Sources};
 
-use miette::{LabeledSpan, Result, SourceSpan};
+use miette::{LabeledSpan, Report, Result, SourceSpan};
 
 use std::cmp;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
-use std::iter::Peekable;
+use std::fs;
+use std::path::Path;
+
+// The maximum depth to which forms may be nested. Forms are parsed and assembled recursively, so
+// without a limit, a pathological program could overflow the stack.
+const MAX_NESTING_DEPTH: usize = 256;
+
+// The forms which may appear wherever code is expected, as they are listed in errors.
+const CODE_FORMS: &str = "block, incbin, stringz, org, skip, ifdef, ifndef, or opcode";
 
 #[derive(Debug)]
 pub struct Parser<'a> {
-    source: &'a str,
-    pub lexer: Peekable<Lexer<'a>>,
+    sources: &'a Sources,
+    file: &'a SourceFile,
+    pub lexer: Tokens<'a>,
+
+    // Register aliases defined by `regalias` statements, along with the labels by which they were
+    // defined.
+    register_aliases: HashMap<&'a str, (Register, Label<'a>)>,
+
+    // Constants defined on the command line.
+    defines: &'a Defines,
+
+    // The errors which have been encountered and recovered from so far.
+    errors: Vec<Report>,
+
+    // The spans of the numeric literals which have been given as the targets of branches.
+    numeric_branches: Vec<SourceSpan>,
+
+    // The spans of the destination operands of instructions which write to r0.
+    discarded_writes: Vec<SourceSpan>,
+
+    // The constants which have been given to `la` in place of labels.
+    constant_addresses: Vec<Label<'a>>,
+
+    // The module declared by a `module` form, if any, within which every label defined in the file
+    // is placed.
+    module: Option<Label<'a>>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(source: &'a str) -> Self {
+    pub fn new(sources: &'a Sources, file: &'a SourceFile, defines: &'a Defines) -> Self {
         Self {
-            source,
-            lexer: Lexer::new(source).peekable(),
+            sources,
+            file,
+            lexer: Tokens::new(Lexer::new(sources, file)),
+            register_aliases: HashMap::new(),
+            defines,
+            errors: Vec::new(),
+            numeric_branches: Vec::new(),
+            discarded_writes: Vec::new(),
+            constant_addresses: Vec::new(),
+            module: None,
         }
     }
 
-    pub fn parse(mut self) -> Result<Program<'a>> {
-        let mut exports = Vec::new();
-        let mut segments = [const { Vec::new() }; 8];
+    pub fn parse(mut self) -> Result<Program<'a>, Vec<Report>> {
+        let mut program = Program {
+            exports: Vec::new(),
+            segments: [const { Vec::new() }; 8],
+            segment_forms: [const { Vec::new() }; 8],
+            numeric_branches: Vec::new(),
+            discarded_writes: Vec::new(),
+            constant_addresses: Vec::new(),
+        };
 
         loop {
             // Consume a LeftParen. If there are no more tokens to be consumed, we have finished
             // parsing the entire source.
-            let token = self.lexer.next();
-            let opening_parenthesis = match token {
-                Some(token) => {
-                    let token = token?;
-
-                    match token.token_kind {
-                        TokenKind::LeftParen => token,
-                        other => {
-                            return Err(miette::miette!(
+            let opening_parenthesis = match self.lexer.next() {
+                Some(Ok(token)) => match token.token_kind {
+                    TokenKind::LeftParen => token,
+                    other => {
+                        self.errors.push(
+                            miette::miette!(
                                 labels = vec![LabeledSpan::underline(token.source_span)],
                                 "expected left parenthesis, found {other} instead",
                             )
-                            .with_source_code(self.source.to_string()))
-                        }
+                            .with_source_code(self.sources.clone()),
+                        );
+                        self.synchronize();
+                        continue;
                     }
+                },
+                Some(Err(e)) => {
+                    self.errors.push(e);
+                    self.synchronize();
+                    continue;
                 }
                 None => break,
             };
 
-            // The next token should either be an Export or a Segment. If it's an Export, we parse
-            // out the export statement here. If it's a Segment, we fall through.
+            // If anything goes wrong while parsing a top-level form, we record the error and skip
+            // ahead to the next top-level form, so that as many errors as possible are reported at
+            // once.
+            if let Err(e) = self.parse_top_level_form(&opening_parenthesis, &mut program) {
+                self.errors.push(e);
+                self.synchronize();
+            }
+        }
+
+        // Everything has been parsed. Return the parsed program, if it was parsed without error.
+        if self.errors.is_empty() {
+            program.numeric_branches = self.numeric_branches;
+            program.discarded_writes = self.discarded_writes;
+            program.constant_addresses = self.constant_addresses;
+            Ok(program)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    // Skip ahead to the beginning of the next top-level form, that is, to the next LeftParen which
+    // is not nested inside of any other parentheses. Since a single missing RightParen would
+    // otherwise cause the entire remainder of the source to be skipped, a LeftParen followed by a
+    // keyword which can only begin a top-level form is also taken to begin the next top-level form.
+    fn synchronize(&mut self) {
+        loop {
+            let at_left_parenthesis = matches!(
+                self.lexer.peek(),
+                Some(Ok(Token {
+                    token_kind: TokenKind::LeftParen,
+                    ..
+                }))
+            );
+
+            if self.at_top_level_form() || (at_left_parenthesis && self.lexer.depth == 0) {
+                self.lexer.depth = 0;
+                return;
+            }
+
+            if self.lexer.next().is_none() {
+                return;
+            }
+        }
+    }
+
+    // Skip the remainder of the Code which was being parsed when an error occurred, that is, skip
+    // until the parser is nested no more deeply than `depth`. As in `synchronize`, we stop early if
+    // we find the beginning of a top-level form.
+    fn skip_to_depth(&mut self, depth: usize) {
+        while self.lexer.depth > depth && !self.at_top_level_form() {
+            if self.lexer.next().is_none() {
+                return;
+            }
+        }
+    }
+
+    // Check whether the next tokens are a LeftParen followed by a keyword which can only begin a
+    // top-level form.
+    fn at_top_level_form(&mut self) -> bool {
+        matches!(
+            self.lexer.peek(),
+            Some(Ok(Token {
+                token_kind: TokenKind::LeftParen,
+                ..
+            }))
+        ) && matches!(
+            self.lexer.peek_second(),
+            Some(Ok(Token {
+                token_kind: TokenKind::Export
+                    | TokenKind::Module
+                    | TokenKind::RegAlias
+                    | TokenKind::Segment,
+                ..
+            }))
+        )
+    }
+
+    // Parse the remainder of a top-level form, whose opening parenthesis has already been
+    // consumed, adding any exports or code which it contains to `program`.
+    fn parse_top_level_form(
+        &mut self,
+        opening_parenthesis: &Token<'a>,
+        program: &mut Program<'a>,
+    ) -> Result<()> {
+        // The next token should either be an Export or a Segment. If it's an Export, we parse
+        // out the export statement here. If it's a Segment, we fall through.
+        match self.lexer.next() {
+            Some(token) => {
+                let token = token?;
+                match token.token_kind {
+                    TokenKind::Export => {
+                        loop {
+                            match self.lexer.peek() {
+                                Some(Ok(Token {
+                                    token_kind: TokenKind::RightParen,
+                                    ..
+                                })) => {
+                                    self.lexer.next();
+                                    break;
+                                }
+                                Some(_) => program.exports.push(self.parse_export()?),
+                                None => {
+                                    return Err(miette::miette!(
+                                        labels = vec![LabeledSpan::at(
+                                            opening_parenthesis.source_span,
+                                            "unpaired opening parenthesis"
+                                        )],
+                                        "expected right parenthesis, found EOF instead",
+                                    )
+                                    .with_source_code(self.sources.clone()));
+                                }
+                            }
+                        }
+                        return Ok(());
+                    }
+                    TokenKind::RegAlias => {
+                        self.parse_register_alias()?;
+                        self.parse_right_parenthesis(opening_parenthesis)?;
+                        return Ok(());
+                    }
+                    TokenKind::Module => {
+                        return self.parse_module(opening_parenthesis, program);
+                    }
+                    TokenKind::IfDef | TokenKind::IfNDef => {
+                        return self.parse_top_level_conditional(
+                            opening_parenthesis,
+                            &token.token_kind,
+                            program,
+                        );
+                    }
+                    TokenKind::Segment => {}
+                    other => {
+                        return Err(miette::miette!(
+                            labels = vec![LabeledSpan::underline(token.source_span)],
+                            "expected export, module, regalias, segment, ifdef, or ifndef, found {other} instead",
+                        )
+                        .with_source_code(self.sources.clone()));
+                    }
+                }
+            }
+            None => {
+                return Err(
+                    self.unexpected_eof("export, module, regalias, segment, ifdef, or ifndef")
+                );
+            }
+        };
+
+        // If we fell through to here, we read a Segment token in the previous step, so next we
+        // parse out the segment's permissions.
+        let (permissions, permissions_span) = match self.lexer.next() {
+            Some(token) => {
+                let token = token?;
+
+                if let TokenKind::SegmentPermissions(permissions) = token.token_kind {
+                    (permissions, token.source_span)
+                } else {
+                    return Err(miette::miette!(
+                        labels = vec![LabeledSpan::underline(token.source_span)],
+                        "expected segment permissions, found {0} instead",
+                        token.token_kind
+                    )
+                    .with_source_code(self.sources.clone()));
+                }
+            }
+            None => {
+                return Err(miette::miette!(
+                    labels = vec![LabeledSpan::at(
+                        opening_parenthesis.source_span,
+                        "unpaired opening parenthesis"
+                    )],
+                    "expected right parenthesis, found EOF instead",
+                )
+                .with_source_code(self.sources.clone()));
+            }
+        };
+
+        // We're now in the body of a segment. At this point, we just parse Code until we see a
+        // RightParen, at which point we have parsed the entire segment.
+        //
+        // NOTE: A segment with the same permissions may be given any number of times, in which
+        // case the contents of each are appended to the segment in the order in which they appear.
+        // Each segment form is recorded, so that it remains possible to tell where each begins.
+        let (contents, _) = self.parse_contents(opening_parenthesis)?;
+        let segment_index = usize::from(u16::from(permissions));
+        program.segment_forms[segment_index].push(SegmentForm {
+            source_span: (opening_parenthesis.source_span.offset()
+                ..permissions_span.offset() + permissions_span.len())
+                .into(),
+            start: program.segments[segment_index].len(),
+        });
+        match self.module {
+            // NOTE: Each piece of code is placed in the module separately, rather than placing
+            // the contents of the segment form in the module all together, so that each piece of
+            // code remains at the index at which the segment form records it to be.
+            Some(module) => {
+                program.segments[segment_index].extend(contents.into_iter().map(|code| {
+                    let source_span = code.source_span;
+                    Code::new(
+                        CodeKind::Module {
+                            name: module.label,
+                            contents: vec![code],
+                        },
+                        source_span,
+                    )
+                }));
+            }
+            None => program.segments[segment_index].extend(contents),
+        }
+
+        Ok(())
+    }
+
+    // Parse a single label named by an `export` form, which is either a label, exported by its own
+    // name, or of the form `(as name label)`, which exports `label` by `name` instead.
+    fn parse_export(&mut self) -> Result<Export<'a>> {
+        let module = self.module.map(|module| module.label);
+
+        if let Some(Ok(Token {
+            token_kind: TokenKind::LeftParen,
+            ..
+        })) = self.lexer.peek()
+        {
+            // NOTE: The `unwrap` is infallible, since we have just `peek`ed at the LeftParen.
+            let opening_parenthesis = self.lexer.next().unwrap()?;
             match self.lexer.next() {
                 Some(token) => {
                     let token = token?;
+                    if token.token_kind != TokenKind::As {
+                        return Err(miette::miette!(
+                            labels = vec![LabeledSpan::underline(token.source_span)],
+                            "expected as, found {0} instead",
+                            token.token_kind
+                        )
+                        .with_source_code(self.sources.clone()));
+                    }
+                }
+                None => return Err(self.unexpected_eof("as")),
+            }
+            let name = self.parse_label()?;
+            let label = self.parse_label()?;
+            self.parse_right_parenthesis(&opening_parenthesis)?;
+
+            return Ok(Export {
+                name,
+                label,
+                module,
+            });
+        }
+
+        let label = self.parse_label()?;
+        Ok(Export {
+            name: label,
+            label,
+            module,
+        })
+    }
+
+    // Parse the remainder of a `module` form, whose opening parenthesis and keyword have already
+    // been consumed.
+    //
+    // NOTE: The module applies to the whole of the file, so it must be declared before anything
+    // which it would apply to.
+    fn parse_module(
+        &mut self,
+        opening_parenthesis: &Token<'a>,
+        program: &Program<'a>,
+    ) -> Result<()> {
+        let name = self.parse_label()?;
+        self.parse_right_parenthesis(opening_parenthesis)?;
+
+        if let Some(previous_module) = self.module {
+            return Err(miette::miette!(
+                labels = vec![
+                    LabeledSpan::at(previous_module.source_span, "module first declared here"),
+                    LabeledSpan::at(name.source_span, "and again here"),
+                ],
+                "module declared more than once",
+            )
+            .with_source_code(self.sources.clone()));
+        }
+
+        if !program.exports.is_empty() || program.segment_forms.iter().any(|f| !f.is_empty()) {
+            return Err(miette::miette!(
+                labels = vec![LabeledSpan::at(name.source_span, "module declared here")],
+                help = "move the module form to the beginning of the file",
+                "module declared after an export or segment form",
+            )
+            .with_source_code(self.sources.clone()));
+        }
+
+        self.module = Some(name);
+        Ok(())
+    }
+
+    // Parse the remainder of an `ifdef` or `ifndef` form at the top level, whose opening
+    // parenthesis and keyword have already been consumed. If the condition holds, the top-level
+    // forms which it contains are parsed as though they appeared in its place, and otherwise they
+    // are skipped entirely.
+    fn parse_top_level_conditional(
+        &mut self,
+        opening_parenthesis: &Token<'a>,
+        keyword: &TokenKind<'a>,
+        program: &mut Program<'a>,
+    ) -> Result<()> {
+        if !self.parse_condition(keyword)? {
+            return self.skip_conditional(opening_parenthesis);
+        }
+
+        loop {
+            match self.lexer.next() {
+                Some(token) => {
+                    let token = token?;
+
                     match token.token_kind {
-                        TokenKind::Export => {
-                            loop {
-                                match self.lexer.next() {
-                                    Some(token) => {
-                                        let token = token?;
-
-                                        match token.token_kind {
-                                            TokenKind::RightParen => break,
-                                            TokenKind::Label(label) => {
-                                                exports.push(Label {
-                                                    label,
-                                                    source_span: token.source_span,
-                                                });
-                                            }
-                                            other => {
-                                                return Err(miette::miette!(
-                                                    labels = vec![LabeledSpan::underline(
-                                                        token.source_span
-                                                    )],
-                                                    "expected label, found {other} instead",
-                                                )
-                                                .with_source_code(self.source.to_string()));
-                                            }
-                                        }
-                                    }
-                                    None => {
-                                        return Err(miette::miette!(
-                                            labels = vec![LabeledSpan::at(
-                                                opening_parenthesis.source_span,
-                                                "unpaired opening parenthesis"
-                                            )],
-                                            "expected right parenthesis, found EOF instead",
-                                        )
-                                        .with_source_code(self.source.to_string()));
-                                    }
-                                }
-                            }
-                            continue;
-                        }
-                        TokenKind::Segment => {}
+                        TokenKind::RightParen => return Ok(()),
+                        TokenKind::LeftParen => self.parse_top_level_form(&token, program)?,
                         other => {
                             return Err(miette::miette!(
                                 labels = vec![LabeledSpan::underline(token.source_span)],
-                                "expected export or segment, found {other} instead",
+                                "expected left or right parenthesis, found {other} instead",
                             )
-                            .with_source_code(self.source.to_string()));
+                            .with_source_code(self.sources.clone()));
                         }
                     }
                 }
                 None => {
                     return Err(miette::miette!(
-                        "expected export or segment, found EOF instead",
-                    ));
+                        labels = vec![LabeledSpan::at(
+                            opening_parenthesis.source_span,
+                            "unpaired opening parenthesis"
+                        )],
+                        "expected right parenthesis, found EOF instead",
+                    )
+                    .with_source_code(self.sources.clone()));
                 }
-            };
+            }
+        }
+    }
 
-            // If we fell through to here, we read a Segment token in the previous step, so next we
-            // parse out the segment's permissions.
-            let permissions = match self.lexer.next() {
-                Some(token) => {
-                    let token = token?;
+    // Parse an `ifdef` or `ifndef` form inside of a segment or block. If the condition holds, the
+    // Code which it contains is returned, to be placed as though it appeared in place of the form,
+    // and otherwise it is skipped entirely.
+    fn parse_conditional_contents(&mut self) -> Result<Vec<Code<'a>>> {
+        // NOTE: The `unwrap`s are infallible, since the caller has just `peek`ed at both tokens.
+        let opening_parenthesis = self.lexer.next().unwrap()?;
+        let keyword = self.lexer.next().unwrap()?.token_kind;
+
+        if self.parse_condition(&keyword)? {
+            let (contents, _) = self.parse_contents(&opening_parenthesis)?;
+            Ok(contents)
+        } else {
+            self.skip_conditional(&opening_parenthesis)?;
+            Ok(Vec::new())
+        }
+    }
+
+    // Check whether the next tokens are a LeftParen followed by `ifdef` or `ifndef`.
+    fn at_conditional(&mut self) -> bool {
+        matches!(
+            self.lexer.peek(),
+            Some(Ok(Token {
+                token_kind: TokenKind::LeftParen,
+                ..
+            }))
+        ) && matches!(
+            self.lexer.peek_second(),
+            Some(Ok(Token {
+                token_kind: TokenKind::IfDef | TokenKind::IfNDef,
+                ..
+            }))
+        )
+    }
+
+    // Parse the name tested by an `ifdef` or `ifndef` form, returning whether or not its contents
+    // should be included.
+    fn parse_condition(&mut self, keyword: &TokenKind<'a>) -> Result<bool> {
+        let name = self.parse_label()?;
+        let defined = self.defines.contains_key(name.label);
+        Ok(defined == (*keyword == TokenKind::IfDef))
+    }
+
+    // NOTE: The contents of a conditional whose condition does not hold are skipped without being
+    // parsed, so that they may freely refer to labels and constants which are not defined when the
+    // condition does not hold.
+    fn skip_conditional(&mut self, opening_parenthesis: &Token<'a>) -> Result<()> {
+        let depth = self.lexer.depth;
+        while self.lexer.depth >= depth {
+            if self.lexer.next().is_none() {
+                return Err(miette::miette!(
+                    labels = vec![LabeledSpan::at(
+                        opening_parenthesis.source_span,
+                        "unpaired opening parenthesis"
+                    )],
+                    "expected right parenthesis, found EOF instead",
+                )
+                .with_source_code(self.sources.clone()));
+            }
+        }
+
+        Ok(())
+    }
 
-                    if let TokenKind::SegmentPermissions(permissions) = token.token_kind {
-                        permissions
+    // Parse Code until a RightParen is reached, returning the Code parsed along with the span of
+    // the RightParen. If an error occurs while parsing any piece of Code, the error is recorded and
+    // the remainder of that piece of Code is skipped, so that parsing may carry on with the next.
+    fn parse_contents(
+        &mut self,
+        opening_parenthesis: &Token<'a>,
+    ) -> Result<(Vec<Code<'a>>, SourceSpan)> {
+        if self.lexer.depth > MAX_NESTING_DEPTH {
+            return Err(miette::miette!(
+                labels = vec![LabeledSpan::at(
+                    opening_parenthesis.source_span,
+                    "this form is nested too deeply"
+                )],
+                help = format!("forms may be nested at most {MAX_NESTING_DEPTH} deep"),
+                "forms are nested too deeply",
+            )
+            .with_source_code(self.sources.clone()));
+        }
+
+        let mut contents = Vec::new();
+        loop {
+            // NOTE: A top-level form can never appear inside of another form, so if we find the
+            // beginning of one here, it is far more likely that a RightParen is missing than that
+            // the top-level form is itself in error.
+            if self.at_top_level_form() {
+                return Err(miette::miette!(
+                    labels = vec![LabeledSpan::at(
+                        opening_parenthesis.source_span,
+                        "unpaired opening parenthesis"
+                    )],
+                    "expected right parenthesis, found the beginning of a top-level form instead",
+                )
+                .with_source_code(self.sources.clone()));
+            }
+
+            match self.lexer.peek() {
+                Some(Ok(token)) if token.token_kind == TokenKind::RightParen => {
+                    // Remember to consume the RightParen, since here we've only `peek`ed at it!
+                    let source_span = token.source_span;
+                    self.lexer.next();
+                    return Ok((contents, source_span));
+                }
+                Some(Ok(_)) => {
+                    let depth = self.lexer.depth;
+                    let code = if self.at_conditional() {
+                        self.parse_conditional_contents()
                     } else {
-                        return Err(miette::miette!(
-                            labels = vec![LabeledSpan::underline(token.source_span)],
-                            "expected segment permissions, found {0} instead",
-                            token.token_kind
-                        )
-                        .with_source_code(self.source.to_string()));
+                        self.parse_code().map(|code| vec![code])
+                    };
+                    match code {
+                        Ok(code) => contents.extend(code),
+                        // If we have run into the beginning of a top-level form, or the end of the
+                        // source, then some enclosing form is missing its RightParen. This is
+                        // reported once, by `parse`, rather than once for every enclosing form.
+                        Err(e) if self.at_top_level_form() || self.lexer.peek().is_none() => {
+                            return Err(e);
+                        }
+                        Err(e) => {
+                            self.errors.push(e);
+                            self.skip_to_depth(depth);
+                        }
                     }
                 }
+                Some(Err(_)) => {
+                    // HACK: I need to do kind of an awkward dance here. The errors from `miette`
+                    // are not `Clone`, so if the next token in the iterator is an error, I need to
+                    // actually take the error out of the iterator, since I can't clone. The
+                    // `unwrap`s are infallible, since we have just `peek`ed at the error.
+                    let e = self.lexer.next().unwrap().unwrap_err();
+                    self.errors.push(e);
+                }
                 None => {
                     return Err(miette::miette!(
                         labels = vec![LabeledSpan::at(
@@ -133,50 +579,60 @@ impl<'a> Parser<'a> {
                         )],
                         "expected right parenthesis, found EOF instead",
                     )
-                    .with_source_code(self.source.to_string()));
+                    .with_source_code(self.sources.clone()));
                 }
-            };
+            }
+        }
+    }
 
-            // We're now in the body of a segment. At this point, we just parse Code until we see a
-            // RightParen, at which point we have parsed the entire segment.
-            loop {
-                match self.lexer.peek() {
-                    Some(token) => {
-                        // HACK: I need to do kind of an awkward dance here. The errors from
-                        // `miette` are not `Clone`, so if the next token in the iterator is an
-                        // error, I need to actually take the error out of the iterator, since I
-                        // can't clone. The `unwrap` is infallible, since we have just confirmed
-                        // that the lexer is non-empty by `peek`ing at it.
-                        let token = match token {
-                            Ok(token) => token,
-                            Err(_) => &self.lexer.next().unwrap()?,
-                        };
+    fn parse_register_alias(&mut self) -> Result<()> {
+        let alias = match self.lexer.next() {
+            Some(token) => {
+                let token = token?;
 
-                        if token.token_kind == TokenKind::RightParen {
-                            // Remember to consume the RightParen, since here we've only `peek`ed
-                            // at it!
-                            self.lexer.next();
-                            break;
-                        } else {
-                            segments[usize::from(u16::from(permissions))].push(self.parse_code()?);
-                        }
+                match token.token_kind {
+                    TokenKind::Label(label) => Label {
+                        label,
+                        source_span: token.source_span,
+                    },
+                    TokenKind::LeftParen | TokenKind::RightParen => {
+                        return Err(miette::miette!(
+                            labels = vec![LabeledSpan::underline(token.source_span)],
+                            "expected register alias, found {0} instead",
+                            token.token_kind
+                        )
+                        .with_source_code(self.sources.clone()));
                     }
-                    None => {
+                    _ => {
+                        let source_span = token.source_span;
+                        let name = self.source_text(source_span);
                         return Err(miette::miette!(
-                            labels = vec![LabeledSpan::at(
-                                opening_parenthesis.source_span,
-                                "unpaired opening parenthesis"
-                            )],
-                            "expected right parenthesis, found EOF instead",
+                            labels = vec![LabeledSpan::at(source_span, "alias defined here")],
+                            "{name} is a reserved keyword, and cannot be used as a register alias",
                         )
-                        .with_source_code(self.source.to_string()));
+                        .with_source_code(self.sources.clone()));
                     }
                 }
             }
+            None => return Err(self.unexpected_eof("register alias")),
+        };
+
+        let register = self.parse_register()?;
+
+        if let Some((_, previous_definition)) =
+            self.register_aliases.insert(alias.label, (register, alias))
+        {
+            return Err(miette::miette!(
+                labels = vec![
+                    LabeledSpan::at(previous_definition.source_span, "alias first defined here"),
+                    LabeledSpan::at(alias.source_span, "and again here")
+                ],
+                "register alias {alias} is defined more than once",
+            )
+            .with_source_code(self.sources.clone()));
         }
 
-        // Everything has been parsed. Return the parsed program.
-        Ok(Program { exports, segments })
+        Ok(())
     }
 
     pub fn parse_register(&mut self) -> Result<Register> {
@@ -186,17 +642,46 @@ impl<'a> Parser<'a> {
 
                 match token.token_kind {
                     TokenKind::Register(register) => Ok(register),
+                    TokenKind::Label(label) if self.register_aliases.contains_key(label) => {
+                        Ok(self.register_aliases[label].0)
+                    }
+                    TokenKind::ControlStatusRegister(_) => Err(miette::miette!(
+                        labels = vec![LabeledSpan::at(
+                            token.source_span,
+                            "this is a control/status register"
+                        )],
+                        help = "control/status registers can't be used as operands to most instructions, and may only be read with rcsr and written with wcsr",
+                        "expected register, found control/status register instead",
+                    )
+                    .with_source_code(self.sources.clone())),
                     other => Err(miette::miette!(
                         labels = vec![LabeledSpan::underline(token.source_span)],
                         "expected register, found {other} instead",
                     )
-                    .with_source_code(self.source.to_string())),
+                    .with_source_code(self.sources.clone())),
                 }
             }
-            None => Err(miette::miette!("expected register, found EOF instead")),
+            None => Err(self.unexpected_eof("register")),
         }
     }
 
+    // Parse the destination register of an instruction with the given opcode, recording it if the
+    // instruction would write to r0, since its result would then be discarded.
+    fn parse_destination_register(&mut self, opcode: Opcode) -> Result<Register> {
+        let source_span = match self.lexer.peek() {
+            Some(Ok(token)) => Some(token.source_span),
+            _ => None,
+        };
+
+        let register = self.parse_register()?;
+        if let Some(source_span) = source_span {
+            if register == Register::R0 && opcode.writes_destination() {
+                self.discarded_writes.push(source_span);
+            }
+        }
+        Ok(register)
+    }
+
     pub fn parse_control_status_register(&mut self) -> Result<ControlStatusRegister> {
         match self.lexer.next() {
             Some(token) => {
@@ -210,12 +695,10 @@ impl<'a> Parser<'a> {
                         labels = vec![LabeledSpan::underline(token.source_span)],
                         "expected control/status register, found {other} instead",
                     )
-                    .with_source_code(self.source.to_string())),
+                    .with_source_code(self.sources.clone())),
                 }
             }
-            None => Err(miette::miette!(
-                "expected control/status register, found EOF instead"
-            )),
+            None => Err(self.unexpected_eof("control/status register")),
         }
     }
 
@@ -227,15 +710,26 @@ impl<'a> Parser<'a> {
             Some(token) => {
                 let token = token?;
                 match token.token_kind {
-                    TokenKind::String(string) => return Ok(Code::String(string)),
-                    TokenKind::Number(n) => return Ok(Code::Number(n)),
+                    TokenKind::String(string) => {
+                        self.validate_string(string, 0, token.source_span)?;
+                        return Ok(Code::new(CodeKind::String(string), token.source_span));
+                    }
+                    TokenKind::Number(n) => {
+                        return Ok(Code::new(CodeKind::Number(n), token.source_span))
+                    }
+                    TokenKind::Label(label) if self.defines.contains_key(label) => {
+                        return Ok(Code::new(
+                            CodeKind::Number(self.defines[label]),
+                            token.source_span,
+                        ))
+                    }
                     TokenKind::LeftParen => token,
                     other => {
                         return Err(miette::miette!(
                             labels = vec![LabeledSpan::underline(token.source_span)],
                             "expected literal or left parenthesis, found {other} instead",
                         )
-                        .with_source_code(self.source.to_string()));
+                        .with_source_code(self.sources.clone()));
                     }
                 }
             }
@@ -254,156 +748,782 @@ impl<'a> Parser<'a> {
                 let token = token?;
                 match token.token_kind {
                     TokenKind::Opcode(opcode) => {
-                        let code = match opcode {
+                        // NOTE: Before each operand, we check that the instruction hasn't already
+                        // been closed, so that too few operands can be reported in terms of the
+                        // instruction, rather than as a stray right parenthesis.
+                        let code_kind = match opcode {
+                            // `wfi` takes no operands, so both of its register fields are zero.
+                            Opcode::WFI => CodeKind::Instruction {
+                                opcode,
+                                dst: Register::R0,
+                                src: Register::R0,
+                            },
                             Opcode::JSH => {
-                                let imm = self.parse_immediate()?;
-                                Ok(Code::JSH { imm })
+                                self.check_operand_present(opcode)?;
+                                let imm = self.parse_jsh_immediate()?;
+                                CodeKind::JSH { imm }
                             }
                             Opcode::WCSR => {
+                                self.check_operand_present(opcode)?;
                                 let dst = self.parse_control_status_register()?;
+                                self.check_operand_present(opcode)?;
                                 let src = self.parse_register()?;
 
-                                Ok(Code::WCSR { src, dst })
+                                CodeKind::WCSR { src, dst }
                             }
                             Opcode::RCSR => {
-                                let dst = self.parse_register()?;
+                                self.check_operand_present(opcode)?;
+                                let dst = self.parse_destination_register(opcode)?;
+                                self.check_operand_present(opcode)?;
                                 let src = self.parse_control_status_register()?;
 
-                                Ok(Code::RCSR { src, dst })
+                                CodeKind::RCSR { src, dst }
                             }
                             _ if opcode.takes_immediate() => {
-                                let dst = self.parse_register()?;
+                                self.check_operand_present(opcode)?;
+                                let dst = self.parse_destination_register(opcode)?;
+                                self.check_operand_present(opcode)?;
                                 let src = self.parse_register()?;
+                                self.check_operand_present(opcode)?;
+                                // NOTE: A branch to a numeric literal is almost always a mistake,
+                                // so they are recorded, in order that they may be warned about.
+                                // The target of jal is relative to its source register, so only
+                                // those which are relative to r0 are absolute.
+                                if opcode.is_branch()
+                                    && (opcode != Opcode::JAL || src == Register::R0)
+                                {
+                                    if let Some(Ok(Token {
+                                        token_kind: TokenKind::Number(_),
+                                        source_span,
+                                    })) = self.lexer.peek()
+                                    {
+                                        self.numeric_branches.push(*source_span);
+                                    }
+                                }
                                 let imm = self.parse_immediate()?;
 
-                                Ok(Code::ImmediateInstruction {
+                                CodeKind::ImmediateInstruction {
                                     opcode,
                                     imm,
                                     src,
                                     dst,
-                                })
+                                }
                             }
                             _ => {
-                                let dst = self.parse_register()?;
+                                self.check_operand_present(opcode)?;
+                                let dst = self.parse_destination_register(opcode)?;
+                                self.check_operand_present(opcode)?;
                                 let src = self.parse_register()?;
 
-                                Ok(Code::Instruction { opcode, src, dst })
+                                // NOTE: `(add r0 r0)` is the canonical nop, which is what `nop`
+                                // expands to, so it is never warned about.
+                                if opcode == Opcode::ADD
+                                    && dst == Register::R0
+                                    && src == Register::R0
+                                {
+                                    self.discarded_writes.pop();
+                                }
+
+                                CodeKind::Instruction { opcode, src, dst }
                             }
                         };
 
-                        // Check that we have the appropriate terminating RightParen.
-                        match self.lexer.next() {
-                            Some(token) => {
-                                let token = token?;
-                                match token.token_kind {
-                                    TokenKind::RightParen => {}
-                                    other => {
-                                        return Err(miette::miette!(
-                                            labels = vec![
-                                                LabeledSpan::at(
-                                                    opening_parenthesis.source_span,
-                                                    "unpaired opening parenthesis"
-                                                ),
-                                                LabeledSpan::at(
-                                                    token.source_span,
-                                                    "expected right parenthesis here"
-                                                )
-                                            ],
-                                            "expected right parenthesis, found {other} instead",
-                                        )
-                                        .with_source_code(self.source.to_string()));
+                        // Check that we have the appropriate terminating RightParen, rather than
+                        // some further operand.
+                        self.check_no_extra_operand(opcode)?;
+                        let closing_parenthesis =
+                            self.parse_right_parenthesis(&opening_parenthesis)?;
+
+                        return Ok(Code::new(
+                            code_kind,
+                            span_between(opening_parenthesis.source_span, closing_parenthesis),
+                        ));
+                    }
+                    TokenKind::PseudoOpcode(pseudo_opcode) => {
+                        // Pseudo-instructions are expanded here, in the parser, into the real
+                        // instructions which they stand for, so that the assembler never has to
+                        // know about them. Any immediate which the expansion introduces wasn't
+                        // written in the source, so it takes the span of the pseudo-opcode.
+                        let number = |value| {
+                            Immediate::Number(Spanned {
+                                value,
+                                source_span: token.source_span,
+                            })
+                        };
+                        let code_kind = match pseudo_opcode {
+                            PseudoOpcode::NOP => CodeKind::Instruction {
+                                opcode: Opcode::ADD,
+                                dst: Register::R0,
+                                src: Register::R0,
+                            },
+                            PseudoOpcode::MOV => {
+                                let dst = self.parse_destination_register(Opcode::ORI)?;
+                                let src = self.parse_register()?;
+
+                                CodeKind::ImmediateInstruction {
+                                    opcode: Opcode::ORI,
+                                    dst,
+                                    src,
+                                    imm: number(0),
+                                }
+                            }
+                            PseudoOpcode::NOT => {
+                                let dst = self.parse_destination_register(Opcode::XORI)?;
+                                let src = self.parse_register()?;
+
+                                CodeKind::ImmediateInstruction {
+                                    opcode: Opcode::XORI,
+                                    dst,
+                                    src,
+                                    imm: number(0xFFFF),
+                                }
+                            }
+                            PseudoOpcode::NEG => {
+                                let dst = self.parse_destination_register(Opcode::XORI)?;
+                                let src = self.parse_register()?;
+
+                                CodeKind::Sequence(vec![
+                                    CodeKind::ImmediateInstruction {
+                                        opcode: Opcode::XORI,
+                                        dst,
+                                        src,
+                                        imm: number(0xFFFF),
+                                    },
+                                    CodeKind::ImmediateInstruction {
+                                        opcode: Opcode::ADDI,
+                                        dst,
+                                        src: dst,
+                                        imm: number(1),
+                                    },
+                                ])
+                            }
+                            PseudoOpcode::LI => {
+                                let dst = self.parse_destination_register(Opcode::ADDI)?;
+                                let imm = self.parse_immediate()?;
+
+                                CodeKind::ImmediateInstruction {
+                                    opcode: Opcode::ADDI,
+                                    dst,
+                                    src: Register::R0,
+                                    imm,
+                                }
+                            }
+                            PseudoOpcode::LA => {
+                                // NOTE: This expands to exactly the same instruction as `li`. The
+                                // only difference is that `la` insists on being given a label,
+                                // since loading an address is the whole point of using it. A
+                                // constant is still loaded as `li` would load it, but is recorded,
+                                // since it is almost certainly not an address.
+                                let dst = self.parse_destination_register(Opcode::ADDI)?;
+                                let label = self.parse_label()?;
+                                let imm = match self.defines.get(label.label) {
+                                    Some(&value) => {
+                                        self.constant_addresses.push(label);
+                                        Immediate::Number(Spanned {
+                                            value,
+                                            source_span: label.source_span,
+                                        })
                                     }
+                                    None => Immediate::Label(label),
+                                };
+
+                                CodeKind::ImmediateInstruction {
+                                    opcode: Opcode::ADDI,
+                                    dst,
+                                    src: Register::R0,
+                                    imm,
+                                }
+                            }
+                            // NOTE: By convention, subroutines are called with the return address
+                            // held in r1, but `call` and `ret` both optionally take the register
+                            // holding the return address as their first argument.
+                            PseudoOpcode::CALL => {
+                                let explicit_register = match self.lexer.peek() {
+                                    Some(Ok(Token {
+                                        token_kind: TokenKind::Register(_),
+                                        ..
+                                    })) => true,
+                                    // NOTE: A register alias is spelled just as a label is, so it
+                                    // is only taken to be the register holding the return address
+                                    // if the label to be called follows it.
+                                    Some(Ok(Token {
+                                        token_kind: TokenKind::Label(label),
+                                        ..
+                                    })) if self.register_aliases.contains_key(*label) => !matches!(
+                                        self.lexer.peek_second(),
+                                        Some(Ok(Token {
+                                            token_kind: TokenKind::RightParen,
+                                            ..
+                                        }))
+                                    ),
+                                    _ => false,
+                                };
+                                let dst = if explicit_register {
+                                    self.parse_register()?
+                                } else {
+                                    Register::R1
+                                };
+                                let imm = self.parse_immediate()?;
+
+                                CodeKind::ImmediateInstruction {
+                                    opcode: Opcode::JAL,
+                                    dst,
+                                    src: Register::R0,
+                                    imm,
                                 }
                             }
-                            None => {
-                                return Err(miette::miette!(
-                                    labels = vec![LabeledSpan::at(
-                                        opening_parenthesis.source_span,
-                                        "unpaired opening parenthesis"
-                                    )],
-                                    "expected right parenthesis, found EOF instead",
-                                )
-                                .with_source_code(self.source.to_string()));
+                            PseudoOpcode::RET => {
+                                let src = if matches!(
+                                    self.lexer.peek(),
+                                    Some(Ok(Token {
+                                        token_kind: TokenKind::RightParen,
+                                        ..
+                                    }))
+                                ) {
+                                    Register::R1
+                                } else {
+                                    self.parse_register()?
+                                };
+
+                                CodeKind::ImmediateInstruction {
+                                    opcode: Opcode::JAL,
+                                    dst: Register::R0,
+                                    src,
+                                    imm: number(0),
+                                }
                             }
-                        }
+                            // NOTE: By convention, the stack pointer is held in r31, and the stack
+                            // grows downwards, with the stack pointer pointing at the value most
+                            // recently pushed.
+                            PseudoOpcode::PUSH => {
+                                let src = self.parse_register()?;
 
-                        return code;
+                                CodeKind::Sequence(vec![
+                                    CodeKind::ImmediateInstruction {
+                                        opcode: Opcode::STIO,
+                                        dst: src,
+                                        src: Register::R31,
+                                        imm: number(0xFFFF),
+                                    },
+                                    CodeKind::ImmediateInstruction {
+                                        opcode: Opcode::ADDI,
+                                        dst: Register::R31,
+                                        src: Register::R31,
+                                        imm: number(0xFFFF),
+                                    },
+                                ])
+                            }
+                            PseudoOpcode::POP => {
+                                let dst = self.parse_destination_register(Opcode::LDIO)?;
+
+                                CodeKind::Sequence(vec![
+                                    CodeKind::ImmediateInstruction {
+                                        opcode: Opcode::LDIO,
+                                        dst,
+                                        src: Register::R31,
+                                        imm: number(0),
+                                    },
+                                    CodeKind::ImmediateInstruction {
+                                        opcode: Opcode::ADDI,
+                                        dst: Register::R31,
+                                        src: Register::R31,
+                                        imm: number(1),
+                                    },
+                                ])
+                            }
+                        };
+
+                        let closing_parenthesis =
+                            self.parse_right_parenthesis(&opening_parenthesis)?;
+
+                        return Ok(Code::new(
+                            code_kind,
+                            span_between(opening_parenthesis.source_span, closing_parenthesis),
+                        ));
+                    }
+                    TokenKind::IncBin => {
+                        let code_kind = self.parse_incbin()?;
+                        let closing_parenthesis =
+                            self.parse_right_parenthesis(&opening_parenthesis)?;
+
+                        return Ok(Code::new(
+                            code_kind,
+                            span_between(opening_parenthesis.source_span, closing_parenthesis),
+                        ));
+                    }
+                    TokenKind::StringZ => {
+                        let code_kind = self.parse_stringz()?;
+                        let closing_parenthesis =
+                            self.parse_right_parenthesis(&opening_parenthesis)?;
+
+                        return Ok(Code::new(
+                            code_kind,
+                            span_between(opening_parenthesis.source_span, closing_parenthesis),
+                        ));
+                    }
+                    TokenKind::Org => {
+                        let code_kind = self.parse_org()?;
+                        let closing_parenthesis =
+                            self.parse_right_parenthesis(&opening_parenthesis)?;
+
+                        return Ok(Code::new(
+                            code_kind,
+                            span_between(opening_parenthesis.source_span, closing_parenthesis),
+                        ));
+                    }
+                    TokenKind::Skip => {
+                        let code_kind = self.parse_skip()?;
+                        let closing_parenthesis =
+                            self.parse_right_parenthesis(&opening_parenthesis)?;
+
+                        return Ok(Code::new(
+                            code_kind,
+                            span_between(opening_parenthesis.source_span, closing_parenthesis),
+                        ));
                     }
                     TokenKind::Block => {}
+                    // NOTE: Unlike the forms above, `offsetof` gives an immediate, rather than
+                    // code, so it's easily misplaced.
+                    TokenKind::OffsetOf => {
+                        return Err(miette::miette!(
+                            labels = vec![LabeledSpan::underline(token.source_span)],
+                            help = "offsetof may only be used as an immediate, as in (ldio t0 gp (offsetof table))",
+                            "expected {CODE_FORMS}, found offsetof instead",
+                        )
+                        .with_source_code(self.sources.clone()));
+                    }
                     other => {
                         return Err(miette::miette!(
                             labels = vec![LabeledSpan::underline(token.source_span)],
-                            "expected immediate, found {other} instead",
+                            "expected {CODE_FORMS}, found {other} instead",
                         )
-                        .with_source_code(self.source.to_string()))
+                        .with_source_code(self.sources.clone()));
                     }
                 };
             }
             None => {
-                return Err(miette::miette!(
-                    "expected block or opcode, found EOF instead"
-                ));
+                return Err(self.unexpected_eof(CODE_FORMS));
             }
         }
 
         // If we've falled through to here, it means that we have consumed a LeftParen followed by
         // a Block. We are in a block, so first parse the label, and then parse conde until we
         // encounter a terminating RightParen.
-        let label = match self.lexer.next() {
+        let label = self.parse_label()?;
+
+        // NOTE: Constants are substituted for their names while parsing, before any label is
+        // resolved, so a label which shares its name with a constant could never be referred to by
+        // that name.
+        if self.defines.contains_key(label.label) {
+            return Err(miette::miette!(
+                labels = vec![LabeledSpan::at(label.source_span, "label defined here")],
+                help = "rename the label, or the constant given with --define",
+                "label {label} shares its name with a constant, which would shadow it",
+            )
+            .with_source_code(self.sources.clone()));
+        }
+
+        let (contents, closing_parenthesis) = self.parse_contents(&opening_parenthesis)?;
+
+        Ok(Code::new(
+            CodeKind::Block { label, contents },
+            span_between(opening_parenthesis.source_span, closing_parenthesis),
+        ))
+    }
+
+    // NOTE: The file to be included is read while parsing, rather than while assembling, since its
+    // size must be known in order to compute the offsets of any labels following it.
+    fn parse_incbin(&mut self) -> Result<CodeKind<'a>> {
+        let (path, source_span) = match self.lexer.next() {
             Some(token) => {
                 let token = token?;
+
                 match token.token_kind {
-                    TokenKind::Label(label) => Label {
-                        label,
-                        source_span: token.source_span,
-                    },
+                    TokenKind::String(path) => (path, token.source_span),
                     other => {
                         return Err(miette::miette!(
                             labels = vec![LabeledSpan::underline(token.source_span)],
-                            "expected label, found {other} instead",
+                            "expected path, found {other} instead",
                         )
-                        .with_source_code(self.source.to_string()));
+                        .with_source_code(self.sources.clone()));
                     }
                 }
             }
-            None => {
-                return Err(miette::miette!("expected label, found EOF instead"));
-            }
+            None => return Err(self.unexpected_eof("path")),
         };
 
-        let mut contents = Vec::new();
-        loop {
-            match self.lexer.peek() {
-                Some(token) => {
-                    // HACK: Same as in `parse`.
-                    let token = match token {
-                        Ok(token) => token,
-                        Err(_) => &self.lexer.next().unwrap()?,
-                    };
+        // NOTE: Paths are resolved relative to the directory containing the source file in which
+        // they appear, so that a program may be assembled from any directory. Source read from
+        // stdin has no such directory, so paths in it are resolved relative to the directory in
+        // which pali is run instead.
+        let resolved_path = Path::new(&self.file.name)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(path);
+        let bytes = fs::read(&resolved_path).map_err(|e| {
+            miette::miette!(
+                labels = vec![LabeledSpan::underline(source_span)],
+                help = format!(
+                    "included paths are relative to the file which includes them, so this refers to {}",
+                    resolved_path.display()
+                ),
+                "unable to read included file {path}: {e}",
+            )
+            .with_source_code(self.sources.clone())
+        })?;
 
-                    if token.token_kind == TokenKind::RightParen {
-                        // Remember to consume the RightParen, since here we've only `peek`ed
-                        // at it!
-                        self.lexer.next();
-                        break;
-                    } else {
-                        contents.push(self.parse_code()?);
+        // NOTE: Words are always read little-endian, rather than in the byte order of the machine
+        // on which pali is run, so that assembly is reproducible. A file with an odd number of
+        // bytes is padded with a zero byte to fill its last word.
+        let contents: Vec<u16> = bytes
+            .chunks(2)
+            .map(|word| u16::from_le_bytes([word[0], word.get(1).copied().unwrap_or(0)]))
+            .collect();
+        if u16::try_from(contents.len()).is_err() {
+            return Err(miette::miette!(
+                labels = vec![LabeledSpan::underline(source_span)],
+                "included file {path} is {0} words long, above the limit of 65535",
+                contents.len()
+            )
+            .with_source_code(self.sources.clone()));
+        }
+
+        Ok(CodeKind::IncBin { path, contents })
+    }
+
+    // NOTE: A zero-terminated string is just a string followed by a zero word, so there is no need
+    // for a separate kind of code to represent it.
+    fn parse_stringz(&mut self) -> Result<CodeKind<'a>> {
+        match self.lexer.next() {
+            Some(token) => {
+                let token = token?;
+
+                match token.token_kind {
+                    TokenKind::String(string) => {
+                        self.validate_string(string, 1, token.source_span)?;
+                        Ok(CodeKind::Sequence(vec![
+                            CodeKind::String(string),
+                            CodeKind::Number(0),
+                        ]))
                     }
+                    other => Err(miette::miette!(
+                        labels = vec![LabeledSpan::underline(token.source_span)],
+                        "expected string, found {other} instead",
+                    )
+                    .with_source_code(self.sources.clone())),
                 }
-                None => {
-                    return Err(miette::miette!(
-                        labels = vec![LabeledSpan::at(
-                            opening_parenthesis.source_span,
-                            "unpaired opening parenthesis"
-                        )],
-                        "expected right parenthesis, found EOF instead",
+            }
+            None => Err(self.unexpected_eof("string")),
+        }
+    }
+
+    // NOTE: The offset to which an org form advances must be known while computing the symbol
+    // table, so, as with the immediate of jsh, it can't be a label.
+    fn parse_org(&mut self) -> Result<CodeKind<'a>> {
+        match self.lexer.next() {
+            Some(token) => {
+                let token = token?;
+
+                match token.token_kind {
+                    TokenKind::Number(n) => Ok(CodeKind::Org(Spanned {
+                        value: n,
+                        source_span: token.source_span,
+                    })),
+                    TokenKind::Label(label) if self.defines.contains_key(label) => {
+                        Ok(CodeKind::Org(Spanned {
+                            value: self.defines[label],
+                            source_span: token.source_span,
+                        }))
+                    }
+                    other => Err(miette::miette!(
+                        labels = vec![LabeledSpan::underline(token.source_span)],
+                        "expected offset, found {other} instead",
                     )
-                    .with_source_code(self.source.to_string()));
+                    .with_source_code(self.sources.clone())),
                 }
             }
+            None => Err(self.unexpected_eof("offset")),
         }
+    }
+
+    // NOTE: As with org, the number of words skipped must be known while computing the symbol
+    // table, so it can't be a label.
+    fn parse_skip(&mut self) -> Result<CodeKind<'a>> {
+        match self.lexer.next() {
+            Some(token) => {
+                let token = token?;
 
-        Ok(Code::Block { label, contents })
+                match token.token_kind {
+                    TokenKind::Number(n) => Ok(CodeKind::Skip(n)),
+                    TokenKind::Label(label) if self.defines.contains_key(label) => {
+                        Ok(CodeKind::Skip(self.defines[label]))
+                    }
+                    other => Err(miette::miette!(
+                        labels = vec![LabeledSpan::underline(token.source_span)],
+                        "expected number of words, found {other} instead",
+                    )
+                    .with_source_code(self.sources.clone())),
+                }
+            }
+            None => Err(self.unexpected_eof("number of words")),
+        }
+    }
+
+    pub fn parse_label(&mut self) -> Result<Label<'a>> {
+        match self.lexer.next() {
+            Some(token) => {
+                let token = token?;
+
+                match token.token_kind {
+                    TokenKind::Label(label) => Ok(Label {
+                        label,
+                        source_span: token.source_span,
+                    }),
+                    // NOTE: Since the lexer recognizes keywords before labels, a label which
+                    // shares its name with a keyword is never lexed as a label at all. Simply
+                    // reporting the keyword which was found is confusing, so we explain why.
+                    TokenKind::Opcode(_)
+                    | TokenKind::PseudoOpcode(_)
+                    | TokenKind::Register(_)
+                    | TokenKind::ControlStatusRegister(_)
+                    | TokenKind::SegmentPermissions(_)
+                    | TokenKind::Segment
+                    | TokenKind::Block
+                    | TokenKind::Export
+                    | TokenKind::As
+                    | TokenKind::Module
+                    | TokenKind::RegAlias
+                    | TokenKind::IncBin
+                    | TokenKind::StringZ
+                    | TokenKind::Org
+                    | TokenKind::Skip
+                    | TokenKind::OffsetOf
+                    | TokenKind::IfDef
+                    | TokenKind::IfNDef => {
+                        let name = self.source_text(token.source_span);
+                        let reserved_as = match token.token_kind {
+                            TokenKind::Opcode(_) => "a reserved opcode name",
+                            TokenKind::PseudoOpcode(_) => "a reserved pseudo-instruction name",
+                            TokenKind::Register(_) => "a reserved register name",
+                            TokenKind::ControlStatusRegister(_) => {
+                                "a reserved control/status register name"
+                            }
+                            TokenKind::SegmentPermissions(_) => "reserved for segment permissions",
+                            _ => "a reserved keyword",
+                        };
+                        Err(miette::miette!(
+                            labels = vec![LabeledSpan::at(
+                                token.source_span,
+                                "expected a label here"
+                            )],
+                            help = "rename the label, since labels may not share their names with opcodes, registers, or keywords",
+                            "{name} is {reserved_as}, and cannot be used as a label",
+                        )
+                        .with_source_code(self.sources.clone()))
+                    }
+                    other => Err(miette::miette!(
+                        labels = vec![LabeledSpan::underline(token.source_span)],
+                        "expected label, found {other} instead",
+                    )
+                    .with_source_code(self.sources.clone())),
+                }
+            }
+            None => Err(self.unexpected_eof("label")),
+        }
+    }
+
+    // The text of the source from which the token at `source_span` was lexed.
+    fn source_text(&self, source_span: SourceSpan) -> &'a str {
+        let offset = source_span.offset() - self.file.offset;
+        &self.file.contents[offset..offset + source_span.len()]
+    }
+
+    // NOTE: The immediate of `jsh` is packed into the upper 10 bits of the instruction word, and is
+    // interpreted as a signed offset from the program counter. Labels can't be used here, since a
+    // relocation can only patch an entire word with an absolute address.
+    pub fn parse_jsh_immediate(&mut self) -> Result<u16> {
+        match self.lexer.next() {
+            Some(token) => {
+                let token = token?;
+
+                match token.token_kind {
+                    TokenKind::Number(n) => {
+                        self.validate_jsh_immediate(n, token.source_span)?;
+                        Ok(n)
+                    }
+                    TokenKind::Label(label) if self.defines.contains_key(label) => {
+                        let n = self.defines[label];
+                        self.validate_jsh_immediate(n, token.source_span)?;
+                        Ok(n)
+                    }
+                    TokenKind::Label(label) => Err(miette::miette!(
+                        labels = vec![LabeledSpan::underline(token.source_span)],
+                        help = "use jal to jump to a label",
+                        "jsh cannot jump to label {label}, as its immediate is a 10-bit offset from the program counter",
+                    )
+                    .with_source_code(self.sources.clone())),
+                    other => Err(miette::miette!(
+                        labels = vec![LabeledSpan::underline(token.source_span)],
+                        "expected immediate, found {other} instead",
+                    )
+                    .with_source_code(self.sources.clone())),
+                }
+            }
+            None => Err(self.unexpected_eof("immediate")),
+        }
+    }
+
+    // Check that `imm`, interpreted as a two's complement number, fits in the 10-bit signed
+    // immediate field of a jsh instruction.
+    fn validate_jsh_immediate(&self, imm: u16, source_span: SourceSpan) -> Result<()> {
+        if (-512..=511).contains(&(imm as i16)) {
+            Ok(())
+        } else {
+            Err(miette::miette!(
+                labels = vec![LabeledSpan::at(
+                    source_span,
+                    "this immediate does not fit in 10 bits"
+                )],
+                help = "the immediate of jsh must lie between -512 and 511, or equivalently be at most 0x01FF or at least 0xFE00",
+                "jsh immediate {imm:#06x} is out of range",
+            )
+            .with_source_code(self.sources.clone()))
+        }
+    }
+
+    // NOTE: Strings are encoded as UTF-16, with each code unit occupying one word, so a string
+    // (along with `extra` words following it, such as a terminating zero) must be at most u16::MAX
+    // code units long for its size to be representable.
+    fn validate_string(&self, string: &str, extra: usize, source_span: SourceSpan) -> Result<()> {
+        let len = string.encode_utf16().count() + extra;
+        if u16::try_from(len).is_ok() {
+            Ok(())
+        } else {
+            Err(miette::miette!(
+                // NOTE: Only the beginning of the string is labelled, since such a string is
+                // far too long to be usefully underlined in its entirety.
+                labels = vec![LabeledSpan::at_offset(
+                    source_span.offset(),
+                    "string begins here"
+                )],
+                "string literal is {len} words long{}, but a segment can hold at most 65535 words",
+                if extra > 0 {
+                    " including its terminator"
+                } else {
+                    ""
+                },
+            )
+            .with_source_code(self.sources.clone()))
+        }
+    }
+
+    // Construct an error for when the source ends unexpectedly, while `expected` was expected.
+    fn unexpected_eof(&self, expected: &str) -> miette::Report {
+        miette::miette!(
+            labels = vec![LabeledSpan::at_offset(
+                self.file.offset + self.file.contents.len(),
+                "source ends here"
+            )],
+            "expected {expected}, found EOF instead",
+        )
+        .with_source_code(self.sources.clone())
+    }
+
+    // Check that the instruction with opcode `opcode` has not been closed before all of its
+    // operands were given.
+    fn check_operand_present(&mut self, opcode: Opcode) -> Result<()> {
+        if let Some(Ok(Token {
+            token_kind: TokenKind::RightParen,
+            source_span,
+        })) = self.lexer.peek()
+        {
+            return Err(miette::miette!(
+                labels = vec![LabeledSpan::at(
+                    *source_span,
+                    "expected another operand here"
+                )],
+                help = format!(
+                    "{} is written as {}",
+                    opcode_name(opcode),
+                    opcode_shape(opcode)
+                ),
+                "too few operands for {}",
+                opcode_name(opcode),
+            )
+            .with_source_code(self.sources.clone()));
+        }
+
+        Ok(())
+    }
+
+    // Check that the instruction with opcode `opcode`, all of whose operands have been parsed, is
+    // not given any further operands. Since the most common cause of this is giving an immediate
+    // to an instruction which doesn't take one, we point out the instruction which does.
+    fn check_no_extra_operand(&mut self, opcode: Opcode) -> Result<()> {
+        let (token_kind, source_span) = match self.lexer.peek() {
+            Some(Ok(Token {
+                token_kind:
+                    token_kind @ (TokenKind::Register(_)
+                    | TokenKind::ControlStatusRegister(_)
+                    | TokenKind::Number(_)
+                    | TokenKind::Label(_)
+                    | TokenKind::Here),
+                source_span,
+            })) => (token_kind.clone(), *source_span),
+            _ => return Ok(()),
+        };
+
+        let name = opcode_name(opcode);
+        let immediate_opcode = format!("{name}i")
+            .parse::<Opcode>()
+            .ok()
+            .filter(|_| !matches!(token_kind, TokenKind::Register(_)));
+        let help = match immediate_opcode {
+            Some(immediate_opcode) => format!(
+                "{name} is written as {}, and takes no immediate. perhaps you meant {}, which is written as {}",
+                opcode_shape(opcode),
+                opcode_name(immediate_opcode),
+                opcode_shape(immediate_opcode)
+            ),
+            None => format!("{name} is written as {}", opcode_shape(opcode)),
+        };
+
+        Err(miette::miette!(
+            labels = vec![LabeledSpan::at(source_span, "unexpected operand")],
+            help = help,
+            "too many operands for {name}",
+        )
+        .with_source_code(self.sources.clone()))
+    }
+
+    fn parse_right_parenthesis(&mut self, opening_parenthesis: &Token) -> Result<SourceSpan> {
+        match self.lexer.next() {
+            Some(token) => {
+                let token = token?;
+                match token.token_kind {
+                    TokenKind::RightParen => Ok(token.source_span),
+                    other => Err(miette::miette!(
+                        labels = vec![
+                            LabeledSpan::at(
+                                opening_parenthesis.source_span,
+                                "unpaired opening parenthesis"
+                            ),
+                            LabeledSpan::at(token.source_span, "expected right parenthesis here")
+                        ],
+                        "expected right parenthesis, found {other} instead",
+                    )
+                    .with_source_code(self.sources.clone())),
+                }
+            }
+            None => Err(miette::miette!(
+                labels = vec![LabeledSpan::at(
+                    opening_parenthesis.source_span,
+                    "unpaired opening parenthesis"
+                )],
+                "expected right parenthesis, found EOF instead",
+            )
+            .with_source_code(self.sources.clone())),
+        }
     }
 
     pub fn parse_immediate(&mut self) -> Result<Immediate<'a>> {
@@ -412,31 +1532,177 @@ impl<'a> Parser<'a> {
                 let token = token?;
 
                 match token.token_kind {
+                    TokenKind::Label(label) if self.defines.contains_key(label) => {
+                        Ok(Immediate::Number(Spanned {
+                            value: self.defines[label],
+                            source_span: token.source_span,
+                        }))
+                    }
                     TokenKind::Label(label) => Ok(Immediate::Label(Label {
                         label,
                         source_span: token.source_span,
                     })),
-                    TokenKind::Number(n) => Ok(Immediate::Number(n)),
+                    TokenKind::Number(n) => Ok(Immediate::Number(Spanned {
+                        value: n,
+                        source_span: token.source_span,
+                    })),
+                    TokenKind::Here => Ok(Immediate::Here),
+                    TokenKind::LeftParen => {
+                        let label = self.parse_offsetof()?;
+                        self.parse_right_parenthesis(&token)?;
+                        Ok(Immediate::OffsetOf(label))
+                    }
                     other => Err(miette::miette!(
                         labels = vec![LabeledSpan::underline(token.source_span)],
                         "expected immediate, found {other} instead",
                     )
-                    .with_source_code(self.source.to_string())),
+                    .with_source_code(self.sources.clone())),
                 }
             }
-            None => Err(miette::miette!("expected immediate, found EOF instead")),
+            None => Err(self.unexpected_eof("immediate")),
+        }
+    }
+
+    // Parse the remainder of an `offsetof` form, whose opening parenthesis has already been
+    // consumed, up to but not including its closing parenthesis.
+    fn parse_offsetof(&mut self) -> Result<Label<'a>> {
+        match self.lexer.next() {
+            Some(token) => {
+                let token = token?;
+
+                match token.token_kind {
+                    TokenKind::OffsetOf => self.parse_label(),
+                    other => Err(miette::miette!(
+                        labels = vec![LabeledSpan::underline(token.source_span)],
+                        "expected offsetof, found {other} instead",
+                    )
+                    .with_source_code(self.sources.clone())),
+                }
+            }
+            None => Err(self.unexpected_eof("offsetof")),
+        }
+    }
+}
+
+// The stream of tokens consumed by the parser. In addition to allowing the parser to look ahead,
+// this keeps track of how deeply nested inside of parentheses the parser is, which is needed in
+// order to recover from errors.
+#[derive(Debug)]
+pub struct Tokens<'a> {
+    lexer: Lexer<'a>,
+    peeked: VecDeque<Result<Token<'a>>>,
+
+    // The number of LeftParens consumed which have not yet been matched by a RightParen.
+    depth: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(lexer: Lexer<'a>) -> Self {
+        Self {
+            lexer,
+            peeked: VecDeque::new(),
+            depth: 0,
+        }
+    }
+
+    pub fn peek(&mut self) -> Option<&Result<Token<'a>>> {
+        self.peek_nth(0)
+    }
+
+    pub fn peek_second(&mut self) -> Option<&Result<Token<'a>>> {
+        self.peek_nth(1)
+    }
+
+    fn peek_nth(&mut self, n: usize) -> Option<&Result<Token<'a>>> {
+        while self.peeked.len() <= n {
+            self.peeked.push_back(self.lexer.next()?);
+        }
+        self.peeked.get(n)
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<Token<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.peeked.pop_front().or_else(|| self.lexer.next())?;
+        if let Ok(token) = &token {
+            match token.token_kind {
+                TokenKind::LeftParen => self.depth += 1,
+                TokenKind::RightParen => self.depth = self.depth.saturating_sub(1),
+                _ => {}
+            }
         }
+
+        Some(token)
     }
 }
 
+// Constants defined on the command line, which may be used in place of numeric literals, and which
+// are tested by `ifdef` and `ifndef` forms.
+pub type Defines = HashMap<String, u16>;
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct Program<'a> {
-    pub exports: Vec<Label<'a>>,
+    pub exports: Vec<Export<'a>>,
     pub segments: [Vec<Code<'a>>; 8],
+    // The segment forms from which the contents of each segment were gathered, in order.
+    pub segment_forms: [Vec<SegmentForm>; 8],
+    // The spans of the numeric literals which are given as the targets of branches.
+    pub numeric_branches: Vec<SourceSpan>,
+    // The spans of the destination operands of instructions which write to r0.
+    pub discarded_writes: Vec<SourceSpan>,
+    // The constants which are given to `la` in place of labels.
+    pub constant_addresses: Vec<Label<'a>>,
+}
+
+// A label named by an `export` form.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Export<'a> {
+    // The name by which the label is exported.
+    pub name: Label<'a>,
+    // The label which is exported, which is resolved relative to `module`.
+    pub label: Label<'a>,
+    // The module of the file in which the export form appears, if any.
+    pub module: Option<&'a str>,
+}
+
+impl fmt::Display for Export<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// A single `segment` form, of which there may be several with the same permissions.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct SegmentForm {
+    // The span of the beginning of the form, up to and including its permissions.
+    pub source_span: SourceSpan,
+    // The index of the first code in the segment which came from this form.
+    pub start: usize,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
-pub enum Code<'a> {
+pub struct Code<'a> {
+    pub code_kind: CodeKind<'a>,
+    pub source_span: SourceSpan,
+}
+
+impl<'a> Code<'a> {
+    fn new(code_kind: CodeKind<'a>, source_span: impl Into<SourceSpan>) -> Self {
+        Self {
+            code_kind,
+            source_span: source_span.into(),
+        }
+    }
+
+    pub fn size(&self, segment_offset: u16) -> u16 {
+        self.code_kind.size(segment_offset)
+    }
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum CodeKind<'a> {
     Block {
         label: Label<'a>,
         contents: Vec<Code<'a>>,
@@ -463,36 +1729,98 @@ pub enum Code<'a> {
         src: Register,
     },
     JSH {
-        imm: Immediate<'a>,
+        imm: u16,
+    },
+    IncBin {
+        path: &'a str,
+        contents: Vec<u16>,
+    },
+    // A sequence of instructions produced by the expansion of a single pseudo-instruction.
+    Sequence(Vec<CodeKind<'a>>),
+    // Zero padding up to the given offset within the segment.
+    Org(Spanned<u16>),
+    // Storage of the given number of words which is reserved, but not initialized. For now, it is
+    // filled with zero words, just as padding is, but it's kept distinct so that the intent is
+    // recorded, and so that it may one day be left out of the poki file altogether.
+    Skip(u16),
+    // Code from a file which declares a module, within which the labels it defines are placed.
+    Module {
+        name: &'a str,
+        contents: Vec<Code<'a>>,
     },
 }
 
-impl Code<'_> {
-    pub fn size(&self) -> u16 {
+impl CodeKind<'_> {
+    // The number of words occupied by the code, when it is placed at `segment_offset`. Most code
+    // occupies the same number of words wherever it is placed, but org forms don't.
+    pub fn size(&self, segment_offset: u16) -> u16 {
         match self {
-            Code::Block { contents, .. } => contents.iter().map(Code::size).sum(),
-            Code::String(s) => {
-                // FIXME: Either the parser should be providing the guarantee that the string
-                // is at most u16::MAX words long in a UTF-16 representation, or we should
-                // signal an error here. It is probably preferable for the parser to
-                // provide this guarantee.
-                u16::try_from(s.encode_utf16().collect::<Vec<_>>().len()).unwrap()
-            }
-            Code::ImmediateInstruction { .. } => 2,
+            CodeKind::Block { contents, .. } | CodeKind::Module { contents, .. } => {
+                contents.iter().fold(0, |size, code| {
+                    size + code.size(segment_offset.wrapping_add(size))
+                })
+            }
+            CodeKind::Sequence(contents) => contents.iter().fold(0, |size, code_kind| {
+                size + code_kind.size(segment_offset.wrapping_add(size))
+            }),
+            // NOTE: The assembler reports an org form which lies before the offset at which it is
+            // placed as an error, so it should never actually need to move backwards.
+            CodeKind::Org(target) => target.value.saturating_sub(segment_offset),
+            CodeKind::Skip(size) => *size,
+            // NOTE: The parser guarantees that strings are at most u16::MAX words long in a UTF-16
+            // representation.
+            CodeKind::String(s) => u16::try_from(s.encode_utf16().count()).unwrap(),
+            // NOTE: The parser guarantees that included files are at most u16::MAX words long.
+            CodeKind::IncBin { contents, .. } => u16::try_from(contents.len()).unwrap(),
+            CodeKind::ImmediateInstruction { .. } => 2,
             _ => 1,
         }
     }
 }
 
+// The name of `opcode`, as it is written in the source.
+pub fn opcode_name(opcode: Opcode) -> String {
+    format!("{opcode:?}").to_lowercase()
+}
+
+// The form in which an instruction with opcode `opcode` is written, naming each of its operands.
+pub fn opcode_shape(opcode: Opcode) -> String {
+    let name = opcode_name(opcode);
+    match opcode {
+        Opcode::WFI => format!("({name})"),
+        Opcode::JSH => format!("({name} imm)"),
+        Opcode::WCSR => format!("({name} csr src)"),
+        Opcode::RCSR => format!("({name} dst csr)"),
+        _ if opcode.takes_immediate() => format!("({name} dst src imm)"),
+        _ => format!("({name} dst src)"),
+    }
+}
+
+// Construct the span which begins at the beginning of `start` and ends at the end of `end`.
+fn span_between(start: SourceSpan, end: SourceSpan) -> SourceSpan {
+    (start.offset()..end.offset() + end.len()).into()
+}
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum Immediate<'a> {
     Label(Label<'a>),
-    Number(u16),
+    Number(Spanned<u16>),
+    // The address of the immediate word itself, written `$`.
+    Here,
+    // The offset of a label within the segment in which it is defined, written `(offsetof label)`.
+    OffsetOf(Label<'a>),
+}
+
+// A value, along with the span of the source from which it was parsed, so that errors found while
+// assembling it can point at where it was written.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub source_span: SourceSpan,
 }
 
-// NOTE: Labels are treated a bit differently, since they are the only possible source of errors at
-// the assembly stage. As such, they need to hold on to the SourceSpan from which they are defined
-// so that errors can be appropriately signalled during the assembly stage.
+// NOTE: Labels are spanned just as other values are, but are compared by name alone, since two
+// labels with the same name refer to the same block wherever they are written.
 #[derive(Debug, Clone, Copy, Hash, Eq)]
 pub struct Label<'a> {
     pub label: &'a str,
@@ -0,0 +1,83 @@
+use miette::{MietteError, MietteSpanContents, SourceCode, SourceSpan, SpanContents};
+
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct SourceFile {
+    pub name: String,
+    pub contents: String,
+
+    // The offset at which this file begins in the combined source. See `Sources`.
+    pub offset: usize,
+}
+
+// NOTE: When several files are assembled together, every span refers to a byte offset in the
+// combined source, which is formed by laying the files out one after another, so that a span alone
+// is enough to identify the file to which it refers. A gap of one byte is left after each file, so
+// that a span pointing just past the end of a file (as we do when reporting an unexpected EOF) is
+// not mistaken for a span pointing to the beginning of the next.
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Sources(Arc<[SourceFile]>);
+
+impl Sources {
+    pub fn new(files: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut offset = 0;
+        Self(
+            files
+                .into_iter()
+                .map(|(name, contents)| {
+                    let file = SourceFile {
+                        name,
+                        offset,
+                        contents,
+                    };
+                    offset += file.contents.len() + 1;
+                    file
+                })
+                .collect(),
+        )
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.0
+    }
+
+    // Find the file to which `offset` in the combined source refers, if any.
+    pub fn file_containing(&self, offset: usize) -> Option<&SourceFile> {
+        let index = self.0.partition_point(|file| file.offset <= offset);
+        self.0.get(index.checked_sub(1)?)
+    }
+}
+
+impl SourceCode for Sources {
+    fn read_span<'a>(
+        &'a self,
+        span: &SourceSpan,
+        context_lines_before: usize,
+        context_lines_after: usize,
+    ) -> Result<Box<dyn SpanContents<'a> + 'a>, MietteError> {
+        // Read the span from the file which contains it, and then translate the span of the
+        // contents which were read back into the combined source.
+        let file = self
+            .file_containing(span.offset())
+            .ok_or(MietteError::OutOfBounds)?;
+        let contents = file.contents.as_str().read_span(
+            &(span.offset() - file.offset, span.len()).into(),
+            context_lines_before,
+            context_lines_after,
+        )?;
+
+        Ok(Box::new(MietteSpanContents::new_named(
+            file.name.clone(),
+            contents.data(),
+            (
+                contents.span().offset() + file.offset,
+                contents.span().len(),
+            )
+                .into(),
+            contents.line(),
+            contents.column(),
+            contents.line_count(),
+        )))
+    }
+}
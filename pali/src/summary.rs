@@ -0,0 +1,45 @@
+use crate::lex::SegmentPermissions;
+
+use poki::Poki;
+
+use std::fmt::Write;
+
+// The number of words which a segment may occupy, since each is addressed by a 16-bit offset.
+const SEGMENT_CAPACITY: usize = 0x10000;
+
+// Render a summary of the size of each non-empty segment of `poki`, followed by the number of
+// unresolved symbols. Everything is taken from the assembled poki itself, so the numbers always
+// match the poki file which is written.
+pub fn render(poki: &Poki) -> String {
+    let mut rendered = String::new();
+    writeln!(rendered, "segment    words  relocations  exports   usage").unwrap();
+    for (segment_index, segment) in poki.segments.iter().enumerate() {
+        if segment.contents.is_empty()
+            && segment.relocation_table.is_empty()
+            && segment.export_table.is_empty()
+        {
+            continue;
+        }
+
+        // NOTE: There are only ever 8 segments, so the index always fits in a `u16`.
+        let permissions = SegmentPermissions::from(u16::try_from(segment_index).unwrap());
+        let usage = 100.0 * segment.contents.len() as f64 / SEGMENT_CAPACITY as f64;
+        writeln!(
+            rendered,
+            "{:<7} {:>8} {:>12} {:>8} {usage:>6.2}%",
+            permissions.to_string(),
+            segment.contents.len(),
+            segment.relocation_table.len(),
+            segment.export_table.len(),
+        )
+        .unwrap();
+    }
+    writeln!(
+        rendered,
+        "{} unresolved symbols",
+        poki.unresolved_table.len()
+    )
+    .unwrap();
+
+    rendered
+}
@@ -0,0 +1,143 @@
+use crate::assemble::{SymbolTable, SymbolTableEntry};
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+// Sort the entries of `symbol_table` by their position in the assembled program, breaking ties
+// (which arise from nested blocks beginning at the same offset) by label.
+fn sorted_entries(symbol_table: &SymbolTable) -> Vec<(&String, &SymbolTableEntry)> {
+    let mut entries: Vec<_> = symbol_table.iter().collect();
+    entries.sort_by_key(|(label, entry)| (entry.segment_index, entry.segment_offset, *label));
+    entries
+}
+
+// Render the symbol table as text, with one symbol per line, in the same `segment:offset` format
+// used by listings. Unresolved symbols follow the defined symbols, with their location replaced
+// by dashes.
+pub fn render(symbol_table: &SymbolTable, unresolved_table: &[String]) -> String {
+    let mut rendered = String::new();
+    for (label, entry) in sorted_entries(symbol_table) {
+        writeln!(
+            rendered,
+            "{}:{:04x} {label}",
+            entry.segment_index, entry.segment_offset
+        )
+        .unwrap();
+    }
+    for label in unresolved_table {
+        writeln!(rendered, "-:---- {label}").unwrap();
+    }
+
+    rendered
+}
+
+// Render the symbol table as a JSON object, of the form
+// `{"symbols":[{"label":...,"segment_index":...,"segment_offset":...},...],"unresolved":[...]}`.
+pub fn render_json(symbol_table: &SymbolTable, unresolved_table: &[String]) -> String {
+    let symbols = sorted_entries(symbol_table)
+        .into_iter()
+        .map(|(label, entry)| {
+            format!(
+                r#"{{"label":{},"segment_index":{},"segment_offset":{}}}"#,
+                json_string(label),
+                entry.segment_index,
+                entry.segment_offset
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let unresolved = unresolved_table
+        .iter()
+        .map(|label| json_string(label))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!(r#"{{"symbols":[{symbols}],"unresolved":[{unresolved}]}}"#) + "\n"
+}
+
+// Render a map of the assembled program, with one label per line in the form
+// `label segment=N offset=0xXXXX size=0xXXXX`, sorted by location, where the size is the number of
+// words occupied by the block which the label names. Exported labels are annotated with `[export]`,
+// or with `[export as name]` if they are exported under a name of their own, and unresolved
+// symbols follow the defined symbols, annotated with `[unresolved]`.
+pub fn render_map(
+    symbol_table: &SymbolTable,
+    exports: &BTreeMap<String, Vec<String>>,
+    unresolved_table: &[String],
+) -> String {
+    let mut rendered = String::new();
+    for (label, entry) in sorted_entries(symbol_table) {
+        write!(
+            rendered,
+            "{label} segment={} offset={:#06x} size={:#06x}",
+            entry.segment_index, entry.segment_offset, entry.size
+        )
+        .unwrap();
+        for name in exports.get(label).into_iter().flatten() {
+            if name == label {
+                write!(rendered, " [export]").unwrap();
+            } else {
+                write!(rendered, " [export as {name}]").unwrap();
+            }
+        }
+        writeln!(rendered).unwrap();
+    }
+    for label in unresolved_table {
+        writeln!(rendered, "{label} [unresolved]").unwrap();
+    }
+
+    rendered
+}
+
+// Render a map of the assembled program as a JSON object, of the form
+// `{"symbols":[{"label":...,"segment":...,"offset":...,"size":...,"exports":[...]},...],"unresolved":[...]}`,
+// where `exports` lists the names under which the label is exported, if any.
+pub fn render_map_json(
+    symbol_table: &SymbolTable,
+    exports: &BTreeMap<String, Vec<String>>,
+    unresolved_table: &[String],
+) -> String {
+    let symbols = sorted_entries(symbol_table)
+        .into_iter()
+        .map(|(label, entry)| {
+            let names = exports
+                .get(label)
+                .into_iter()
+                .flatten()
+                .map(|name| json_string(name))
+                .collect::<Vec<_>>()
+                .join(",");
+            format!(
+                r#"{{"label":{},"segment":{},"offset":{},"size":{},"exports":[{names}]}}"#,
+                json_string(label),
+                entry.segment_index,
+                entry.segment_offset,
+                entry.size
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let unresolved = unresolved_table
+        .iter()
+        .map(|label| json_string(label))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    format!(r#"{{"symbols":[{symbols}],"unresolved":[{unresolved}]}}"#) + "\n"
+}
+
+// NOTE: Labels are identifiers, and so will essentially never contain characters which need
+// escaping, but we escape them anyway so that the output is always valid JSON.
+pub fn json_string(s: &str) -> String {
+    let mut escaped = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
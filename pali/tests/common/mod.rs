@@ -0,0 +1,22 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+// Check `source`, read from stdin, with the given extra arguments, printing diagnostics as json so
+// that they may be compared exactly.
+pub fn check(source: &str, extra: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pali"))
+        .args(["-", "--check", "--message-format", "json"])
+        .args(extra)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
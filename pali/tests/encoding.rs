@@ -0,0 +1,622 @@
+use pali::assemble::{Assembler, Assembly};
+use pali::lex::Opcode;
+use pali::parse::Defines;
+use pali::source::Sources;
+use poki::RelocationTableEntry;
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// The index of the segment in which code with rx permissions is placed.
+const RX: usize = 0b101;
+// The index of the segment in which data with rw permissions is placed.
+const RW: usize = 0b110;
+
+// Assemble `source`, panicking if it can't be assembled.
+fn assemble(source: &str) -> Assembly {
+    assemble_with_defines(source, &Defines::new())
+}
+
+fn assemble_with_defines(source: &str, defines: &Defines) -> Assembly {
+    let sources = Sources::new([("test.pali".to_string(), source.to_string())]);
+    Assembler::try_new(&sources, defines)
+        .and_then(|assembler| assembler.assemble())
+        .unwrap()
+}
+
+// Assemble `source` as --resolve-local does, panicking if it can't be assembled.
+fn assemble_locally(source: &str) -> Assembly {
+    let sources = Sources::new([("test.pali".to_string(), source.to_string())]);
+    let defines = Defines::new();
+    Assembler::try_new(&sources, &defines)
+        .and_then(|assembler| assembler.resolve_local(true).assemble())
+        .unwrap()
+}
+
+// The messages of the errors which prevent `source` from being assembled, panicking if it can be.
+fn errors(source: &str) -> Vec<String> {
+    errors_with_defines(source, &Defines::new())
+}
+
+fn errors_with_defines(source: &str, defines: &Defines) -> Vec<String> {
+    let sources = Sources::new([("test.pali".to_string(), source.to_string())]);
+    match Assembler::try_new(&sources, defines).and_then(|assembler| assembler.assemble()) {
+        Ok(_) => panic!("{source} was assembled without error"),
+        Err(reports) => reports.iter().map(|report| report.to_string()).collect(),
+    }
+}
+
+// The words of the image produced by placing the rx segment of `assembly` at `base`, and applying
+// its relocations.
+fn link(assembly: &Assembly, base: u16) -> Vec<u16> {
+    let mut segment_bases = [None; 8];
+    segment_bases[RX] = Some(base);
+    let image = assembly.poki.flatten(&segment_bases).unwrap();
+    image
+        .chunks_exact(2)
+        .map(|word| u16::from_ne_bytes([word[0], word[1]]))
+        .skip(usize::from(base))
+        .take(assembly.poki.segments[RX].contents.len())
+        .collect()
+}
+
+// Assemble `source` into a flat binary with pali, passing `extra` as further arguments, and return
+// the words of the image, or the errors printed should it fail.
+fn flat_binary(source: &str, extra: &[&str]) -> Result<Vec<u16>, String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pali"))
+        .args(["-", "--format", "bin", "--output", "-"])
+        .args(extra)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+    }
+
+    Ok(output
+        .stdout
+        .chunks_exact(2)
+        .map(|word| u16::from_le_bytes([word[0], word[1]]))
+        .collect())
+}
+
+// The first word of an instruction, which holds its source register, destination register, and
+// opcode, from the most significant bit down.
+fn instruction(opcode: Opcode, dst: u16, src: u16) -> u16 {
+    src << 11 | dst << 6 | opcode as u16
+}
+
+fn relocation(offset: u16, segment_offset: u16) -> RelocationTableEntry {
+    RelocationTableEntry {
+        offset,
+        segment_index: RX as u16,
+        segment_offset,
+    }
+}
+
+#[test]
+fn call_accepts_a_register_alias() {
+    let assembly = assemble(
+        "(regalias link r5)
+         (segment rx
+             (block f
+                 (call link f)
+                 (ret link)))",
+    );
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(
+        segment.contents,
+        [
+            instruction(Opcode::JAL, 5, 0),
+            0x0000,
+            instruction(Opcode::JAL, 0, 5),
+            0x0000,
+        ]
+    );
+    assert_eq!(segment.relocation_table, [relocation(1, 0)]);
+}
+
+#[test]
+fn la_loads_the_relocated_address_of_a_label() {
+    let assembly = assemble(
+        "(segment rx
+             (nop)
+             (block target (la r2 target)))",
+    );
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(segment.relocation_table, [relocation(2, 1)]);
+    assert_eq!(
+        link(&assembly, 0x1000),
+        [
+            instruction(Opcode::ADD, 0, 0),
+            instruction(Opcode::ADDI, 2, 0),
+            0x1001
+        ]
+    );
+}
+
+#[test]
+fn la_loads_a_constant_as_li_would() {
+    let defines = HashMap::from([("FOO".to_string(), 5)]);
+    let assembly = assemble_with_defines("(segment rx (la r1 FOO))", &defines);
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(segment.contents, [instruction(Opcode::ADDI, 1, 0), 0x0005]);
+    assert!(segment.relocation_table.is_empty());
+    assert_eq!(assembly.warnings.len(), 1);
+}
+
+#[test]
+fn a_constant_stands_in_for_a_number() {
+    let defines = HashMap::from([("STACK_SIZE".to_string(), 0x0400)]);
+    let assembly =
+        assemble_with_defines("(segment rx (addi r1 r0 STACK_SIZE) STACK_SIZE)", &defines);
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(
+        segment.contents,
+        [instruction(Opcode::ADDI, 1, 0), 0x0400, 0x0400]
+    );
+    assert!(segment.relocation_table.is_empty());
+}
+
+#[test]
+fn ifdef_and_ifndef_include_their_contents_by_whether_a_constant_is_defined() {
+    const SOURCE: &str = "
+        (ifdef DEBUG (segment rx (addi r1 r0 1)))
+        (ifndef DEBUG (segment rx (addi r1 r0 2)))
+        (segment rx
+            (ifdef DEBUG (addi r2 r0 3))
+            (ifndef DEBUG (addi r2 r0 4) (call missing)))";
+
+    let defined = HashMap::from([("DEBUG".to_string(), 0)]);
+    let assembly = assemble_with_defines(SOURCE, &defined);
+    assert_eq!(
+        assembly.poki.segments[RX].contents,
+        [
+            instruction(Opcode::ADDI, 1, 0),
+            0x0001,
+            instruction(Opcode::ADDI, 2, 0),
+            0x0003,
+        ]
+    );
+    // NOTE: The call in the ifndef form is skipped, so `missing` is never referred to.
+    assert!(assembly.poki.unresolved_table.is_empty());
+
+    let assembly = assemble(SOURCE);
+    assert_eq!(
+        assembly.poki.segments[RX].contents[..4],
+        [
+            instruction(Opcode::ADDI, 1, 0),
+            0x0002,
+            instruction(Opcode::ADDI, 2, 0),
+            0x0004,
+        ]
+    );
+    assert_eq!(assembly.poki.unresolved_table.len(), 1);
+}
+
+#[test]
+fn a_label_may_not_share_its_name_with_a_constant() {
+    let defines = HashMap::from([("N".to_string(), 1)]);
+    assert_eq!(
+        errors_with_defines("(segment rx (block N (jal r0 r0 N)))", &defines),
+        ["label N shares its name with a constant, which would shadow it"]
+    );
+}
+
+#[test]
+fn li_of_a_label_emits_a_relocation() {
+    let assembly = assemble(
+        "(segment rx
+             (nop)
+             (block target (li r5 target)))",
+    );
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(
+        segment.contents,
+        [
+            instruction(Opcode::ADD, 0, 0),
+            instruction(Opcode::ADDI, 5, 0),
+            0x0000
+        ]
+    );
+    assert_eq!(segment.relocation_table, [relocation(2, 1)]);
+    assert_eq!(
+        link(&assembly, 0x0400),
+        [
+            instruction(Opcode::ADD, 0, 0),
+            instruction(Opcode::ADDI, 5, 0),
+            0x0401
+        ]
+    );
+}
+
+#[test]
+fn offsetof_encodes_the_offset_of_a_label_without_relocation() {
+    let assembly = assemble(
+        "(segment rx
+             (nop)
+             (nop)
+             (block table (ldio r3 r2 (offsetof table))))",
+    );
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(
+        segment.contents,
+        [
+            instruction(Opcode::ADD, 0, 0),
+            instruction(Opcode::ADD, 0, 0),
+            instruction(Opcode::LDIO, 3, 2),
+            0x0002
+        ]
+    );
+    assert_eq!(segment.relocation_table, []);
+}
+
+#[test]
+fn segment_forms_with_the_same_permissions_are_appended_in_order() {
+    let assembly = assemble(
+        "(export first second)
+         (segment rx (block first (nop)))
+         (segment rw (block data 0x1234))
+         (segment rx (block second (jal r0 r0 first)))",
+    );
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(
+        segment.contents,
+        [
+            instruction(Opcode::ADD, 0, 0),
+            instruction(Opcode::JAL, 0, 0),
+            0x0000
+        ]
+    );
+    assert_eq!(segment.relocation_table, [relocation(2, 0)]);
+    let labels: Vec<_> = segment
+        .export_table
+        .iter()
+        .map(|entry| (entry.label.as_str(), entry.offset))
+        .collect();
+    assert_eq!(labels, [("first", 0), ("second", 1)]);
+    assert_eq!(assembly.poki.segments[RW].contents, [0x1234]);
+}
+
+#[test]
+fn call_and_ret_link_through_r1_by_default() {
+    let assembly = assemble(
+        "(segment rx
+             (call f)
+             (block f (ret)))",
+    );
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(
+        segment.contents,
+        [
+            instruction(Opcode::JAL, 1, 0),
+            0x0000,
+            instruction(Opcode::JAL, 0, 1),
+            0x0000,
+        ]
+    );
+    assert_eq!(segment.relocation_table, [relocation(1, 2)]);
+}
+
+#[test]
+fn call_and_ret_link_through_an_explicit_register() {
+    let assembly = assemble(
+        "(segment rx
+             (call r7 f)
+             (block f (ret r7)))",
+    );
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(
+        segment.contents,
+        [
+            instruction(Opcode::JAL, 7, 0),
+            0x0000,
+            instruction(Opcode::JAL, 0, 7),
+            0x0000,
+        ]
+    );
+    assert_eq!(segment.relocation_table, [relocation(1, 2)]);
+}
+
+#[test]
+fn jsh_accepts_immediates_at_the_ends_of_its_range() {
+    let assembly = assemble("(segment rx (jsh 511) (jsh -512))");
+
+    assert_eq!(
+        assembly.poki.segments[RX].contents,
+        [0x7FC0 | Opcode::JSH as u16, 0x8000 | Opcode::JSH as u16]
+    );
+}
+
+#[test]
+fn jsh_rejects_immediates_just_outside_of_its_range() {
+    assert_eq!(
+        errors("(segment rx (jsh 512) (jsh -513))"),
+        [
+            "jsh immediate 0x0200 is out of range",
+            "jsh immediate 0xfdff is out of range"
+        ]
+    );
+}
+
+#[test]
+fn jsh_rejects_labels() {
+    assert_eq!(
+        errors("(segment rx (block f (jsh f)))"),
+        ["jsh cannot jump to label f, as its immediate is a 10-bit offset from the program counter"]
+    );
+}
+
+// NOTE: `$` follows a one-word instruction and then a two-word instruction, so that it is at offset
+// 2 and then at offset 4.
+const HERE: &str = "(segment rx (nop) (addi r1 r0 $) (addi r2 r0 $))";
+
+#[test]
+fn here_is_the_offset_of_the_immediate_word() {
+    let assembly = assemble_locally(HERE);
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(
+        segment.contents,
+        [
+            instruction(Opcode::ADD, 0, 0),
+            instruction(Opcode::ADDI, 1, 0),
+            0x0002,
+            instruction(Opcode::ADDI, 2, 0),
+            0x0004,
+        ]
+    );
+    assert!(segment.relocation_table.is_empty());
+}
+
+#[test]
+fn here_is_relocated_to_the_address_of_the_immediate_word() {
+    let assembly = assemble(HERE);
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(
+        segment.relocation_table,
+        [relocation(2, 2), relocation(4, 4)]
+    );
+    assert_eq!(
+        link(&assembly, 0x0100),
+        [
+            instruction(Opcode::ADD, 0, 0),
+            instruction(Opcode::ADDI, 1, 0),
+            0x0102,
+            instruction(Opcode::ADDI, 2, 0),
+            0x0104,
+        ]
+    );
+}
+
+// NOTE: The only one-word instruction which takes an immediate is jsh, whose immediate is already an
+// offset from the program counter, so `$` has no meaning there.
+#[test]
+fn here_is_rejected_by_jsh() {
+    assert_eq!(
+        errors("(segment rx (jsh $))"),
+        ["expected immediate, found Here instead"]
+    );
+}
+
+// A program with a segment which refers to a label in another, so that the image depends on where
+// each segment is placed.
+const TWO_SEGMENTS: &str = "
+    (segment rx (block start (la r1 data) (jal r0 r0 start)))
+    (segment rw (block data 0xBEEF))";
+
+#[test]
+fn flat_binaries_place_segments_one_after_another_by_default() {
+    assert_eq!(
+        flat_binary(TWO_SEGMENTS, &[]),
+        Ok(vec![
+            instruction(Opcode::ADDI, 1, 0),
+            0x0004,
+            instruction(Opcode::JAL, 0, 0),
+            0x0000,
+            0xBEEF,
+        ])
+    );
+}
+
+#[test]
+fn flat_binaries_place_segments_at_their_base_addresses() {
+    let mut image = vec![0; 0x14];
+    image[0x08] = 0xBEEF;
+    image[0x10..].copy_from_slice(&[
+        instruction(Opcode::ADDI, 1, 0),
+        0x0008,
+        instruction(Opcode::JAL, 0, 0),
+        0x0010,
+    ]);
+
+    assert_eq!(
+        flat_binary(TWO_SEGMENTS, &["--base-addrs", "0,0,0,0,0,0x10,0x8,0"]),
+        Ok(image)
+    );
+}
+
+#[test]
+fn flat_binaries_reject_unresolved_labels() {
+    let errors = flat_binary("(segment rx (call missing) (call absent))", &[]).unwrap_err();
+    assert!(
+        errors.contains("unresolved symbols: absent, missing"),
+        "{errors}"
+    );
+}
+
+// A program whose rx segment refers both to a label within itself and to a label in its rw segment.
+const CROSS_SEGMENT: &str = "
+    (segment rx
+        (nop)
+        (block start (la r1 data) (jal r0 r0 start)))
+    (segment rw (block data 0xBEEF))";
+
+#[test]
+fn resolve_local_matches_a_loader_placing_the_segment_at_0() {
+    let mut segment_bases = [None; 8];
+    segment_bases[RX] = Some(0x0000);
+    segment_bases[RW] = Some(0x0100);
+
+    let assembly = assemble_locally(CROSS_SEGMENT);
+    let segment = &assembly.poki.segments[RX];
+    assert_eq!(segment.contents[4], 0x0001);
+    // NOTE: The reference to `data` lies in another segment, whose base isn't known, so it remains
+    // a relocation.
+    assert_eq!(
+        segment.relocation_table,
+        [RelocationTableEntry {
+            offset: 2,
+            segment_index: RW as u16,
+            segment_offset: 0,
+        }]
+    );
+
+    let image = assembly.poki.flatten(&segment_bases).unwrap();
+    assert_eq!(
+        image,
+        assemble(CROSS_SEGMENT)
+            .poki
+            .flatten(&segment_bases)
+            .unwrap()
+    );
+    assert_eq!(image[4..6], 0x0100u16.to_ne_bytes());
+}
+
+#[test]
+fn resolve_local_leaves_unresolved_references_to_the_loader() {
+    let assembly = assemble_locally("(segment rx (nop) (call missing))");
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(segment.contents[2], 0x0000);
+    assert_eq!(
+        segment.relocation_table,
+        [RelocationTableEntry {
+            offset: 2,
+            segment_index: 0xFFFF,
+            segment_offset: 0,
+        }]
+    );
+    assert_eq!(assembly.poki.unresolved_table, ["missing"]);
+}
+
+#[test]
+fn each_label_is_exported_exactly_once() {
+    let assembly = assemble(
+        "(export start helper start)
+         (export start)
+         (segment rx
+             (block start (call helper))
+             (block helper (ret)))",
+    );
+
+    let mut labels: Vec<_> = assembly.poki.segments[RX]
+        .export_table
+        .iter()
+        .map(|entry| (entry.label.as_str(), entry.offset))
+        .collect();
+    labels.sort();
+    assert_eq!(labels, [("helper", 2), ("start", 0)]);
+    assert_eq!(assembly.warnings.len(), 2);
+}
+
+#[test]
+fn nested_blocks_are_exported_by_their_full_dotted_names() {
+    let assembly = assemble(
+        "(export outer.inner a.b.c.d)
+         (segment rx
+             (block outer (nop) (block inner (nop)))
+             (block a (block b (nop) (block c (nop) (block d (ret))))))",
+    );
+
+    let mut labels: Vec<_> = assembly.poki.segments[RX]
+        .export_table
+        .iter()
+        .map(|entry| (entry.label.as_str(), entry.offset))
+        .collect();
+    labels.sort();
+    assert_eq!(labels, [("a.b.c.d", 4), ("outer.inner", 1)]);
+}
+
+#[test]
+fn exporting_a_nonexistent_dotted_label_is_an_error() {
+    assert_eq!(
+        errors("(export outer.missing) (segment rx (block outer (block inner (nop))))"),
+        ["label outer.missing exported, but is not defined"]
+    );
+}
+
+#[test]
+fn labels_following_org_are_exported_at_its_offset() {
+    let assembly = assemble(
+        "(export entry)
+         (segment rx
+             (nop)
+             (org 0x100)
+             (block entry (ret)))",
+    );
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(segment.contents.len(), 0x102);
+    assert_eq!(segment.export_table.len(), 1);
+    assert_eq!(segment.export_table[0].label, "entry");
+    assert_eq!(segment.export_table[0].offset, 0x100);
+}
+
+#[test]
+fn skip_reserves_zeroed_words_and_moves_the_following_labels() {
+    let defines = HashMap::from([("BUFFER_SIZE".to_string(), 2)]);
+    let assembly = assemble_with_defines(
+        "(export after buffer end)
+         (segment rx
+             (block start (nop) (skip 3))
+             (block after (nop))
+             (block buffer (skip BUFFER_SIZE))
+             (block end (la r1 end)))",
+        &defines,
+    );
+    let segment = &assembly.poki.segments[RX];
+
+    assert_eq!(
+        segment.contents,
+        [
+            instruction(Opcode::ADD, 0, 0),
+            0x0000,
+            0x0000,
+            0x0000,
+            instruction(Opcode::ADD, 0, 0),
+            0x0000,
+            0x0000,
+            instruction(Opcode::ADDI, 1, 0),
+            0x0000,
+        ]
+    );
+    assert_eq!(segment.relocation_table, [relocation(8, 7)]);
+    let mut labels: Vec<_> = segment
+        .export_table
+        .iter()
+        .map(|entry| (entry.label.as_str(), entry.offset))
+        .collect();
+    labels.sort();
+    assert_eq!(labels, [("after", 4), ("buffer", 5), ("end", 7)]);
+}
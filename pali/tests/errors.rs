@@ -0,0 +1,314 @@
+mod common;
+
+use common::check;
+
+use std::process::Output;
+
+// The message and line of each diagnostic printed by `output`, in the order in which they were
+// printed. The line is `None` for diagnostics which don't refer to the source.
+// NOTE: The messages compared here contain no escaped quotes, and every diagnostic begins with its
+// severity and message, followed by its help, so there is no need to actually parse the json.
+fn diagnostics(output: &Output) -> Vec<(String, Option<usize>)> {
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .map(|diagnostic| {
+            let message = between(diagnostic, "\"message\":\"", "\",\"help\":");
+            let line = between(diagnostic, "\"line\":", ",");
+            (message.to_string(), line.parse().ok())
+        })
+        .collect()
+}
+
+// The part of `text` following the first occurrence of `start`, up to the next occurrence of
+// `end`.
+fn between<'a>(text: &'a str, start: &str, end: &str) -> &'a str {
+    let text = &text[text.find(start).unwrap() + start.len()..];
+    &text[..text.find(end).unwrap()]
+}
+
+// Three independent mistakes, on lines 2, 3, and 4.
+const MISTAKES: &str = "(segment rx
+    (addi r1 r99 1)
+    (jsh 9999)
+    (frob r1))";
+
+#[test]
+fn independent_errors_are_reported_together() {
+    let output = check(MISTAKES, &[]);
+
+    assert!(!output.status.success());
+    assert_eq!(
+        diagnostics(&output),
+        [
+            (
+                "expected register, found Label instead".to_string(),
+                Some(2)
+            ),
+            ("jsh immediate 0x270f is out of range".to_string(), Some(3)),
+            (
+                "expected block, incbin, stringz, org, skip, ifdef, ifndef, or opcode, found Label instead"
+                    .to_string(),
+                Some(4)
+            ),
+            (
+                "unable to assemble due to 3 previous errors".to_string(),
+                None
+            ),
+        ]
+    );
+}
+
+#[test]
+fn reported_errors_are_capped_by_max_errors() {
+    let output = check(MISTAKES, &["--max-errors", "2"]);
+
+    assert!(!output.status.success());
+    assert_eq!(
+        diagnostics(&output),
+        [
+            (
+                "expected register, found Label instead".to_string(),
+                Some(2)
+            ),
+            ("jsh immediate 0x270f is out of range".to_string(), Some(3)),
+            (
+                "unable to assemble due to 3 errors, of which only the first 2 were reported"
+                    .to_string(),
+                None
+            ),
+        ]
+    );
+}
+
+#[test]
+fn a_missing_right_paren_is_reported_once() {
+    let output = check(
+        "(segment rx
+    (block a (addi r1 r1 1)
+    (block b (addi r2 r2 2))
+    (block c (addi r3 r3 3)))",
+        &[],
+    );
+
+    // NOTE: The unpaired parenthesis is that of the segment, which swallows the rest of the file,
+    // rather than each of the following blocks being reported as misplaced.
+    assert!(!output.status.success());
+    assert_eq!(
+        diagnostics(&output),
+        [
+            (
+                "expected right parenthesis, found EOF instead".to_string(),
+                Some(1)
+            ),
+            (
+                "unable to assemble due to the previous error".to_string(),
+                None
+            ),
+        ]
+    );
+}
+
+#[test]
+fn symbol_table_errors_are_reported_together() {
+    let output = check(
+        "(segment rx
+    (block a (nop))
+    (block a (nop))
+    (block b (nop))
+    (block b (nop))
+    (org 1))",
+        &[],
+    );
+
+    assert!(!output.status.success());
+    assert_eq!(
+        diagnostics(&output),
+        [
+            ("label a is defined more than once".to_string(), Some(2)),
+            ("label b is defined more than once".to_string(), Some(4)),
+            ("org moves backwards in segment rx".to_string(), Some(6)),
+            (
+                "unable to assemble due to 3 previous errors".to_string(),
+                None
+            ),
+        ]
+    );
+}
+
+#[test]
+fn operand_mistakes_are_explained_in_terms_of_the_instruction() {
+    let output = check(
+        "(segment rx
+    (add r1 r2 r3)
+    (addi r1 r2)
+    (add r1 iv))",
+        &[],
+    );
+
+    assert!(!output.status.success());
+    assert_eq!(
+        diagnostics(&output),
+        [
+            ("too many operands for add".to_string(), Some(2)),
+            ("too few operands for addi".to_string(), Some(3)),
+            (
+                "expected register, found control/status register instead".to_string(),
+                Some(4)
+            ),
+            (
+                "unable to assemble due to 3 previous errors".to_string(),
+                None
+            ),
+        ]
+    );
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for help in [
+        "add is written as (add dst src)",
+        "addi is written as (addi dst src imm)",
+        "may only be read with rcsr and written with wcsr",
+    ] {
+        assert!(stderr.contains(help), "{help} is missing from {stderr}");
+    }
+}
+
+#[test]
+fn reserved_names_are_explained_when_used_as_labels() {
+    for (name, message) in [
+        ("add", "add is a reserved opcode name"),
+        ("push", "push is a reserved pseudo-instruction name"),
+        ("sp", "sp is a reserved register name"),
+        ("ipc", "ipc is a reserved control/status register name"),
+        ("org", "org is a reserved keyword"),
+        ("rx", "rx is reserved for segment permissions"),
+    ] {
+        let output = check(&format!("(segment rx\n    (block {name} (nop)))"), &[]);
+
+        assert!(!output.status.success());
+        assert_eq!(
+            diagnostics(&output),
+            [
+                (format!("{message}, and cannot be used as a label"), Some(2)),
+                (
+                    "unable to assemble due to the previous error".to_string(),
+                    None
+                ),
+            ]
+        );
+    }
+}
+
+#[test]
+fn exporting_a_nested_block_by_its_short_name_suggests_its_full_name() {
+    let output = check(
+        "(export inner)
+(segment rx
+    (block outer (block inner (nop))))",
+        &[],
+    );
+
+    assert!(!output.status.success());
+    assert_eq!(
+        diagnostics(&output),
+        [
+            (
+                "label inner exported, but is not defined".to_string(),
+                Some(1)
+            ),
+            (
+                "unable to assemble due to the previous error".to_string(),
+                None
+            ),
+        ]
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("perhaps you meant outer.inner"));
+}
+
+#[test]
+fn offsetof_in_place_of_code_is_explained() {
+    let output = check(
+        "(segment rx
+    (block table (offsetof table)))",
+        &[],
+    );
+
+    assert!(!output.status.success());
+    assert_eq!(
+        diagnostics(&output),
+        [
+            (
+                "expected block, incbin, stringz, org, skip, ifdef, ifndef, or opcode, found offsetof instead"
+                    .to_string(),
+                Some(2)
+            ),
+            (
+                "unable to assemble due to the previous error".to_string(),
+                None
+            ),
+        ]
+    );
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("offsetof may only be used as an immediate"));
+}
+
+// A segment containing `depth` nested ifndef forms, so that the innermost form is nested
+// `depth + 1` deep.
+fn nested(depth: usize) -> String {
+    format!(
+        "(segment rx {}(nop){})",
+        "(ifndef UNDEFINED ".repeat(depth),
+        ")".repeat(depth)
+    )
+}
+
+#[test]
+fn forms_may_be_nested_256_deep_but_no_deeper() {
+    assert!(check(&nested(255), &[]).status.success());
+
+    let output = check(&nested(256), &[]);
+    assert!(!output.status.success());
+    assert_eq!(
+        diagnostics(&output),
+        [
+            ("forms are nested too deeply".to_string(), Some(1)),
+            (
+                "unable to assemble due to the previous error".to_string(),
+                None
+            ),
+        ]
+    );
+}
+
+#[test]
+fn labels_may_be_256_characters_long_but_no_longer() {
+    let at_limit = "a".repeat(256);
+    let nested_at_limit = "d".repeat(254);
+    let source =
+        format!("(segment rx (block {at_limit} (nop)) (block c (block {nested_at_limit} (nop))))");
+    assert!(check(&source, &[]).status.success());
+
+    // NOTE: The label of a nested block includes the labels of the blocks enclosing it, but only
+    // the outermost block whose label is too long is reported.
+    let too_long = "b".repeat(257);
+    let nested_too_long = "f".repeat(255);
+    let output = check(
+        &format!(
+            "(segment rx
+    (block {too_long} (nop))
+    (block e (block {nested_too_long} (block g (nop)))))"
+        ),
+        &[],
+    );
+    assert!(!output.status.success());
+    assert_eq!(
+        diagnostics(&output),
+        [
+            (format!("label {too_long} is too long"), Some(2)),
+            (format!("label {nested_too_long} is too long"), Some(3)),
+            (
+                "unable to assemble due to 2 previous errors".to_string(),
+                None
+            ),
+        ]
+    );
+}
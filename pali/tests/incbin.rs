@@ -0,0 +1,61 @@
+use poki::Poki;
+
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// A fresh temporary directory containing `src/main.pali`, which includes `data.bin` from within
+// `src`, which holds the bytes 0x34, 0x12, 0x56.
+fn project(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("pali-incbin-{name}-{}", std::process::id()));
+    fs::create_dir_all(directory.join("src")).unwrap();
+    fs::write(
+        directory.join("src/main.pali"),
+        "(segment rx (incbin \"data.bin\"))",
+    )
+    .unwrap();
+    fs::write(directory.join("src/data.bin"), [0x34, 0x12, 0x56]).unwrap();
+    directory
+}
+
+// Assemble `src/main.pali` with pali run from `directory`, passing `extra` as further arguments,
+// and writing the output to `out.poki`.
+fn pali(directory: &PathBuf, extra: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pali"))
+        .current_dir(directory)
+        .args(["src/main.pali", "--output", "out.poki"])
+        .args(extra)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn included_paths_are_relative_to_the_including_file() {
+    let directory = project("relative");
+
+    let output = pali(&directory, &[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let poki = Poki::deserialize(&mut File::open(directory.join("out.poki")).unwrap()).unwrap();
+    assert_eq!(poki.segments[0b101].contents, [0x1234, 0x0056]);
+}
+
+#[test]
+fn missing_included_files_are_reported_where_they_were_looked_for() {
+    let directory = project("missing");
+    fs::remove_file(directory.join("src/data.bin")).unwrap();
+
+    // NOTE: Diagnostics are printed as json, so that the help isn't wrapped.
+    let output = pali(&directory, &["--message-format", "json"]);
+    assert!(!output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("so this refers to src/data.bin"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,207 @@
+use pali::lex::{Lexer, Token, TokenKind};
+use pali::source::Sources;
+
+// Lex `source`, applying `f` to each token along with `source`, or give the message of the first
+// error encountered.
+fn lex<'s, T>(source: &'s str, f: impl Fn(&'s str, Token<'_>) -> T) -> Result<Vec<T>, String> {
+    let sources = Sources::new([("test.pali".to_string(), source.to_string())]);
+    Lexer::new(&sources, &sources.files()[0])
+        .map(|token| {
+            token
+                .map(|token| f(source, token))
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+// The text of the source from which each token of `source` was lexed.
+fn texts(source: &str) -> Result<Vec<&str>, String> {
+    lex(source, |source, token| {
+        let start = token.source_span.offset();
+        &source[start..start + token.source_span.len()]
+    })
+}
+
+#[test]
+fn block_comments_are_skipped() {
+    assert_eq!(
+        texts("(nop #| a comment\nspanning ( several lines |# )"),
+        Ok(vec!["(", "nop", ")"])
+    );
+}
+
+#[test]
+fn block_comments_nest() {
+    assert_eq!(
+        texts("#| outer #| inner |# still outer |# (nop)"),
+        Ok(vec!["(", "nop", ")"])
+    );
+    assert_eq!(
+        texts("#| outer #| inner |# (nop)"),
+        Err("unterminated block comment".to_string())
+    );
+}
+
+#[test]
+fn comment_markers_within_strings_begin_no_comments() {
+    assert_eq!(
+        texts("(stringz \"#|\") (stringz \";\") (nop)"),
+        Ok(vec![
+            "(", "stringz", "\"#|\"", ")", "(", "stringz", "\";\"", ")", "(", "nop", ")"
+        ])
+    );
+}
+
+#[test]
+fn block_comment_markers_within_line_comments_begin_no_comments() {
+    assert_eq!(
+        texts("; #| not a block comment\n(nop)"),
+        Ok(vec!["(", "nop", ")"])
+    );
+}
+
+// The value of `source`, which should be a single numeric or character literal, or the message of
+// the error encountered while lexing it.
+fn number(source: &str) -> Result<u16, String> {
+    let numbers = lex(source, |_, token| match token.token_kind {
+        TokenKind::Number(n) => Some(n),
+        _ => None,
+    })?;
+    match numbers[..] {
+        [Some(n)] => Ok(n),
+        _ => panic!("{source} is not a single number"),
+    }
+}
+
+#[test]
+fn character_literals_stand_for_their_code_points() {
+    assert_eq!(number("'a'"), Ok(0x0061));
+    assert_eq!(number("'\\n'"), Ok(0x000A));
+    assert_eq!(number("'\\u0041'"), Ok(0x0041));
+    assert_eq!(number("'é'"), Ok(0x00E9));
+}
+
+#[test]
+fn character_literals_must_contain_exactly_one_character() {
+    assert_eq!(
+        number("'ab'"),
+        Err("character literal contains more than one character".to_string())
+    );
+    assert_eq!(number("''"), Err("empty character literal".to_string()));
+}
+
+// NOTE: This pins down the difference from character literals which the README points out.
+#[test]
+fn string_literals_are_not_unescaped() {
+    let strings = lex("\"a\\nb\"", |_, token| match token.token_kind {
+        TokenKind::String(string) => string.to_string(),
+        _ => panic!("expected a string"),
+    });
+    assert_eq!(strings, Ok(vec!["a\\nb".to_string()]));
+}
+
+#[test]
+fn negative_literals_are_stored_in_twos_complement() {
+    assert_eq!(number("-1"), Ok(0xFFFF));
+    assert_eq!(number("-0x8000"), Ok(0x8000));
+    assert_eq!(number("-32768"), Ok(0x8000));
+    assert_eq!(
+        number("-0x8001"),
+        Err("numeric literal -0x8001 is out of range".to_string())
+    );
+}
+
+#[test]
+fn a_lone_minus_is_not_a_literal() {
+    assert_eq!(
+        texts("(-)"),
+        Err("encountered invalid character '-' in input".to_string())
+    );
+    assert_eq!(
+        texts("- 1"),
+        Err("encountered invalid character '-' in input".to_string())
+    );
+}
+
+#[test]
+fn digit_separators_may_appear_between_digits() {
+    assert_eq!(number("1_000"), Ok(1000));
+    assert_eq!(number("0xFF_FF"), Ok(0xFFFF));
+    assert_eq!(number("0b1111_0000"), Ok(0b1111_0000));
+    assert_eq!(number("0o7_7"), Ok(0o77));
+    assert_eq!(number("-1_000"), Ok(1000u16.wrapping_neg()));
+}
+
+#[test]
+fn digit_separators_may_not_begin_or_end_a_literal() {
+    assert_eq!(
+        number("0x_FF"),
+        Err("numeric literal 0x_FF begins with a digit separator".to_string())
+    );
+    assert_eq!(
+        number("1_"),
+        Err("numeric literal 1_ ends with a digit separator".to_string())
+    );
+    assert_eq!(
+        number("0x__"),
+        Err("numeric literal 0x__ has no digits, only digit separators".to_string())
+    );
+}
+
+// The message and help of the error encountered while lexing `source`, which should be invalid.
+fn error(source: &str) -> (String, String) {
+    let sources = Sources::new([("test.pali".to_string(), source.to_string())]);
+    let error = Lexer::new(&sources, &sources.files()[0])
+        .find_map(Result::err)
+        .unwrap_or_else(|| panic!("{source} was lexed without error"));
+    let help = error.help().unwrap().to_string();
+    (error.to_string(), help)
+}
+
+#[test]
+fn invalid_digits_are_named_by_radix() {
+    assert_eq!(
+        error("0b102"),
+        (
+            "invalid binary numeric literal".to_string(),
+            "binary literals may only contain the digits 0 and 1".to_string()
+        )
+    );
+    assert_eq!(
+        error("0o19"),
+        (
+            "invalid octal numeric literal".to_string(),
+            "octal literals may only contain the digits 0 through 7".to_string()
+        )
+    );
+    assert_eq!(
+        error("0xFG"),
+        (
+            "invalid hexadecimal numeric literal".to_string(),
+            "hexadecimal literals may only contain the digits 0 through 9 and a through f"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        error("12a"),
+        (
+            "invalid decimal numeric literal".to_string(),
+            "decimal literals may only contain the digits 0 through 9, or may be given a radix prefix of 0b, 0o, or 0x".to_string()
+        )
+    );
+}
+
+#[test]
+fn out_of_range_literals_are_bounded_in_their_own_radix() {
+    assert_eq!(
+        error("0x1_0000"),
+        (
+            "numeric literal 0x1_0000 is out of range".to_string(),
+            "numeric literals must lie in the range 0..=65535, so a hexadecimal literal may be at most 0xFFFF".to_string()
+        )
+    );
+    assert_eq!(
+        error("70000").1,
+        "numeric literals must lie in the range 0..=65535, so a decimal literal may be at most 65535"
+    );
+}
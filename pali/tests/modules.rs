@@ -0,0 +1,117 @@
+use poki::Poki;
+
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process::Command;
+
+// Write each of `sources` to a file of its own in a fresh temporary directory, returning the paths
+// of the files, followed by a path in the same directory to which output may be written.
+fn write_sources(name: &str, sources: &[&str]) -> (Vec<PathBuf>, PathBuf) {
+    let directory = std::env::temp_dir().join(format!("pali-{name}-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+
+    let paths = sources
+        .iter()
+        .enumerate()
+        .map(|(index, source)| {
+            let path = directory.join(format!("{index}.pali"));
+            fs::write(&path, source).unwrap();
+            path
+        })
+        .collect();
+
+    (paths, directory.join("out.poki"))
+}
+
+fn pali(source_paths: &[PathBuf], output_path: &PathBuf, extra: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_pali"))
+        .args(source_paths)
+        .arg("--output")
+        .arg(output_path)
+        .args(extra)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn identical_labels_in_different_modules_link_cleanly() {
+    let (source_paths, output_path) = write_sources(
+        "identical-labels",
+        &[
+            "(module uart)
+             (export init)
+             (segment rx
+                 (block init (jal r0 r0 loop))
+                 (block loop (jal r0 r0 loop)))",
+            "(module main)
+             (export (as _start start))
+             (segment rx
+                 (block start (call uart.init) (jal r0 r0 loop))
+                 (block loop (jal r0 r0 loop)))",
+        ],
+    );
+    let symbols_path = output_path.with_extension("sym");
+
+    let output = pali(
+        &source_paths,
+        &output_path,
+        &["--symbols", symbols_path.to_str().unwrap()],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let symbols = fs::read_to_string(&symbols_path).unwrap();
+    assert!(symbols.contains("5:0002 uart.loop"));
+    assert!(symbols.contains("5:0008 main.loop"));
+
+    let poki = Poki::deserialize(&mut File::open(&output_path).unwrap()).unwrap();
+    assert!(poki.unresolved_table.is_empty());
+
+    let mut exports: Vec<_> = poki.segments[5]
+        .export_table
+        .iter()
+        .map(|export_table_entry| (export_table_entry.label.as_str(), export_table_entry.offset))
+        .collect();
+    exports.sort();
+    assert_eq!(exports, [("_start", 4), ("init", 0)]);
+
+    // Each `loop` refers to the `loop` in its own module, rather than to whichever came first.
+    let relocation_targets: Vec<_> = poki.segments[5]
+        .relocation_table
+        .iter()
+        .map(|relocation_table_entry| relocation_table_entry.segment_offset)
+        .collect();
+    assert_eq!(relocation_targets, [2, 2, 0, 8, 8]);
+}
+
+#[test]
+fn identical_labels_without_modules_collide() {
+    let (source_paths, output_path) = write_sources(
+        "colliding-labels",
+        &[
+            "(segment rx (block loop (jal r0 r0 loop)))",
+            "(segment rx (block loop (jal r0 r0 loop)))",
+        ],
+    );
+
+    let output = pali(&source_paths, &output_path, &[]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn exports_of_the_same_name_from_different_modules_are_rejected() {
+    let (source_paths, output_path) = write_sources(
+        "colliding-exports",
+        &[
+            "(module a) (export loop) (segment rx (block loop (jal r0 r0 loop)))",
+            "(module b) (export loop) (segment rx (block loop (jal r0 r0 loop)))",
+        ],
+    );
+
+    let output = pali(&source_paths, &output_path, &[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("exported as loop"));
+}
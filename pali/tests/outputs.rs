@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const PROGRAM: &str = "(segment rx (block main (jal r0 r0 main)))";
+
+// A directory of its own for the test `name`, emptied of anything left by a previous run.
+fn directory(name: &str) -> PathBuf {
+    let directory =
+        std::env::temp_dir().join(format!("pali-outputs-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+// Assemble `source` into `output`, passing `extra` as further arguments.
+fn assemble(directory: &Path, source: &str, output: &Path, extra: &[&str]) -> Output {
+    let source_path = directory.join("program.pali");
+    fs::write(&source_path, source).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_pali"))
+        .arg(&source_path)
+        .arg("--output")
+        .arg(output)
+        .args(extra)
+        .output()
+        .unwrap()
+}
+
+// The names of the files in `directory`, other than the source, in sorted order.
+fn outputs(directory: &Path) -> Vec<String> {
+    let mut names: Vec<_> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name != "program.pali")
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn a_failed_assembly_leaves_the_existing_output_intact() {
+    let directory = directory("failed");
+    let output = directory.join("out.poki");
+    fs::write(&output, "previous").unwrap();
+
+    let result = assemble(&directory, "(segment rx (add r0 r0 r0 r0))", &output, &[]);
+    assert!(!result.status.success());
+    assert_eq!(fs::read_to_string(&output).unwrap(), "previous");
+    assert_eq!(outputs(&directory), ["out.poki"]);
+}
+
+#[test]
+fn a_missing_directory_is_reported_unless_it_may_be_created() {
+    let directory = directory("missing");
+    let output = directory.join("missing").join("out.poki");
+
+    let result = assemble(&directory, PROGRAM, &output, &[]);
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("does not exist"));
+    assert!(!output.exists());
+
+    let result = assemble(&directory, PROGRAM, &output, &["--create-dirs"]);
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert!(output.is_file());
+}
+
+#[cfg(unix)]
+#[test]
+fn a_read_only_directory_leaves_the_existing_output_intact() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // NOTE: The source is written alongside the read-only directory, rather than within it.
+    let directory = directory("read-only");
+    let read_only = directory.join("out");
+    fs::create_dir(&read_only).unwrap();
+    let output = read_only.join("out.poki");
+    fs::write(&output, "previous").unwrap();
+    fs::set_permissions(&read_only, fs::Permissions::from_mode(0o555)).unwrap();
+
+    // NOTE: Permissions don't bind a privileged user, such as root in a container, in which case
+    // there is nothing to test.
+    let probe = read_only.join("probe");
+    if fs::write(&probe, "").is_ok() {
+        fs::remove_file(&probe).unwrap();
+        return;
+    }
+
+    let result = assemble(&directory, PROGRAM, &output, &[]);
+    fs::set_permissions(&read_only, fs::Permissions::from_mode(0o755)).unwrap();
+    assert!(!result.status.success());
+    assert!(String::from_utf8_lossy(&result.stderr).contains("unable to write output"));
+    assert_eq!(fs::read_to_string(&output).unwrap(), "previous");
+    assert_eq!(outputs(&read_only), ["out.poki"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn the_permissions_of_an_existing_output_are_kept() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let directory = directory("permissions");
+    let output = directory.join("out.poki");
+    fs::write(&output, "previous").unwrap();
+    fs::set_permissions(&output, fs::Permissions::from_mode(0o600)).unwrap();
+
+    let result = assemble(&directory, PROGRAM, &output, &[]);
+    assert!(
+        result.status.success(),
+        "{}",
+        String::from_utf8_lossy(&result.stderr)
+    );
+    assert_ne!(fs::read(&output).unwrap(), b"previous");
+    assert_eq!(
+        fs::metadata(&output).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+}
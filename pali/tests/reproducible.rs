@@ -0,0 +1,70 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Exports, relocations, and unresolved symbols in several segments, so that the order of every
+// table in the poki file, and of every line of the symbol table and map, is exercised.
+const PROGRAM: &str = "
+    (export main helpers.twice (as entry main))
+    (segment rx
+        (block main
+            (la r2 message)
+            (call helpers.twice)
+            (call external.print)
+            (block halt (jal r0 r0 halt)))
+        (block helpers
+            (block twice (add r2 r2) (ret))
+            (block unused (la r3 counter))))
+    (segment rw
+        (block counter 0)
+        (block message (stringz \"hello\")))";
+
+// Assemble `source` into the directory `directory`, writing every output which pali can produce,
+// and returning the paths of the outputs.
+fn assemble(directory: &Path, source: &Path) -> Vec<PathBuf> {
+    fs::create_dir_all(directory).unwrap();
+    let outputs = ["out.poki", "out.lst", "out.sym", "out.sym.json", "out.map"]
+        .map(|name| directory.join(name));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pali"))
+        .arg(source)
+        .arg("--output")
+        .arg(&outputs[0])
+        .arg("--listing")
+        .arg(&outputs[1])
+        .arg("--symbols")
+        .arg(&outputs[2])
+        .arg("--symbols-json")
+        .arg(&outputs[3])
+        .arg("--map")
+        .arg(&outputs[4])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    outputs.to_vec()
+}
+
+#[test]
+fn assembling_twice_gives_identical_output() {
+    let directory = std::env::temp_dir().join(format!("pali-reproducible-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let source = directory.join("program.pali");
+    fs::write(&source, PROGRAM).unwrap();
+
+    let first = assemble(&directory.join("first"), &source);
+    let second = assemble(&directory.join("second"), &source);
+
+    for (first, second) in first.iter().zip(&second) {
+        assert_eq!(
+            fs::read(first).unwrap(),
+            fs::read(second).unwrap(),
+            "{} differs between assemblies",
+            first.file_name().unwrap().to_string_lossy()
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use miette::{GraphicalReportHandler, GraphicalTheme, SourceSpan};
+use pali::assemble::Assembler;
+use pali::parse::{CodeKind, Defines, Immediate, Parser};
+use pali::source::Sources;
+
+fn sources(source: &str) -> Sources {
+    Sources::new([("test.pali".to_string(), source.to_string())])
+}
+
+// Assemble `source`, which must fail to assemble, rendering each of the errors reported as it would
+// be shown in a terminal without colour.
+fn errors(source: &str) -> Vec<String> {
+    errors_with(source, |assembler| assembler)
+}
+
+// As `errors`, but with the assembler configured by `configure` before assembling.
+fn errors_with(
+    source: &str,
+    configure: impl for<'a> FnOnce(Assembler<'a>) -> Assembler<'a>,
+) -> Vec<String> {
+    let sources = sources(source);
+    let defines = Defines::new();
+    let errors = Assembler::try_new(&sources, &defines)
+        .and_then(|assembler| configure(assembler).assemble())
+        .unwrap_err();
+
+    let handler = GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor());
+    errors
+        .iter()
+        .map(|error| {
+            let mut rendered = String::new();
+            handler
+                .render_report(&mut rendered, error.as_ref())
+                .unwrap();
+            rendered
+        })
+        .collect()
+}
+
+// Parse `source`, which must consist of a single segment, returning the span of every numeric
+// immediate within it, in order.
+fn number_spans(source: &str, defines: &Defines) -> Vec<SourceSpan> {
+    let sources = sources(source);
+    let program = Parser::new(&sources, &sources.files()[0], defines)
+        .parse()
+        .unwrap();
+
+    let mut spans = Vec::new();
+    for code in program.segments.iter().flatten() {
+        let code_kinds = match &code.code_kind {
+            CodeKind::Sequence(contents) => contents.iter().collect(),
+            code_kind => vec![code_kind],
+        };
+        for code_kind in code_kinds {
+            if let CodeKind::ImmediateInstruction {
+                imm: Immediate::Number(n),
+                ..
+            } = code_kind
+            {
+                spans.push(n.source_span);
+            }
+        }
+    }
+    spans
+}
+
+#[test]
+fn numeric_immediates_carry_their_spans() {
+    let defines = Defines::from([("BASE".to_string(), 0x100)]);
+    let source = "(segment rx (addi r1 r0 0x10) (ori r1 r1 BASE) (not r1 r2))";
+
+    // NOTE: The immediate of a pseudo-instruction isn't written anywhere, so it takes the span of
+    // the pseudo-opcode instead.
+    assert_eq!(
+        number_spans(source, &defines),
+        [
+            SourceSpan::from((24, 4)),
+            SourceSpan::from((41, 4)),
+            SourceSpan::from((48, 3)),
+        ]
+    );
+}
+
+// NOTE: Each of the diagnostics below is compared in full, to check that it underlines exactly the
+// part of the source to which it refers.
+
+#[test]
+fn jsh_range_is_underlined() {
+    assert_eq!(
+        errors("(segment rx (jsh 600))"),
+        ["  × jsh immediate 0x0258 is out of range
+   ╭─[test.pali:1:18]
+ 1 │ (segment rx (jsh 600))
+   ·                  ─┬─
+   ·                   ╰── this immediate does not fit in 10 bits
+   ╰────
+  help: the immediate of jsh must lie between -512 and 511, or equivalently be at most 0x01FF or at least 0xFE00
+"]
+    );
+}
+
+#[test]
+fn segment_overflow_is_underlined() {
+    assert_eq!(
+        errors("(segment rx (org 0xFFFF) (nop))"),
+        ["  × segment rx is too large
+   ╭─[test.pali:1:26]
+ 1 │ (segment rx (org 0xFFFF) (nop))
+   ·                          ──┬──
+   ·                            ╰── this brings segment rx to 65536 words
+   ╰────
+  help: segment rx is already 65535 words long, but segments may be at most 65535 words long
+"]
+    );
+}
+
+#[test]
+fn export_of_undefined_label_is_underlined() {
+    assert_eq!(
+        errors("(export missing) (segment rx (nop))"),
+        ["  × label missing exported, but is not defined
+   ╭─[test.pali:1:9]
+ 1 │ (export missing) (segment rx (nop))
+   ·         ───────
+   ╰────
+"]
+    );
+}
+
+#[test]
+fn backwards_org_underlines_its_offset() {
+    assert_eq!(
+        errors("(segment rx (nop) (org 0))"),
+        ["  × org moves backwards in segment rx
+   ╭─[test.pali:1:24]
+ 1 │ (segment rx (nop) (org 0))
+   ·                        ┬
+   ·                        ╰── this would place the following code at 0x0000
+   ╰────
+  help: segment rx is already 0x0001 words long, so the following code would overlap the code before it
+"]
+    );
+}
+
+#[test]
+fn duplicate_label_is_underlined_twice() {
+    assert_eq!(
+        errors("(segment rx (block a (nop)) (block a (nop)))"),
+        ["  × label a is defined more than once
+   ╭─[test.pali:1:20]
+ 1 │ (segment rx (block a (nop)) (block a (nop)))
+   ·                    ┬               ┬
+   ·                    │               ╰── and again here
+   ·                    ╰── label first defined here
+   ╰────
+"]
+    );
+}
+
+#[test]
+fn offsetof_undefined_label_is_underlined() {
+    assert_eq!(
+        errors("(segment rx (addi r1 r0 (offsetof missing)))"),
+        ["  × offsetof refers to label missing, which is not defined
+   ╭─[test.pali:1:35]
+ 1 │ (segment rx (addi r1 r0 (offsetof missing)))
+   ·                                   ───────
+   ╰────
+  help: offsetof may only refer to labels defined in the program being assembled
+"]
+    );
+}
+
+#[test]
+fn unresolved_label_is_underlined_with_its_near_miss() {
+    assert_eq!(
+        errors_with(
+            "(segment rx (jal r0 r0 mising) (block missing (nop)))",
+            |assembler| assembler.error_unresolved(true)
+        ),
+        ["  × label mising is referred to, but is not defined
+   ╭─[test.pali:1:24]
+ 1 │ (segment rx (jal r0 r0 mising) (block missing (nop)))
+   ·                        ───┬──         ───┬───
+   ·                           │              ╰── similar label defined here
+   ·                           ╰── referred to here
+   ╰────
+  help: perhaps you meant missing
+"]
+    );
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::check;
+
+fn discarded_write(start: usize, len: usize) -> String {
+    format!(
+        r#"{{"severity":"warning","message":"instruction writes to r0, so its result is discarded","help":"use (nop) if this is intended, or pass --no-warn-discarded-write","file":"<stdin>","line":1,"column":{column},"start":{start},"len":{len},"labels":[{{"text":"r0 is always zero","file":"<stdin>","line":1,"column":{column},"start":{start},"len":{len}}}]}}"#,
+        column = start + 1
+    )
+}
+
+#[test]
+fn write_to_r0_is_warned_about() {
+    let output = check("(segment rx (addi r0 r1 5))", &[]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        discarded_write(18, 2) + "\n"
+    );
+}
+
+#[test]
+fn write_to_alias_of_r0_is_warned_about() {
+    let output = check(
+        "(regalias nothing r0) (segment rx (ld nothing r1) (mov zero r2))",
+        &[],
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        discarded_write(38, 7) + "\n" + &discarded_write(55, 4) + "\n"
+    );
+}
+
+#[test]
+fn nops_and_jumps_are_not_warned_about() {
+    let output = check(
+        "(segment rx (block start (nop) (add r0 r0) (st r0 r1) (jal r0 r0 start)))",
+        &[],
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn write_to_r0_may_be_silenced() {
+    let output = check(
+        "(segment rx (addi r0 r1 5))",
+        &["--no-warn-discarded-write"],
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn deny_warnings_makes_write_to_r0_fatal() {
+    let output = check("(segment rx (addi r0 r1 5))", &["--deny-warnings"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        discarded_write(18, 2)
+            + "\n"
+            + r#"{"severity":"error","message":"unable to assemble due to the previous warning","help":"warnings are treated as errors, since --deny-warnings was passed","file":null,"line":null,"column":null,"start":null,"len":null,"labels":[]}"#
+            + "\n"
+    );
+}
+
+#[test]
+fn diagnostics_give_line_and_column() {
+    let output = check("(segment rx\n  (addi r0 r1 5))", &[]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains(r#""file":"<stdin>","line":2,"column":9,"start":20,"len":2"#));
+}
+
+#[test]
+fn la_of_a_constant_is_warned_about() {
+    let output = check("(segment rx (la r1 FOO))", &["--define", "FOO=5"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        r#"{"severity":"warning","message":"la loads the constant FOO, rather than the address of a label","help":"use li to load a constant","file":"<stdin>","line":1,"column":20,"start":19,"len":3,"labels":[{"text":"this is a constant","file":"<stdin>","line":1,"column":20,"start":19,"len":3}]}"#
+            .to_string()
+            + "\n"
+    );
+}
+
+#[test]
+fn duplicate_exports_are_warned_about() {
+    let output = check("(export a a) (segment rx (block a (nop)))", &[]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        r#"{"severity":"warning","message":"label a is exported more than once","help":null,"file":"<stdin>","line":1,"column":9,"start":8,"len":1,"labels":[{"text":"label first exported here","file":"<stdin>","line":1,"column":9,"start":8,"len":1},{"text":"and again here","file":"<stdin>","line":1,"column":11,"start":10,"len":1}]}"#
+            .to_string()
+            + "\n"
+    );
+}
@@ -1,7 +1,24 @@
-use std::error::Error;
-use std::fmt::{self, Display, Formatter};
-use std::io::{self, Read, Write};
-use std::string;
+#![forbid(unsafe_code)]
+// NOTE: Without the `std` feature, only the data types and the operations which need nothing but
+// an allocator are available, so that poki files may be examined and built in environments such
+// as bootloaders. Serialization, deserialization, and the text format all depend on `std::io`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::string::{self, String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt::{self, Display, Formatter};
+#[cfg(feature = "std")]
+use std::io::{self, Cursor, Read, Write};
+
+#[cfg(feature = "std")]
+mod text;
+
+#[cfg(feature = "std")]
+pub use text::PokiTextDeserializationError;
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct Poki {
@@ -29,6 +46,7 @@ pub struct ExportTableEntry {
     pub offset: u16,
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 struct SegmentHeader {
     contents_size: u16,
@@ -36,6 +54,7 @@ struct SegmentHeader {
     export_table_size: u16,
 }
 
+#[cfg(feature = "std")]
 impl SegmentHeader {
     fn deserialize(reader: &mut impl Read) -> Result<Self, PokiDeserializationError> {
         let contents_size = reader.read_word()?;
@@ -64,6 +83,7 @@ impl Poki {
         }
     }
 
+    #[cfg(feature = "std")]
     pub fn serialize(&self, writer: &mut impl Write) -> Result<(), PokiSerializationError> {
         // Write the magic header.
         writer.write_all_words(&"poki".encode_utf16().collect::<Vec<_>>())?;
@@ -89,7 +109,59 @@ impl Poki {
         Ok(())
     }
 
+    // The number of bytes which `serialize` would write, computed without performing any I/O.
+    pub fn serialized_size(&self) -> usize {
+        // The magic header is 4 words long, and each of the 8 segment headers is 3 words long.
+        let headers_size = 2 * 4 + 2 * 3 * self.segments.len();
+        let segments_size = self
+            .segments
+            .iter()
+            .map(Segment::serialized_size)
+            .sum::<usize>();
+        let unresolved_table_size = self
+            .unresolved_table
+            .iter()
+            .map(|symbol| 2 * (1 + symbol.encode_utf16().count()))
+            .sum::<usize>();
+
+        headers_size + segments_size + unresolved_table_size
+    }
+
+    #[cfg(feature = "std")]
     pub fn deserialize(reader: &mut impl Read) -> Result<Self, PokiDeserializationError> {
+        let mut poki = Self::deserialize_segments(reader)?;
+
+        // NOTE: The table of unresolved symbols has no header giving its size, and instead simply
+        // extends to the end of the file. A single call to `read` may return fewer bytes than
+        // requested without the reader being exhausted, so we read the remainder of the reader in
+        // full and parse the table from that, rather than trying to detect its end piecemeal.
+        let mut unresolved_table = Vec::new();
+        reader.read_to_end(&mut unresolved_table)?;
+        poki.unresolved_table = deserialize_unresolved_table(&unresolved_table)?;
+
+        Ok(poki)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, PokiDeserializationError> {
+        let mut cursor = Cursor::new(bytes);
+        let mut poki = Self::deserialize_segments(&mut cursor)?;
+
+        // Since all of the data is already in memory, we know exactly where the table of
+        // unresolved symbols ends, and there is no need to copy it out first.
+        //
+        // NOTE: The position of the cursor never exceeds the length of `bytes`, so it always fits
+        // in a `usize`.
+        let unresolved_table = &bytes[usize::try_from(cursor.position()).unwrap()..];
+        poki.unresolved_table = deserialize_unresolved_table(unresolved_table)?;
+
+        Ok(poki)
+    }
+
+    // Deserialize the magic header, the segment headers, and the segments themselves, leaving the
+    // table of unresolved symbols empty.
+    #[cfg(feature = "std")]
+    fn deserialize_segments(reader: &mut impl Read) -> Result<Self, PokiDeserializationError> {
         let mut magic_buffer = [0; 4];
         reader.read_exact_words(&mut magic_buffer)?;
         if magic_buffer != *"poki".encode_utf16().collect::<Vec<_>>() {
@@ -102,38 +174,331 @@ impl Poki {
             export_table_size: 0,
         }; 8];
 
-        for segment_index in 0..8 {
-            segment_headers[segment_index] = SegmentHeader::deserialize(reader)?;
+        for segment_header in &mut segment_headers {
+            *segment_header = SegmentHeader::deserialize(reader)?;
         }
 
         let mut poki = Self::new_empty();
 
-        for segment_index in 0..8 {
-            poki.segments[segment_index] =
-                Segment::deserialize(reader, segment_headers[segment_index])?;
+        for (segment, segment_header) in poki.segments.iter_mut().zip(segment_headers) {
+            *segment = Segment::deserialize(reader, segment_header)?;
         }
 
-        let mut label_size = [0];
-        loop {
-            // HACK: This is kind of a goofy way to figure out if we've reached the (probable) end
-            // of our reader, but whatever.
-            if reader.read_words(&mut label_size)? == 0 {
-                break;
+        Ok(poki)
+    }
+
+    // Iterate over the entries of the export tables of all of the segments, in segment order,
+    // along with the index of the segment to which each belongs.
+    pub fn find_all_exports(&self) -> impl Iterator<Item = (usize, &ExportTableEntry)> {
+        self.segments
+            .iter()
+            .enumerate()
+            .flat_map(|(segment_index, segment)| {
+                segment
+                    .export_table
+                    .iter()
+                    .map(move |export_table_entry| (segment_index, export_table_entry))
+            })
+    }
+
+    // Find the segment index and offset at which the label `name` is exported, if it is exported
+    // at all. If it is exported more than once, the first export is returned.
+    pub fn find_export(&self, name: &str) -> Option<(usize, u16)> {
+        self.find_all_exports()
+            .find(|(_, export_table_entry)| export_table_entry.label == name)
+            .map(|(segment_index, export_table_entry)| (segment_index, export_table_entry.offset))
+    }
+
+    // Rename the export `old_name` to `new_name`, in whichever segment it is exported. The table of
+    // unresolved symbols is left untouched, since its entries name labels which this poki imports
+    // from elsewhere, rather than labels which it exports.
+    pub fn rename_export(&mut self, old_name: &str, new_name: &str) -> Result<(), RenameError> {
+        if self.find_export(old_name).is_none() {
+            return Err(RenameError::NotFound(old_name.to_string()));
+        }
+        if old_name == new_name {
+            return Ok(());
+        }
+        if self.find_export(new_name).is_some() {
+            return Err(RenameError::Duplicate(new_name.to_string()));
+        }
+
+        for segment in &mut self.segments {
+            for export_table_entry in &mut segment.export_table {
+                if export_table_entry.label == old_name {
+                    export_table_entry.label = new_name.to_string();
+                }
             }
+        }
 
-            let mut label = Vec::new();
-            for _ in 0..label_size[0] {
-                label.push(reader.read_word()?);
+        Ok(())
+    }
+
+    // Check that every entry of every relocation table refers to something which exists, returning
+    // every problem found, in segment order.
+    //
+    // NOTE: Relocations may patch words at any offset, odd or even, since the immediate of an
+    // instruction is the word immediately following it. A relocation may also refer to the offset
+    // one past the end of a segment, since a label may be defined at the very end of one.
+    pub fn verify_relocations(&self) -> Result<(), Vec<RelocationError>> {
+        let mut errors = Vec::new();
+        for (segment_index, segment) in self.segments.iter().enumerate() {
+            for (entry_index, relocation_table_entry) in segment.relocation_table.iter().enumerate()
+            {
+                if usize::from(relocation_table_entry.offset) >= segment.contents.len() {
+                    errors.push(RelocationError::OffsetOutOfBounds(
+                        segment_index,
+                        entry_index,
+                        relocation_table_entry.offset,
+                    ));
+                }
+
+                if relocation_table_entry.segment_index == 0xFFFF {
+                    if usize::from(relocation_table_entry.segment_offset)
+                        >= self.unresolved_table.len()
+                    {
+                        errors.push(RelocationError::UnresolvedIndexOutOfBounds(
+                            segment_index,
+                            entry_index,
+                            relocation_table_entry.segment_offset,
+                        ));
+                    }
+                    continue;
+                }
+
+                match self
+                    .segments
+                    .get(usize::from(relocation_table_entry.segment_index))
+                {
+                    Some(target_segment) => {
+                        if usize::from(relocation_table_entry.segment_offset)
+                            > target_segment.contents.len()
+                        {
+                            errors.push(RelocationError::TargetOutOfBounds(
+                                segment_index,
+                                entry_index,
+                                relocation_table_entry.segment_index,
+                                relocation_table_entry.segment_offset,
+                            ));
+                        }
+                    }
+                    None => errors.push(RelocationError::InvalidSegmentIndex(
+                        segment_index,
+                        entry_index,
+                        relocation_table_entry.segment_index,
+                    )),
+                }
             }
-            let label = String::from_utf16(&label)?;
-            poki.unresolved_table.push(label);
         }
 
-        Ok(poki)
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Put the tables of the poki into a canonical order, so that the same program always produces
+    // the same poki, however the source which produced it happens to be arranged. The export
+    // tables are sorted by label and the table of unresolved symbols is sorted, both without
+    // duplicates, and the relocation tables are sorted by offset. Relocations which refer to
+    // unresolved symbols are updated to refer to their new positions.
+    //
+    // NOTE: Relocations which refer past the end of the table of unresolved symbols are invalid,
+    // and have no new position to refer to, so they are left as they are.
+    pub fn normalize(&mut self) {
+        let mut unresolved_table = self.unresolved_table.clone();
+        unresolved_table.sort();
+        unresolved_table.dedup();
+
+        // NOTE: Every entry of the old table is present in the new one, so the search always
+        // succeeds, and since the new table is no longer than the old one, which was indexed by
+        // words, every index fits in a word.
+        let new_indices: Vec<u16> = self
+            .unresolved_table
+            .iter()
+            .map(|symbol| {
+                let index = unresolved_table.binary_search(symbol).unwrap();
+                u16::try_from(index).unwrap_or(u16::MAX)
+            })
+            .collect();
+
+        for segment in &mut self.segments {
+            segment
+                .export_table
+                .sort_by(|a, b| (&a.label, a.offset).cmp(&(&b.label, b.offset)));
+            segment.export_table.dedup();
+
+            for relocation_table_entry in &mut segment.relocation_table {
+                if relocation_table_entry.segment_index != 0xFFFF {
+                    continue;
+                }
+                if let Some(&index) =
+                    new_indices.get(usize::from(relocation_table_entry.segment_offset))
+                {
+                    relocation_table_entry.segment_offset = index;
+                }
+            }
+            // NOTE: Relocations at the same offset are sorted by what they refer to, so that
+            // their order is canonical as well.
+            segment
+                .relocation_table
+                .sort_by_key(|relocation_table_entry| {
+                    (
+                        relocation_table_entry.offset,
+                        relocation_table_entry.segment_index,
+                        relocation_table_entry.segment_offset,
+                    )
+                });
+        }
+
+        self.unresolved_table = unresolved_table;
+    }
+
+    // Find every occurrence of `pattern` within the contents of every segment, as pairs of the
+    // index of the segment and the offset within it at which the occurrence begins, in order.
+    pub fn find_sequence_in_all_segments(&self, pattern: &[u16]) -> Vec<(usize, u16)> {
+        self.segments
+            .iter()
+            .enumerate()
+            .flat_map(|(segment_index, segment)| {
+                segment
+                    .find_sequence(pattern)
+                    .into_iter()
+                    .map(move |offset| (segment_index, offset))
+            })
+            .collect()
+    }
+
+    pub fn flatten(&self, segment_bases: &[Option<u16>; 8]) -> Result<Vec<u8>, FlattenError> {
+        // Begin by checking that every segment which is to be placed fits within the address
+        // space, and that no two segments which are to be placed overlap.
+        let mut placed_segments = Vec::new();
+        for (segment_index, (segment, base)) in self.segments.iter().zip(segment_bases).enumerate()
+        {
+            let Some(base) = base else {
+                continue;
+            };
+
+            let start = usize::from(*base);
+            let end = start + segment.contents.len();
+            if end > 0x10000 {
+                return Err(FlattenError::SegmentOutOfBounds(segment_index));
+            }
+
+            // NOTE: Empty segments occupy no space, and so can never overlap with anything.
+            if start == end {
+                continue;
+            }
+
+            for &(other_segment_index, other_start, other_end) in &placed_segments {
+                if start < other_end && other_start < end {
+                    return Err(FlattenError::SegmentOverlap(
+                        other_segment_index,
+                        segment_index,
+                    ));
+                }
+            }
+            placed_segments.push((segment_index, start, end));
+        }
+
+        let mut image = vec![0; 2 * 0x10000];
+        for (segment_index, (segment, base)) in self.segments.iter().zip(segment_bases).enumerate()
+        {
+            let Some(base) = base else {
+                continue;
+            };
+
+            // Apply the relocations of the segment to a copy of its contents.
+            let mut contents = segment.contents.clone();
+            for relocation_table_entry in &segment.relocation_table {
+                if relocation_table_entry.segment_index == 0xFFFF {
+                    return Err(FlattenError::UnresolvedRelocation(
+                        self.unresolved_table
+                            .get(usize::from(relocation_table_entry.segment_offset))
+                            .cloned()
+                            .unwrap_or_default(),
+                    ));
+                }
+
+                let target_base = segment_bases
+                    .get(usize::from(relocation_table_entry.segment_index))
+                    .ok_or(FlattenError::InvalidRelocation(
+                        segment_index,
+                        relocation_table_entry.offset,
+                    ))?
+                    .ok_or(FlattenError::RelocationIntoSkippedSegment(
+                        segment_index,
+                        relocation_table_entry.offset,
+                    ))?;
+
+                let word = contents
+                    .get_mut(usize::from(relocation_table_entry.offset))
+                    .ok_or(FlattenError::InvalidRelocation(
+                        segment_index,
+                        relocation_table_entry.offset,
+                    ))?;
+                *word = word
+                    .wrapping_add(target_base)
+                    .wrapping_add(relocation_table_entry.segment_offset);
+            }
+
+            for (offset, word) in contents.iter().enumerate() {
+                let address = usize::from(*base) + offset;
+                image[2 * address..2 * address + 2].copy_from_slice(&word.to_ne_bytes());
+            }
+        }
+
+        Ok(image)
     }
 }
 
 impl Segment {
+    pub fn from_raw_bytes(bytes: &[u8]) -> Result<Self, OddLengthError> {
+        if !bytes.len().is_multiple_of(2) {
+            return Err(OddLengthError(bytes.len()));
+        }
+
+        Ok(Self {
+            contents: bytes
+                .chunks_exact(2)
+                .map(|word| u16::from_ne_bytes([word[0], word[1]]))
+                .collect(),
+            relocation_table: Vec::new(),
+            export_table: Vec::new(),
+        })
+    }
+
+    // Find every offset within the contents of the segment at which `pattern` begins, in
+    // ascending order. Occurrences may overlap, but do not wrap around the end of the contents, and
+    // an empty pattern is never found.
+    pub fn find_sequence(&self, pattern: &[u16]) -> Vec<u16> {
+        if pattern.is_empty() {
+            return Vec::new();
+        }
+
+        // NOTE: The size of a segment is stored in a single word, so its offsets always fit in a
+        // `u16`, at least for any segment which can actually be serialized.
+        (0..=u16::MAX)
+            .zip(self.contents.windows(pattern.len()))
+            .filter(|(_, window)| *window == pattern)
+            .map(|(offset, _)| offset)
+            .collect()
+    }
+
+    // The number of bytes which `serialize` would write for this segment, not including its
+    // header.
+    pub fn serialized_size(&self) -> usize {
+        2 * self.contents.len()
+            + 2 * 3 * self.relocation_table.len()
+            + 2 * self
+                .export_table
+                .iter()
+                .map(ExportTableEntry::len)
+                .sum::<usize>()
+    }
+
+    #[cfg(feature = "std")]
     fn serialize_header(&self, writer: &mut impl Write) -> Result<(), PokiSerializationError> {
         let contents_size = u16::try_from(self.contents.len())
             .map_err(|_| PokiSerializationError::OversizedSegmentContents(self.contents.len()))?;
@@ -161,6 +526,7 @@ impl Segment {
         Ok(())
     }
 
+    #[cfg(feature = "std")]
     fn serialize(&self, writer: &mut impl Write) -> Result<(), PokiSerializationError> {
         writer.write_all_words(&self.contents)?;
 
@@ -175,6 +541,7 @@ impl Segment {
         Ok(())
     }
 
+    #[cfg(feature = "std")]
     fn deserialize(
         reader: &mut impl Read,
         segment_header: SegmentHeader,
@@ -185,7 +552,7 @@ impl Segment {
         }
 
         let mut relocation_table = Vec::new();
-        if segment_header.relocation_table_size % 3 != 0 {
+        if !segment_header.relocation_table_size.is_multiple_of(3) {
             return Err(PokiDeserializationError::InvalidRelocationTableSize(
                 segment_header.relocation_table_size,
             ));
@@ -207,9 +574,10 @@ impl Segment {
         let mut remaining_export_table_size = segment_header.export_table_size;
         while remaining_export_table_size != 0 {
             let label_size = reader.read_word()?;
-            if label_size + 2 > remaining_export_table_size {
+            // NOTE: The label size is read from untrusted input, so adding to it could overflow.
+            if u32::from(label_size) + 2 > u32::from(remaining_export_table_size) {
                 return Err(PokiDeserializationError::StringOverrun(
-                    label_size + 1 - remaining_export_table_size,
+                    label_size - (remaining_export_table_size - 1),
                 ));
             }
 
@@ -234,6 +602,7 @@ impl Segment {
     }
 }
 
+#[cfg(feature = "std")]
 impl RelocationTableEntry {
     fn serialize(&self, writer: &mut impl Write) -> Result<(), PokiSerializationError> {
         writer.write_all_words(&[self.offset, self.segment_index, self.segment_offset])?;
@@ -242,6 +611,7 @@ impl RelocationTableEntry {
 }
 
 impl ExportTableEntry {
+    #[cfg(feature = "std")]
     fn serialize(&self, writer: &mut impl Write) -> Result<(), PokiSerializationError> {
         let label_size = u16::try_from(self.label.encode_utf16().count()).map_err(|_| {
             PokiSerializationError::OversizedLabel(self.label.encode_utf16().count())
@@ -258,8 +628,27 @@ impl ExportTableEntry {
     }
 }
 
+// Deserialize a table of unresolved symbols which occupies the entirety of `bytes`.
+#[cfg(feature = "std")]
+fn deserialize_unresolved_table(mut bytes: &[u8]) -> Result<Vec<String>, PokiDeserializationError> {
+    let mut unresolved_table = Vec::new();
+    while !bytes.is_empty() {
+        let label_size = bytes.read_word()?;
+
+        let mut label = Vec::new();
+        for _ in 0..label_size {
+            label.push(bytes.read_word()?);
+        }
+        let label = String::from_utf16(&label)?;
+        unresolved_table.push(label);
+    }
+
+    Ok(unresolved_table)
+}
+
 #[derive(Debug)]
 pub enum PokiSerializationError {
+    #[cfg(feature = "std")]
     IOError(io::Error),
     OversizedSegmentContents(usize),
     OversizedRelocationTable(usize),
@@ -270,6 +659,7 @@ pub enum PokiSerializationError {
 impl Display for PokiSerializationError {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
         match self {
+            #[cfg(feature = "std")]
             Self::IOError(e) => write!(f, "{}", e),
             Self::OversizedSegmentContents(s) => {
                 write!(
@@ -294,15 +684,16 @@ impl Display for PokiSerializationError {
 }
 
 impl Error for PokiSerializationError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        if let Self::IOError(e) = self {
-            Some(e)
-        } else {
-            None
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Self::IOError(e) => Some(e),
+            _ => None,
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for PokiSerializationError {
     fn from(value: io::Error) -> Self {
         Self::IOError(value)
@@ -311,6 +702,7 @@ impl From<io::Error> for PokiSerializationError {
 
 #[derive(Debug)]
 pub enum PokiDeserializationError {
+    #[cfg(feature = "std")]
     IOError(io::Error),
     FromUtf16Error(string::FromUtf16Error),
     InvalidMagic([u16; 4]),
@@ -321,6 +713,7 @@ pub enum PokiDeserializationError {
 impl Display for PokiDeserializationError {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
         match self {
+            #[cfg(feature = "std")]
             Self::IOError(e) => write!(f, "{}", e),
             Self::FromUtf16Error(e) => write!(f, "{}", e),
             Self::InvalidMagic(m) => write!(
@@ -335,8 +728,9 @@ impl Display for PokiDeserializationError {
 }
 
 impl Error for PokiDeserializationError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
+            #[cfg(feature = "std")]
             Self::IOError(e) => Some(e),
             Self::FromUtf16Error(e) => Some(e),
             _ => None,
@@ -344,6 +738,7 @@ impl Error for PokiDeserializationError {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for PokiDeserializationError {
     fn from(value: io::Error) -> Self {
         Self::IOError(value)
@@ -356,12 +751,117 @@ impl From<string::FromUtf16Error> for PokiDeserializationError {
     }
 }
 
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum FlattenError {
+    SegmentOverlap(usize, usize),
+    SegmentOutOfBounds(usize),
+    UnresolvedRelocation(String),
+    RelocationIntoSkippedSegment(usize, u16),
+    InvalidRelocation(usize, u16),
+}
+
+impl Display for FlattenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::SegmentOverlap(a, b) => {
+                write!(f, "segments {a} and {b} overlap at the provided base addresses")
+            }
+            Self::SegmentOutOfBounds(i) => write!(
+                f,
+                "segment {i} extends past the end of the address space at the provided base address"
+            ),
+            Self::UnresolvedRelocation(label) => write!(
+                f,
+                "unable to flatten poki which refers to unresolved symbol \"{label}\""
+            ),
+            Self::RelocationIntoSkippedSegment(i, offset) => write!(
+                f,
+                "relocation at offset {offset:#06x} of segment {i} refers to a segment which is not being placed"
+            ),
+            Self::InvalidRelocation(i, offset) => write!(
+                f,
+                "relocation at offset {offset:#06x} of segment {i} is invalid"
+            ),
+        }
+    }
+}
+
+impl Error for FlattenError {}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum RenameError {
+    NotFound(String),
+    Duplicate(String),
+}
+
+impl Display for RenameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::NotFound(label) => write!(f, "no export named \"{label}\" exists"),
+            Self::Duplicate(label) => write!(f, "an export named \"{label}\" already exists"),
+        }
+    }
+}
+
+impl Error for RenameError {}
+
+// NOTE: Each variant begins with the index of the segment containing the offending relocation,
+// followed by the index of the relocation within that segment's relocation table.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum RelocationError {
+    OffsetOutOfBounds(usize, usize, u16),
+    InvalidSegmentIndex(usize, usize, u16),
+    TargetOutOfBounds(usize, usize, u16, u16),
+    UnresolvedIndexOutOfBounds(usize, usize, u16),
+}
+
+impl Display for RelocationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::OffsetOutOfBounds(i, entry, offset) => write!(
+                f,
+                "relocation {entry} of segment {i} patches the word at offset {offset:#06x}, which lies past the end of the segment"
+            ),
+            Self::InvalidSegmentIndex(i, entry, segment_index) => write!(
+                f,
+                "relocation {entry} of segment {i} refers to segment {segment_index}, which does not exist"
+            ),
+            Self::TargetOutOfBounds(i, entry, segment_index, segment_offset) => write!(
+                f,
+                "relocation {entry} of segment {i} refers to offset {segment_offset:#06x} of segment {segment_index}, which lies past the end of that segment"
+            ),
+            Self::UnresolvedIndexOutOfBounds(i, entry, index) => write!(
+                f,
+                "relocation {entry} of segment {i} refers to unresolved symbol {index}, but there are not that many unresolved symbols"
+            ),
+        }
+    }
+}
+
+impl Error for RelocationError {}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct OddLengthError(pub usize);
+
+impl Display for OddLengthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "unable to interpret {} bytes as a sequence of words, as the number of bytes is odd",
+            self.0
+        )
+    }
+}
+
+impl Error for OddLengthError {}
+
+#[cfg(feature = "std")]
 trait ReadWordsExt {
     fn read_word(&mut self) -> io::Result<u16>;
-    fn read_words(&mut self, buffer: &mut [u16]) -> io::Result<usize>;
     fn read_exact_words(&mut self, buffer: &mut [u16]) -> io::Result<()>;
 }
 
+#[cfg(feature = "std")]
 impl<T> ReadWordsExt for T
 where
     T: Read,
@@ -373,28 +873,25 @@ where
         Ok(u16::from_ne_bytes(buffer))
     }
 
-    fn read_words(&mut self, buffer: &mut [u16]) -> io::Result<usize> {
-        let len = buffer.len().checked_mul(2).unwrap();
-        let ptr: *mut u8 = buffer.as_mut_ptr().cast();
-        let buffer = unsafe { std::slice::from_raw_parts_mut(ptr, len) };
-
-        self.read(buffer).map(|n| n / 2)
-    }
-
     fn read_exact_words(&mut self, buffer: &mut [u16]) -> io::Result<()> {
-        let len = buffer.len().checked_mul(2).unwrap();
-        let ptr: *mut u8 = buffer.as_mut_ptr().cast();
-        let buffer = unsafe { std::slice::from_raw_parts_mut(ptr, len) };
+        let mut bytes = vec![0; 2 * buffer.len()];
+        self.read_exact(&mut bytes)?;
+
+        for (word, bytes) in buffer.iter_mut().zip(bytes.chunks_exact(2)) {
+            *word = u16::from_ne_bytes([bytes[0], bytes[1]]);
+        }
 
-        self.read_exact(buffer)
+        Ok(())
     }
 }
 
+#[cfg(feature = "std")]
 trait WriteWordsExt {
     fn write_word(&mut self, word: u16) -> io::Result<()>;
     fn write_all_words(&mut self, words: &[u16]) -> io::Result<()>;
 }
 
+#[cfg(feature = "std")]
 impl<T> WriteWordsExt for T
 where
     T: Write,
@@ -404,10 +901,8 @@ where
     }
 
     fn write_all_words(&mut self, words: &[u16]) -> io::Result<()> {
-        let len = words.len().checked_mul(2).unwrap();
-        let ptr: *const u8 = words.as_ptr().cast();
-        let words = unsafe { std::slice::from_raw_parts(ptr, len) };
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_ne_bytes()).collect();
 
-        self.write_all(words)
+        self.write_all(&bytes)
     }
 }
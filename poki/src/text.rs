@@ -0,0 +1,283 @@
+// A line-oriented text rendering of poki files, which is meant to be read by humans and kept under
+// version control, where binary files neither diff nor merge well. The binary format remains the
+// canonical one, and the text format holds exactly the same information.
+//
+// The text format begins with the line `poki`, followed by each of the eight segments in turn, and
+// finally the table of unresolved symbols:
+//
+//     poki
+//     seg5 words 3 relocations 1 exports 1
+//     seg5 0x0000: 0x0028
+//     seg5 0x0001: 0x0000
+//     seg5 0x0002: 0x0017
+//     seg5 reloc 0x0001 -> seg5 0x0002
+//     seg5 export 0x0000 "_start"
+//     unresolved "putc"
+//
+// Each segment begins with a header line giving the number of words, relocations, and exports
+// which follow it, and then has a line for each of them, in that order. Relocations which refer
+// to unresolved symbols are written as `-> unresolved <index>`. Labels are quoted and escaped as
+// Rust string literals, so that they may contain any character, including spaces and newlines.
+
+use crate::{ExportTableEntry, Poki, RelocationTableEntry};
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+
+impl Poki {
+    // NOTE: The output depends on nothing but the contents of the poki, so the same poki always
+    // produces the same text, which is what makes diffs of it meaningful.
+    pub fn serialize_text(&self, writer: &mut impl Write) -> io::Result<()> {
+        writeln!(writer, "poki")?;
+
+        for (segment_index, segment) in self.segments.iter().enumerate() {
+            writeln!(
+                writer,
+                "seg{segment_index} words {} relocations {} exports {}",
+                segment.contents.len(),
+                segment.relocation_table.len(),
+                segment.export_table.len()
+            )?;
+
+            for (offset, word) in segment.contents.iter().enumerate() {
+                writeln!(writer, "seg{segment_index} {offset:#06x}: {word:#06x}")?;
+            }
+
+            for relocation_table_entry in &segment.relocation_table {
+                write!(
+                    writer,
+                    "seg{segment_index} reloc {:#06x} -> ",
+                    relocation_table_entry.offset
+                )?;
+                if relocation_table_entry.segment_index == 0xFFFF {
+                    writeln!(
+                        writer,
+                        "unresolved {:#06x}",
+                        relocation_table_entry.segment_offset
+                    )?;
+                } else {
+                    writeln!(
+                        writer,
+                        "seg{} {:#06x}",
+                        relocation_table_entry.segment_index, relocation_table_entry.segment_offset
+                    )?;
+                }
+            }
+
+            for export_table_entry in &segment.export_table {
+                writeln!(
+                    writer,
+                    "seg{segment_index} export {:#06x} {:?}",
+                    export_table_entry.offset, export_table_entry.label
+                )?;
+            }
+        }
+
+        for symbol in &self.unresolved_table {
+            writeln!(writer, "unresolved {symbol:?}")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn deserialize_text(reader: &mut impl Read) -> Result<Self, PokiTextDeserializationError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        // NOTE: Line numbers are counted from 1, as they are in any text editor.
+        let mut lines = text.lines().zip(1..).peekable();
+
+        match lines.next() {
+            Some(("poki", _)) => {}
+            _ => return Err(PokiTextDeserializationError::InvalidMagic),
+        }
+
+        let mut poki = Self::new_empty();
+        for (segment_index, segment) in poki.segments.iter_mut().enumerate() {
+            let prefix = format!("seg{segment_index} ");
+
+            let (line, line_number) = lines
+                .next()
+                .ok_or(PokiTextDeserializationError::MissingSegment(segment_index))?;
+            let (contents_size, relocation_table_size, export_table_size) = line
+                .strip_prefix(&prefix)
+                .and_then(parse_segment_header)
+                .ok_or(PokiTextDeserializationError::InvalidLine(line_number))?;
+
+            // Every line which belongs to this segment begins with its prefix, so we consume lines
+            // until we find one which doesn't, and only then check that the counts in the header
+            // were right.
+            while let Some(&(line, line_number)) = lines.peek() {
+                let Some(line) = line.strip_prefix(&prefix) else {
+                    break;
+                };
+                lines.next();
+
+                if let Some(entry) = line.strip_prefix("reloc ") {
+                    segment.relocation_table.push(
+                        parse_relocation_table_entry(entry)
+                            .ok_or(PokiTextDeserializationError::InvalidLine(line_number))?,
+                    );
+                } else if let Some(entry) = line.strip_prefix("export ") {
+                    segment.export_table.push(
+                        parse_export_table_entry(entry)
+                            .ok_or(PokiTextDeserializationError::InvalidLine(line_number))?,
+                    );
+                } else {
+                    // NOTE: The offset is only there for the benefit of the reader, but since the
+                    // words must appear in order, an offset which doesn't match its position means
+                    // that the file has been edited incorrectly.
+                    let word = parse_word(line)
+                        .filter(|(offset, _)| usize::from(*offset) == segment.contents.len())
+                        .ok_or(PokiTextDeserializationError::InvalidLine(line_number))?;
+                    segment.contents.push(word.1);
+                }
+            }
+
+            if segment.contents.len() != contents_size
+                || segment.relocation_table.len() != relocation_table_size
+                || segment.export_table.len() != export_table_size
+            {
+                return Err(PokiTextDeserializationError::SizeMismatch(segment_index));
+            }
+        }
+
+        for (line, line_number) in lines {
+            let symbol = line
+                .strip_prefix("unresolved ")
+                .and_then(parse_string)
+                .ok_or(PokiTextDeserializationError::InvalidLine(line_number))?;
+            poki.unresolved_table.push(symbol);
+        }
+
+        Ok(poki)
+    }
+}
+
+fn parse_number(text: &str) -> Option<u16> {
+    u16::from_str_radix(text.strip_prefix("0x")?, 16).ok()
+}
+
+// Parse the remainder of a segment header after its prefix, as in `words 3 relocations 1 exports 1`.
+fn parse_segment_header(text: &str) -> Option<(usize, usize, usize)> {
+    let mut words = text.split(' ');
+    let mut field = |name| {
+        if words.next()? != name {
+            return None;
+        }
+        words.next()?.parse().ok()
+    };
+
+    let sizes = (field("words")?, field("relocations")?, field("exports")?);
+    words.next().is_none().then_some(sizes)
+}
+
+// Parse a word and the offset at which it appears, as in `0x0000: 0x0028`.
+fn parse_word(text: &str) -> Option<(u16, u16)> {
+    let (offset, word) = text.split_once(": ")?;
+    Some((parse_number(offset)?, parse_number(word)?))
+}
+
+// Parse the remainder of a relocation line after `reloc`, as in `0x0001 -> seg5 0x0002` or
+// `0x0001 -> unresolved 0x0000`.
+fn parse_relocation_table_entry(text: &str) -> Option<RelocationTableEntry> {
+    let (offset, target) = text.split_once(" -> ")?;
+    let (segment_index, segment_offset) = target.split_once(' ')?;
+
+    let segment_index = match segment_index {
+        "unresolved" => 0xFFFF,
+        segment_index => segment_index.strip_prefix("seg")?.parse().ok()?,
+    };
+
+    Some(RelocationTableEntry {
+        offset: parse_number(offset)?,
+        segment_index,
+        segment_offset: parse_number(segment_offset)?,
+    })
+}
+
+// Parse the remainder of an export line after `export`, as in `0x0000 "_start"`.
+fn parse_export_table_entry(text: &str) -> Option<ExportTableEntry> {
+    let (offset, label) = text.split_once(' ')?;
+    Some(ExportTableEntry {
+        label: parse_string(label)?,
+        offset: parse_number(offset)?,
+    })
+}
+
+// Parse a quoted string, undoing the escapes which `{:?}` produces.
+fn parse_string(text: &str) -> Option<String> {
+    let text = text.strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut string = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            return None;
+        }
+        if c != '\\' {
+            string.push(c);
+            continue;
+        }
+
+        string.push(match chars.next()? {
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            'u' => {
+                let code = chars.as_str().strip_prefix('{')?;
+                let (code, rest) = code.split_once('}')?;
+                chars = rest.chars();
+                char::from_u32(u32::from_str_radix(code, 16).ok()?)?
+            }
+            _ => return None,
+        });
+    }
+
+    Some(string)
+}
+
+#[derive(Debug)]
+pub enum PokiTextDeserializationError {
+    IOError(io::Error),
+    InvalidMagic,
+    MissingSegment(usize),
+    InvalidLine(usize),
+    SizeMismatch(usize),
+}
+
+impl Display for PokiTextDeserializationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::IOError(e) => write!(f, "{}", e),
+            Self::InvalidMagic => write!(f, "expected file to begin with the line \"poki\""),
+            Self::MissingSegment(s) => write!(f, "file ends before the header of segment {s}"),
+            Self::InvalidLine(n) => write!(f, "unable to make sense of line {n}"),
+            Self::SizeMismatch(s) => write!(
+                f,
+                "the header of segment {s} does not match the number of words, relocations, and exports which follow it"
+            ),
+        }
+    }
+}
+
+impl Error for PokiTextDeserializationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        if let Self::IOError(e) = self {
+            Some(e)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<io::Error> for PokiTextDeserializationError {
+    fn from(value: io::Error) -> Self {
+        Self::IOError(value)
+    }
+}
@@ -0,0 +1,39 @@
+use poki::{Poki, Segment};
+
+fn segment(contents: &[u16]) -> Segment {
+    Segment {
+        contents: contents.to_vec(),
+        relocation_table: Vec::new(),
+        export_table: Vec::new(),
+    }
+}
+
+#[test]
+fn overlapping_occurrences_are_all_found() {
+    let segment = segment(&[7, 7, 7, 1, 7, 7]);
+
+    assert_eq!(segment.find_sequence(&[7, 7]), [0, 1, 4]);
+    assert_eq!(segment.find_sequence(&[7, 1, 7]), [2]);
+}
+
+#[test]
+fn empty_and_overlong_patterns_are_never_found() {
+    let segment = segment(&[1, 2]);
+
+    assert_eq!(segment.find_sequence(&[]), []);
+    assert_eq!(segment.find_sequence(&[1, 2, 3]), []);
+}
+
+#[test]
+fn sequences_straddling_the_end_of_a_segment_are_not_found() {
+    let mut poki = Poki::new_empty();
+    poki.segments[5] = segment(&[1, 2, 3]);
+    poki.segments[6] = segment(&[4, 5, 3]);
+
+    // NOTE: The contents of the segments are only laid out one after another once loaded, if at
+    // all, so a sequence beginning at the end of one segment doesn't continue into the next.
+    assert_eq!(poki.segments[5].find_sequence(&[3, 4]), []);
+    assert_eq!(poki.find_sequence_in_all_segments(&[3, 4]), []);
+    assert_eq!(poki.find_sequence_in_all_segments(&[2, 3]), [(5, 1)]);
+    assert_eq!(poki.find_sequence_in_all_segments(&[3]), [(5, 2), (6, 2)]);
+}
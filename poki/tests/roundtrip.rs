@@ -0,0 +1,239 @@
+// NOTE: Every test here serializes or deserializes, which requires the `std` feature.
+#![cfg(feature = "std")]
+
+use poki::{
+    ExportTableEntry, Poki, PokiDeserializationError, RelocationTableEntry, RenameError, Segment,
+};
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+// Labels are generated from arbitrary printable characters, including those outside of the basic
+// multilingual plane, which occupy two words each once encoded.
+fn label() -> impl Strategy<Value = String> {
+    "\\PC{0,32}"
+}
+
+fn relocation_table_entry() -> impl Strategy<Value = RelocationTableEntry> {
+    (any::<u16>(), any::<u16>(), any::<u16>()).prop_map(
+        |(offset, segment_index, segment_offset)| RelocationTableEntry {
+            offset,
+            segment_index,
+            segment_offset,
+        },
+    )
+}
+
+fn export_table_entry() -> impl Strategy<Value = ExportTableEntry> {
+    (label(), any::<u16>()).prop_map(|(label, offset)| ExportTableEntry { label, offset })
+}
+
+// NOTE: The sizes here are kept well below the limits imposed by the format, so that every
+// generated poki can be serialized, and so that each case remains quick to run. The limits
+// themselves are exercised separately below.
+fn segment() -> impl Strategy<Value = Segment> {
+    (
+        vec(any::<u16>(), 0..64),
+        vec(relocation_table_entry(), 0..16),
+        vec(export_table_entry(), 0..8),
+    )
+        .prop_map(|(contents, relocation_table, export_table)| Segment {
+            contents,
+            relocation_table,
+            export_table,
+        })
+}
+
+fn poki() -> impl Strategy<Value = Poki> {
+    (
+        [
+            segment(),
+            segment(),
+            segment(),
+            segment(),
+            segment(),
+            segment(),
+            segment(),
+            segment(),
+        ],
+        vec(label(), 0..8),
+    )
+        .prop_map(|(segments, unresolved_table)| Poki {
+            segments,
+            unresolved_table,
+        })
+}
+
+fn serialize(poki: &Poki) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    poki.serialize(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), poki.serialized_size());
+    bytes
+}
+
+proptest! {
+    #[test]
+    fn deserialize_inverts_serialize(poki in poki()) {
+        let bytes = serialize(&poki);
+        prop_assert_eq!(Poki::deserialize(&mut bytes.as_slice()).unwrap(), poki);
+    }
+
+    #[test]
+    fn from_slice_inverts_serialize(poki in poki()) {
+        let bytes = serialize(&poki);
+        prop_assert_eq!(Poki::from_slice(&bytes).unwrap(), poki);
+    }
+
+    #[test]
+    fn deserialize_text_inverts_serialize_text(poki in poki()) {
+        let mut text = Vec::new();
+        poki.serialize_text(&mut text).unwrap();
+        prop_assert_eq!(Poki::deserialize_text(&mut text.as_slice()).unwrap(), poki);
+    }
+
+    #[test]
+    fn normalize_preserves_unresolved_references(poki in poki()) {
+        // Find the label to which each relocation refers, for those which refer to unresolved
+        // symbols, in a canonical order.
+        fn unresolved_references(poki: &Poki) -> Vec<(usize, u16, Option<String>)> {
+            let mut references = Vec::new();
+            for (segment_index, segment) in poki.segments.iter().enumerate() {
+                for relocation_table_entry in &segment.relocation_table {
+                    if relocation_table_entry.segment_index == 0xFFFF {
+                        references.push((
+                            segment_index,
+                            relocation_table_entry.offset,
+                            poki.unresolved_table
+                                .get(usize::from(relocation_table_entry.segment_offset))
+                                .cloned(),
+                        ));
+                    }
+                }
+            }
+            references.sort();
+            references
+        }
+
+        let mut normalized = poki.clone();
+        normalized.normalize();
+        prop_assert!(normalized.unresolved_table.is_sorted());
+        prop_assert_eq!(unresolved_references(&normalized), unresolved_references(&poki));
+
+        let mut renormalized = normalized.clone();
+        renormalized.normalize();
+        prop_assert_eq!(renormalized, normalized);
+    }
+
+    #[test]
+    fn deserialize_does_not_panic(bytes in vec(any::<u8>(), 0..512)) {
+        let _ = Poki::deserialize(&mut bytes.as_slice());
+        let _ = Poki::from_slice(&bytes);
+    }
+}
+
+#[test]
+fn roundtrip_at_limits() {
+    let mut poki = Poki::new_empty();
+    poki.segments[0].contents = vec![0xFFFF; usize::from(u16::MAX)];
+    poki.segments[1].relocation_table = vec![
+        RelocationTableEntry {
+            offset: 0xFFFF,
+            segment_index: 0xFFFF,
+            segment_offset: 0xFFFF,
+        };
+        usize::from(u16::MAX) / 3
+    ];
+    // An export table entry occupies two words besides its label, and the export table as a whole
+    // may be at most `u16::MAX` words long.
+    poki.segments[2].export_table = vec![ExportTableEntry {
+        label: "a".repeat(usize::from(u16::MAX) - 2),
+        offset: 0xFFFF,
+    }];
+    poki.unresolved_table = vec![String::new(), "b".repeat(usize::from(u16::MAX))];
+
+    let bytes = serialize(&poki);
+    assert_eq!(Poki::deserialize(&mut bytes.as_slice()).unwrap(), poki);
+    assert_eq!(Poki::from_slice(&bytes).unwrap(), poki);
+}
+
+#[test]
+fn oversized_export_label_does_not_panic() {
+    let mut poki = Poki::new_empty();
+    poki.segments[0].export_table = vec![ExportTableEntry {
+        label: String::new(),
+        offset: 0,
+    }];
+    let mut bytes = serialize(&poki);
+
+    // Claim that the export table is `u16::MAX` words long, and that its only label is as well.
+    let export_table_size = 2 * 4 + 2 * 2;
+    let label_size = 2 * 4 + 2 * 3 * 8;
+    bytes[export_table_size..export_table_size + 2].copy_from_slice(&u16::MAX.to_ne_bytes());
+    bytes[label_size..label_size + 2].copy_from_slice(&u16::MAX.to_ne_bytes());
+
+    assert!(matches!(
+        Poki::deserialize(&mut bytes.as_slice()),
+        Err(PokiDeserializationError::StringOverrun(_))
+    ));
+    assert!(matches!(
+        Poki::from_slice(&bytes),
+        Err(PokiDeserializationError::StringOverrun(_))
+    ));
+}
+
+#[test]
+fn relocation_table_size_must_be_a_multiple_of_three() {
+    let mut bytes = serialize(&Poki::new_empty());
+
+    // Claim that the relocation table of the first segment is a single word long.
+    let relocation_table_size = 2 * 4 + 2;
+    bytes[relocation_table_size..relocation_table_size + 2].copy_from_slice(&1u16.to_ne_bytes());
+    bytes.extend([0; 2]);
+
+    assert!(matches!(
+        Poki::deserialize(&mut bytes.as_slice()),
+        Err(PokiDeserializationError::InvalidRelocationTableSize(1))
+    ));
+    assert!(matches!(
+        Poki::from_slice(&bytes),
+        Err(PokiDeserializationError::InvalidRelocationTableSize(1))
+    ));
+}
+
+#[test]
+fn rename_export_survives_roundtrip() {
+    let mut poki = Poki::new_empty();
+    poki.segments[5].export_table = vec![
+        ExportTableEntry {
+            label: "init".to_string(),
+            offset: 0x0010,
+        },
+        ExportTableEntry {
+            label: "main".to_string(),
+            offset: 0x0000,
+        },
+    ];
+    poki.segments[6].export_table = vec![ExportTableEntry {
+        label: "buffer".to_string(),
+        offset: 0x0004,
+    }];
+    // NOTE: The unresolved table names a label imported from elsewhere, which merely shares its name
+    // with the export, and so mustn't be renamed along with it.
+    poki.unresolved_table = vec!["init".to_string()];
+
+    assert_eq!(
+        poki.rename_export("missing", "uart.missing"),
+        Err(RenameError::NotFound("missing".to_string()))
+    );
+    assert_eq!(
+        poki.rename_export("init", "buffer"),
+        Err(RenameError::Duplicate("buffer".to_string()))
+    );
+    poki.rename_export("init", "uart.init").unwrap();
+
+    let poki = Poki::from_slice(&serialize(&poki)).unwrap();
+    assert_eq!(poki.find_export("init"), None);
+    assert_eq!(poki.find_export("uart.init"), Some((5, 0x0010)));
+    assert_eq!(poki.find_export("main"), Some((5, 0x0000)));
+    assert_eq!(poki.find_export("buffer"), Some((6, 0x0004)));
+    assert_eq!(poki.unresolved_table, ["init"]);
+}
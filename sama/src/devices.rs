@@ -0,0 +1,4 @@
+//! devices which may be connected to an emulator's peripheral bus
+
+pub mod framebuffer;
+pub mod storage;
@@ -0,0 +1,78 @@
+use crate::emulator::Device;
+
+/// the width of the display, in pixels
+pub const WIDTH: usize = 64;
+/// the height of the display, in pixels
+pub const HEIGHT: usize = 32;
+
+/// a pixel display, which programs draw to one pixel at a time
+///
+/// the pixels are numbered row by row from the top left, and the device keeps a cursor holding
+/// the number of the pixel which is drawn to next. outputting a value in context 0 draws it to
+/// the pixel at the cursor, as a 16-bit colour with 5 bits of red, 6 bits of green, and 5 bits of
+/// blue, from the most significant bit down, and then advances the cursor to the next pixel, so
+/// that consecutive pixels may be drawn without moving the cursor each time. outputting a value
+/// in context 1 or 2 sets the high or low byte of the cursor respectively to the low byte of the
+/// value, and inputting from context 0 returns the cursor
+///
+/// the cursor wraps around to the first pixel after the last, and any cursor beyond the last
+/// pixel is taken modulo the number of pixels
+pub struct FramebufferDevice {
+    pixels: [[u8; 3]; WIDTH * HEIGHT],
+    cursor: u16,
+}
+
+impl Default for FramebufferDevice {
+    fn default() -> Self {
+        Self {
+            pixels: [[0; 3]; WIDTH * HEIGHT],
+            cursor: 0,
+        }
+    }
+}
+
+impl FramebufferDevice {
+    /// the colour of the pixel at column `x' and row `y', as red, green, and blue bytes
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        self.pixels[y * WIDTH + x]
+    }
+}
+
+/// expand a 16-bit colour, with 5 bits of red, 6 bits of green, and 5 bits of blue, into red,
+/// green, and blue bytes
+fn expand(value: u16) -> [u8; 3] {
+    let red = (value >> 11) & 0b11111;
+    let green = (value >> 5) & 0b111111;
+    let blue = value & 0b11111;
+
+    // NOTE: each channel is scaled so that its largest value becomes 0xFF
+    [
+        (red * 0xFF / 0b11111) as u8,
+        (green * 0xFF / 0b111111) as u8,
+        (blue * 0xFF / 0b11111) as u8,
+    ]
+}
+
+impl Device for FramebufferDevice {
+    fn input(&mut self, context: u8) -> u16 {
+        match context {
+            0 => self.cursor,
+            _ => 0,
+        }
+    }
+
+    fn output(&mut self, context: u8, value: u16) {
+        let [_, low_byte] = value.to_be_bytes();
+        let [cursor_high, cursor_low] = self.cursor.to_be_bytes();
+        match context {
+            0 => {
+                let index = usize::from(self.cursor) % self.pixels.len();
+                self.pixels[index] = expand(value);
+                self.cursor = ((index + 1) % self.pixels.len()) as u16;
+            }
+            1 => self.cursor = u16::from_be_bytes([low_byte, cursor_low]),
+            2 => self.cursor = u16::from_be_bytes([cursor_high, low_byte]),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::emulator::{Device, Ram};
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// the number of words in each block
+pub const BLOCK_WORDS: u16 = 512;
+
+/// the status of a block storage device, as returned by inputting from context 0
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+enum Status {
+    #[default]
+    Idle = 0,
+    /// NOTE: transfers are made synchronously, within the step which requests them, so a program
+    /// never observes the device while it's busy. the status exists so that programs may be
+    /// written for devices which aren't synchronous
+    #[allow(dead_code)]
+    Busy = 1,
+    /// the last transfer failed, such as because the host file couldn't be read or written
+    Error = 2,
+}
+
+/// the transfers which a block storage device may be asked to make
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+enum Transfer {
+    Read,
+    Write,
+}
+
+/// a block storage device, backed by a file on the host
+///
+/// the storage is divided into blocks of `BLOCK_WORDS' words, which are stored in the file one
+/// after another, each word little-endian. the device copies whole blocks directly between the
+/// file and ram, without going through the cpu. outputting a value in context 0 or 1 sets the high
+/// or low byte respectively of the address in ram to which blocks are copied to and from to the
+/// low byte of the value, and outputting a value in context 2 selects the block to be copied.
+/// outputting 0 in context 3 then copies the selected block from the file into ram, while
+/// outputting 1 copies it from ram into the file. inputting from context 0 returns the status of
+/// the device, which is 0 while it is idle, and 2 if the last transfer failed
+///
+/// blocks beyond the end of the file read as zeroes, and writing to them extends the file. the
+/// addresses in ram wrap around from 0xffff to 0x0000
+pub struct BlockStorageDevice {
+    file: File,
+    address: u16,
+    block: u16,
+    status: Status,
+    pending_transfer: Option<Transfer>,
+}
+
+impl BlockStorageDevice {
+    /// open the file at `path' as the storage of a new device, creating it if it doesn't exist
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        Ok(Self {
+            file,
+            address: 0,
+            block: 0,
+            status: Status::Idle,
+            pending_transfer: None,
+        })
+    }
+
+    fn read_block(&mut self, ram: &mut Ram) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(usize::from(BLOCK_WORDS) * 2);
+        self.file.seek(SeekFrom::Start(self.block_offset()))?;
+        (&mut self.file)
+            .take(u64::from(BLOCK_WORDS) * 2)
+            .read_to_end(&mut bytes)?;
+        bytes.resize(usize::from(BLOCK_WORDS) * 2, 0);
+
+        for (offset, word) in (0..BLOCK_WORDS).zip(bytes.chunks_exact(2)) {
+            ram[self.address.wrapping_add(offset)] = u16::from_le_bytes([word[0], word[1]]);
+        }
+        Ok(())
+    }
+
+    fn write_block(&mut self, ram: &Ram) -> io::Result<()> {
+        let bytes: Vec<u8> = (0..BLOCK_WORDS)
+            .flat_map(|offset| ram[self.address.wrapping_add(offset)].to_le_bytes())
+            .collect();
+
+        self.file.seek(SeekFrom::Start(self.block_offset()))?;
+        self.file.write_all(&bytes)?;
+        self.file.flush()
+    }
+
+    /// the offset in bytes of the selected block within the file
+    fn block_offset(&self) -> u64 {
+        u64::from(self.block) * u64::from(BLOCK_WORDS) * 2
+    }
+}
+
+impl Device for BlockStorageDevice {
+    fn input(&mut self, context: u8) -> u16 {
+        match context {
+            0 => self.status as u16,
+            _ => 0,
+        }
+    }
+
+    fn output(&mut self, context: u8, value: u16) {
+        let [_, low_byte] = value.to_be_bytes();
+        let [address_high, address_low] = self.address.to_be_bytes();
+        match context {
+            0 => self.address = u16::from_be_bytes([low_byte, address_low]),
+            1 => self.address = u16::from_be_bytes([address_high, low_byte]),
+            2 => self.block = value,
+            3 => {
+                self.pending_transfer = match value {
+                    0 => Some(Transfer::Read),
+                    1 => Some(Transfer::Write),
+                    _ => None,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn access_ram(&mut self, ram: &mut Ram) {
+        let result = match self.pending_transfer.take() {
+            Some(Transfer::Read) => self.read_block(ram),
+            Some(Transfer::Write) => self.write_block(ram),
+            None => return,
+        };
+        self.status = match result {
+            Ok(()) => Status::Idle,
+            Err(_) => Status::Error,
+        };
+    }
+}
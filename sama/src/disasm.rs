@@ -0,0 +1,175 @@
+use crate::emulator::{arithmetic, Emulator};
+
+use std::collections::BTreeMap;
+
+/// the mnemonic of each opcode, indexed by the opcode itself. opcodes which are not defined by
+/// lawa's specification have no mnemonic
+const MNEMONICS: [Option<&str>; 64] = {
+    let mut mnemonics = [None; 64];
+    mnemonics[0b000000] = Some("add");
+    mnemonics[0b000001] = Some("sub");
+    mnemonics[0b000010] = Some("and");
+    mnemonics[0b000011] = Some("or");
+    mnemonics[0b000100] = Some("xor");
+    mnemonics[0b000101] = Some("sll");
+    mnemonics[0b000110] = Some("srl");
+    mnemonics[0b000111] = Some("sra");
+    mnemonics[0b001000] = Some("addi");
+    mnemonics[0b001010] = Some("andi");
+    mnemonics[0b001011] = Some("ori");
+    mnemonics[0b001100] = Some("xori");
+    mnemonics[0b001101] = Some("slli");
+    mnemonics[0b001111] = Some("srai");
+    mnemonics[0b010000] = Some("ld");
+    mnemonics[0b010001] = Some("st");
+    mnemonics[0b010010] = Some("dei");
+    mnemonics[0b010011] = Some("deo");
+    mnemonics[0b010100] = Some("rcsr");
+    mnemonics[0b010101] = Some("wcsr");
+    mnemonics[0b010110] = Some("swpr");
+    mnemonics[0b010111] = Some("wfi");
+    mnemonics[0b011000] = Some("ldio");
+    mnemonics[0b011001] = Some("stio");
+    mnemonics[0b101000] = Some("jal");
+    mnemonics[0b101001] = Some("jsh");
+    mnemonics[0b101010] = Some("beq");
+    mnemonics[0b101011] = Some("bne");
+    mnemonics[0b101100] = Some("blt");
+    mnemonics[0b101101] = Some("bge");
+    mnemonics[0b101110] = Some("bltu");
+    mnemonics[0b101111] = Some("bgeu");
+    mnemonics
+};
+
+/// the names of the control/status registers, indexed by their numbers
+const CONTROL_STATUS_REGISTERS: [&str; 32] = [
+    "im0", "im1", "im2", "im3", "im4", "im5", "im6", "im7", "im8", "im9", "im10", "im11", "im12",
+    "im13", "im14", "im15", "iv", "ipc", "ic", "cycle", "instret", "counterh", "mpc0", "mpc1",
+    "mpa0", "mpa1", "mpa2", "mpa3", "mpa4", "mpa5", "mpa6", "mpa7",
+];
+
+const RCSR: u16 = 0b010100;
+const WCSR: u16 = 0b010101;
+const WFI: u16 = 0b010111;
+const JSH: u16 = 0b101001;
+
+/// an instruction, decoded from the words at some address in ram
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: u16,
+    pub dst: u16,
+    pub src: u16,
+    /// the word following the instruction, if the instruction takes an immediate
+    pub imm: Option<u16>,
+}
+
+impl Instruction {
+    /// decode the instruction `word', which is followed in ram by `next_word', returning `None'
+    /// if its opcode is not defined by lawa's specification
+    pub fn decode(word: u16, next_word: u16) -> Option<Self> {
+        let opcode = word & 0b0000000000111111;
+        MNEMONICS[usize::from(opcode)]?;
+
+        // NOTE: jsh packs its immediate into the instruction word itself, and so is the one
+        // instruction whose fourth opcode bit is set which doesn't take an immediate
+        let takes_imm = (opcode & 0b001000) != 0 && opcode != JSH;
+        Some(Self {
+            opcode,
+            dst: (word & 0b0000011111000000) >> 6,
+            src: (word & 0b1111100000000000) >> 11,
+            imm: takes_imm.then_some(next_word),
+        })
+    }
+
+    /// decode the instruction located at `address' in the ram of `emulator'
+    pub fn decode_at(emulator: &Emulator, address: u16) -> Option<Self> {
+        Self::decode(emulator.ram[address], emulator.ram[address.wrapping_add(1)])
+    }
+
+    /// the number of words which the instruction occupies
+    pub fn size(&self) -> u16 {
+        if self.imm.is_some() {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        // NOTE: an instruction is only ever decoded if its opcode has a mnemonic
+        MNEMONICS[usize::from(self.opcode)].unwrap_or_default()
+    }
+
+    /// the general-purpose registers whose values the instruction reads
+    pub fn registers_read(&self) -> Vec<u16> {
+        match self.opcode {
+            RCSR | WFI | JSH => Vec::new(),
+            WCSR => vec![self.src],
+            // loads, dei, jal, and the arithmetic and logical instructions which take an
+            // immediate only read their source register
+            0b001000..=0b001111 | 0b010000 | 0b010010 | 0b011000 | 0b101000 => vec![self.src],
+            _ => vec![self.dst, self.src],
+        }
+    }
+
+    /// the value which the instruction writes to its destination register, given the current
+    /// values of the registers of `emulator', or `None' if it isn't an arithmetic or logical
+    /// instruction
+    pub fn result(&self, emulator: &Emulator) -> Option<u16> {
+        arithmetic(
+            self.opcode,
+            emulator.registers[self.dst],
+            emulator.registers[self.src],
+            self.imm.unwrap_or_default(),
+        )
+    }
+
+    /// render the instruction as pali would assemble it, as in `(addi r3 r1 0x0010)', with any
+    /// immediate which is the address of one of `symbols' replaced by the symbol's label
+    pub fn render(&self, symbols: &BTreeMap<u16, String>) -> String {
+        let mnemonic = self.mnemonic();
+        match self.opcode {
+            WFI => format!("({mnemonic})"),
+            JSH => format!("({mnemonic} {:#05x})", (self.src << 5) | self.dst),
+            RCSR => format!(
+                "({mnemonic} r{} {})",
+                self.dst,
+                CONTROL_STATUS_REGISTERS[usize::from(self.src)]
+            ),
+            WCSR => format!(
+                "({mnemonic} {} r{})",
+                CONTROL_STATUS_REGISTERS[usize::from(self.dst)],
+                self.src
+            ),
+            _ => match self.imm {
+                Some(imm) => match symbols.get(&imm) {
+                    Some(label) => format!("({mnemonic} r{} r{} {label})", self.dst, self.src),
+                    None => format!("({mnemonic} r{} r{} {imm:#06x})", self.dst, self.src),
+                },
+                None => format!("({mnemonic} r{} r{})", self.dst, self.src),
+            },
+        }
+    }
+}
+
+/// the address of the instruction following the one at `address' in the ram of `emulator'. words
+/// which can't be decoded are treated as occupying a single word
+pub fn next_address(emulator: &Emulator, address: u16) -> u16 {
+    let size =
+        Instruction::decode_at(emulator, address).map_or(1, |instruction| instruction.size());
+    address.wrapping_add(size)
+}
+
+/// the address of the instruction preceding the one at `address' in the ram of `emulator'
+///
+/// NOTE: instructions are of different sizes, so there's no way of knowing for certain where the
+/// previous instruction begins. if the word two before `address' decodes to an instruction which
+/// takes an immediate, it's assumed that the word immediately before `address' is that
+/// immediate, and otherwise that the previous instruction occupies a single word
+pub fn previous_address(emulator: &Emulator, address: u16) -> u16 {
+    let candidate = address.wrapping_sub(2);
+    match Instruction::decode_at(emulator, candidate) {
+        Some(instruction) if instruction.size() == 2 => candidate,
+        _ => address.wrapping_sub(1),
+    }
+}
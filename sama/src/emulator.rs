@@ -1,4 +1,10 @@
+use poki::Poki;
+
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::default::Default;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::ops::{Index, IndexMut};
 
 // TODO: once an actual spec has been written for lawa, include it in the doc comment here
@@ -11,8 +17,40 @@ pub struct Emulator {
     pub control_status_registers: ControlStatusRegisters,
     pub devices: Devices,
     pub ram: Ram,
+
+    /// the most recent writes to each general-purpose register, oldest first, as the cycle during
+    /// which each write happened and the value written. at most `REGISTER_HISTORY_LENGTH' writes
+    /// are kept for each register, and writes to r0 are never recorded
+    pub register_history: [VecDeque<(u64, u16)>; 32],
+
+    /// the addresses at which `run_for' stops before executing the instruction located there
+    pub breakpoints: BTreeSet<u16>,
+    /// `true' iff the emulator has executed a `wfi' instruction, and has not since been interrupted
+    pub waiting_for_interrupt: bool,
+
+    /// the ranges of addresses at which devices are mapped into memory
+    pub mmio_regions: Vec<MmioRegion>,
+
+    /// the labels exported by the poki files which have been loaded into ram, by the addresses at
+    /// which they were loaded
+    pub symbols: BTreeMap<u16, String>,
+
+    /// `true' iff the emulator has encountered an error from which it can't recover, in which
+    /// case it refuses to execute any further instructions until it is reset
+    pub poisoned: bool,
+
+    /// the function called by `step' before each instruction is executed
+    pre_step_hook: Option<StepHook>,
+    /// the function called by `step' after each instruction is executed
+    post_step_hook: Option<StepHook>,
 }
 
+/// a function which observes the state of the emulator as it steps, such as to trace execution
+pub type StepHook = Box<dyn Fn(&Emulator)>;
+
+/// the number of writes to each general-purpose register which are kept in `register_history'
+pub const REGISTER_HISTORY_LENGTH: usize = 64;
+
 impl Default for Emulator {
     fn default() -> Self {
         Self {
@@ -23,6 +61,17 @@ impl Default for Emulator {
             control_status_registers: ControlStatusRegisters::default(),
             devices: Devices::default(),
             ram: Ram::default(),
+
+            register_history: [const { VecDeque::new() }; 32],
+
+            breakpoints: BTreeSet::new(),
+            waiting_for_interrupt: false,
+            mmio_regions: Vec::new(),
+            symbols: BTreeMap::new(),
+            poisoned: false,
+
+            pre_step_hook: None,
+            post_step_hook: None,
         }
     }
 }
@@ -55,10 +104,13 @@ impl IndexMut<u16> for Registers {
 
 /// the control/status registers contained within a lawa cpu
 ///
-/// lawa formally has 32 control/status registers, but only 29 of them currently have specified
-/// uses, with the remaining 3 reserved for potential future specified uses. it is undefined
-/// behaviour to read from or write to any of the control/status registers which do not currently
-/// have a specified use.
+/// lawa formally has 32 control/status registers. 29 of them simply hold a value, while the
+/// remaining 3 give access to a pair of 64-bit counters, which count the cycles which have passed
+/// and the instructions which have been executed. reading `cycle' (0b10011) or `instret' (0b10100)
+/// returns the low 16 bits of the corresponding counter, and latches the next 16 bits into
+/// `counterh' (0b10101), so that both halves are read from the same moment even if the counter
+/// carries between the reads. writing to `cycle' or `instret' resets the corresponding counter to
+/// zero, whatever the value written
 #[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct ControlStatusRegisters {
     pub im: [u16; 16],
@@ -67,37 +119,94 @@ pub struct ControlStatusRegisters {
     pub ic: u16,
     pub mpc: [u16; 2],
     pub mpa: [u16; 8],
+
+    /// the number of cycles which have passed, including those spent waiting for an interrupt
+    pub cycle_counter: u64,
+    /// the number of instructions which have been executed
+    pub instruction_counter: u64,
+    /// the bits of a counter latched by the last read of `cycle' or `instret'
+    pub counterh: u16,
+}
+
+impl ControlStatusRegisters {
+    /// read the control/status register at `index' as an instruction would, latching the high
+    /// bits of a counter if it is one, or return `None' if that control/status register is
+    /// currently undefined
+    pub fn read(&mut self, index: u16) -> Option<u16> {
+        let counter = match index {
+            0b10011 => self.cycle_counter,
+            0b10100 => self.instruction_counter,
+            _ => return self.get(index).copied(),
+        };
+        self.counterh = (counter >> 16) as u16;
+        Some(counter as u16)
+    }
+
+    /// write `value' to the control/status register at `index' as an instruction would, resetting
+    /// a counter if it is one, or return `None' if that control/status register is currently
+    /// undefined
+    pub fn write(&mut self, index: u16, value: u16) -> Option<()> {
+        match index {
+            0b10011 => self.cycle_counter = 0,
+            0b10100 => self.instruction_counter = 0,
+            _ => *self.get_mut(index)? = value,
+        }
+        Some(())
+    }
+
+    /// return the value of the control/status register at `index' without latching anything, or
+    /// `None' if that control/status register is currently undefined
+    pub fn peek(&self, index: u16) -> Option<u16> {
+        match index {
+            0b10011 => Some(self.cycle_counter as u16),
+            0b10100 => Some(self.instruction_counter as u16),
+            _ => self.get(index).copied(),
+        }
+    }
+
+    /// return a reference to the control/status register at `index', or `None' if that
+    /// control/status register is currently undefined, or is a counter, which has no value of its
+    /// own to refer to
+    pub fn get(&self, index: u16) -> Option<&u16> {
+        match index {
+            0b00000..=0b01111 => Some(&self.im[usize::from(index)]),
+            0b10000 => Some(&self.iv),
+            0b10001 => Some(&self.ipc),
+            0b10010 => Some(&self.ic),
+            0b10101 => Some(&self.counterh),
+            0b10110..=0b10111 => Some(&self.mpc[usize::from(index & 0b00001)]),
+            0b11000..=0b11111 => Some(&self.mpa[usize::from(index & 0b00111)]),
+            _ => None,
+        }
+    }
+
+    /// return a mutable reference to the control/status register at `index', or `None' if that
+    /// control/status register is currently undefined, or is a counter
+    pub fn get_mut(&mut self, index: u16) -> Option<&mut u16> {
+        match index {
+            0b00000..=0b01111 => Some(&mut self.im[usize::from(index)]),
+            0b10000 => Some(&mut self.iv),
+            0b10001 => Some(&mut self.ipc),
+            0b10010 => Some(&mut self.ic),
+            0b10101 => Some(&mut self.counterh),
+            0b10110..=0b10111 => Some(&mut self.mpc[usize::from(index & 0b00001)]),
+            0b11000..=0b11111 => Some(&mut self.mpa[usize::from(index & 0b00111)]),
+            _ => None,
+        }
+    }
 }
 
 impl Index<u16> for ControlStatusRegisters {
     type Output = u16;
 
     fn index(&self, index: u16) -> &Self::Output {
-        match index {
-            0b00000..=0b01111 => &self.im[usize::from(index)],
-            0b10000 => &self.iv,
-            0b10001 => &self.ipc,
-            0b10010 => &self.ic,
-            0b10011..=0b10101 => panic!("control/status register {index} is currently undefined, and is reserved for potential future usage"),
-            0b10110..=0b10111 => &self.mpc[usize::from(index & 0b00001)],
-            0b11000..=0b11111 => &self.mpa[usize::from(index & 0b00111)],
-            _ => unreachable!(),
-        }
+        self.get(index).unwrap_or_else(|| panic!("control/status register {index} is currently undefined, and is reserved for potential future usage"))
     }
 }
 
 impl IndexMut<u16> for ControlStatusRegisters {
     fn index_mut(&mut self, index: u16) -> &mut Self::Output {
-        match index {
-            0b00000..=0b01111 => &mut self.im[usize::from(index)],
-            0b10000 => &mut self.iv,
-            0b10001 => &mut self.ipc,
-            0b10010 => &mut self.ic,
-            0b10011..=0b10101 => panic!("control/status register {index} is currently undefined, and is reserved for potential future usage"),
-            0b10110..=0b10111 => &mut self.mpc[usize::from(index & 0b00001)],
-            0b11000..=0b11111 => &mut self.mpa[usize::from(index & 0b00111)],
-            _ => unreachable!(),
-        }
+        self.get_mut(index).unwrap_or_else(|| panic!("control/status register {index} is currently undefined, and is reserved for potential future usage"))
     }
 }
 
@@ -138,16 +247,71 @@ impl Default for Devices {
     }
 }
 
+impl Devices {
+    /// the device attached at `index', or `None' if there is no device attached there, or if
+    /// `index' is the reserved device index 0
+    pub fn get(&self, index: u8) -> Option<&dyn Device> {
+        match index {
+            0 => None,
+            _ => self.0[usize::from(index)].as_deref(),
+        }
+    }
+
+    /// the device attached at `index', or `None' if there is no device attached there, or if
+    /// `index' is the reserved device index 0
+    pub fn get_mut(&mut self, index: u8) -> Option<&mut Box<dyn Device>> {
+        match index {
+            0 => None,
+            _ => self.0[usize::from(index)].as_mut(),
+        }
+    }
+
+    /// the connected device of type `D' with the lowest index, if any, such as for displaying its
+    /// state
+    pub fn find<D: Device>(&self) -> Option<&D> {
+        self.0
+            .iter()
+            .flatten()
+            .find_map(|device| (device.as_ref() as &dyn Any).downcast_ref())
+    }
+}
+
 /// a device which may be connected to an emulator's peripheral bus
 ///
 /// # volatility
 ///
 /// this interface is currently volatile, and will change in the future. in particular, it does not
 /// provide a means for devices to update their internal state except for when they are polled by
-/// the cpu, nor does it provice a means for devices to trigger hardware interrupts
-pub trait Device {
+/// the cpu
+pub trait Device: Any {
     fn input(&mut self, context: u8) -> u16;
     fn output(&mut self, context: u8, value: u16);
+
+    /// called with the ram of the emulator after each output to the device, so that the device
+    /// may copy data directly to and from ram, as is done for dma
+    fn access_ram(&mut self, _ram: &mut Ram) {}
+
+    /// `true' iff the device is requesting a hardware interrupt
+    ///
+    /// this is asked before each step, for as long as the device is enabled by the interrupt masks,
+    /// and the interrupt is taken whenever the emulator is able to take it. a device should go on
+    /// requesting the interrupt until software acknowledges it, such as by polling the device,
+    /// lest the interrupt be taken again as soon as the handler returns
+    fn interrupt_pending(&self) -> bool {
+        false
+    }
+}
+
+/// a range of addresses at which a device is mapped into memory
+///
+/// loads from and stores to addresses in `start..end' are redirected to the device at
+/// `device_index', rather than to ram, with the offset of the address from `start' used as the
+/// device context
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct MmioRegion {
+    pub start: u16,
+    pub end: u16,
+    pub device_index: u8,
 }
 
 /// the ram used by the emulator
@@ -180,20 +344,203 @@ impl Default for Ram {
 }
 
 impl Emulator {
+    /// restore the emulator to the state in which it was created, leaving the devices connected to
+    /// its peripheral bus, the regions at which they are mapped into memory, and its breakpoints in
+    /// place
+    ///
+    /// this zeroes the registers, the control/status registers, and the ram, returns the program
+    /// counter to 0x0000, and puts the emulator back in privileged mode
+    pub fn reset(&mut self) {
+        let devices = std::mem::take(&mut self.devices);
+        let mmio_regions = std::mem::take(&mut self.mmio_regions);
+        let breakpoints = std::mem::take(&mut self.breakpoints);
+        let pre_step_hook = self.pre_step_hook.take();
+        let post_step_hook = self.post_step_hook.take();
+        *self = Self {
+            devices,
+            mmio_regions,
+            breakpoints,
+            pre_step_hook,
+            post_step_hook,
+            ..Self::default()
+        };
+    }
+
+    /// restore the emulator to the state in which it was created, additionally disconnecting all
+    /// of the devices connected to its peripheral bus, unmapping them from memory, and clearing
+    /// its breakpoints and step hooks
+    pub fn hard_reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// write `value' to the general-purpose register `index', recording the write in
+    /// `register_history'
+    ///
+    /// writes to the registers should always be made through this function, rather than through
+    /// `registers' directly, so that none of them go unrecorded. as writes to r0 are ignored,
+    /// they are not recorded
+    pub fn write_register(&mut self, index: u16, value: u16) {
+        self.registers[index] = value;
+        if index == 0 {
+            return;
+        }
+
+        let history = &mut self.register_history[usize::from(index)];
+        if history.len() == REGISTER_HISTORY_LENGTH {
+            history.pop_front();
+        }
+        history.push_back((self.control_status_registers.cycle_counter, value));
+    }
+
+    /// copy the segments of `poki' into ram, placing each segment at the corresponding address in
+    /// `segment_bases', and patch the words referred to by its relocations accordingly
+    ///
+    /// once loaded, the program counter is pointed at the label `_start' if any segment exports
+    /// it, and otherwise at the beginning of segment 0. relocations which refer to labels which
+    /// are not defined in `poki' can't be patched, and so are left as they are, with a warning
+    /// describing each of them returned
+    pub fn load_poki(&mut self, poki: &Poki, segment_bases: &[u16; 8]) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        for (segment, &base) in poki.segments.iter().zip(segment_bases) {
+            for (offset, &word) in (0..=u16::MAX).zip(&segment.contents) {
+                self.ram[base.wrapping_add(offset)] = word;
+            }
+        }
+
+        for (segment_index, (segment, &base)) in poki.segments.iter().zip(segment_bases).enumerate()
+        {
+            for relocation_table_entry in &segment.relocation_table {
+                let address = base.wrapping_add(relocation_table_entry.offset);
+                match segment_bases.get(usize::from(relocation_table_entry.segment_index)) {
+                    Some(target_base) => {
+                        self.ram[address] = self.ram[address]
+                            .wrapping_add(*target_base)
+                            .wrapping_add(relocation_table_entry.segment_offset);
+                    }
+                    None if relocation_table_entry.segment_index == 0xFFFF => {
+                        warnings.push(format!(
+                            "the word at {address:#06x} (segment {segment_index}, offset {:#06x}) refers to the unresolved label {}, and was left unpatched",
+                            relocation_table_entry.offset,
+                            poki.unresolved_table
+                                .get(usize::from(relocation_table_entry.segment_offset))
+                                .map_or("<unknown>", |label| label.as_str()),
+                        ));
+                    }
+                    None => {
+                        warnings.push(format!(
+                            "the word at {address:#06x} (segment {segment_index}, offset {:#06x}) refers to the nonexistent segment {}, and was left unpatched",
+                            relocation_table_entry.offset, relocation_table_entry.segment_index,
+                        ));
+                    }
+                }
+            }
+        }
+
+        for (segment_index, export_table_entry) in poki.find_all_exports() {
+            self.symbols.insert(
+                segment_bases[segment_index].wrapping_add(export_table_entry.offset),
+                export_table_entry.label.clone(),
+            );
+        }
+
+        self.program_counter = poki
+            .find_export("_start")
+            .map(|(segment_index, offset)| segment_bases[segment_index].wrapping_add(offset))
+            .unwrap_or(segment_bases[0]);
+
+        warnings
+    }
+
+    /// map the device at `device_index' into memory at the addresses in `start..end'
+    ///
+    /// since the offset of an address into the region is passed to the device as its context,
+    /// which is a single byte, a region may be at most 256 words long. regions may not overlap
+    pub fn register_mmio(
+        &mut self,
+        start: u16,
+        end: u16,
+        device_index: u8,
+    ) -> Result<(), MmioError> {
+        if device_index == 0 {
+            return Err(MmioError::ReservedDevice);
+        }
+        if start >= end || end - start > 0x100 {
+            return Err(MmioError::InvalidRange(start, end));
+        }
+        if let Some(region) = self
+            .mmio_regions
+            .iter()
+            .find(|region| start < region.end && region.start < end)
+        {
+            return Err(MmioError::Overlap(*region));
+        }
+
+        self.mmio_regions.push(MmioRegion {
+            start,
+            end,
+            device_index,
+        });
+        Ok(())
+    }
+
+    /// return the region at which a device is mapped into memory containing `address', if any
+    fn mmio_region(&self, address: u16) -> Option<MmioRegion> {
+        self.mmio_regions
+            .iter()
+            .find(|region| (region.start..region.end).contains(&address))
+            .copied()
+    }
+
+    /// read the word at `address', either from ram or from a device mapped into memory there
+    fn load(&mut self, address: u16) -> u16 {
+        match self.mmio_region(address) {
+            // NOTE: as with `dei', reading from a device which isn't connected is undefined
+            // behaviour. rather than crashing, we simply read 0
+            Some(region) => self.devices[region.device_index]
+                .as_mut()
+                .map_or(0, |device| device.input((address - region.start) as u8)),
+            None => self.ram[address],
+        }
+    }
+
+    /// write `value' to `address', either in ram or to a device mapped into memory there
+    fn store(&mut self, address: u16, value: u16) {
+        match self.mmio_region(address) {
+            Some(region) => {
+                if let Some(device) = self.devices[region.device_index].as_mut() {
+                    device.output((address - region.start) as u8, value);
+                    device.access_ram(&mut self.ram);
+                }
+            }
+            None => self.ram[address] = value,
+        }
+    }
+
     /// return `true' iff the emulator, in its current state, has read permissions at `address'
-    fn readable(&self, index: u16) -> bool {
+    fn readable(&self, address: u16) -> bool {
+        // devices mapped into memory are always accessible
+        if self.mmio_region(address).is_some() {
+            return true;
+        }
+
         // TODO
         true
     }
 
     /// return `true' iff the emulator, in its current state, has write permissions at `address'
-    fn writable(&self, index: u16) -> bool {
+    fn writable(&self, address: u16) -> bool {
+        // devices mapped into memory are always accessible
+        if self.mmio_region(address).is_some() {
+            return true;
+        }
+
         // TODO
         true
     }
 
     /// return `true' iff the emulator, in its current state, has execute permissions at `address'
-    fn executable(&self, index: u16) -> bool {
+    fn executable(&self, _address: u16) -> bool {
         // TODO
         true
     }
@@ -213,17 +560,104 @@ impl Emulator {
         self.control_status_registers.ic = u16::from_le_bytes([0x00, context]);
         self.program_counter = self.control_status_registers.iv;
         self.privileged = true;
+        self.waiting_for_interrupt = false;
+    }
+
+    /// set up the state of the emulator to reflect the device at `device_index' having triggered
+    /// an interrupt, before the instruction at the program counter is executed
+    ///
+    /// unlike a software-triggered interrupt, which leaves the low byte of the interrupt context
+    /// zeroed, a hardware interrupt sets it to the index of the device which triggered it
+    fn hardware_interrupt(&mut self, device_index: u8) {
+        self.control_status_registers.ipc = self.program_counter;
+        self.control_status_registers.ic = u16::from_le_bytes([device_index, 0x00]);
+        self.program_counter = self.control_status_registers.iv;
+        self.privileged = true;
+        self.waiting_for_interrupt = false;
+    }
+
+    /// `true' iff the interrupt masks enable the device at `device_index' to trigger interrupts
+    ///
+    /// the 16 interrupt masks hold one bit for each device index, beginning from the least
+    /// significant bit of `im0'
+    pub fn interrupt_enabled(&self, device_index: u8) -> bool {
+        let mask = self.control_status_registers.im[usize::from(device_index / 16)];
+        mask & (1 << (device_index % 16)) != 0
+    }
+
+    /// `true' iff the interrupt masks enable any device to trigger interrupts, without which an
+    /// emulator waiting for an interrupt can never stop waiting
+    pub fn interrupts_enabled(&self) -> bool {
+        self.control_status_registers
+            .im
+            .iter()
+            .any(|&mask| mask != 0)
+    }
+
+    /// the lowest index of a device which is requesting an interrupt that the interrupt masks
+    /// enable, if any
+    fn pending_interrupt(&self) -> Option<u8> {
+        (1..=u8::MAX).find(|&device_index| {
+            self.interrupt_enabled(device_index)
+                && self
+                    .devices
+                    .get(device_index)
+                    .is_some_and(|device| device.interrupt_pending())
+        })
     }
 
     /// executes the instruction located at the address currently in the program counter
     ///
-    /// # panics
+    /// # errors
     ///
-    /// calling this function will panic if executing the instruction which the program counter is
-    /// pointing to depends on any behaviour which is not defined by lawa's specification. in the
-    /// future, this will probably change to something more lenient, with this function instead
-    /// returning some kind of error, and marking the emulator as `poisoned'.
-    pub fn step(&mut self) {
+    /// if executing the instruction which the program counter is pointing to depends on any
+    /// behaviour which is not defined by lawa's specification, an error is returned, and the
+    /// emulator is marked as `poisoned'. once poisoned, the emulator refuses to execute any
+    /// further instructions, returning `EmulatorError::EmulatorPoisoned' instead, until it is
+    /// reset
+    ///
+    /// before the instruction is executed, an interrupt requested by a device is taken if the
+    /// interrupt masks enable it and the emulator is either in user mode or waiting for an
+    /// interrupt. while the emulator is waiting for an interrupt which isn't taken, this function
+    /// does nothing besides calling the step hooks
+    pub fn step(&mut self) -> Result<(), EmulatorError> {
+        if self.poisoned {
+            return Err(EmulatorError::EmulatorPoisoned);
+        }
+
+        if let Some(hook) = &self.pre_step_hook {
+            hook(self);
+        }
+
+        // NOTE: an interrupt handler runs in privileged mode, so hardware interrupts aren't taken
+        // there, lest they interrupt the handler itself, unless privileged code is waiting for one
+        if !self.privileged || self.waiting_for_interrupt {
+            if let Some(device_index) = self.pending_interrupt() {
+                self.hardware_interrupt(device_index);
+            }
+        }
+
+        // NOTE: cycles pass while waiting, but no instructions are executed
+        let counters = &mut self.control_status_registers;
+        counters.cycle_counter = counters.cycle_counter.wrapping_add(1);
+        if !self.waiting_for_interrupt {
+            counters.instruction_counter = counters.instruction_counter.wrapping_add(1);
+
+            if let Err(e) = self.execute() {
+                self.poisoned = true;
+                return Err(e);
+            }
+        }
+
+        if let Some(hook) = &self.post_step_hook {
+            hook(self);
+        }
+        Ok(())
+    }
+
+    /// executes the instruction located at the address currently in the program counter, without
+    /// regard for whether or not the emulator is poisoned
+    fn execute(&mut self) -> Result<(), EmulatorError> {
         // first, just pull apart the instruction into its parts, and grab all of the values that
         // will be useful to the various instructions
         let instr = self.ram[self.program_counter];
@@ -240,13 +674,13 @@ impl Emulator {
         // required
         if !self.executable(self.program_counter) {
             self.interrupt(0b00000001, if takes_imm { 2 } else { 1 });
-            return;
+            return Ok(());
         }
 
         let imm = if takes_imm {
             if !self.executable(self.program_counter.wrapping_add(1)) {
                 self.interrupt(0b00000001, 2);
-                return;
+                return Ok(());
             }
 
             self.ram[self.program_counter.wrapping_add(1)]
@@ -257,240 +691,191 @@ impl Emulator {
         };
 
         match opc {
-            0b000000 => {
-                // add
-                self.registers[dst_idx] = dst.wrapping_add(src);
-            }
-            0b000001 => {
-                // sub
-                self.registers[dst_idx] = dst.wrapping_sub(src);
-            }
-            0b000010 => {
-                // and
-                self.registers[dst_idx] &= src;
-            }
-            0b000011 => {
-                // or
-                self.registers[dst_idx] |= src;
-            }
-            0b000100 => {
-                // xor
-                self.registers[dst_idx] ^= src;
-            }
-            0b000101 => {
-                // sll
-                if (src as i16).is_positive() {
-                    self.registers[dst_idx] <<= src
-                } else {
-                    self.registers[dst_idx] >>= src.wrapping_neg()
-                }
-            }
-            0b000110 => {
-                // srl
-                if (src as i16).is_positive() {
-                    self.registers[dst_idx] >>= src
-                } else {
-                    self.registers[dst_idx] <<= src.wrapping_neg()
-                }
-            }
-            0b000111 => {
-                // sra
-                if (src as i16).is_positive() {
-                    self.registers[dst_idx] = ((dst as i16) >> src) as u16;
-                } else {
-                    self.registers[dst_idx] <<= src.wrapping_neg()
-                }
-            }
-            0b001000 => {
-                // addi
-                self.registers[dst_idx] = src.wrapping_add(imm);
-            }
-
-            0b001010 => {
-                // andi
-                self.registers[dst_idx] = src & imm;
-            }
-            0b001011 => {
-                // ori
-                self.registers[dst_idx] = src | imm;
-            }
-            0b001100 => {
-                // xori
-                self.registers[dst_idx] = src ^ imm;
-            }
-            0b001101 => {
-                // slli
-                self.registers[dst_idx] = if (imm as i16).is_positive() {
-                    src << imm
-                } else {
-                    src >> imm.wrapping_neg()
-                }
-            }
-
-            0b001111 => {
-                // srai
-                self.registers[dst_idx] = if (imm as i16).is_positive() {
-                    ((src as i16) >> imm) as u16
-                } else {
-                    src << imm
-                }
+            0b000000..=0b000111 | 0b001000 | 0b001010..=0b001101 | 0b001111 => {
+                // add, sub, and, or, xor, sll, srl, sra, addi, andi, ori, xori, slli, srai
+                //
+                // NOTE: the opcodes matched here are exactly those for which `arithmetic' returns
+                // a result
+                self.write_register(dst_idx, arithmetic(opc, dst, src, imm).unwrap_or_default());
             }
             0b010000 => {
                 // ld
                 if !self.readable(src) {
                     self.interrupt(0b00000100, 1);
-                    return;
+                    return Ok(());
                 }
 
-                self.registers[dst_idx] = self.ram[src];
+                let value = self.load(src);
+                self.write_register(dst_idx, value);
             }
             0b010001 => {
                 // st
                 if !self.writable(src) {
                     self.interrupt(0b00000010, 1);
-                    return;
+                    return Ok(());
                 }
 
-                self.ram[src] = dst;
+                self.store(src, dst);
             }
             0b010010 => {
                 // dei
                 if !self.privileged {
                     self.interrupt(0b00001100, 1);
-                    return;
+                    return Ok(());
                 }
 
                 let device_index = src.to_be_bytes()[0];
                 let device_context = src.to_be_bytes()[1];
 
                 // NOTE: attempting to read input from a device index at which there is no device
-                // attached to the device bus is undefined behaviour. in a hardware implementation,
-                // this is likely to simply return garbage
-                let device = self.devices[device_index].as_mut().expect("attempted to read intput from device at index {device_index}, but no such device exists");
-                self.registers[dst_idx] = device.input(device_context);
+                // attached to the device bus, or from the reserved device index 0, is undefined
+                // behaviour. in a hardware implementation, this is likely to simply return garbage
+                let device = self
+                    .devices
+                    .get_mut(device_index)
+                    .ok_or(EmulatorError::UndefinedDevice(device_index))?;
+                let value = device.input(device_context);
+                self.write_register(dst_idx, value);
             }
             0b010011 => {
                 // deo
                 if !self.privileged {
                     self.interrupt(0b00001010, 1);
-                    return;
+                    return Ok(());
                 }
 
                 let device_index = src.to_be_bytes()[0];
                 let device_context = src.to_be_bytes()[1];
-                let device = &mut self.devices[device_index];
-                if let Some(ref mut device) = device {
+
+                // NOTE: output to a device index at which there is no device attached is simply
+                // lost, but output to the reserved device index 0 is undefined behaviour
+                if device_index == 0 {
+                    return Err(EmulatorError::UndefinedDevice(device_index));
+                }
+                if let Some(device) = self.devices.get_mut(device_index) {
                     device.output(device_context, dst);
+                    device.access_ram(&mut self.ram);
                 }
             }
             0b010100 => {
                 // rcsr
                 if !self.privileged {
                     self.interrupt(0b00010100, 1);
-                    return;
+                    return Ok(());
                 }
 
-                self.registers[dst_idx] = self.control_status_registers[src_idx];
+                let value = self
+                    .control_status_registers
+                    .read(src_idx)
+                    .ok_or(EmulatorError::UndefinedCSR(src_idx))?;
+                self.write_register(dst_idx, value);
             }
             0b010101 => {
                 // wcsr
                 if !self.privileged {
                     self.interrupt(0b00010010, 1);
-                    return;
+                    return Ok(());
                 }
 
-                self.control_status_registers[dst_idx] = src;
+                self.control_status_registers
+                    .write(dst_idx, src)
+                    .ok_or(EmulatorError::UndefinedCSR(dst_idx))?;
             }
             0b010110 => {
                 // swpr
                 if self.privileged {
                     self.program_counter = self.control_status_registers.ipc;
                     self.privileged = false;
-                    return;
+                    return Ok(());
                 } else {
                     self.interrupt(0b00000000, 1);
-                    return;
+                    return Ok(());
                 }
             }
+            0b010111 => {
+                // wfi
+                self.waiting_for_interrupt = true;
+            }
 
             0b011000 => {
                 // ldio
                 if !self.readable(src.wrapping_add(imm)) {
                     self.interrupt(0b00000100, 2);
-                    return;
+                    return Ok(());
                 }
 
-                self.registers[dst_idx] = self.ram[src.wrapping_add(imm)];
+                let value = self.load(src.wrapping_add(imm));
+                self.write_register(dst_idx, value);
             }
             0b011001 => {
                 // stio
                 if !self.writable(src.wrapping_add(imm)) {
                     self.interrupt(0b00000010, 2);
-                    return;
+                    return Ok(());
                 }
 
-                self.ram[src.wrapping_add(imm)] = self.registers[dst_idx];
+                self.store(src.wrapping_add(imm), self.registers[dst_idx]);
             }
 
             0b101000 => {
                 // jal
-                self.registers[dst_idx] = self.program_counter.wrapping_add(2);
+                self.write_register(dst_idx, self.program_counter.wrapping_add(2));
                 self.program_counter = src.wrapping_add(imm);
-                return;
+                return Ok(());
             }
             0b101001 => {
                 // jlo
                 let imm = (((instr & 0b1111111111000000) as i16) >> 6) as u16;
                 self.program_counter = self.program_counter.wrapping_add(imm);
-                return;
+                return Ok(());
             }
             0b101010 => {
                 // beq
                 if dst == src {
                     self.program_counter = imm;
-                    return;
+                    return Ok(());
                 }
             }
             0b101011 => {
                 // bne
                 if dst != src {
                     self.program_counter = imm;
-                    return;
+                    return Ok(());
                 }
             }
             0b101100 => {
                 // blt
                 if (dst as i16) < (src as i16) {
                     self.program_counter = imm;
-                    return;
+                    return Ok(());
                 }
             }
             0b101101 => {
                 // bge
                 if (dst as i16) >= (src as i16) {
                     self.program_counter = imm;
-                    return;
+                    return Ok(());
                 }
             }
             0b101110 => {
                 // bltu
                 if dst < src {
                     self.program_counter = imm;
-                    return;
+                    return Ok(());
                 }
             }
             0b101111 => {
                 // bgeu
                 if dst >= src {
                     self.program_counter = imm;
-                    return;
+                    return Ok(());
                 }
             }
             _ => {
                 // NOTE: in this implementation, attempting to execute an instruction with an
-                // undefined opcode causes a panic. this is undefined behaviour, so a
+                // undefined opcode poisons the emulator. this is undefined behaviour, so a
                 // specification-compliant implementation could do anything here
-                panic!("opcode {opc} is currently undefined, and is reserved for potential future usage")
+                return Err(EmulatorError::UndefinedOpcode(opc));
             }
         }
 
@@ -500,5 +885,284 @@ impl Emulator {
         self.program_counter = self
             .program_counter
             .wrapping_add(if takes_imm { 2 } else { 1 });
+
+        Ok(())
+    }
+
+    /// set the function called by `step' before each instruction is executed, replacing any which
+    /// was set before. the hook is kept when the emulator is reset, but not when it is hard reset
+    // NOTE: nothing within sama sets hooks from rust, since hooks set from lua can't be called
+    // while the emulator is borrowed, and so are called by the lua bindings instead
+    pub fn set_pre_step_hook<F: Fn(&Emulator) + 'static>(&mut self, hook: F) {
+        self.pre_step_hook = Some(Box::new(hook));
+    }
+
+    /// set the function called by `step' after each instruction is executed successfully,
+    /// replacing any which was set before. the hook is kept when the emulator is reset, but not
+    /// when it is hard reset
+    pub fn set_post_step_hook<F: Fn(&Emulator) + 'static>(&mut self, hook: F) {
+        self.post_step_hook = Some(Box::new(hook));
+    }
+
+    /// execute up to `n_cycles' instructions, returning the number of instructions which were
+    /// actually executed, along with why execution stopped
+    ///
+    /// execution stops early if the program counter reaches one of the emulator's breakpoints. a
+    /// breakpoint at the address in the program counter when this function is called does not stop
+    /// execution, so that calling this function again after stopping at a breakpoint resumes
+    /// execution. cycles pass as usual while the emulator is waiting for an interrupt, in case a
+    /// device interrupts it, but execution stops early if the interrupt masks enable no device to
+    /// do so, and a run which ends while the emulator is still waiting reports as much, rather than
+    /// reporting that it ran out of cycles
+    ///
+    /// # errors
+    ///
+    /// this function returns an error, stopping execution, under the same circumstances as `step'
+    pub fn run_for(&mut self, n_cycles: u64) -> Result<(u64, RunResult), EmulatorError> {
+        self.run(|_| false, n_cycles)
+    }
+
+    /// execute up to `max_cycles' instructions, stopping once the program counter is `target'
+    /// after an instruction is executed, and returning why execution stopped
+    ///
+    /// this makes for a one-off breakpoint at `target', although since the program counter is only
+    /// compared with `target' after each instruction, at least one instruction is always executed.
+    /// execution also stops early under the same circumstances as `run_for'
+    ///
+    /// # errors
+    ///
+    /// this function returns an error, stopping execution, under the same circumstances as `step'
+    pub fn run_until_pc(
+        &mut self,
+        target: u16,
+        max_cycles: u64,
+    ) -> Result<RunResult, EmulatorError> {
+        self.run(|emulator| emulator.program_counter == target, max_cycles)
+            .map(|(_, result)| result)
+    }
+
+    /// execute up to `max_cycles' instructions, stopping once `condition' returns `true' after
+    /// an instruction is executed, and returning why execution stopped
+    ///
+    /// `condition' is only borrowed for the duration of the run, so it may freely refer to local
+    /// state, as in `run_until(|emulator| emulator.registers[1] == expected, 1000)'. execution
+    /// also stops early under the same circumstances as `run_for'
+    ///
+    /// # errors
+    ///
+    /// this function returns an error, stopping execution, under the same circumstances as `step'
+    pub fn run_until<F: Fn(&Emulator) -> bool>(
+        &mut self,
+        condition: F,
+        max_cycles: u64,
+    ) -> Result<RunResult, EmulatorError> {
+        self.run(condition, max_cycles).map(|(_, result)| result)
+    }
+
+    /// execute up to `max_cycles' instructions, stopping once `condition' returns `true' after an
+    /// instruction is executed, and returning the number of instructions which were actually
+    /// executed, along with why execution stopped
+    ///
+    /// execution also stops early under the same circumstances as `run_for'
+    fn run<F: Fn(&Emulator) -> bool>(
+        &mut self,
+        condition: F,
+        max_cycles: u64,
+    ) -> Result<(u64, RunResult), EmulatorError> {
+        for cycle in 0..max_cycles {
+            if let Some(result) = self.stops_before(cycle) {
+                return Ok((cycle, result));
+            }
+
+            self.step()?;
+
+            if condition(self) {
+                return Ok((cycle + 1, RunResult::ReachedTarget));
+            }
+        }
+
+        Ok((max_cycles, self.exhausted()))
+    }
+
+    /// the reason for which a run which has already executed `cycle' instructions stops before
+    /// executing another, as described for `run_for', or `None' if it doesn't stop
+    pub fn stops_before(&self, cycle: u64) -> Option<RunResult> {
+        if cycle != 0 && self.breakpoints.contains(&self.program_counter) {
+            return Some(RunResult::Breakpoint(self.program_counter));
+        }
+
+        if self.waiting_for_interrupt && !self.interrupts_enabled() {
+            return Some(RunResult::WaitingForInterrupt);
+        }
+
+        None
+    }
+
+    /// the reason for which a run stopped, given that it executed as many instructions as it was
+    /// allowed to
+    pub fn exhausted(&self) -> RunResult {
+        if self.waiting_for_interrupt {
+            RunResult::WaitingForInterrupt
+        } else {
+            RunResult::MaxCyclesReached
+        }
+    }
+}
+
+/// the reason for which a run of the emulator stopped
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum RunResult {
+    /// the run reached the address or condition at which it was asked to stop
+    ReachedTarget,
+    /// the run executed as many instructions as it was allowed to
+    MaxCyclesReached,
+    /// the run stopped before executing the instruction at a breakpoint, at the given address
+    Breakpoint(u16),
+    /// the run stopped while the emulator was waiting for an interrupt
+    WaitingForInterrupt,
+}
+
+impl RunResult {
+    /// the name of the variant, without any of its fields
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ReachedTarget => "ReachedTarget",
+            Self::MaxCyclesReached => "MaxCyclesReached",
+            Self::Breakpoint(_) => "Breakpoint",
+            Self::WaitingForInterrupt => "WaitingForInterrupt",
+        }
     }
 }
+
+/// the result of the arithmetic or logical instruction with opcode `opcode', given the values of
+/// its destination and source registers and its immediate, or `None' if `opcode' is not that of an
+/// arithmetic or logical instruction
+pub fn arithmetic(opcode: u16, dst: u16, src: u16, imm: u16) -> Option<u16> {
+    let result = match opcode {
+        // add
+        0b000000 => dst.wrapping_add(src),
+        // sub
+        0b000001 => dst.wrapping_sub(src),
+        // and
+        0b000010 => dst & src,
+        // or
+        0b000011 => dst | src,
+        // xor
+        0b000100 => dst ^ src,
+        // sll
+        0b000101 => {
+            if (src as i16).is_positive() {
+                dst << src
+            } else {
+                dst >> src.wrapping_neg()
+            }
+        }
+        // srl
+        0b000110 => {
+            if (src as i16).is_positive() {
+                dst >> src
+            } else {
+                dst << src.wrapping_neg()
+            }
+        }
+        // sra
+        0b000111 => {
+            if (src as i16).is_positive() {
+                ((dst as i16) >> src) as u16
+            } else {
+                dst << src.wrapping_neg()
+            }
+        }
+        // addi
+        0b001000 => src.wrapping_add(imm),
+        // andi
+        0b001010 => src & imm,
+        // ori
+        0b001011 => src | imm,
+        // xori
+        0b001100 => src ^ imm,
+        // slli
+        0b001101 => {
+            if (imm as i16).is_positive() {
+                src << imm
+            } else {
+                src >> imm.wrapping_neg()
+            }
+        }
+        // srai
+        0b001111 => {
+            if (imm as i16).is_positive() {
+                ((src as i16) >> imm) as u16
+            } else {
+                src << imm
+            }
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// an error which occurs when the emulator attempts to execute an instruction whose behaviour is
+/// not defined by lawa's specification
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum EmulatorError {
+    UndefinedOpcode(u16),
+    UndefinedCSR(u16),
+    UndefinedDevice(u8),
+    EmulatorPoisoned,
+}
+
+impl Display for EmulatorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UndefinedOpcode(opc) => write!(
+                f,
+                "opcode {opc} is currently undefined, and is reserved for potential future usage"
+            ),
+            Self::UndefinedCSR(index) => write!(
+                f,
+                "control/status register {index} is currently undefined, and is reserved for potential future usage"
+            ),
+            Self::UndefinedDevice(index) => write!(
+                f,
+                "there is no device at device index {index}, so it can't be accessed"
+            ),
+            Self::EmulatorPoisoned => write!(
+                f,
+                "the emulator has previously encountered undefined behaviour, and must be reset before it can execute any further instructions"
+            ),
+        }
+    }
+}
+
+impl Error for EmulatorError {}
+
+/// an error which occurs when a device can't be mapped into memory at the requested addresses
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum MmioError {
+    ReservedDevice,
+    InvalidRange(u16, u16),
+    Overlap(MmioRegion),
+}
+
+impl Display for MmioError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReservedDevice => write!(
+                f,
+                "device index 0 is reserved, and can't be mapped into memory"
+            ),
+            Self::InvalidRange(start, end) => write!(
+                f,
+                "{start:#06x}..{end:#06x} is not a valid region, as regions must be non-empty and at most 256 words long"
+            ),
+            Self::Overlap(region) => write!(
+                f,
+                "region overlaps with the region {:#06x}..{:#06x}, at which device {} is already mapped",
+                region.start, region.end, region.device_index
+            ),
+        }
+    }
+}
+
+impl Error for MmioError {}
@@ -0,0 +1,391 @@
+use sama::emulator::{Emulator, RunResult};
+
+use std::fmt::Write as _;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+/// the number of instructions executed each time the stub is polled while the debugger has asked
+/// the emulator to continue
+const CYCLES_PER_POLL: u64 = 0x1000;
+
+/// a minimal gdb remote serial protocol server, through which a debugger may examine and control
+/// the emulator
+///
+/// gdb addresses memory in bytes, while lawa addresses memory in 16-bit words, so the byte at
+/// address `a' is taken to be the low byte of the word at address `a / 2' if `a' is even, or its
+/// high byte if `a' is odd. registers and words are sent little-endian
+///
+/// # polling
+///
+/// the emulator is shared with lua, which can't be done across threads, so rather than running in
+/// a thread of its own, the stub does a bounded amount of work whenever it is polled, and so must
+/// be polled regularly. while the debugger has asked the emulator to continue, each poll executes
+/// up to `CYCLES_PER_POLL' instructions
+pub struct GdbStub {
+    listener: TcpListener,
+    connection: Option<Connection>,
+}
+
+/// a connection to a debugger
+struct Connection {
+    stream: TcpStream,
+    /// bytes which have been received, but which don't yet form a complete packet
+    buffer: Vec<u8>,
+    /// `true' iff the debugger has asked the emulator to continue, and it has not yet stopped
+    running: bool,
+}
+
+impl GdbStub {
+    /// begin listening for a debugger on `port'
+    pub fn listen(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            connection: None,
+        })
+    }
+
+    /// accept a connection from a debugger if one is waiting, respond to any packets which it has
+    /// sent, and execute instructions if it has asked the emulator to continue
+    ///
+    /// should anything go wrong with the connection, it is dropped, and the stub waits for a new
+    /// one
+    pub fn poll(&mut self, emulator: &mut Emulator) {
+        if self.connection.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(true).is_ok() {
+                        self.connection = Some(Connection {
+                            stream,
+                            buffer: Vec::new(),
+                            running: false,
+                        });
+                    }
+                }
+                Err(_) => return,
+            }
+        }
+
+        if let Some(connection) = &mut self.connection {
+            if connection.poll(emulator).is_err() {
+                self.connection = None;
+            }
+        }
+    }
+}
+
+impl Connection {
+    fn poll(&mut self, emulator: &mut Emulator) -> io::Result<()> {
+        let mut bytes = [0; 0x1000];
+        loop {
+            match self.stream.read(&mut bytes) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.buffer.extend_from_slice(&bytes[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        while let Some(packet) = next_packet(&mut self.buffer) {
+            match packet {
+                // NOTE: the debugger interrupts the emulator by sending a lone 0x03 byte, outside
+                // of any packet
+                Packet::Interrupt => {
+                    if self.running {
+                        self.running = false;
+                        self.send("S02")?;
+                    }
+                }
+                Packet::Command(command) => {
+                    self.write(b"+")?;
+                    if let Some(response) = self.respond(&command, emulator) {
+                        self.send(&response)?;
+                    }
+                }
+            }
+        }
+
+        if self.running {
+            let stop_reason = match emulator.run_for(CYCLES_PER_POLL) {
+                Ok((_, RunResult::Breakpoint(_))) => Some("S05"),
+                // NOTE: the emulator keeps running while it waits for an interrupt, unless no
+                // device is able to interrupt it
+                Ok((_, RunResult::WaitingForInterrupt)) if !emulator.interrupts_enabled() => {
+                    Some("S05")
+                }
+                Ok(_) => None,
+                Err(_) => Some("S04"),
+            };
+            if let Some(stop_reason) = stop_reason {
+                self.running = false;
+                self.send(stop_reason)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let checksum = data.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        self.write(format!("${data}#{checksum:02x}").as_bytes())
+    }
+
+    /// write all of `bytes' to the debugger
+    ///
+    /// the stream is only nonblocking so that polling it for packets doesn't block, so it is made
+    /// blocking while writing, lest a full socket buffer cut a packet short
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.stream.set_nonblocking(false)?;
+        let result = self.stream.write_all(bytes);
+        self.stream.set_nonblocking(true)?;
+        result
+    }
+
+    /// carry out `command', returning the response which should be sent to the debugger, if any
+    fn respond(&mut self, command: &str, emulator: &mut Emulator) -> Option<String> {
+        let mut chars = command.chars();
+        let kind = chars.next();
+        let arguments = chars.as_str();
+
+        let response = match kind {
+            Some('?') => "S05".to_string(),
+            Some('g') => {
+                let mut response = String::new();
+                for index in 0..32 {
+                    push_word(&mut response, emulator.registers[index]);
+                }
+                // NOTE: the program counter is a word address, but gdb expects a byte address
+                push_word(&mut response, emulator.program_counter.wrapping_mul(2));
+                response
+            }
+            Some('G') => match parse_words(arguments) {
+                Some(words) if words.len() == 33 => {
+                    for (index, word) in (1..32).zip(&words[1..32]) {
+                        emulator.write_register(index, *word);
+                    }
+                    emulator.program_counter = words[32] / 2;
+                    "OK".to_string()
+                }
+                _ => "E01".to_string(),
+            },
+            Some('m') => match parse_range(arguments) {
+                Some((address, length)) => {
+                    let mut response = String::new();
+                    for offset in 0..length {
+                        let address = address.wrapping_add(offset);
+                        write!(response, "{:02x}", read_byte(emulator, address)).unwrap();
+                    }
+                    response
+                }
+                None => "E01".to_string(),
+            },
+            Some('M') => {
+                let data = arguments
+                    .split_once(':')
+                    .and_then(|(range, data)| Some((parse_range(range)?, parse_bytes(data)?)));
+                match data {
+                    Some(((address, length), bytes)) if bytes.len() == length as usize => {
+                        for (offset, byte) in (0..).zip(bytes) {
+                            write_byte(emulator, address.wrapping_add(offset), byte);
+                        }
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                }
+            }
+            Some('s') => match emulator.step() {
+                Ok(()) => "S05".to_string(),
+                Err(_) => "S04".to_string(),
+            },
+            Some('c') => {
+                // NOTE: the response is only sent once the emulator stops
+                self.running = true;
+                return None;
+            }
+            Some('Z' | 'z') => match parse_breakpoint(arguments) {
+                Some(address) => {
+                    if kind == Some('Z') {
+                        emulator.breakpoints.insert(address);
+                    } else {
+                        emulator.breakpoints.remove(&address);
+                    }
+                    "OK".to_string()
+                }
+                // NOTE: an empty response tells the debugger that this kind of breakpoint is not
+                // supported, so that it falls back to something else
+                None => String::new(),
+            },
+            Some('D') => "OK".to_string(),
+            _ => String::new(),
+        };
+
+        Some(response)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Packet {
+    Command(String),
+    Interrupt,
+}
+
+/// remove the next complete packet from `buffer', skipping over acknowledgements
+fn next_packet(buffer: &mut Vec<u8>) -> Option<Packet> {
+    loop {
+        match buffer.first()? {
+            b'$' => {
+                // NOTE: the checksum is not verified, since tcp already guarantees that the packet
+                // arrived intact
+                let end = buffer.iter().position(|&b| b == b'#')?;
+                if buffer.len() < end + 3 {
+                    return None;
+                }
+                let command = String::from_utf8_lossy(&buffer[1..end]).into_owned();
+                buffer.drain(..end + 3);
+                return Some(Packet::Command(command));
+            }
+            0x03 => {
+                buffer.remove(0);
+                return Some(Packet::Interrupt);
+            }
+            _ => {
+                buffer.remove(0);
+            }
+        }
+    }
+}
+
+fn push_word(response: &mut String, word: u16) {
+    for byte in word.to_le_bytes() {
+        write!(response, "{byte:02x}").unwrap();
+    }
+}
+
+fn read_byte(emulator: &Emulator, address: u32) -> u8 {
+    // NOTE: addresses past the end of memory wrap around, as they do for the emulator itself
+    let word = emulator.ram[(address / 2) as u16];
+    word.to_le_bytes()[(address % 2) as usize]
+}
+
+fn write_byte(emulator: &mut Emulator, address: u32, byte: u8) {
+    let word = &mut emulator.ram[(address / 2) as u16];
+    let mut bytes = word.to_le_bytes();
+    bytes[(address % 2) as usize] = byte;
+    *word = u16::from_le_bytes(bytes);
+}
+
+fn parse_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_words(hex: &str) -> Option<Vec<u16>> {
+    let bytes = parse_bytes(hex)?;
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(
+        bytes
+            .chunks_exact(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect(),
+    )
+}
+
+/// parse the `addr,length' arguments of a memory access, which may cover at most the entire
+/// address space
+fn parse_range(arguments: &str) -> Option<(u32, u32)> {
+    let (address, length) = arguments.split_once(',')?;
+    let address = u32::from_str_radix(address, 16).ok()?;
+    let length = u32::from_str_radix(length, 16).ok()?;
+    (length <= 2 * 0x10000).then_some((address, length))
+}
+
+/// parse the `type,addr,kind' arguments of a breakpoint, returning the word address at which the
+/// breakpoint should be placed, or `None' if it is not a software breakpoint
+fn parse_breakpoint(arguments: &str) -> Option<u16> {
+    let mut arguments = arguments.split(',');
+    if arguments.next()? != "0" {
+        return None;
+    }
+    let address = u32::from_str_radix(arguments.next()?, 16).ok()?;
+    Some((address / 2) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packets_are_framed_skipping_acknowledgements() {
+        let mut buffer = b"+$g#67-\x03$m0,4#fd".to_vec();
+
+        assert_eq!(
+            next_packet(&mut buffer),
+            Some(Packet::Command("g".to_string()))
+        );
+        assert_eq!(next_packet(&mut buffer), Some(Packet::Interrupt));
+        assert_eq!(
+            next_packet(&mut buffer),
+            Some(Packet::Command("m0,4".to_string()))
+        );
+        assert_eq!(next_packet(&mut buffer), None);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn incomplete_packets_are_left_in_the_buffer() {
+        // NOTE: a packet is only complete once both digits of its checksum have arrived
+        let mut buffer = b"$m0,4#f".to_vec();
+        assert_eq!(next_packet(&mut buffer), None);
+        assert_eq!(buffer, b"$m0,4#f");
+
+        buffer.push(b'd');
+        assert_eq!(
+            next_packet(&mut buffer),
+            Some(Packet::Command("m0,4".to_string()))
+        );
+    }
+
+    #[test]
+    fn ranges_may_cover_at_most_the_address_space() {
+        assert_eq!(parse_range("1f,4"), Some((0x1f, 4)));
+        assert_eq!(parse_range("0,20000"), Some((0, 0x20000)));
+        assert_eq!(parse_range("0,20001"), None);
+        assert_eq!(parse_range("1f"), None);
+        assert_eq!(parse_range("1g,4"), None);
+    }
+
+    #[test]
+    fn only_software_breakpoints_are_supported() {
+        assert_eq!(parse_breakpoint("0,20,2"), Some(0x10));
+        assert_eq!(parse_breakpoint("0,21,2"), Some(0x10));
+        assert_eq!(parse_breakpoint("1,20,2"), None);
+        assert_eq!(parse_breakpoint("0"), None);
+    }
+
+    #[test]
+    fn even_byte_addresses_are_low_bytes_and_odd_ones_high_bytes() {
+        let mut emulator = Emulator::default();
+
+        write_byte(&mut emulator, 0x21, 0xab);
+        write_byte(&mut emulator, 0x20, 0xcd);
+        assert_eq!(emulator.ram[0x10], 0xabcd);
+        assert_eq!(read_byte(&emulator, 0x20), 0xcd);
+        assert_eq!(read_byte(&emulator, 0x21), 0xab);
+
+        emulator.ram[0x0000] = 0x1234;
+        assert_eq!(read_byte(&emulator, 0x20000), 0x34);
+
+        let mut response = String::new();
+        push_word(&mut response, 0xabcd);
+        assert_eq!(response, "cdab");
+    }
+}
@@ -1,15 +1,18 @@
 widgets = {
     ram = {
         view_offset = 0x0000,
+        show_ascii = false,
         style = function(_) end,
     },
     registers = {
         aliases = {},
+        display_mode = {},
         visibility_bitmask = 0xFFFFFFFF,
         style = function(_) end,
     },
     control_status_registers = {
         aliases = {},
+        display_mode = {},
         visibility_bitmask = 0xFFFFFFFF,
         style = function(_) end,
     },
@@ -0,0 +1,6 @@
+//! the emulator underlying sama, along with the devices which may be connected to it, so that it
+//! may be driven from rust, such as by tests
+
+pub mod devices;
+pub mod disasm;
+pub mod emulator;
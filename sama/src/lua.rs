@@ -1,13 +1,19 @@
-use crate::emulator::Emulator;
-
 use mlua::{
-    Error::FromLuaConversionError, FromLua, Lua, MetaMethod, Result, Table, UserData,
+    Error::FromLuaConversionError, FromLua, Function, Lua, MetaMethod, Result, Table, UserData,
     UserDataFields, UserDataMethods, Value,
 };
 
+use poki::Poki;
+
 use ratatui::style::{Color, Modifier, Style};
 
+use sama::devices::framebuffer::FramebufferDevice;
+use sama::devices::storage::BlockStorageDevice;
+use sama::emulator::{Device, Emulator, EmulatorError, RunResult};
+
 use std::cell::{Ref, RefCell, RefMut};
+use std::fs::File;
+use std::io::BufReader;
 use std::rc::Rc;
 
 /// a wrapper to allow lua interop for emulator::Emulator
@@ -32,10 +38,88 @@ struct LuaMemoryProtectionControlRegisters(Rc<RefCell<Emulator>>);
 /// a wrapper to allow lua interop for the memory protection address control/status registers
 struct LuaMemoryProtectionAddressRegisters(Rc<RefCell<Emulator>>);
 
+/// a wrapper to allow lua interop for poki::Poki
+pub struct LuaPoki(pub Poki);
+
 /// a wrapper to allow lua interop for ratatui::style::Style
 #[derive(Default)]
 pub struct LuaStyle(Style);
 
+impl LuaEmulator {
+    /// the names of the fields and methods which the emulator exposes to lua
+    // NOTE: mlua offers no way to enumerate the fields and methods of userdata from the lua side,
+    // so this has to be kept in sync with `add_fields' and `add_methods' by hand
+    pub const MEMBERS: &'static [&'static str] = &[
+        "ram",
+        "registers",
+        "control_status_registers",
+        "program_counter",
+        "waiting_for_interrupt",
+        "step",
+        "run_for",
+        "run_until_pc",
+        "set_pre_step_hook",
+        "set_post_step_hook",
+        "add_breakpoint",
+        "remove_breakpoint",
+        "reset",
+        "hard_reset",
+        "load_poki",
+        "register_mmio",
+        "attach_device",
+    ];
+}
+
+/// the names under which the functions set with `set_pre_step_hook' and `set_post_step_hook' are
+/// stored in the lua registry
+const PRE_STEP_HOOK: &str = "sama.pre_step_hook";
+const POST_STEP_HOOK: &str = "sama.post_step_hook";
+
+impl LuaEmulator {
+    /// execute a single instruction, calling the step hooks set from lua before and after it
+    // NOTE: the hooks are called here, rather than being set on the emulator itself, as the
+    // emulator must not be borrowed while they run, so that they may examine it through `e'
+    fn step_with_hooks<'lua>(
+        &self,
+        pre_step_hook: &Option<Function<'lua>>,
+        post_step_hook: &Option<Function<'lua>>,
+    ) -> Result<()> {
+        if let Some(hook) = pre_step_hook {
+            hook.call::<_, ()>(LuaEmulator(self.0.clone()))?;
+        }
+        self.0
+            .borrow_mut()
+            .step()
+            .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+        if let Some(hook) = post_step_hook {
+            hook.call::<_, ()>(LuaEmulator(self.0.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// execute up to `max_cycles' instructions as `Emulator::run_for' does, stopping once
+    /// `condition' returns `true' after an instruction is executed, and calling the step hooks set
+    /// from lua around each instruction
+    fn run_with_hooks<'lua, F: Fn(&Emulator) -> bool>(
+        &self,
+        pre_step_hook: &Option<Function<'lua>>,
+        post_step_hook: &Option<Function<'lua>>,
+        condition: F,
+        max_cycles: u64,
+    ) -> Result<(u64, RunResult)> {
+        for cycle in 0..max_cycles {
+            if let Some(result) = self.0.borrow().stops_before(cycle) {
+                return Ok((cycle, result));
+            }
+            self.step_with_hooks(pre_step_hook, post_step_hook)?;
+            if condition(&self.0.borrow()) {
+                return Ok((cycle + 1, RunResult::ReachedTarget));
+            }
+        }
+        Ok((max_cycles, self.0.borrow().exhausted()))
+    }
+}
+
 impl FromLua<'_> for LuaEmulator {
     fn from_lua(value: Value, _: &Lua) -> Result<Self> {
         match value {
@@ -63,13 +147,182 @@ impl UserData for LuaEmulator {
             this.0.borrow_mut().program_counter = value;
             Ok(())
         });
+        fields.add_field_method_get("waiting_for_interrupt", |_, this| {
+            Ok(this.0.borrow().waiting_for_interrupt)
+        });
+        fields.add_field_method_set("waiting_for_interrupt", |_, this, value: bool| {
+            this.0.borrow_mut().waiting_for_interrupt = value;
+            Ok(())
+        });
     }
 
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
-        methods.add_method_mut("step", |_, this, ()| {
-            this.0.borrow_mut().step();
+        // NOTE: stepping only borrows the userdata immutably, so that a hook may itself call the
+        // methods of the `emulator' global
+        methods.add_method("step", |lua, this, ()| {
+            this.step_with_hooks(
+                &lua.named_registry_value(PRE_STEP_HOOK)?,
+                &lua.named_registry_value(POST_STEP_HOOK)?,
+            )
+        });
+        // NOTE: runs return the names of the variants of `RunResult', which are easier to compare
+        // against from lua than any richer representation would be
+        methods.add_method("run_for", |lua, this, n_cycles: u64| {
+            let pre_step_hook: Option<Function> = lua.named_registry_value(PRE_STEP_HOOK)?;
+            let post_step_hook: Option<Function> = lua.named_registry_value(POST_STEP_HOOK)?;
+            let (cycles, result) = if pre_step_hook.is_none() && post_step_hook.is_none() {
+                this.0
+                    .borrow_mut()
+                    .run_for(n_cycles)
+                    .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
+            } else {
+                this.run_with_hooks(&pre_step_hook, &post_step_hook, |_| false, n_cycles)?
+            };
+            Ok((cycles, result.name()))
+        });
+        methods.add_method(
+            "run_until_pc",
+            |lua, this, (target, max_cycles): (u16, u64)| {
+                let pre_step_hook: Option<Function> = lua.named_registry_value(PRE_STEP_HOOK)?;
+                let post_step_hook: Option<Function> = lua.named_registry_value(POST_STEP_HOOK)?;
+                let result = if pre_step_hook.is_none() && post_step_hook.is_none() {
+                    this.0
+                        .borrow_mut()
+                        .run_until_pc(target, max_cycles)
+                        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?
+                } else {
+                    this.run_with_hooks(
+                        &pre_step_hook,
+                        &post_step_hook,
+                        |emulator| emulator.program_counter == target,
+                        max_cycles,
+                    )?
+                    .1
+                };
+                Ok(result.name())
+            },
+        );
+        methods.add_method("set_pre_step_hook", |lua, _, hook: Option<Function>| {
+            lua.set_named_registry_value(PRE_STEP_HOOK, hook)
+        });
+        methods.add_method("set_post_step_hook", |lua, _, hook: Option<Function>| {
+            lua.set_named_registry_value(POST_STEP_HOOK, hook)
+        });
+        methods.add_method_mut("add_breakpoint", |_, this, address: u16| {
+            this.0.borrow_mut().breakpoints.insert(address);
             Ok(())
         });
+        methods.add_method_mut("remove_breakpoint", |_, this, address: u16| {
+            this.0.borrow_mut().breakpoints.remove(&address);
+            Ok(())
+        });
+        methods.add_method_mut("reset", |_, this, ()| {
+            this.0.borrow_mut().reset();
+            Ok(())
+        });
+        methods.add_method_mut("hard_reset", |lua, this, ()| {
+            this.0.borrow_mut().hard_reset();
+            lua.unset_named_registry_value(PRE_STEP_HOOK)?;
+            lua.unset_named_registry_value(POST_STEP_HOOK)
+        });
+        methods.add_method_mut(
+            "register_mmio",
+            |_, this, (start, end, device_index): (u16, u16, u8)| {
+                this.0
+                    .borrow_mut()
+                    .register_mmio(start, end, device_index)
+                    .map_err(|e| mlua::Error::RuntimeError(e.to_string()))
+            },
+        );
+        // NOTE: each kind of device takes whatever options it needs from `options', such as the
+        // path of the file which backs a storage device
+        methods.add_method_mut(
+            "attach_device",
+            |_, this, (device_index, kind, options): (u8, String, Option<Table>)| {
+                if device_index == 0 {
+                    return Err(mlua::Error::RuntimeError(
+                        "device index 0 is reserved, and no device may be attached to it"
+                            .to_string(),
+                    ));
+                }
+
+                let device: Box<dyn Device> = match kind.as_str() {
+                    "framebuffer" => Box::new(FramebufferDevice::default()),
+                    "storage" => {
+                        let path: String = options
+                            .ok_or_else(|| {
+                                mlua::Error::RuntimeError(
+                                    "a storage device must be given the path of the file which backs it, as in { path = \"disk.img\" }".to_string(),
+                                )
+                            })?
+                            .get("path")?;
+                        Box::new(BlockStorageDevice::open(path)?)
+                    }
+                    _ => {
+                        return Err(mlua::Error::RuntimeError(format!(
+                            "{kind} is not a kind of device, which must be one of framebuffer or storage"
+                        )))
+                    }
+                };
+                this.0.borrow_mut().devices[device_index] = Some(device);
+                Ok(())
+            },
+        );
+        methods.add_method_mut(
+            "load_poki",
+            |_, this, (path, segment_bases): (String, Option<Table>)| {
+                let poki = Poki::deserialize(&mut BufReader::new(File::open(path)?))
+                    .map_err(mlua::Error::external)?;
+
+                // segments whose base address isn't given are placed at address 0
+                let mut bases = [0; 8];
+                if let Some(segment_bases) = segment_bases {
+                    for (segment_index, base) in bases.iter_mut().enumerate() {
+                        *base = segment_bases
+                            .get::<_, Option<u16>>(segment_index)?
+                            .unwrap_or_default();
+                    }
+                }
+
+                Ok(this.0.borrow_mut().load_poki(&poki, &bases))
+            },
+        );
+    }
+}
+
+impl UserData for LuaPoki {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("find_export", |lua, this, name: String| {
+            this.0
+                .find_export(&name)
+                .map(|(segment_index, offset)| {
+                    let export = lua.create_table()?;
+                    export.set("segment", segment_index)?;
+                    export.set("offset", offset)?;
+                    Ok(export)
+                })
+                .transpose()
+        });
+        methods.add_method("exports", |lua, this, ()| {
+            lua.create_sequence_from(
+                this.0
+                    .find_all_exports()
+                    .map(|(segment_index, export_table_entry)| {
+                        lua.create_table_from([
+                            (
+                                "label",
+                                Value::String(lua.create_string(&export_table_entry.label)?),
+                            ),
+                            ("segment", Value::Integer(segment_index as i64)),
+                            (
+                                "offset",
+                                Value::Integer(i64::from(export_table_entry.offset)),
+                            ),
+                        ])
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            )
+        });
     }
 }
 
@@ -98,11 +351,28 @@ impl UserData for LuaRegisters {
         methods.add_meta_method_mut(
             MetaMethod::NewIndex,
             |_, this, (index, value): (u16, u16)| {
-                RefMut::map(this.0.borrow_mut(), |e: &mut Emulator| &mut e.registers)[index] =
-                    value;
+                this.0.borrow_mut().write_register(index, value);
                 Ok(())
             },
         );
+
+        // NOTE: each write is given as a pair of the cycle during which it happened and the value
+        // written, oldest first
+        methods.add_method("history", |lua, this, index: u16| {
+            let emulator = this.0.borrow();
+            let history = emulator
+                .register_history
+                .get(usize::from(index))
+                .ok_or_else(|| {
+                    mlua::Error::RuntimeError(format!("there is no register r{index}"))
+                })?;
+            lua.create_sequence_from(
+                history
+                    .iter()
+                    .map(|&(cycle, value)| lua.create_sequence_from([cycle, u64::from(value)]))
+                    .collect::<Result<Vec<_>>>()?,
+            )
+        });
     }
 }
 
@@ -132,6 +402,23 @@ impl UserData for LuaControlStatusRegisters {
             this.0.borrow_mut().control_status_registers.ic = value;
             Ok(())
         });
+        fields.add_field_method_get("cycle_counter", |_, this| {
+            Ok(this.0.borrow().control_status_registers.cycle_counter)
+        });
+        fields.add_field_method_set("cycle_counter", |_, this, value: u64| {
+            this.0.borrow_mut().control_status_registers.cycle_counter = value;
+            Ok(())
+        });
+        fields.add_field_method_get("instruction_counter", |_, this| {
+            Ok(this.0.borrow().control_status_registers.instruction_counter)
+        });
+        fields.add_field_method_set("instruction_counter", |_, this, value: u64| {
+            this.0
+                .borrow_mut()
+                .control_status_registers
+                .instruction_counter = value;
+            Ok(())
+        });
         fields.add_field_method_get("mpc", |_, this| {
             Ok(LuaMemoryProtectionControlRegisters(this.0.clone()))
         });
@@ -141,16 +428,25 @@ impl UserData for LuaControlStatusRegisters {
     }
     fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_meta_method(MetaMethod::Index, |_, this, index: u16| {
-            Ok(Ref::map(this.0.borrow(), |e: &Emulator| &e.control_status_registers)[index])
+            this.0
+                .borrow()
+                .control_status_registers
+                .peek(index)
+                .ok_or_else(|| {
+                    mlua::Error::RuntimeError(EmulatorError::UndefinedCSR(index).to_string())
+                })
         });
 
         methods.add_meta_method_mut(
             MetaMethod::NewIndex,
             |_, this, (index, value): (u16, u16)| {
-                RefMut::map(this.0.borrow_mut(), |e: &mut Emulator| {
-                    &mut e.control_status_registers
-                })[index] = value;
-                Ok(())
+                this.0
+                    .borrow_mut()
+                    .control_status_registers
+                    .write(index, value)
+                    .ok_or_else(|| {
+                        mlua::Error::RuntimeError(EmulatorError::UndefinedCSR(index).to_string())
+                    })
             },
         );
     }
@@ -216,9 +512,9 @@ impl UserData for LuaMemoryProtectionAddressRegisters {
     }
 }
 
-impl Into<Style> for LuaStyle {
-    fn into(self) -> Style {
-        self.0
+impl From<LuaStyle> for Style {
+    fn from(style: LuaStyle) -> Self {
+        style.0
     }
 }
 
@@ -226,42 +522,41 @@ impl FromLua<'_> for LuaStyle {
     fn from_lua(value: Value, _: &Lua) -> Result<Self> {
         match value {
             Value::Table(t) => {
-                let mut style = Style::default();
-
-                style.fg = t
-                    .get::<_, Table>("fg")
-                    .and_then(|t| {
-                        Ok(Some(Color::Rgb(
-                            t.get("r").unwrap_or(0xFF),
-                            t.get("g").unwrap_or(0xFF),
-                            t.get("b").unwrap_or(0xFF),
-                        )))
-                    })
-                    .unwrap_or_default();
-
-                style.bg = t
-                    .get::<_, Table>("bg")
-                    .and_then(|t| {
-                        Ok(Some(Color::Rgb(
-                            t.get("r").unwrap_or_default(),
-                            t.get("g").unwrap_or_default(),
-                            t.get("b").unwrap_or_default(),
-                        )))
-                    })
-                    .unwrap_or_default();
-
-                // NOTE: i am undecided on whether or not i hate breaking parity with the field
-                // names from ratatui::style::Style more than i hate american english
-                style.underline_color = t
-                    .get::<_, Table>("underline_color")
-                    .and_then(|t| {
-                        Ok(Some(Color::Rgb(
-                            t.get("r").unwrap_or_default(),
-                            t.get("g").unwrap_or_default(),
-                            t.get("b").unwrap_or_default(),
-                        )))
-                    })
-                    .unwrap_or_default();
+                let mut style = Style {
+                    fg: t
+                        .get::<_, Table>("fg")
+                        .map(|t| {
+                            Some(Color::Rgb(
+                                t.get("r").unwrap_or(0xFF),
+                                t.get("g").unwrap_or(0xFF),
+                                t.get("b").unwrap_or(0xFF),
+                            ))
+                        })
+                        .unwrap_or_default(),
+                    bg: t
+                        .get::<_, Table>("bg")
+                        .map(|t| {
+                            Some(Color::Rgb(
+                                t.get("r").unwrap_or_default(),
+                                t.get("g").unwrap_or_default(),
+                                t.get("b").unwrap_or_default(),
+                            ))
+                        })
+                        .unwrap_or_default(),
+                    // NOTE: i am undecided on whether or not i hate breaking parity with the field
+                    // names from ratatui::style::Style more than i hate american english
+                    underline_color: t
+                        .get::<_, Table>("underline_color")
+                        .map(|t| {
+                            Some(Color::Rgb(
+                                t.get("r").unwrap_or_default(),
+                                t.get("g").unwrap_or_default(),
+                                t.get("b").unwrap_or_default(),
+                            ))
+                        })
+                        .unwrap_or_default(),
+                    ..Style::default()
+                };
 
                 if t.get::<_, bool>("bold").unwrap_or_default() {
                     style = style.add_modifier(Modifier::BOLD);
@@ -1,13 +1,19 @@
-mod emulator;
+mod gdb_stub;
 mod lua;
 mod ui;
 
-use lua::LuaEmulator;
-use ui::{ControlStatusRegistersWidget, PromptWidget, RamWidget, RegistersWidget};
+use gdb_stub::GdbStub;
+use lua::{LuaEmulator, LuaPoki};
+use ui::{
+    ControlStatusRegistersWidget, DisassemblyWidget, DisplayMode, FramebufferWidget,
+    InstructionDecodeWidget, PromptWidget, RamWidget, RegisterHistoryWidget, RegistersWidget,
+};
+
+use clap::Parser;
 
 use directories::ProjectDirs;
 
-use mlua::{Function, Lua, MultiValue};
+use mlua::{Lua, MultiValue};
 
 use ratatui::{
     crossterm::event::{self, KeyCode, KeyEventKind, KeyModifiers},
@@ -15,43 +21,85 @@ use ratatui::{
     DefaultTerminal,
 };
 
-use std::fs::read_to_string;
+use poki::Poki;
+
+use sama::devices::framebuffer::FramebufferDevice;
+use sama::disasm;
+
+use std::fs::{read_to_string, File};
 use std::io;
+use std::io::BufReader;
+use std::time::Duration;
+
+/// how long to wait for input before polling the gdb stub again
+const GDB_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// a scriptable, extendible emulator for the lawa isa
+#[derive(Parser, Debug, Clone, Hash, PartialEq, Eq)]
+#[command(version, about, long_about)]
+struct Args {
+    /// listen for a debugger speaking the gdb remote serial protocol on this port
+    #[arg(long)]
+    gdb_port: Option<u16>,
+}
 
 fn main() -> io::Result<()> {
+    let args = Args::parse();
+
+    // NOTE: the stub begins listening before the tui starts, so that any error in doing so is
+    // printed to a usable terminal
+    let gdb_stub = args.gdb_port.map(GdbStub::listen).transpose()?;
+
     let mut terminal = ratatui::init();
     terminal.clear()?;
-    let app_result = run(terminal);
+    let app_result = run(terminal, gdb_stub);
     ratatui::restore();
     app_result
 }
 
-fn run(mut terminal: DefaultTerminal) -> io::Result<()> {
+fn run(mut terminal: DefaultTerminal, mut gdb_stub: Option<GdbStub>) -> io::Result<()> {
     // Initialize the Lua state by sending the emulator over, as well as running the initialization
     // code.
     let lua = Lua::new();
-    lua.globals().set("emulator", LuaEmulator::default());
+    lua.globals()
+        .set("emulator", LuaEmulator::default())
+        .unwrap();
 
     // attach a callback function to allow reloading configuration dynamically
-    lua.globals().set(
-        "reload_configuration",
-        lua.create_function(|lua, _: MultiValue| {
-            lua.load(include_str!("init.lua")).exec();
-
-            // load and execute user `init.lua' file
-            if let Some(project_dirs) = ProjectDirs::from("", "", "sama") {
-                let init_lua_path = project_dirs.config_dir().join("init.lua");
-                if let Ok(init_lua) = read_to_string(init_lua_path) {
-                    lua.load(init_lua).exec();
+    lua.globals()
+        .set(
+            "reload_configuration",
+            lua.create_function(|lua, _: MultiValue| {
+                lua.load(include_str!("init.lua")).exec()?;
+
+                // load and execute user `init.lua' file
+                if let Some(project_dirs) = ProjectDirs::from("", "", "sama") {
+                    let init_lua_path = project_dirs.config_dir().join("init.lua");
+                    if let Ok(init_lua) = read_to_string(init_lua_path) {
+                        lua.load(init_lua).exec()?;
+                    }
                 }
-            }
 
-            Ok(())
-        })
-        .unwrap(),
-    );
+                Ok(())
+            })
+            .unwrap(),
+        )
+        .unwrap();
+
+    // attach a function to allow reading poki files, so that their exports may be examined
+    lua.globals()
+        .set(
+            "read_poki",
+            lua.create_function(|_, path: String| {
+                Poki::deserialize(&mut BufReader::new(File::open(path)?))
+                    .map(LuaPoki)
+                    .map_err(mlua::Error::external)
+            })
+            .unwrap(),
+        )
+        .unwrap();
 
-    lua.load(include_str!("init.lua")).exec();
+    lua.load(include_str!("init.lua")).exec().unwrap();
 
     // load and execute user `init.lua' file
     if let Some(project_dirs) = ProjectDirs::from("", "", "sama") {
@@ -63,6 +111,22 @@ fn run(mut terminal: DefaultTerminal) -> io::Result<()> {
 
     let mut prompt_widget = PromptWidget::default();
 
+    // the disassembly widget follows the program counter until it's scrolled, and does so again
+    // once the program counter changes, so that the program counter remains visible after every
+    // step
+    let mut disassembly_focused = false;
+    let mut disassembly_view_address = None;
+    let mut last_program_counter = 0;
+
+    // the registers whose display modes may be changed, and the general-purpose register whose
+    // most recent writes are shown, are chosen with the arrow keys while their widgets have focus.
+    // r0 is always zero, and is never written, so the selection of a general-purpose register
+    // skips it
+    let mut registers_focused = false;
+    let mut selected_register = 1;
+    let mut control_status_registers_focused = false;
+    let mut selected_control_status_register = 0;
+
     loop {
         terminal.draw(|frame| {
             let globals = lua.globals();
@@ -76,29 +140,56 @@ fn run(mut terminal: DefaultTerminal) -> io::Result<()> {
                     // something happened to the emulator! we need to replace it with a new one.
                     let emulator = LuaEmulator::default();
                     let emulator_handle = LuaEmulator(emulator.0.clone());
-                    lua.globals().set("emulator", emulator);
+                    lua.globals().set("emulator", emulator).unwrap();
                     emulator_handle
                 }
             };
             let emulator = emulator.0.borrow();
 
+            if emulator.program_counter != last_program_counter {
+                last_program_counter = emulator.program_counter;
+                disassembly_view_address = None;
+            }
+
             // create all of the widgets
             let ram_widget = RamWidget::new(&emulator, &lua);
-            let registers_widget = RegistersWidget::new(&emulator, &lua);
-            let control_status_registers_widget =
-                ControlStatusRegistersWidget::new(&emulator, &lua);
+            let registers_widget =
+                RegistersWidget::new(&emulator, &lua, selected_register, registers_focused);
+            let control_status_registers_widget = ControlStatusRegistersWidget::new(
+                &emulator,
+                &lua,
+                selected_control_status_register,
+                control_status_registers_focused,
+            );
+            let instruction_decode_widget = InstructionDecodeWidget::new(&emulator);
+            let register_history_widget = RegisterHistoryWidget::new(&emulator, selected_register);
+            // NOTE: the framebuffer widget is only shown while a framebuffer device is attached,
+            // and is redrawn from the device's pixels every frame
+            let framebuffer_widget = emulator
+                .devices
+                .find::<FramebufferDevice>()
+                .map(FramebufferWidget::new);
+            let disassembly_widget = DisassemblyWidget::new(
+                &emulator,
+                disassembly_view_address.unwrap_or(emulator.program_counter),
+                disassembly_focused,
+            );
 
             // Compute the areas in which the various widgets should be rendered.
             let split = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints(vec![Constraint::Fill(0), Constraint::Max(4)])
+                .constraints(vec![
+                    Constraint::Fill(0),
+                    Constraint::Max(prompt_widget.minimum_height()),
+                ])
                 .split(frame.area());
 
             let prompt_area = split[1];
 
             let registers_widgets_width = registers_widget
                 .minimum_width()
-                .max(control_status_registers_widget.minimum_width());
+                .max(control_status_registers_widget.minimum_width())
+                .max(instruction_decode_widget.minimum_width());
             let split = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints(vec![
@@ -107,41 +198,198 @@ fn run(mut terminal: DefaultTerminal) -> io::Result<()> {
                 ])
                 .split(split[0]);
 
-            let ram_area = split[0];
+            let ram_column_area = split[0];
+            let registers_column_area = split[1];
+
+            let split = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(vec![
+                    Constraint::Max(
+                        framebuffer_widget
+                            .as_ref()
+                            .map_or(0, FramebufferWidget::minimum_height),
+                    ),
+                    Constraint::Fill(0),
+                    Constraint::Max(disassembly_widget.minimum_height()),
+                ])
+                .split(ram_column_area);
+
+            let framebuffer_area = split[0];
+            let ram_area = split[1];
+            let disassembly_area = split[2];
 
             let split = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints(vec![
                     Constraint::Max(registers_widget.minimum_height()),
                     Constraint::Max(control_status_registers_widget.minimum_height()),
+                    Constraint::Max(instruction_decode_widget.minimum_height()),
+                    Constraint::Max(register_history_widget.minimum_height()),
                 ])
-                .split(split[1]);
+                .split(registers_column_area);
 
             let registers_area = split[0];
             let control_status_registers_area = split[1];
+            let instruction_decode_area = split[2];
+            let register_history_area = split[3];
 
             // Render the widgets.
+            if let Some(framebuffer_widget) = framebuffer_widget {
+                frame.render_widget(framebuffer_widget, framebuffer_area);
+            }
             frame.render_widget(ram_widget, ram_area);
+            frame.render_widget(disassembly_widget, disassembly_area);
             frame.render_widget(registers_widget, registers_area);
             frame.render_widget(
                 control_status_registers_widget,
                 control_status_registers_area,
             );
+            frame.render_widget(instruction_decode_widget, instruction_decode_area);
+            frame.render_widget(register_history_widget, register_history_area);
             frame.render_widget(&prompt_widget, prompt_area);
         })?;
 
+        // NOTE: the gdb stub must be polled regularly, so rather than blocking until the next
+        // event, we only wait briefly before polling it and redrawing
+        if let Some(gdb_stub) = &mut gdb_stub {
+            if let Ok(emulator) = lua.globals().get::<_, LuaEmulator>("emulator") {
+                gdb_stub.poll(&mut emulator.0.borrow_mut());
+            }
+            if !event::poll(GDB_POLL_INTERVAL)? {
+                continue;
+            }
+        }
+
         if let event::Event::Key(key) = event::read()? {
             if key.kind == KeyEventKind::Press {
+                // any keypress other than tab accepts the completion currently in the prompt
+                if key.code != KeyCode::Tab {
+                    prompt_widget.reset_completions();
+                }
+
                 match key.code {
                     KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                         return Ok(())
                     }
-                    KeyCode::Enter => {
+                    // NOTE: while an address to go to is being read, the prompt takes every key
+                    KeyCode::Enter if prompt_widget.reading_goto_address() => {
+                        prompt_widget.goto(&lua);
+                    }
+                    _ if prompt_widget.reading_goto_address() => {
+                        prompt_widget.process_key_event(key);
+                    }
+                    // NOTE: while the disassembly widget has focus, `g' alone also suffices, at the
+                    // cost of not being able to type it into the prompt
+                    KeyCode::Char('g')
+                        if key.modifiers.contains(KeyModifiers::ALT) || disassembly_focused =>
+                    {
+                        prompt_widget.begin_goto();
+                    }
+                    // NOTE: while searching through history, enter accepts the current match
+                    // rather than evaluating it, and enter with shift or alt held begins a new
+                    // line rather than evaluating the input
+                    KeyCode::Enter
+                        if !prompt_widget.searching_history()
+                            && !key
+                                .modifiers
+                                .intersects(KeyModifiers::SHIFT | KeyModifiers::ALT) =>
+                    {
                         prompt_widget.evaluate_input_buffer(&lua);
                     }
-                    KeyCode::Char('m') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    KeyCode::Char('m')
+                        if key.modifiers.contains(KeyModifiers::CONTROL)
+                            && !prompt_widget.searching_history() =>
+                    {
                         prompt_widget.evaluate_input_buffer(&lua);
                     }
+                    // NOTE: the prompt always has focus, so plain keys can't be used as shortcuts
+                    // without making it impossible to type them into the prompt
+                    KeyCode::Char('a') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        let _ = lua
+                            .load("widgets.ram.show_ascii = not widgets.ram.show_ascii")
+                            .exec();
+                    }
+                    // NOTE: while either of the register widgets has focus, `d' cycles the display
+                    // mode of its selected register, taking precedence over focusing the
+                    // disassembly widget
+                    KeyCode::Char('d') if registers_focused => {
+                        DisplayMode::cycle(
+                            &lua,
+                            "widgets.registers.display_mode",
+                            selected_register,
+                        );
+                    }
+                    KeyCode::Char('d') if control_status_registers_focused => {
+                        DisplayMode::cycle(
+                            &lua,
+                            "widgets.control_status_registers.display_mode",
+                            selected_control_status_register,
+                        );
+                    }
+                    // NOTE: while the disassembly or either of the register widgets has focus, the
+                    // arrow keys scroll it or move its selection rather than moving through the
+                    // prompt's history. only one of them has focus at a time
+                    KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        disassembly_focused = !disassembly_focused;
+                        registers_focused = false;
+                        control_status_registers_focused = false;
+                    }
+                    KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        registers_focused = !registers_focused;
+                        disassembly_focused = false;
+                        control_status_registers_focused = false;
+                    }
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::ALT) => {
+                        control_status_registers_focused = !control_status_registers_focused;
+                        disassembly_focused = false;
+                        registers_focused = false;
+                    }
+                    KeyCode::Esc
+                        if disassembly_focused
+                            || registers_focused
+                            || control_status_registers_focused =>
+                    {
+                        disassembly_focused = false;
+                        registers_focused = false;
+                        control_status_registers_focused = false;
+                    }
+                    KeyCode::Up if registers_focused => {
+                        selected_register = if selected_register == 1 {
+                            31
+                        } else {
+                            selected_register - 1
+                        };
+                    }
+                    KeyCode::Down if registers_focused => {
+                        selected_register = if selected_register == 31 {
+                            1
+                        } else {
+                            selected_register + 1
+                        };
+                    }
+                    KeyCode::Up if control_status_registers_focused => {
+                        selected_control_status_register =
+                            (selected_control_status_register + 31) % 32;
+                    }
+                    KeyCode::Down if control_status_registers_focused => {
+                        selected_control_status_register =
+                            (selected_control_status_register + 1) % 32;
+                    }
+                    KeyCode::Up | KeyCode::Down if disassembly_focused => {
+                        if let Ok(emulator) = lua.globals().get::<_, LuaEmulator>("emulator") {
+                            let emulator = emulator.0.borrow();
+                            let view_address =
+                                disassembly_view_address.unwrap_or(emulator.program_counter);
+                            disassembly_view_address = Some(if key.code == KeyCode::Up {
+                                disasm::previous_address(&emulator, view_address)
+                            } else {
+                                disasm::next_address(&emulator, view_address)
+                            });
+                        }
+                    }
+                    KeyCode::Tab if !prompt_widget.searching_history() => {
+                        prompt_widget.complete(&lua);
+                    }
                     _ => {
                         prompt_widget.process_key_event(key);
                     }
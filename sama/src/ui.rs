@@ -1,18 +1,22 @@
-use crate::emulator::{ControlStatusRegisters, Emulator, Ram, Registers};
 use crate::lua::{LuaEmulator, LuaStyle};
 
 use directories::ProjectDirs;
 
-use mlua::{Function, Lua, MultiValue, Table};
+use mlua::{Function, Lua, MultiValue, Table, Value};
 
 use ratatui::{
     crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     layout::{Constraint, Direction, Layout},
     prelude::{Buffer, Rect},
+    style::{Color, Modifier, Style},
     text::{Line, Span, Text},
     widgets::{Block, BorderType, Padding, Widget, WidgetRef},
 };
 
+use sama::devices::framebuffer::{self, FramebufferDevice};
+use sama::disasm::{self, Instruction};
+use sama::emulator::{ControlStatusRegisters, Emulator, Ram, Registers};
+
 use tui_textarea::{CursorMove, TextArea};
 
 use std::fs::{create_dir_all, File, OpenOptions};
@@ -27,10 +31,12 @@ use std::io::{BufRead, BufReader, Write};
 /// occasionally wish to jump to a different point in ram, say to examine the state of some data
 /// structure. styling the widget via a provided lua function is also possible. when displaying the
 /// contents of each address in ram, the `style_handle' function will be called, with the address
-/// provided as an argument
+/// provided as an argument. if `show_ascii' is set, each row is followed by a column showing the
+/// bytes of its values as ascii characters, as in a hex editor
 pub struct RamWidget<'lua> {
     ram: &'lua Ram,
     view_offset: u16,
+    show_ascii: bool,
     style_handle: Function<'lua>,
 }
 
@@ -43,6 +49,11 @@ impl<'lua> RamWidget<'lua> {
             .eval()
             .unwrap_or_default();
 
+        let show_ascii = lua
+            .load("widgets.ram.show_ascii")
+            .eval()
+            .unwrap_or_default();
+
         let style_handle = lua
             .load("widgets.ram.style")
             .eval()
@@ -51,6 +62,7 @@ impl<'lua> RamWidget<'lua> {
         Self {
             ram,
             view_offset,
+            show_ascii,
             style_handle,
         }
     }
@@ -67,8 +79,14 @@ impl Widget for RamWidget<'_> {
         block.render(area, buf);
 
         // 7 columns are taken up by the address which begins each line and the colon, and
-        // displaying each value takes 5 columns, 4 for the value and 1 for the space
-        let values_per_row = (inner_area.width - 7) / 5;
+        // displaying each value takes 5 columns, 4 for the value and 1 for the space. the ascii
+        // column, if shown, takes up 1 column for the space which separates it from the values,
+        // and then 2 more columns for each value
+        let values_per_row = if self.show_ascii {
+            inner_area.width.saturating_sub(8) / 7
+        } else {
+            inner_area.width.saturating_sub(7) / 5
+        };
 
         // construct the lines which the widget displays, calling the `style_handle' function for
         // each address to determine how it should be styled
@@ -89,6 +107,26 @@ impl Widget for RamWidget<'_> {
 
                 line.push(Span::styled(format!("{:04x}", self.ram[address]), style));
             }
+
+            if self.show_ascii {
+                line.push(Span::raw(" "));
+
+                // each value is displayed as its low byte followed by its high byte, with any
+                // byte which is not a printable ascii character displayed as `.'
+                let mut ascii = String::new();
+                for value_index in 0..values_per_row {
+                    let address = self.view_offset + values_per_row * row_index + value_index;
+                    for byte in self.ram[address].to_le_bytes() {
+                        ascii.push(if (0x20..=0x7E).contains(&byte) {
+                            char::from(byte)
+                        } else {
+                            '.'
+                        });
+                    }
+                }
+                line.push(Span::raw(ascii));
+            }
+
             lines.push(Line::from(line));
         }
 
@@ -97,29 +135,124 @@ impl Widget for RamWidget<'_> {
     }
 }
 
+/// the ways in which the value of a register may be displayed
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum DisplayMode {
+    #[default]
+    Hex,
+    UnsignedDecimal,
+    SignedDecimal,
+}
+
+impl DisplayMode {
+    /// return the display mode referred to by `name' in lua configuration, if any
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "hex" => Some(Self::Hex),
+            "unsigned" => Some(Self::UnsignedDecimal),
+            "signed" => Some(Self::SignedDecimal),
+            _ => None,
+        }
+    }
+
+    /// return the name by which the display mode is referred to in lua configuration
+    fn name(self) -> &'static str {
+        match self {
+            Self::Hex => "hex",
+            Self::UnsignedDecimal => "unsigned",
+            Self::SignedDecimal => "signed",
+        }
+    }
+
+    /// return the display mode which follows this one when cycling through them
+    fn next(self) -> Self {
+        match self {
+            Self::Hex => Self::UnsignedDecimal,
+            Self::UnsignedDecimal => Self::SignedDecimal,
+            Self::SignedDecimal => Self::Hex,
+        }
+    }
+
+    /// replace the display mode of register `register_index' in the lua table produced by
+    /// evaluating `chunk' with the one which follows it, treating a missing or invalid display
+    /// mode as hexadecimal, as `load' does
+    pub fn cycle(lua: &Lua, chunk: &str, register_index: u16) {
+        if let Ok(table) = lua.load(chunk).eval::<Table>() {
+            let display_mode = table
+                .get::<_, String>(register_index)
+                .ok()
+                .and_then(|name| Self::from_name(&name))
+                .unwrap_or_default();
+            let _ = table.set(register_index, display_mode.next().name());
+        }
+    }
+
+    /// format `value' according to the display mode
+    ///
+    /// values are always padded to 6 columns, which is the width of both a hexadecimal value
+    /// (`0xffff') and of the widest signed decimal value (`-32768'), so that the width of the
+    /// register widgets does not depend on their display modes
+    fn format(self, value: u16) -> String {
+        match self {
+            Self::Hex => format!("{:#06x}", value),
+            Self::UnsignedDecimal => format!("{:>6}", value),
+            Self::SignedDecimal => format!("{:>6}", value as i16),
+        }
+    }
+
+    /// load the display modes of each of 32 registers from the lua table produced by evaluating
+    /// `chunk', defaulting to hexadecimal for any register whose display mode is missing or invalid
+    fn load(lua: &Lua, chunk: &str) -> [Self; 32] {
+        let mut display_modes = [Self::default(); 32];
+        if let Ok(table) = lua.load(chunk).eval::<Table>() {
+            for (register_index, display_mode) in display_modes.iter_mut().enumerate() {
+                if let Some(d) = table
+                    .get::<_, String>(register_index)
+                    .ok()
+                    .and_then(|name| Self::from_name(&name))
+                {
+                    *display_mode = d;
+                }
+            }
+        }
+
+        display_modes
+    }
+}
+
 /// a widget for displaying the contents of the general-purpose registers
 ///
 /// this widget is reponsible for rendering the contents of the general-purpose registers. the
 /// widget can be styled via a provided lua function. when displaying the contents of a register,
 /// the `style_handle' function will be called, with the index of the register provided as an
-/// argument
+/// argument. each register is displayed according to its entry in `display_modes'. the border is
+/// drawn more heavily while `focused' is set, in which case the name of `selected_register' is
+/// highlighted, and the arrow keys move the selection
 pub struct RegistersWidget<'lua> {
     registers: &'lua Registers,
     aliases: [Option<String>; 32],
+    display_modes: [DisplayMode; 32],
     visibility_bitmask: u32,
     style_handle: Function<'lua>,
+    selected_register: u16,
+    focused: bool,
 }
 
 impl<'lua> RegistersWidget<'lua> {
-    pub fn new(emulator: &'lua Emulator, lua: &'lua Lua) -> Self {
+    pub fn new(
+        emulator: &'lua Emulator,
+        lua: &'lua Lua,
+        selected_register: u16,
+        focused: bool,
+    ) -> Self {
         let registers = &emulator.registers;
 
         let aliases = match lua.load("widgets.registers.aliases").eval::<Table>() {
             Ok(table) => {
                 let mut aliases = [const { None }; 32];
-                for register_index in 0..32 {
-                    if let Ok(alias) = table.get(register_index) {
-                        aliases[register_index] = Some(alias);
+                for (register_index, alias) in aliases.iter_mut().enumerate() {
+                    if let Ok(a) = table.get(register_index) {
+                        *alias = Some(a);
                     }
                 }
 
@@ -128,6 +261,8 @@ impl<'lua> RegistersWidget<'lua> {
             Err(_) => [const { None }; 32],
         };
 
+        let display_modes = DisplayMode::load(lua, "widgets.registers.display_mode");
+
         let visibility_bitmask = lua
             .load("widgets.registers.visibility_bitmask")
             .eval()
@@ -141,8 +276,11 @@ impl<'lua> RegistersWidget<'lua> {
         Self {
             registers,
             aliases,
+            display_modes,
             visibility_bitmask,
             style_handle,
+            selected_register,
+            focused,
         }
     }
 
@@ -172,7 +310,11 @@ impl<'lua> RegistersWidget<'lua> {
 impl Widget for RegistersWidget<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let block = Block::bordered()
-            .border_type(BorderType::Rounded)
+            .border_type(if self.focused {
+                BorderType::Thick
+            } else {
+                BorderType::Rounded
+            })
             .title("Registers")
             .padding(Padding::horizontal(1));
 
@@ -189,16 +331,25 @@ impl Widget for RegistersWidget<'_> {
                 let mut line = Vec::new();
 
                 let register_name = match &self.aliases[usize::from(i)] {
-                    Some(a) => format!("{}", a),
+                    Some(a) => a.to_string(),
                     None => format!("r{}", i),
                 };
-                line.push(Span::raw(format!(
-                    "{:register_names_max_length$} ",
-                    register_name
-                )));
+                let register_name = format!("{:register_names_max_length$}", register_name);
+                if self.focused && i == self.selected_register {
+                    line.push(Span::styled(
+                        register_name,
+                        Style::new().add_modifier(Modifier::REVERSED),
+                    ));
+                } else {
+                    line.push(Span::raw(register_name));
+                }
+                line.push(Span::raw(" "));
 
                 let style = self.style_handle.call::<_, LuaStyle>(i).unwrap_or_default();
-                line.push(Span::styled(format!("{:#06x}", self.registers[i]), style));
+                line.push(Span::styled(
+                    self.display_modes[usize::from(i)].format(self.registers[i]),
+                    style,
+                ));
 
                 lines.push(Line::from(line));
             }
@@ -214,16 +365,26 @@ impl Widget for RegistersWidget<'_> {
 /// this widget is reponsible for rendering the contents of the control/status registers. the
 /// widget can be styled via a provided lua function. when displaying the contents of a register,
 /// the `style_handle' function will be called, with the index of the register provided as an
-/// argument
+/// argument. each register is displayed according to its entry in `display_modes'. the border is
+/// drawn more heavily while `focused' is set, in which case the name of `selected_register' is
+/// highlighted, and the arrow keys move the selection
 pub struct ControlStatusRegistersWidget<'lua> {
     control_status_registers: &'lua ControlStatusRegisters,
     aliases: [Option<String>; 32],
+    display_modes: [DisplayMode; 32],
     visibility_bitmask: u32,
     style_handle: Function<'lua>,
+    selected_register: u16,
+    focused: bool,
 }
 
 impl<'lua> ControlStatusRegistersWidget<'lua> {
-    pub fn new(emulator: &'lua Emulator, lua: &'lua Lua) -> Self {
+    pub fn new(
+        emulator: &'lua Emulator,
+        lua: &'lua Lua,
+        selected_register: u16,
+        focused: bool,
+    ) -> Self {
         let control_status_registers = &emulator.control_status_registers;
 
         let aliases = match lua
@@ -232,9 +393,9 @@ impl<'lua> ControlStatusRegistersWidget<'lua> {
         {
             Ok(table) => {
                 let mut aliases = [const { None }; 32];
-                for register_index in 0..32 {
-                    if let Ok(alias) = table.get(register_index) {
-                        aliases[register_index] = Some(alias);
+                for (register_index, alias) in aliases.iter_mut().enumerate() {
+                    if let Ok(a) = table.get(register_index) {
+                        *alias = Some(a);
                     }
                 }
 
@@ -243,6 +404,8 @@ impl<'lua> ControlStatusRegistersWidget<'lua> {
             Err(_) => [const { None }; 32],
         };
 
+        let display_modes = DisplayMode::load(lua, "widgets.control_status_registers.display_mode");
+
         let visibility_bitmask = lua
             .load("widgets.control_status_registers.visibility_bitmask")
             .eval()
@@ -256,8 +419,11 @@ impl<'lua> ControlStatusRegistersWidget<'lua> {
         Self {
             control_status_registers,
             aliases,
+            display_modes,
             visibility_bitmask,
             style_handle,
+            selected_register,
+            focused,
         }
     }
 
@@ -277,6 +443,9 @@ impl<'lua> ControlStatusRegistersWidget<'lua> {
                             0b10000 => 2,
                             0b10001 => 3,
                             0b10010 => 2,
+                            0b10011 => 5,
+                            0b10100 => 7,
+                            0b10101 => 8,
                             0b10110..=0b10111 => 4,
                             0b11000..=0b11111 => 4,
                             _ => 0,
@@ -292,14 +461,18 @@ impl<'lua> ControlStatusRegistersWidget<'lua> {
     }
 
     pub fn minimum_height(&self) -> u16 {
-        (self.visibility_bitmask.count_ones() as u16) - 1
+        (self.visibility_bitmask.count_ones() as u16) + 2
     }
 }
 
 impl Widget for ControlStatusRegistersWidget<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let block = Block::bordered()
-            .border_type(BorderType::Rounded)
+            .border_type(if self.focused {
+                BorderType::Thick
+            } else {
+                BorderType::Rounded
+            })
             .title("C/S Registers")
             .padding(Padding::horizontal(1));
 
@@ -315,26 +488,35 @@ impl Widget for ControlStatusRegistersWidget<'_> {
                 let mut line = Vec::new();
 
                 let register_name = match &self.aliases[usize::from(i)] {
-                    Some(a) => format!("{}", a),
+                    Some(a) => a.to_string(),
                     None => match i {
                         0b00000..=0b01111 => format!("im{}", i),
-                        0b10000 => format!("iv"),
-                        0b10001 => format!("ipc"),
-                        0b10010 => format!("ic"),
-                        0b10011..=0b10101 => continue,
+                        0b10000 => "iv".to_string(),
+                        0b10001 => "ipc".to_string(),
+                        0b10010 => "ic".to_string(),
+                        0b10011 => "cycle".to_string(),
+                        0b10100 => "instret".to_string(),
+                        0b10101 => "counterh".to_string(),
                         0b10110..=0b10111 => format!("mpc{}", i & 0b00001),
                         0b11000..=0b11111 => format!("mpa{}", i & 0b00111),
                         _ => unreachable!(),
                     },
                 };
-                line.push(Span::raw(format!(
-                    "{:register_names_max_length$} ",
-                    register_name
-                )));
+                let register_name = format!("{:register_names_max_length$}", register_name);
+                if self.focused && i == self.selected_register {
+                    line.push(Span::styled(
+                        register_name,
+                        Style::new().add_modifier(Modifier::REVERSED),
+                    ));
+                } else {
+                    line.push(Span::raw(register_name));
+                }
+                line.push(Span::raw(" "));
 
                 let style = self.style_handle.call::<_, LuaStyle>(i).unwrap_or_default();
                 line.push(Span::styled(
-                    format!("{:#06x}", self.control_status_registers[i]),
+                    self.display_modes[usize::from(i)]
+                        .format(self.control_status_registers.peek(i).unwrap_or_default()),
                     style,
                 ));
 
@@ -346,6 +528,324 @@ impl Widget for ControlStatusRegistersWidget<'_> {
     }
 }
 
+/// a widget for displaying the instruction which the program counter points to
+///
+/// this widget is responsible for rendering the instruction which the emulator will execute next
+/// as pali would assemble it, along with the current values of the registers which it reads and,
+/// for arithmetic and logical instructions, the value which it will write to its destination
+/// register. immediates which are the addresses of labels exported by a loaded poki file are
+/// replaced by those labels
+pub struct InstructionDecodeWidget<'a> {
+    emulator: &'a Emulator,
+}
+
+impl<'a> InstructionDecodeWidget<'a> {
+    pub fn new(emulator: &'a Emulator) -> Self {
+        Self { emulator }
+    }
+
+    // NOTE: the width is fixed, rather than depending upon the instruction, so that the layout
+    // doesn't shift as the emulator steps. it's wide enough for any instruction whose immediate
+    // isn't replaced by a label
+    pub fn minimum_width(&self) -> u16 {
+        25
+    }
+
+    pub fn minimum_height(&self) -> u16 {
+        5
+    }
+}
+
+impl Widget for InstructionDecodeWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title("Instruction")
+            .padding(Padding::horizontal(1));
+
+        let inner_area = block.inner(area);
+
+        block.render(area, buf);
+
+        let emulator = self.emulator;
+        let program_counter = emulator.program_counter;
+        let lines = match Instruction::decode_at(emulator, program_counter) {
+            Some(instruction) => {
+                let operands = instruction
+                    .registers_read()
+                    .iter()
+                    .map(|&i| format!("r{i}={:#06x}", emulator.registers[i]))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let result = instruction
+                    .result(emulator)
+                    .map(|result| format!("=> r{} will be {result:#06x}", instruction.dst))
+                    .unwrap_or_default();
+
+                vec![
+                    Line::from(instruction.render(&emulator.symbols)),
+                    Line::from(format!("; {operands}")),
+                    Line::from(result),
+                ]
+            }
+            None => vec![
+                Line::from(format!("(.word {:#06x})", emulator.ram[program_counter])),
+                Line::from("; undefined opcode"),
+            ],
+        };
+
+        Text::from(lines).render(inner_area, buf);
+    }
+}
+
+/// the bit of a braille character which raises the dot in each column and row of its cell, as
+/// given by unicode
+const BRAILLE_DOTS: [[u32; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+/// a widget for displaying the pixels of a framebuffer device
+///
+/// this widget is responsible for rendering the pixels of `framebuffer' as braille characters,
+/// each of which covers 2 columns and 4 rows of pixels. a dot is raised for each pixel which
+/// isn't black, and as a character may only be drawn in a single colour, each is drawn in the
+/// average of the colours of the pixels which it covers which aren't black
+pub struct FramebufferWidget<'a> {
+    framebuffer: &'a FramebufferDevice,
+}
+
+impl<'a> FramebufferWidget<'a> {
+    pub fn new(framebuffer: &'a FramebufferDevice) -> Self {
+        Self { framebuffer }
+    }
+
+    pub fn minimum_height(&self) -> u16 {
+        (framebuffer::HEIGHT / 4) as u16 + 2
+    }
+}
+
+impl Widget for FramebufferWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title("Framebuffer")
+            .padding(Padding::horizontal(1));
+
+        let inner_area = block.inner(area);
+
+        block.render(area, buf);
+
+        let mut lines = Vec::new();
+        for cell_y in 0..framebuffer::HEIGHT / 4 {
+            let mut line = Vec::new();
+            for cell_x in 0..framebuffer::WIDTH / 2 {
+                let mut dots = 0;
+                let mut lit = 0;
+                let mut sum = [0u32; 3];
+                for (dx, column) in BRAILLE_DOTS.iter().enumerate() {
+                    for (dy, dot) in column.iter().enumerate() {
+                        let pixel = self.framebuffer.pixel(cell_x * 2 + dx, cell_y * 4 + dy);
+                        if pixel != [0; 3] {
+                            dots |= dot;
+                            lit += 1;
+                            for (sum, channel) in sum.iter_mut().zip(pixel) {
+                                *sum += u32::from(channel);
+                            }
+                        }
+                    }
+                }
+
+                let character = char::from_u32(0x2800 + dots).unwrap_or(' ');
+                let [red, green, blue] = sum.map(|sum| (sum / lit.max(1)) as u8);
+                line.push(Span::styled(
+                    character.to_string(),
+                    Style::new().fg(Color::Rgb(red, green, blue)),
+                ));
+            }
+            lines.push(Line::from(line));
+        }
+
+        Text::from(lines).render(inner_area, buf);
+    }
+}
+
+/// the number of writes shown by the register history widget
+const REGISTER_HISTORY_SHOWN: usize = 3;
+
+/// a widget for displaying the most recent writes to a general-purpose register
+///
+/// this widget is responsible for rendering the last few writes to `register', newest first, each
+/// as the cycle during which it happened and the value written
+pub struct RegisterHistoryWidget<'a> {
+    emulator: &'a Emulator,
+    register: u16,
+}
+
+impl<'a> RegisterHistoryWidget<'a> {
+    pub fn new(emulator: &'a Emulator, register: u16) -> Self {
+        Self { emulator, register }
+    }
+
+    pub fn minimum_height(&self) -> u16 {
+        REGISTER_HISTORY_SHOWN as u16 + 2
+    }
+}
+
+impl Widget for RegisterHistoryWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title(format!("Writes to r{}", self.register))
+            .padding(Padding::horizontal(1));
+
+        let inner_area = block.inner(area);
+
+        block.render(area, buf);
+
+        let history = &self.emulator.register_history[usize::from(self.register)];
+        let lines: Vec<_> = if history.is_empty() {
+            vec![Line::from("none yet")]
+        } else {
+            history
+                .iter()
+                .rev()
+                .take(REGISTER_HISTORY_SHOWN)
+                .map(|(cycle, value)| Line::from(format!("cycle {cycle}: {value:#06x}")))
+                .collect()
+        };
+
+        Text::from(lines).render(inner_area, buf);
+    }
+}
+
+/// a widget for displaying the instructions surrounding the program counter
+///
+/// this widget is responsible for rendering the instructions in ram before and after
+/// `view_address', which is usually the program counter, each along with its address and the
+/// words which it occupies, as pali would assemble it. the instruction which the program counter
+/// points to is marked with `>', and the labels exported by a loaded poki file are displayed
+/// above the instructions at their addresses. the border is drawn more heavily while `focused' is
+/// set, in which case the arrow keys scroll the widget
+pub struct DisassemblyWidget<'a> {
+    emulator: &'a Emulator,
+    view_address: u16,
+    focused: bool,
+}
+
+impl<'a> DisassemblyWidget<'a> {
+    pub fn new(emulator: &'a Emulator, view_address: u16, focused: bool) -> Self {
+        Self {
+            emulator,
+            view_address,
+            focused,
+        }
+    }
+
+    pub fn minimum_height(&self) -> u16 {
+        18
+    }
+}
+
+impl Widget for DisassemblyWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .border_type(if self.focused {
+                BorderType::Thick
+            } else {
+                BorderType::Rounded
+            })
+            .title("Disassembly")
+            .padding(Padding::horizontal(1));
+
+        let inner_area = block.inner(area);
+
+        block.render(area, buf);
+
+        let emulator = self.emulator;
+
+        // NOTE: the instructions before the view address are found by walking backwards from it,
+        // rather than by walking forwards from some earlier address, so that the instruction at
+        // the view address is always displayed, even if the walk misjudges where some earlier
+        // instruction begins
+        let height = usize::from(inner_area.height);
+        let mut addresses = Vec::with_capacity(height);
+        let mut address = self.view_address;
+        for _ in 0..height / 2 {
+            address = disasm::previous_address(emulator, address);
+            addresses.push(address);
+        }
+        addresses.reverse();
+        let mut address = self.view_address;
+        for _ in height / 2..height {
+            addresses.push(address);
+            address = disasm::next_address(emulator, address);
+        }
+
+        let mut lines = Vec::new();
+        for address in addresses {
+            if let Some(label) = emulator.symbols.get(&address) {
+                lines.push(Line::from(format!("{label}:")));
+            }
+
+            let (words, assembly) = match Instruction::decode_at(emulator, address) {
+                Some(instruction) => (
+                    (0..instruction.size())
+                        .map(|offset| format!("{:04x}", emulator.ram[address.wrapping_add(offset)]))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    instruction.render(&emulator.symbols),
+                ),
+                None => (
+                    format!("{:04x}", emulator.ram[address]),
+                    format!("(.word {:#06x})", emulator.ram[address]),
+                ),
+            };
+
+            let current = address == emulator.program_counter;
+            let marker = if current { '>' } else { ' ' };
+            let line = format!("{marker} {address:#06x}: {words:<9}  {assembly}");
+            lines.push(if current {
+                Line::styled(line, Style::default().add_modifier(Modifier::BOLD))
+            } else {
+                Line::from(line)
+            });
+        }
+
+        Text::from(lines).render(inner_area, buf);
+    }
+}
+
+/// the character which separates the lines of a multi-line entry in the history file
+///
+/// each entry in the history file occupies a single line of the file, so the line breaks within
+/// multi-line entries are replaced by this character (the ascii record separator), which should
+/// never appear in lua source typed at the prompt
+const HISTORY_LINE_SEPARATOR: char = '\u{1e}';
+
+/// the greatest number of completions displayed beneath the prompt at once
+const MAX_VISIBLE_COMPLETIONS: usize = 10;
+
+/// the greatest number of instructions executed when running to an address entered at the goto
+/// prompt
+const GOTO_MAX_CYCLES: u64 = 100_000;
+
+/// return a copy of `text_area' in which `old', which must immediately precede the cursor, has
+/// been replaced by `new'
+fn replace_before_cursor<'a>(text_area: &TextArea<'a>, old: &str, new: &str) -> TextArea<'a> {
+    let mut text_area = text_area.clone();
+    for _ in old.chars() {
+        text_area.delete_char();
+    }
+    text_area.insert_str(new);
+    text_area
+}
+
+/// construct a text area containing `contents', with the cursor placed at the end
+fn text_area_containing<'a>(contents: &str) -> TextArea<'a> {
+    let mut text_area = TextArea::new(contents.split('\n').map(String::from).collect());
+    text_area.move_cursor(CursorMove::Bottom);
+    text_area.move_cursor(CursorMove::End);
+    text_area
+}
+
 pub struct PromptWidget<'a> {
     text_area: TextArea<'a>,
     input_buffer: String,
@@ -354,6 +854,16 @@ pub struct PromptWidget<'a> {
     history_file: Option<File>,
     history: Vec<String>,
     history_index: usize,
+
+    searching_history: bool,
+    history_search_query: String,
+    history_search_match: Option<usize>,
+
+    reading_goto_address: bool,
+    goto_address: String,
+
+    completions: Vec<String>,
+    completion_index: usize,
 }
 
 impl Default for PromptWidget<'_> {
@@ -363,7 +873,7 @@ impl Default for PromptWidget<'_> {
         let project_dirs = ProjectDirs::from("", "", "sama").unwrap();
 
         // make sure that the directory which contains the history exists
-        create_dir_all(project_dirs.data_dir());
+        let _ = create_dir_all(project_dirs.data_dir());
 
         // fetch the existing history
         let history_file_path = project_dirs.data_dir().join("history");
@@ -378,7 +888,7 @@ impl Default for PromptWidget<'_> {
             .map(|f| {
                 BufReader::new(f)
                     .lines()
-                    .filter_map(|r| r.map(|s| s.to_string()).ok())
+                    .filter_map(|r| r.map(|s| s.replace(HISTORY_LINE_SEPARATOR, "\n")).ok())
                     .collect::<Vec<_>>()
             })
             .unwrap_or_default();
@@ -391,101 +901,314 @@ impl Default for PromptWidget<'_> {
             history_file,
             history,
             history_index,
+            searching_history: false,
+            history_search_query: String::new(),
+            history_search_match: None,
+            reading_goto_address: false,
+            goto_address: String::new(),
+            completions: Vec::new(),
+            completion_index: 0,
         }
     }
 }
 
-impl PromptWidget<'_> {
+impl<'a> PromptWidget<'a> {
+    /// return `true' iff the prompt is currently in the middle of a reverse search through history
+    pub fn searching_history(&self) -> bool {
+        self.searching_history
+    }
+
+    /// search backwards through history for the most recent entry before `before' which contains
+    /// the search query, displaying it in the prompt if one is found
+    fn search_history(&mut self, before: usize) {
+        if let Some(index) = self.history[..before]
+            .iter()
+            .rposition(|entry| entry.contains(&self.history_search_query))
+        {
+            self.history_search_match = Some(index);
+            self.text_area = text_area_containing(&self.history[index]);
+        }
+    }
+
+    /// handle a key event while a reverse search through history is in progress
+    ///
+    /// typing refines the search query, `ctrl+r' moves on to the next older match, `enter' accepts
+    /// the current match, and `esc' abandons the search, restoring the prompt to its prior state
+    fn process_history_search_key_event(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Char('r') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                let before = self.history_search_match.unwrap_or(self.history.len());
+                self.search_history(before);
+            }
+            KeyCode::Enter => {
+                self.searching_history = false;
+                if let Some(index) = self.history_search_match {
+                    self.history_index = index;
+                }
+            }
+            KeyCode::Char('m') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.searching_history = false;
+                if let Some(index) = self.history_search_match {
+                    self.history_index = index;
+                }
+            }
+            KeyCode::Esc => {
+                self.searching_history = false;
+                self.text_area = text_area_containing(
+                    self.history
+                        .get(self.history_index)
+                        .unwrap_or(&self.input_buffer),
+                );
+            }
+            KeyCode::Backspace => {
+                self.history_search_query.pop();
+                self.search_history(self.history.len());
+            }
+            KeyCode::Char(c) if !key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.history_search_query.push(c);
+                self.search_history(self.history.len());
+            }
+            _ => {}
+        }
+    }
+
+    /// return `true' iff the prompt is currently reading an address to which to run the emulator
+    pub fn reading_goto_address(&self) -> bool {
+        self.reading_goto_address
+    }
+
+    /// begin reading an address to which to run the emulator, in place of the usual input, which
+    /// is left untouched
+    ///
+    /// the address may be any lua expression, so that it may be written in hex, as in `0x0100',
+    /// or computed from other values. `enter' runs the emulator until it reaches the address, as
+    /// `emulator:run_until_pc' does, and `esc' abandons the address
+    pub fn begin_goto(&mut self) {
+        self.reset_completions();
+        self.searching_history = false;
+        self.reading_goto_address = true;
+        self.goto_address = String::new();
+    }
+
+    /// handle a key event while an address to which to run the emulator is being read
+    fn process_goto_key_event(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Esc => self.reading_goto_address = false,
+            KeyCode::Backspace => {
+                self.goto_address.pop();
+            }
+            KeyCode::Char(c) if !key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.goto_address.push(c);
+            }
+            _ => {}
+        }
+    }
+
+    /// run the emulator until it reaches the address which has been read, for at most
+    /// `GOTO_MAX_CYCLES' instructions, displaying why it stopped
+    pub fn goto(&mut self, lua: &Lua) {
+        self.reading_goto_address = false;
+
+        let run = format!(
+            "return emulator:run_until_pc(({}), {GOTO_MAX_CYCLES})",
+            self.goto_address
+        );
+        self.output_buffer = match lua.load(&run).eval::<String>() {
+            Ok(result) => result,
+            Err(e) => format!("{}", e),
+        };
+    }
+
     pub fn process_key_event(&mut self, key_event: KeyEvent) {
         if key_event.kind == KeyEventKind::Press {
+            if self.searching_history {
+                self.process_history_search_key_event(key_event);
+                return;
+            }
+
+            if self.reading_goto_address {
+                self.process_goto_key_event(key_event);
+                return;
+            }
+
             match key_event.code {
-                // FIXME: this feels horribly hacky, but it works, at least as far as i can tell
-                KeyCode::Up => {
-                    if self.history_index > 0 {
-                        self.history_index -= 1;
-                        self.text_area =
-                            TextArea::new(vec![self.history[self.history_index].clone()]);
-                        self.text_area.move_cursor(CursorMove::End);
-                    }
+                KeyCode::Char('r') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.searching_history = true;
+                    self.history_search_query = String::new();
+                    self.history_search_match = None;
                 }
+                // NOTE: since the input may span several lines, the arrow keys only navigate
+                // through history when the cursor is already on the first or last line, and
+                // otherwise move the cursor between lines as usual
+                KeyCode::Up if self.text_area.cursor().0 == 0 => self.previous_history_entry(),
                 KeyCode::Char('p') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                    if self.history_index > 0 {
-                        self.history_index -= 1;
-                        self.text_area =
-                            TextArea::new(vec![self.history[self.history_index].clone()]);
-                        self.text_area.move_cursor(CursorMove::End);
-                    }
+                    self.previous_history_entry()
                 }
-                KeyCode::Down => {
-                    if self.history_index < self.history.len() {
-                        self.history_index += 1;
-                        self.text_area = if let Some(history) = self.history.get(self.history_index)
-                        {
-                            TextArea::new(vec![history.clone()])
-                        } else {
-                            TextArea::new(vec![self.input_buffer.clone()])
-                        };
-                        self.text_area.move_cursor(CursorMove::End);
-                    }
+                KeyCode::Down if self.text_area.cursor().0 + 1 == self.text_area.lines().len() => {
+                    self.next_history_entry()
                 }
                 KeyCode::Char('n') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                    if self.history_index < self.history.len() {
-                        self.history_index += 1;
-                        self.text_area = if let Some(history) = self.history.get(self.history_index)
-                        {
-                            TextArea::new(vec![history.clone()])
-                        } else {
-                            TextArea::new(vec![self.input_buffer.clone()])
-                        };
-                        self.text_area.move_cursor(CursorMove::End);
-                    }
+                    self.next_history_entry()
+                }
+                // a bare enter evaluates the input, and so never reaches this point, but enter
+                // with a modifier begins a new line
+                KeyCode::Enter => {
+                    let mut new_text_area = self.text_area.clone();
+                    new_text_area.insert_newline();
+                    self.modify_input(new_text_area);
                 }
                 _ => {
-                    // if we're currently examining history, we need to check whether or not we're
-                    // modifying it, in which case the modified history should become the new input
-                    // state.
-                    if self.history_index != self.history.len() {
-                        let mut new_text_area = self.text_area.clone();
-                        new_text_area.input(key_event);
-
-                        // we need to be a bit careful about checking that we're actually making
-                        // a modification, since otherwise navigaing through history, copy/pasting,
-                        // etc. could cause our current input to be overwritten
-                        if self.text_area.lines()[0] != new_text_area.lines()[0] {
-                            // we're attempting to modify an entry in history. make it the new
-                            // current input
-                            self.input_buffer = new_text_area.lines()[0].clone();
-                            self.text_area = new_text_area;
-                            self.history_index = self.history.len();
-                            return;
-                        }
-                    }
-
-                    self.text_area.input(key_event);
-
-                    if self.history_index == self.history.len() {
-                        self.input_buffer = self.text_area.lines()[0].clone();
-                    }
+                    let mut new_text_area = self.text_area.clone();
+                    new_text_area.input(key_event);
+                    self.modify_input(new_text_area);
                 }
             }
         }
     }
 
+    // FIXME: this feels horribly hacky, but it works, at least as far as i can tell
+    fn previous_history_entry(&mut self) {
+        if self.history_index > 0 {
+            self.history_index -= 1;
+            self.text_area = text_area_containing(&self.history[self.history_index]);
+        }
+    }
+
+    fn next_history_entry(&mut self) {
+        if self.history_index < self.history.len() {
+            self.history_index += 1;
+            self.text_area = text_area_containing(
+                self.history
+                    .get(self.history_index)
+                    .unwrap_or(&self.input_buffer),
+            );
+        }
+    }
+
+    /// replace the text area with `new_text_area', which is the result of editing it
+    fn modify_input(&mut self, new_text_area: TextArea<'a>) {
+        // if we're currently examining history, we need to check whether or not we're modifying
+        // it, in which case the modified history should become the new input state. we need to be
+        // a bit careful about checking that we're actually making a modification, since otherwise
+        // navigaing through history, copy/pasting, etc. could cause our current input to be
+        // overwritten
+        if self.history_index != self.history.len()
+            && self.text_area.lines() != new_text_area.lines()
+        {
+            self.history_index = self.history.len();
+        }
+
+        self.text_area = new_text_area;
+
+        if self.history_index == self.history.len() {
+            self.input_buffer = self.text_area.lines().join("\n");
+        }
+    }
+
+    /// complete the name before the cursor, or cycle to the next completion if completion is
+    /// already in progress
+    ///
+    /// the name before the cursor is completed against the keys of the table in which it would be
+    /// looked up, which is the globals table for a bare name. names looked up in the emulator are
+    /// completed against the fields and methods which it exposes. if there is only a single
+    /// completion, it is inserted immediately, and otherwise the first is inserted and the rest
+    /// may be cycled through by completing again
+    pub fn complete(&mut self, lua: &Lua) {
+        if !self.completions.is_empty() {
+            let previous = &self.completions[self.completion_index];
+            self.completion_index = (self.completion_index + 1) % self.completions.len();
+            let next = &self.completions[self.completion_index];
+            let new_text_area = replace_before_cursor(&self.text_area, previous, next);
+            self.modify_input(new_text_area);
+            return;
+        }
+
+        let (row, col) = self.text_area.cursor();
+        let before_cursor = self.text_area.lines()[row]
+            .chars()
+            .take(col)
+            .collect::<String>();
+
+        // the name being completed may be a path through several nested tables, such as
+        // `widgets.ram.view_offset', so everything up to the last `.' or `:' is the table in which
+        // the name is looked up, and everything after it is the part of the name already typed
+        let path_start = before_cursor
+            .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '.' || c == ':')
+            .len();
+        let path = &before_cursor[path_start..];
+        let (base, partial) = match path.rfind(['.', ':']) {
+            Some(i) => (Some(&path[..i]), &path[i + 1..]),
+            None => (None, path),
+        };
+
+        let table = match base {
+            None => Value::Table(lua.globals()),
+            Some(base) => lua.load(base).eval::<Value>().unwrap_or(Value::Nil),
+        };
+        let mut completions = match table {
+            Value::Table(t) => t
+                .pairs::<Value, Value>()
+                .filter_map(|pair| match pair {
+                    Ok((Value::String(key), _)) => key.to_str().ok().map(String::from),
+                    _ => None,
+                })
+                .collect(),
+            Value::UserData(ud) if ud.is::<LuaEmulator>() => LuaEmulator::MEMBERS
+                .iter()
+                .map(|member| member.to_string())
+                .collect(),
+            _ => Vec::new(),
+        };
+        completions.retain(|completion| completion.starts_with(partial));
+        completions.sort();
+        completions.dedup();
+
+        if let Some(first) = completions.first() {
+            self.modify_input(replace_before_cursor(&self.text_area, partial, first));
+        }
+
+        if completions.len() > 1 {
+            self.completions = completions;
+            self.completion_index = 0;
+        }
+    }
+
+    /// abandon any completion in progress, leaving the current completion in place
+    pub fn reset_completions(&mut self) {
+        self.completions = Vec::new();
+        self.completion_index = 0;
+    }
+
+    /// return the completions which should currently be displayed, along with the index of the
+    /// selected completion among them
+    fn visible_completions(&self) -> (&[String], usize) {
+        // keep the selected completion in view as they are cycled through
+        let start = self
+            .completion_index
+            .saturating_sub(MAX_VISIBLE_COMPLETIONS - 1);
+        let end = self.completions.len().min(start + MAX_VISIBLE_COMPLETIONS);
+        (&self.completions[start..end], self.completion_index - start)
+    }
+
+    /// return the number of lines of input currently in the prompt
+    pub fn minimum_height(&self) -> u16 {
+        // 2 lines are taken up by the border and 1 by the output of the last evaluation
+        self.text_area.lines().len() as u16 + self.visible_completions().0.len() as u16 + 3
+    }
+
     pub fn evaluate_input_buffer(&mut self, lua: &Lua) {
-        let input_buffer = self.text_area.lines()[0].clone();
+        self.reset_completions();
+
+        let input_buffer = self.text_area.lines().join("\n");
 
         // FIXME: this is just a repl that i copied from an example in the `mlua' repository. it
         // definitely merits a more careful look
         self.output_buffer = match lua.load(&input_buffer).eval::<MultiValue>() {
-            Ok(v) => {
-                format!(
-                    "{}",
-                    v.iter()
-                        .map(|value| format!("{:#?}", value))
-                        .collect::<Vec<_>>()
-                        .join("\t")
-                )
-            }
+            Ok(v) => v
+                .iter()
+                .map(|value| format!("{:#?}", value))
+                .collect::<Vec<_>>()
+                .join("\t"),
             Err(e) => {
                 format!("{}", e)
             }
@@ -494,7 +1217,11 @@ impl PromptWidget<'_> {
         // if we evaluate an empty buffer, don't pollute the history with blank lines
         if !input_buffer.is_empty() {
             if let Some(file) = &mut self.history_file {
-                writeln!(file, "{}", &input_buffer);
+                let _ = writeln!(
+                    file,
+                    "{}",
+                    input_buffer.replace('\n', &HISTORY_LINE_SEPARATOR.to_string())
+                );
             }
             self.history.push(input_buffer);
             self.history_index = self.history.len();
@@ -518,13 +1245,58 @@ impl WidgetRef for PromptWidget<'_> {
         let inner_area = block.inner(area);
         let split = Layout::default()
             .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Length(1), Constraint::Length(1)])
+            .constraints(vec![
+                Constraint::Length(1),
+                Constraint::Length(self.text_area.lines().len() as u16),
+                Constraint::Fill(0),
+            ])
             .split(inner_area);
         let output_area = split[0];
         let text_area = split[1];
+        let completions_area = split[2];
 
         block.render(area, buf);
         Text::from(self.output_buffer.as_str()).render(output_area, buf);
-        self.text_area.render(text_area, buf);
+
+        // while searching through history, the search query is displayed before the input
+        if self.searching_history {
+            let prefix = format!("(reverse-i-search)`{}': ", self.history_search_query);
+            let split = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![
+                    Constraint::Length(prefix.chars().count() as u16),
+                    Constraint::Fill(0),
+                ])
+                .split(text_area);
+
+            Text::from(prefix).render(split[0], buf);
+            self.text_area.render(split[1], buf);
+        } else if self.reading_goto_address {
+            // NOTE: the usual input is hidden, rather than lost, while the address is read
+            Text::from(format!("(goto) address: {}", self.goto_address)).render(text_area, buf);
+        } else {
+            self.text_area.render(text_area, buf);
+        }
+
+        // any completions being cycled through are listed beneath the input, with the one which
+        // is currently inserted highlighted
+        let (completions, selected) = self.visible_completions();
+        Text::from(
+            completions
+                .iter()
+                .enumerate()
+                .map(|(i, completion)| {
+                    if i == selected {
+                        Line::styled(
+                            completion.as_str(),
+                            Style::new().add_modifier(Modifier::REVERSED),
+                        )
+                    } else {
+                        Line::raw(completion.as_str())
+                    }
+                })
+                .collect::<Vec<_>>(),
+        )
+        .render(completions_area, buf);
     }
 }
@@ -0,0 +1,64 @@
+use sama::emulator::Emulator;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// `addi r1, r1, 1`, which takes up two words.
+const INCREMENT_R1: [u16; 2] = [1 << 11 | 1 << 6 | 0b001000, 0x0001];
+
+fn emulator_incrementing_r1(times: u16) -> Emulator {
+    let mut emulator = Emulator::default();
+    for i in 0..times {
+        emulator.ram[2 * i] = INCREMENT_R1[0];
+        emulator.ram[2 * i + 1] = INCREMENT_R1[1];
+    }
+    emulator
+}
+
+#[test]
+fn hooks_observe_each_step() {
+    let mut emulator = emulator_incrementing_r1(3);
+
+    // NOTE: The pre-step hook sees each instruction before it is executed, and the post-step hook
+    // sees its effects.
+    let program_counters = Rc::new(RefCell::new(Vec::new()));
+    let r1_values = Rc::new(RefCell::new(Vec::new()));
+    {
+        let program_counters = program_counters.clone();
+        emulator.set_pre_step_hook(move |emulator| {
+            program_counters.borrow_mut().push(emulator.program_counter)
+        });
+    }
+    {
+        let r1_values = r1_values.clone();
+        emulator
+            .set_post_step_hook(move |emulator| r1_values.borrow_mut().push(emulator.registers[1]));
+    }
+
+    for _ in 0..3 {
+        emulator.step().unwrap();
+    }
+
+    assert_eq!(*program_counters.borrow(), [0, 2, 4]);
+    assert_eq!(*r1_values.borrow(), [1, 2, 3]);
+}
+
+#[test]
+fn hooks_survive_reset_but_not_hard_reset() {
+    let mut emulator = emulator_incrementing_r1(1);
+
+    let steps = Rc::new(RefCell::new(0));
+    {
+        let steps = steps.clone();
+        emulator.set_post_step_hook(move |_| *steps.borrow_mut() += 1);
+    }
+
+    emulator.step().unwrap();
+    emulator.reset();
+    emulator.step().unwrap();
+    assert_eq!(*steps.borrow(), 2);
+
+    emulator.hard_reset();
+    emulator.step().unwrap();
+    assert_eq!(*steps.borrow(), 2);
+}
@@ -0,0 +1,111 @@
+use pali::assemble::Assembler;
+use pali::parse::Defines;
+use pali::source::Sources;
+use poki::Poki;
+use sama::emulator::{Device, Emulator, RunResult};
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+// Waits for an interrupt, and then spins at `start.halt`. The handler sets r1 to 1, and then spins
+// at `handler.done`.
+const WAIT: &str = "
+    (export start.halt handler)
+    (segment rx
+        (block start
+            (wfi)
+            (block halt (jal r0 r0 halt)))
+        (block handler
+            (addi r1 r0 1)
+            (block done (jal r0 r0 done))))";
+
+// The index at which the test device is attached.
+const DEVICE_INDEX: u8 = 3;
+
+// A device which requests an interrupt for as long as `pending` is set.
+struct TestDevice {
+    pending: Rc<Cell<bool>>,
+}
+
+impl Device for TestDevice {
+    fn input(&mut self, _context: u8) -> u16 {
+        0
+    }
+
+    fn output(&mut self, _context: u8, _value: u16) {}
+
+    fn interrupt_pending(&self) -> bool {
+        self.pending.get()
+    }
+}
+
+// Assemble `WAIT`, load it into a fresh emulator with every segment placed at address 0, and attach
+// the test device, with its interrupt vectored to the handler, but not yet enabled.
+fn load() -> (Emulator, Poki, Rc<Cell<bool>>) {
+    let sources = Sources::new([("test.pali".to_string(), WAIT.to_string())]);
+    let defines = Defines::new();
+    let assembly = Assembler::try_new(&sources, &defines)
+        .and_then(|assembler| assembler.assemble())
+        .unwrap();
+
+    let mut emulator = Emulator::default();
+    let warnings = emulator.load_poki(&assembly.poki, &[0; 8]);
+    assert!(warnings.is_empty(), "{warnings:?}");
+
+    emulator.control_status_registers.iv = assembly.poki.find_export("handler").unwrap().1;
+    let pending = Rc::new(Cell::new(false));
+    emulator.devices[DEVICE_INDEX] = Some(Box::new(TestDevice {
+        pending: pending.clone(),
+    }));
+
+    (emulator, assembly.poki, pending)
+}
+
+#[test]
+fn waiting_without_enabled_interrupts_stops_the_run() {
+    let (mut emulator, _, pending) = load();
+    pending.set(true);
+
+    let result = emulator.run_for(1000);
+
+    assert_eq!(result, Ok((1, RunResult::WaitingForInterrupt)));
+    assert!(emulator.waiting_for_interrupt);
+}
+
+#[test]
+fn enabled_interrupts_wake_the_emulator() {
+    let (mut emulator, poki, pending) = load();
+    emulator.control_status_registers.im[0] = 1 << DEVICE_INDEX;
+
+    // NOTE: Since the device may yet interrupt the emulator, the run goes on waiting for it until
+    // the run is over.
+    let result = emulator.run_for(1000);
+    assert_eq!(result, Ok((1000, RunResult::WaitingForInterrupt)));
+    assert_eq!(emulator.control_status_registers.instruction_counter, 1);
+
+    pending.set(true);
+    let result = emulator.run_until(|emulator| emulator.registers[1] == 1, 1000);
+
+    assert_eq!(result, Ok(RunResult::ReachedTarget));
+    assert!(!emulator.waiting_for_interrupt);
+    assert_eq!(
+        emulator.control_status_registers.ic,
+        u16::from(DEVICE_INDEX)
+    );
+    assert_eq!(
+        emulator.control_status_registers.ipc,
+        poki.find_export("start.halt").unwrap().1
+    );
+}
+
+#[test]
+fn masked_interrupts_are_not_taken() {
+    let (mut emulator, _, pending) = load();
+    emulator.control_status_registers.im[0] = 1 << (DEVICE_INDEX + 1);
+    pending.set(true);
+
+    let result = emulator.run_until(|emulator| emulator.registers[1] == 1, 1000);
+
+    assert_eq!(result, Ok(RunResult::WaitingForInterrupt));
+    assert_eq!(emulator.registers[1], 0);
+}
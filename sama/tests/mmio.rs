@@ -0,0 +1,130 @@
+use pali::assemble::Assembler;
+use pali::parse::Defines;
+use pali::source::Sources;
+use sama::emulator::{Device, Emulator, MmioError, MmioRegion};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// The index at which the test device is attached.
+const DEVICE_INDEX: u8 = 3;
+
+// A device which records each output made to it, and answers each input with 0x0100 plus the
+// context of the input.
+struct TestDevice {
+    outputs: Rc<RefCell<Vec<(u8, u16)>>>,
+}
+
+impl Device for TestDevice {
+    fn input(&mut self, context: u8) -> u16 {
+        0x0100 + u16::from(context)
+    }
+
+    fn output(&mut self, context: u8, value: u16) {
+        self.outputs.borrow_mut().push((context, value));
+    }
+}
+
+fn region(start: u16, end: u16, device_index: u8) -> MmioRegion {
+    MmioRegion {
+        start,
+        end,
+        device_index,
+    }
+}
+
+#[test]
+fn accesses_within_a_region_are_redirected_to_its_device() {
+    let sources = Sources::new([(
+        "test.pali".to_string(),
+        "(segment rx
+             (addi r1 r0 0x1234)
+             (stio r1 r0 0x8002)
+             (ldio r2 r0 0x8003)
+             (stio r1 r0 0x8010)
+             (ldio r3 r0 0x9000))"
+            .to_string(),
+    )]);
+    let defines = Defines::new();
+    let assembly = Assembler::try_new(&sources, &defines)
+        .and_then(|assembler| assembler.assemble())
+        .unwrap();
+
+    let mut emulator = Emulator::default();
+    emulator.load_poki(&assembly.poki, &[0; 8]);
+    let outputs = Rc::new(RefCell::new(Vec::new()));
+    emulator.devices[DEVICE_INDEX] = Some(Box::new(TestDevice {
+        outputs: outputs.clone(),
+    }));
+    emulator
+        .register_mmio(0x8000, 0x8010, DEVICE_INDEX)
+        .unwrap();
+    emulator.ram[0x9000] = 0x5555;
+
+    for _ in 0..5 {
+        emulator.step().unwrap();
+    }
+
+    // NOTE: The end of a region is exclusive, so the store to 0x8010 goes to ram.
+    assert_eq!(*outputs.borrow(), [(2, 0x1234)]);
+    assert_eq!(emulator.ram[0x8002], 0x0000);
+    assert_eq!(emulator.ram[0x8010], 0x1234);
+    assert_eq!(emulator.registers[2], 0x0103);
+    assert_eq!(emulator.registers[3], 0x5555);
+}
+
+#[test]
+fn overlapping_regions_are_rejected() {
+    let mut emulator = Emulator::default();
+    emulator.register_mmio(0x8000, 0x8010, 3).unwrap();
+
+    assert_eq!(
+        emulator.register_mmio(0x800F, 0x8020, 4),
+        Err(MmioError::Overlap(region(0x8000, 0x8010, 3)))
+    );
+    assert_eq!(
+        emulator.register_mmio(0x7FF0, 0x8001, 4),
+        Err(MmioError::Overlap(region(0x8000, 0x8010, 3)))
+    );
+    assert_eq!(
+        emulator.register_mmio(0x8004, 0x8008, 4),
+        Err(MmioError::Overlap(region(0x8000, 0x8010, 3)))
+    );
+
+    // NOTE: Regions which merely touch don't overlap.
+    assert_eq!(emulator.register_mmio(0x7FF0, 0x8000, 4), Ok(()));
+    assert_eq!(emulator.register_mmio(0x8010, 0x8020, 5), Ok(()));
+    assert_eq!(
+        emulator.mmio_regions,
+        [
+            region(0x8000, 0x8010, 3),
+            region(0x7FF0, 0x8000, 4),
+            region(0x8010, 0x8020, 5),
+        ]
+    );
+}
+
+#[test]
+fn empty_overlong_and_reserved_regions_are_rejected() {
+    let mut emulator = Emulator::default();
+
+    assert_eq!(
+        emulator.register_mmio(0x0010, 0x0010, 3),
+        Err(MmioError::InvalidRange(0x0010, 0x0010))
+    );
+    assert_eq!(
+        emulator.register_mmio(0x0020, 0x0010, 3),
+        Err(MmioError::InvalidRange(0x0020, 0x0010))
+    );
+    assert_eq!(
+        emulator.register_mmio(0x0000, 0x0101, 3),
+        Err(MmioError::InvalidRange(0x0000, 0x0101))
+    );
+    assert_eq!(
+        emulator.register_mmio(0x0000, 0x0100, 0),
+        Err(MmioError::ReservedDevice)
+    );
+    assert!(emulator.mmio_regions.is_empty());
+
+    assert_eq!(emulator.register_mmio(0x0000, 0x0100, 3), Ok(()));
+}
@@ -0,0 +1,78 @@
+use sama::emulator::{Device, Emulator};
+
+// The index at which the test device is attached.
+const DEVICE_INDEX: u8 = 3;
+
+struct TestDevice;
+
+impl Device for TestDevice {
+    fn input(&mut self, _context: u8) -> u16 {
+        0
+    }
+
+    fn output(&mut self, _context: u8, _value: u16) {}
+}
+
+// An emulator whose state differs from that of a fresh one in every respect, with a device
+// attached, mapped into memory, and a breakpoint set.
+fn used_emulator() -> Emulator {
+    let mut emulator = Emulator::default();
+    emulator.program_counter = 0x1234;
+    emulator.privileged = false;
+    emulator.write_register(1, 0xBEEF);
+    emulator.control_status_registers.iv = 0x0100;
+    emulator.control_status_registers.cycle_counter = 7;
+    emulator.ram[0x4000] = 0xFFFF;
+    emulator.waiting_for_interrupt = true;
+    emulator.symbols.insert(0x1234, "here".to_string());
+    emulator.poisoned = true;
+
+    emulator.devices[DEVICE_INDEX] = Some(Box::new(TestDevice));
+    emulator
+        .register_mmio(0x8000, 0x8010, DEVICE_INDEX)
+        .unwrap();
+    emulator.breakpoints.insert(0x0002);
+    emulator
+}
+
+// Assert that everything besides the devices, their regions, and the breakpoints is as it is in a
+// fresh emulator.
+fn assert_fresh(emulator: &Emulator) {
+    assert_eq!(emulator.program_counter, 0x0000);
+    assert!(emulator.privileged);
+    assert_eq!(emulator.registers.0, [0; 32]);
+    assert!(emulator
+        .register_history
+        .iter()
+        .all(|history| history.is_empty()));
+    assert_eq!(emulator.control_status_registers.iv, 0x0000);
+    assert_eq!(emulator.control_status_registers.cycle_counter, 0);
+    assert_eq!(emulator.ram[0x4000], 0x0000);
+    assert!(!emulator.waiting_for_interrupt);
+    assert!(emulator.symbols.is_empty());
+    assert!(!emulator.poisoned);
+}
+
+#[test]
+fn reset_keeps_devices_regions_and_breakpoints() {
+    let mut emulator = used_emulator();
+
+    emulator.reset();
+
+    assert_fresh(&emulator);
+    assert!(emulator.devices.get(DEVICE_INDEX).is_some());
+    assert_eq!(emulator.mmio_regions.len(), 1);
+    assert_eq!(emulator.breakpoints.len(), 1);
+}
+
+#[test]
+fn hard_reset_discards_devices_regions_and_breakpoints() {
+    let mut emulator = used_emulator();
+
+    emulator.hard_reset();
+
+    assert_fresh(&emulator);
+    assert!(emulator.devices.get(DEVICE_INDEX).is_none());
+    assert!(emulator.mmio_regions.is_empty());
+    assert!(emulator.breakpoints.is_empty());
+}
@@ -0,0 +1,110 @@
+use pali::assemble::Assembler;
+use pali::parse::Defines;
+use pali::source::Sources;
+use poki::Poki;
+use sama::emulator::{Emulator, RunResult};
+
+// Sums 10 + 9 + ... + 1 into r1, counting r2 down to zero, and then spins at `start.halt`.
+const SUM: &str = "
+    (export start.halt)
+    (segment rx
+        (block start
+            (addi r2 r0 10)
+            (block loop
+                (add r1 r2)
+                (addi r2 r2 0xFFFF)
+                (bne r2 r0 loop))
+            (block halt (jal r0 r0 halt))))";
+
+// Assemble `source`, and load it into a fresh emulator with every segment placed at address 0.
+fn load(source: &str) -> (Emulator, Poki) {
+    let sources = Sources::new([("test.pali".to_string(), source.to_string())]);
+    let defines = Defines::new();
+    let assembly = Assembler::try_new(&sources, &defines)
+        .and_then(|assembler| assembler.assemble())
+        .unwrap();
+
+    let mut emulator = Emulator::default();
+    let warnings = emulator.load_poki(&assembly.poki, &[0; 8]);
+    assert!(warnings.is_empty(), "{warnings:?}");
+    (emulator, assembly.poki)
+}
+
+fn halt_address(poki: &Poki) -> u16 {
+    poki.find_export("start.halt").unwrap().1
+}
+
+#[test]
+fn run_until_stops_once_the_condition_holds() {
+    let (mut emulator, _) = load(SUM);
+
+    let result = emulator.run_until(|emulator| emulator.registers[1] == 55, 1000);
+
+    assert_eq!(result, Ok(RunResult::ReachedTarget));
+    assert_eq!(emulator.registers[1], 55);
+    // NOTE: r1 reaches 55 as the last term is added, before r2 is counted down to zero.
+    assert_eq!(emulator.registers[2], 1);
+}
+
+#[test]
+fn run_until_gives_up_after_max_cycles() {
+    let (mut emulator, _) = load(SUM);
+
+    let result = emulator.run_until(|emulator| emulator.registers[1] == 56, 1000);
+
+    assert_eq!(result, Ok(RunResult::MaxCyclesReached));
+    assert_eq!(emulator.registers[1], 55);
+}
+
+#[test]
+fn run_until_stops_at_breakpoints() {
+    let (mut emulator, poki) = load(SUM);
+    let halt = halt_address(&poki);
+    emulator.breakpoints.insert(halt);
+
+    let result = emulator.run_until(|emulator| emulator.registers[1] == 56, 1000);
+
+    assert_eq!(result, Ok(RunResult::Breakpoint(halt)));
+    assert_eq!(emulator.program_counter, halt);
+    assert_eq!(emulator.registers[1], 55);
+}
+
+#[test]
+fn run_until_pc_stops_at_the_target() {
+    let (mut emulator, poki) = load(SUM);
+    let halt = halt_address(&poki);
+
+    let result = emulator.run_until_pc(halt, 1000);
+
+    assert_eq!(result, Ok(RunResult::ReachedTarget));
+    assert_eq!(emulator.program_counter, halt);
+    assert_eq!(emulator.registers[1], 55);
+}
+
+#[test]
+fn run_for_stops_at_breakpoints_and_resumes_from_them() {
+    let (mut emulator, poki) = load(SUM);
+    let halt = halt_address(&poki);
+    emulator.breakpoints.insert(halt);
+
+    // NOTE: The loop adds each of 10 terms in 3 instructions, after the 1 instruction which sets
+    // up the count.
+    assert_eq!(
+        emulator.run_for(1000),
+        Ok((31, RunResult::Breakpoint(halt)))
+    );
+    assert_eq!(emulator.program_counter, halt);
+
+    // NOTE: Having stopped at a breakpoint, the next run executes the instruction there, only
+    // stopping when it comes back around to it.
+    assert_eq!(emulator.run_for(1000), Ok((1, RunResult::Breakpoint(halt))));
+    assert_eq!(emulator.registers[1], 55);
+}
+
+#[test]
+fn run_for_ignores_a_breakpoint_at_the_starting_pc() {
+    let (mut emulator, _) = load(SUM);
+    emulator.breakpoints.insert(emulator.program_counter);
+
+    assert_eq!(emulator.run_for(10), Ok((10, RunResult::MaxCyclesReached)));
+}